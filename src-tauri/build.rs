@@ -1,3 +1,6 @@
 fn main() {
-    tauri_build::build()
+    // Only the desktop app needs the Tauri context; CLI-only builds skip it.
+    if std::env::var_os("CARGO_FEATURE_GUI").is_some() {
+        tauri_build::build()
+    }
 }
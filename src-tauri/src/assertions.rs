@@ -0,0 +1,584 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+
+use crate::dns_tester::spec::{self, SYSTEM_SERVER};
+use crate::dns_tester::DnsTestResult;
+use crate::history;
+use crate::speed_tester::DownloadTestResult;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    // Percentiles over successful samples
+    P50,
+    P95,
+    P99,
+    // Median latency as reported in the results table
+    Median,
+    Jitter,
+    // Percent of successful samples (speed tests: 100 or 0)
+    Success,
+    BandwidthMbps,
+}
+
+impl Metric {
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "p50" => Metric::P50,
+            "p95" => Metric::P95,
+            "p99" => Metric::P99,
+            "median" | "latency" => Metric::Median,
+            "jitter" => Metric::Jitter,
+            "success" | "success_rate" => Metric::Success,
+            "bandwidth" | "mbps" => Metric::BandwidthMbps,
+            _ => return None,
+        })
+    }
+
+    fn unit(&self) -> &'static str {
+        match self {
+            Metric::Success => "%",
+            Metric::BandwidthMbps => " Mbps",
+            _ => " ms",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = "=")]
+    Eq,
+}
+
+impl Comparison {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+            Comparison::Eq => "=",
+        }
+    }
+
+    fn holds(&self, actual: f64, expected: f64) -> bool {
+        match self {
+            Comparison::Lt => actual < expected,
+            Comparison::Le => actual <= expected,
+            Comparison::Gt => actual > expected,
+            Comparison::Ge => actual >= expected,
+            Comparison::Eq => (actual - expected).abs() < f64::EPSILON,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Check {
+    Metric {
+        metric: Metric,
+        op: Comparison,
+        value: f64,
+    },
+    DnssecSecure,
+    AnswerContains {
+        value: String,
+    },
+}
+
+// One rule, e.g. "p95 < 50ms for 1.1.1.1". Without a server it applies to every
+// tested server except the system baseline.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Assertion {
+    pub text: String,
+    pub check: Check,
+    pub server: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AssertionOutcome {
+    pub assertion: String,
+    pub server_address: Option<String>,
+    pub passed: bool,
+    pub actual: Option<String>,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Verdict {
+    pub passed: bool,
+    pub total: usize,
+    pub failed: usize,
+    pub outcomes: Vec<AssertionOutcome>,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} of {} checks failed",
+            if self.passed { "PASS" } else { "FAIL" },
+            self.failed,
+            self.total
+        )
+    }
+}
+
+// What assertions can read from a result, whichever test produced it.
+pub trait Measured {
+    fn server_address(&self) -> &str;
+    fn metric(&self, metric: Metric) -> Option<f64>;
+    // None when the run did not validate DNSSEC
+    fn dnssec_secure(&self) -> Option<bool>;
+    fn answers(&self) -> Vec<String>;
+}
+
+// Percentile over the successful samples
+fn latency_percentile(result: &DnsTestResult, p: f64) -> Option<f64> {
+    let mut ok: Vec<f64> = result
+        .samples
+        .iter()
+        .filter(|s| s.ok)
+        .map(|s| s.latency_ms)
+        .collect();
+    ok.sort_by(|a, b| a.total_cmp(b));
+    history::percentile(&ok, p)
+}
+
+impl Measured for DnsTestResult {
+    fn server_address(&self) -> &str {
+        &self.server_address
+    }
+
+    fn metric(&self, metric: Metric) -> Option<f64> {
+        match metric {
+            Metric::P50 => latency_percentile(self, 50.0),
+            Metric::P95 => latency_percentile(self, 95.0),
+            Metric::P99 => latency_percentile(self, 99.0),
+            Metric::Median => self.latency_avg_ms,
+            Metric::Jitter => self.jitter_avg_ms,
+            Metric::Success => Some(self.success_percent),
+            Metric::BandwidthMbps => None,
+        }
+    }
+
+    fn dnssec_secure(&self) -> Option<bool> {
+        self.dnssec_enabled
+            .then_some(self.dnssec_secure && !self.dnssec_bogus)
+    }
+
+    fn answers(&self) -> Vec<String> {
+        self.ipv4_ips
+            .iter()
+            .chain(&self.ipv6_ips)
+            .chain(&self.answers)
+            .cloned()
+            .collect()
+    }
+}
+
+impl Measured for DownloadTestResult {
+    fn server_address(&self) -> &str {
+        &self.server_address
+    }
+
+    fn metric(&self, metric: Metric) -> Option<f64> {
+        match metric {
+            Metric::Success => Some(if self.query_successful { 100.0 } else { 0.0 }),
+            Metric::BandwidthMbps => self.query_successful.then_some(self.bandwidth_mbps),
+            _ => None,
+        }
+    }
+
+    fn dnssec_secure(&self) -> Option<bool> {
+        None
+    }
+
+    fn answers(&self) -> Vec<String> {
+        self.resolved_ip.iter().cloned().collect()
+    }
+}
+
+const OPERATORS: [(&str, Comparison); 8] = [
+    ("<=", Comparison::Le),
+    (">=", Comparison::Ge),
+    ("≤", Comparison::Le),
+    ("≥", Comparison::Ge),
+    ("==", Comparison::Eq),
+    ("<", Comparison::Lt),
+    (">", Comparison::Gt),
+    ("=", Comparison::Eq),
+];
+
+// "50ms", "0.05s", "99%", "99.9 %", "20mbps"
+fn parse_value(s: &str, metric: Metric) -> Result<f64, String> {
+    let s = s.trim().to_ascii_lowercase();
+    let number_end = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let value: f64 = s[..number_end]
+        .parse()
+        .map_err(|_| format!("Expected a number, got '{}'", s))?;
+    let scale = match (s[number_end..].trim(), metric) {
+        ("", _) => 1.0,
+        ("%", Metric::Success) => 1.0,
+        ("ms", m) if m.unit() == " ms" => 1.0,
+        ("s", m) if m.unit() == " ms" => 1000.0,
+        ("mbps" | "mbit/s", Metric::BandwidthMbps) => 1.0,
+        ("gbps", Metric::BandwidthMbps) => 1000.0,
+        (unit, _) => return Err(format!("Unit '{}' does not fit this metric", unit)),
+    };
+    Ok(value * scale)
+}
+
+pub fn parse(text: &str) -> Result<Assertion, String> {
+    let trimmed = text.trim();
+    let lower = trimmed.to_ascii_lowercase();
+    let (body, server) = match lower.rfind(" for ") {
+        Some(i) => (
+            &trimmed[..i],
+            Some(trimmed[i + 5..].trim().to_string()).filter(|s| !s.is_empty()),
+        ),
+        None => (trimmed, None),
+    };
+    let body_lower = body.trim().to_ascii_lowercase();
+
+    let check = if body_lower == "dnssec secure" || body_lower == "dnssec" {
+        Check::DnssecSecure
+    } else if let Some(value) = body_lower.strip_prefix("answer contains ") {
+        // Keep the original case for non-IP answers
+        let value = body.trim()[body.trim().len() - value.len()..].trim();
+        Check::AnswerContains {
+            value: value.to_string(),
+        }
+    } else {
+        let (pos, token, op) = OPERATORS
+            .iter()
+            .filter_map(|(token, op)| body_lower.find(token).map(|pos| (pos, *token, *op)))
+            .min_by_key(|(pos, token, _)| (*pos, std::cmp::Reverse(token.len())))
+            .ok_or_else(|| format!("No comparison in '{}'", trimmed))?;
+        let name = body_lower[..pos].trim();
+        let metric = Metric::parse(name).ok_or_else(|| format!("Unknown metric '{}'", name))?;
+        let value = parse_value(&body_lower[pos + token.len()..], metric)?;
+        Check::Metric { metric, op, value }
+    };
+
+    if let Some(server) = &server {
+        if !server.eq_ignore_ascii_case(SYSTEM_SERVER) {
+            spec::parse(server).map_err(|e| format!("Invalid server '{}': {}", server, e))?;
+        }
+    }
+    Ok(Assertion {
+        text: trimmed.to_string(),
+        check,
+        server,
+    })
+}
+
+pub fn parse_all(rules: &[String]) -> Result<Vec<Assertion>, String> {
+    rules
+        .iter()
+        .filter(|r| !r.trim().is_empty())
+        .map(|r| parse(r).map_err(|e| format!("{}: {}", r.trim(), e)))
+        .collect()
+}
+
+fn same_server(a: &str, b: &str) -> bool {
+    let canonical = |s: &str| spec::parse(s).map(|p| p.to_string()).ok();
+    a.trim().eq_ignore_ascii_case(b.trim())
+        || (canonical(a).is_some() && canonical(a) == canonical(b))
+}
+
+fn answer_matches(answer: &str, expected: &str) -> bool {
+    match (answer.parse::<IpAddr>(), expected.parse::<IpAddr>()) {
+        (Ok(a), Ok(e)) => a == e,
+        (_, Ok(_)) => false,
+        _ => answer
+            .to_ascii_lowercase()
+            .contains(&expected.to_ascii_lowercase()),
+    }
+}
+
+fn check_one<R: Measured>(assertion: &Assertion, result: &R) -> AssertionOutcome {
+    let (passed, actual, message) = match &assertion.check {
+        Check::Metric { metric, op, value } => match result.metric(*metric) {
+            Some(actual) => {
+                let unit = metric.unit();
+                let text = format!("{:.1}{}", actual, unit);
+                let message = format!("{} (expected {} {}{})", text, op.symbol(), value, unit);
+                (op.holds(actual, *value), Some(text), message)
+            }
+            None => (false, None, "no value for this metric".to_string()),
+        },
+        Check::DnssecSecure => match result.dnssec_secure() {
+            Some(true) => (true, Some("secure".to_string()), "secure".to_string()),
+            Some(false) => (
+                false,
+                Some("insecure".to_string()),
+                "answers were not proven secure".to_string(),
+            ),
+            None => (false, None, "DNSSEC validation was not enabled".to_string()),
+        },
+        Check::AnswerContains { value } => {
+            let answers = result.answers();
+            let passed = answers.iter().any(|a| answer_matches(a, value));
+            let actual = answers.join(", ");
+            let message = if passed {
+                format!("found {}", value)
+            } else {
+                format!("{} not in [{}]", value, actual)
+            };
+            (passed, Some(actual), message)
+        }
+    };
+    AssertionOutcome {
+        assertion: assertion.text.clone(),
+        server_address: Some(result.server_address().to_string()),
+        passed,
+        actual,
+        message,
+    }
+}
+
+pub fn evaluate<R: Measured>(assertions: &[Assertion], results: &[R]) -> Verdict {
+    let mut outcomes = vec![];
+    for assertion in assertions {
+        let targets: Vec<&R> = results
+            .iter()
+            .filter(|r| match &assertion.server {
+                Some(server) => same_server(r.server_address(), server),
+                None => !r.server_address().eq_ignore_ascii_case(SYSTEM_SERVER),
+            })
+            .collect();
+        if targets.is_empty() {
+            outcomes.push(AssertionOutcome {
+                assertion: assertion.text.clone(),
+                server_address: assertion.server.clone(),
+                passed: false,
+                actual: None,
+                message: "no matching result".to_string(),
+            });
+            continue;
+        }
+        outcomes.extend(targets.into_iter().map(|r| check_one(assertion, r)));
+    }
+    let failed = outcomes.iter().filter(|o| !o.passed).count();
+    Verdict {
+        passed: failed == 0,
+        total: outcomes.len(),
+        failed,
+        outcomes,
+    }
+}
+
+pub fn needs_dnssec(assertions: &[Assertion]) -> bool {
+    assertions.iter().any(|a| a.check == Check::DnssecSecure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns_tester::{DnsError, DnsErrorKind, Sample};
+
+    fn metric(metric: Metric, op: Comparison, value: f64) -> Check {
+        Check::Metric { metric, op, value }
+    }
+
+    fn dns(server: &str, latencies: &[f64]) -> DnsTestResult {
+        let mut result = DnsTestResult::failure(
+            server.to_string(),
+            false,
+            DnsError::new(DnsErrorKind::Other, ""),
+        );
+        result.query_successful = true;
+        result.success_percent = 100.0;
+        result.samples = latencies
+            .iter()
+            .map(|&latency_ms| Sample {
+                latency_ms,
+                ok: true,
+                error: None,
+            })
+            .collect();
+        result
+    }
+
+    #[test]
+    fn parses_rules_with_operators_units_and_servers() {
+        let cases = [
+            (
+                "p95 < 50ms for 1.1.1.1",
+                metric(Metric::P95, Comparison::Lt, 50.0),
+                Some("1.1.1.1"),
+            ),
+            (
+                "success ≥ 99%",
+                metric(Metric::Success, Comparison::Ge, 99.0),
+                None,
+            ),
+            (
+                "success_rate >= 99.9 %",
+                metric(Metric::Success, Comparison::Ge, 99.9),
+                None,
+            ),
+            (
+                "p99 ≤ 0.2s",
+                metric(Metric::P99, Comparison::Le, 200.0),
+                None,
+            ),
+            (
+                "latency == 20 ms FOR tls://1.1.1.1@one.one.one.one",
+                metric(Metric::Median, Comparison::Eq, 20.0),
+                Some("tls://1.1.1.1@one.one.one.one"),
+            ),
+            (
+                "bandwidth > 1gbps",
+                metric(Metric::BandwidthMbps, Comparison::Gt, 1000.0),
+                None,
+            ),
+            ("DNSSEC secure", Check::DnssecSecure, None),
+            (
+                "answer contains 93.184.216.34",
+                Check::AnswerContains {
+                    value: "93.184.216.34".to_string(),
+                },
+                None,
+            ),
+            // only the last " for " names the server; the answer keeps its case
+            (
+                "answer contains Mail.Example.COM for 8.8.8.8",
+                Check::AnswerContains {
+                    value: "Mail.Example.COM".to_string(),
+                },
+                Some("8.8.8.8"),
+            ),
+            (
+                "answer contains 2606:4700::1111 for [2606:4700:4700::1111]:53",
+                Check::AnswerContains {
+                    value: "2606:4700::1111".to_string(),
+                },
+                Some("[2606:4700:4700::1111]:53"),
+            ),
+        ];
+        for (text, check, server) in cases {
+            let parsed = parse(text).unwrap_or_else(|e| panic!("{}: {}", text, e));
+            assert_eq!(parsed.check, check, "{}", text);
+            assert_eq!(parsed.server.as_deref(), server, "{}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        for (text, error) in [
+            ("p95 50ms", "No comparison"),
+            ("speed < 5", "Unknown metric 'speed'"),
+            ("success < 5ms", "Unit 'ms'"),
+            ("p95 < 2mbps", "Unit 'mbps'"),
+            ("p95 < fast", "Expected a number"),
+            ("p95 < 5 for not a server!", "Invalid server"),
+        ] {
+            let e = parse(text).unwrap_err();
+            assert!(e.contains(error), "{}: {}", text, e);
+        }
+        assert!(parse_all(&["p95 < 5".to_string(), "  ".to_string()]).is_ok());
+        assert!(parse_all(&["p95 5".to_string()])
+            .unwrap_err()
+            .starts_with("p95 5: "));
+    }
+
+    #[test]
+    fn evaluates_against_matching_servers() {
+        let mut cloudflare = dns("udp://1.1.1.1:53", &[10.0, 20.0, 30.0, 40.0, 50.0]);
+        cloudflare.ipv6_ips = vec!["2606:4700:0:0:0:0:0:1111".to_string()];
+        cloudflare.answers = vec!["10 mail.example.com.".to_string()];
+        let results = vec![dns(SYSTEM_SERVER, &[500.0]), cloudflare];
+        let rules = parse_all(&[
+            "p50 <= 30ms".to_string(),
+            "p95 < 50ms for 1.1.1.1".to_string(),
+            "answer contains 2606:4700::1111".to_string(),
+            "answer contains MAIL.example.com".to_string(),
+            "answer contains 2606:4700::1".to_string(),
+            "dnssec secure".to_string(),
+            "success >= 99% for 9.9.9.9".to_string(),
+        ])
+        .unwrap();
+
+        let verdict = evaluate(&rules, &results);
+        let passed: Vec<bool> = verdict.outcomes.iter().map(|o| o.passed).collect();
+        // the system baseline is only checked when named
+        assert_eq!(passed, vec![true, false, true, true, false, false, false]);
+        assert_eq!(verdict.outcomes[1].actual.as_deref(), Some("50.0 ms"));
+        assert_eq!(
+            verdict.outcomes[5].message,
+            "DNSSEC validation was not enabled"
+        );
+        assert_eq!(verdict.outcomes[6].message, "no matching result");
+        assert!(!verdict.passed);
+        assert_eq!((verdict.total, verdict.failed), (7, 4));
+        assert!(needs_dnssec(&rules));
+    }
+
+    #[test]
+    fn dnssec_secure_needs_signed_answers() {
+        let rules = parse_all(&["dnssec secure".to_string()]).unwrap();
+        let mut signed = dns("9.9.9.9", &[10.0]);
+        signed.dnssec_enabled = true;
+        signed.dnssec_validated = true;
+        signed.dnssec_secure = true;
+        // validation was on and the server answered, but the zone is unsigned (Insecure)
+        let insecure = DnsTestResult {
+            server_address: "1.1.1.1".to_string(),
+            dnssec_secure: false,
+            ..signed.clone()
+        };
+        let bogus = DnsTestResult {
+            server_address: "8.8.8.8".to_string(),
+            dnssec_bogus: true,
+            ..signed.clone()
+        };
+
+        let verdict = evaluate(&rules, &[signed, insecure, bogus]);
+        let passed: Vec<bool> = verdict.outcomes.iter().map(|o| o.passed).collect();
+        assert_eq!(passed, vec![true, false, false]);
+        assert_eq!(
+            verdict.outcomes[1].message,
+            "answers were not proven secure"
+        );
+    }
+
+    #[test]
+    fn evaluates_download_results() {
+        let download = DownloadTestResult {
+            server_address: "8.8.8.8".to_string(),
+            provider: None,
+            resolved_ip: Some("192.0.2.80".to_string()),
+            duration_ms: 1000,
+            bytes_read: 2_500_000,
+            bandwidth_mbps: 20.0,
+            query_successful: true,
+            http_status: Some(200),
+            error_msg: None,
+        };
+        let rules = parse_all(&[
+            "bandwidth >= 20mbps".to_string(),
+            "success = 100%".to_string(),
+            "answer contains 192.0.2.80".to_string(),
+            "p95 < 50ms".to_string(),
+        ])
+        .unwrap();
+        let verdict = evaluate(&rules, &[download]);
+        let passed: Vec<bool> = verdict.outcomes.iter().map(|o| o.passed).collect();
+        assert_eq!(passed, vec![true, true, true, false]);
+        assert_eq!(verdict.outcomes[3].message, "no value for this metric");
+        assert_eq!(verdict.to_string(), "FAIL: 1 of 4 checks failed");
+    }
+}
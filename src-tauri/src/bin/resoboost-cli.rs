@@ -0,0 +1,1047 @@
+// Headless front end over the same benchmark code as the desktop app.
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use tauri_lib::assertions::{self, Assertion, Verdict};
+use tauri_lib::compare::{self, RunComparison};
+use tauri_lib::dns_tester::{self, servers, spec, subscriptions, tls_hosts, DnsBenchmarkArgs};
+use tauri_lib::exporter::{self, ExporterConfig};
+use tauri_lib::history;
+use tauri_lib::load_test::{self, LoadTestArgs};
+use tauri_lib::monitor;
+use tauri_lib::proxy;
+use tauri_lib::snippets::{self, SnippetFormat};
+use tauri_lib::report::{
+    self, csv_escape, error_breakdown, error_line, ReportFormat, ReportRun, RunResults,
+};
+use tauri_lib::speed_tester::{self, DownloadSpeedArgs};
+use tauri_lib::storage;
+use tauri_lib::system_dns::{self, ApplyRequest, ApplyTarget};
+
+// Same directory the desktop app uses (identifier from tauri.conf.json)
+const APP_IDENTIFIER: &str = "com.ed.resoboost";
+// Distinct from 1 (errors) and 2 (usage errors from clap)
+const EXIT_ASSERTION_FAILED: u8 = 3;
+
+#[derive(Parser)]
+#[command(
+    name = "resoboost-cli",
+    version,
+    about = "Benchmark DNS resolvers from the command line",
+    after_help = "Exit status: 0 success, 1 error, 2 usage error, 3 an --assert check failed"
+)]
+struct Cli {
+    #[arg(long, short, value_enum, global = true, default_value_t = Format::Table)]
+    format: Format,
+    /// Settings and history location; defaults to the desktop app's data dir
+    #[arg(long, global = true, env = "RESOBOOST_DATA_DIR")]
+    data_dir: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    Csv,
+    Ndjson,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Benchmark DNS servers for a domain (or a reverse lookup for an IP)
+    Dns(DnsArgs),
+    /// Measure download speed through each DNS server's answer
+    Speed(SpeedArgs),
+    /// Show, validate or replace the server list
+    #[command(subcommand)]
+    Servers(ServersCommand),
+    /// Show or import IP -> TLS name mappings
+    #[command(subcommand, name = "tls-hosts")]
+    TlsHosts(TlsHostsCommand),
+    /// Render a stored run or a saved results file as CSV, JSON, Markdown or HTML
+    Report(ReportArgs),
+    /// Compare two runs (history ids, "latest", or JSON result files)
+    Compare { a: String, b: String },
+    /// Run the saved monitor checks on a schedule and serve them as Prometheus metrics
+    Serve(ServeArgs),
+    /// Run a local DNS proxy that forwards to the best servers of the latest benchmark
+    Proxy(ProxyArgs),
+    /// Set the system DNS servers (Linux), backing up the current configuration first
+    Apply(ApplyArgs),
+    /// Undo every change made by `apply`, newest first
+    Revert,
+    /// Print configuration for other DNS software (Unbound, dnsmasq, CoreDNS, ...)
+    Snippets(SnippetsArgs),
+    /// Ramp queries per second against servers to find where they start failing
+    Load(LoadArgs),
+}
+
+#[derive(Args)]
+struct LoadArgs {
+    /// Queries go to unique random names under this domain
+    domain: String,
+    /// Server to test (repeatable)
+    #[arg(long = "server", short = 's', required = true)]
+    servers: Vec<String>,
+    #[arg(long = "type")]
+    record_type: Option<String>,
+    #[arg(long)]
+    start_qps: Option<u32>,
+    #[arg(long)]
+    step_qps: Option<u32>,
+    #[arg(long)]
+    max_qps: Option<u32>,
+    /// Seconds per step
+    #[arg(long)]
+    step_secs: Option<u64>,
+    /// Queries in flight at once
+    #[arg(long)]
+    concurrency: Option<usize>,
+    #[arg(long)]
+    timeout: Option<u64>,
+    /// Error rate (percent) that ends the ramp
+    #[arg(long)]
+    error_threshold: Option<f64>,
+    /// Required: the test sends real traffic at the configured rates
+    #[arg(long)]
+    confirm: bool,
+}
+
+#[derive(Args)]
+struct SnippetsArgs {
+    /// Servers in order of preference; defaults to the best of the latest DNS benchmark
+    servers: Vec<String>,
+    /// How many benchmark winners to use when no servers are given
+    #[arg(long, default_value_t = 3)]
+    best: usize,
+    /// Only these formats (repeatable): unbound, dnsmasq, coredns, dnscrypt-proxy,
+    /// adguard-home, pihole, systemd-resolved, android-private-dns
+    #[arg(long = "as", value_parser = parse_snippet_format)]
+    formats: Vec<SnippetFormat>,
+}
+
+fn parse_snippet_format(s: &str) -> Result<SnippetFormat, String> {
+    serde_json::from_value(serde_json::Value::String(s.to_ascii_lowercase().replace('-', "_")))
+        .map_err(|_| format!("unknown snippet format '{}'", s))
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Backend {
+    Resolved,
+    NetworkManager,
+    ResolvConf,
+}
+
+#[derive(Args)]
+struct ApplyArgs {
+    /// Servers in order of preference
+    #[arg(required = true)]
+    servers: Vec<String>,
+    #[arg(long, value_enum, default_value = "resolved")]
+    backend: Backend,
+    /// Drop-in directory (resolved) or file (resolv-conf) instead of the system default
+    #[arg(long)]
+    path: Option<PathBuf>,
+    /// NetworkManager connection; defaults to the first active one
+    #[arg(long)]
+    connection: Option<String>,
+    /// Only print the diff
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+struct ProxyArgs {
+    /// Listen address (UDP and TCP); defaults to the saved proxy setting
+    #[arg(long)]
+    listen: Option<String>,
+    /// Upstream server (repeatable); defaults to the best of the latest DNS benchmark
+    #[arg(long = "upstream", short = 'u')]
+    upstreams: Vec<String>,
+    /// Ask all upstreams at once and use the first answer
+    #[arg(long)]
+    race: bool,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// Listen address for /metrics; defaults to the saved exporter setting
+    #[arg(long)]
+    bind: Option<String>,
+    /// Minutes between checks; defaults to the saved monitor interval
+    #[arg(long)]
+    interval_minutes: Option<u32>,
+}
+
+#[derive(Args)]
+struct DnsArgs {
+    domain_or_ip: String,
+    #[arg(long)]
+    samples: Option<u32>,
+    #[arg(long)]
+    timeout_secs: Option<u64>,
+    /// Server to test (repeatable); defaults to the enabled servers of the active profile
+    #[arg(long = "server", short = 's')]
+    servers: Vec<String>,
+    #[arg(long)]
+    validate_dnssec: bool,
+    #[arg(long)]
+    warm_up: bool,
+    /// Skip the certificate/session inspection of encrypted servers
+    #[arg(long)]
+    no_inspect_tls: bool,
+    /// Leave out the system resolvers, which are otherwise measured as the baseline
+    #[arg(long)]
+    no_system: bool,
+    /// A/AAAA (default), A, AAAA, MX, TXT, ...
+    #[arg(long)]
+    record_type: Option<String>,
+    /// Also write a report of this run (.csv, .json, .md or .html)
+    #[arg(long)]
+    report: Option<PathBuf>,
+    #[command(flatten)]
+    assert: AssertArgs,
+}
+
+#[derive(Args)]
+struct AssertArgs {
+    /// Check such as "p95 < 50ms for 1.1.1.1" or "answer contains 93.184.216.34" (repeatable);
+    /// prints the verdict instead of the results
+    #[arg(long = "assert", short = 'a')]
+    rules: Vec<String>,
+    /// File with one check per line ('#' starts a comment)
+    #[arg(long)]
+    assert_file: Option<PathBuf>,
+}
+
+impl AssertArgs {
+    fn load(&self) -> Result<Vec<Assertion>, String> {
+        let mut rules = self.rules.clone();
+        if let Some(file) = &self.assert_file {
+            rules.extend(
+                read_input(file)?
+                    .lines()
+                    .filter(|l| !l.trim_start().starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+        assertions::parse_all(&rules)
+    }
+}
+
+#[derive(Args)]
+struct SpeedArgs {
+    url: String,
+    #[arg(long)]
+    duration_secs: Option<u64>,
+    #[arg(long)]
+    timeout_secs: Option<u64>,
+    /// Server to test (repeatable); defaults to the enabled servers of the active profile
+    #[arg(long = "server", short = 's')]
+    servers: Vec<String>,
+    /// Also write a report of this run (.csv, .json, .md or .html)
+    #[arg(long)]
+    report: Option<PathBuf>,
+    #[command(flatten)]
+    assert: AssertArgs,
+}
+
+#[derive(Args)]
+struct ReportArgs {
+    /// History run id, "latest", or a JSON file of results
+    run: String,
+    /// Output file; stdout when omitted
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// csv, json, markdown or html; defaults to the output file's extension, else markdown
+    #[arg(long = "as", value_parser = parse_report_format)]
+    report_format: Option<ReportFormat>,
+}
+
+fn parse_report_format(s: &str) -> Result<ReportFormat, String> {
+    serde_json::from_value(serde_json::Value::String(s.to_ascii_lowercase()))
+        .map_err(|_| "expected csv, json, markdown or html".to_string())
+}
+
+// Fails before the benchmark runs if the format cannot be told from the file name.
+fn report_format_for(path: &Path) -> Result<ReportFormat, String> {
+    ReportFormat::from_path(path).ok_or_else(|| {
+        format!(
+            "{}: use a .csv, .json, .md or .html extension for the report",
+            path.display()
+        )
+    })
+}
+
+#[derive(Subcommand)]
+enum ServersCommand {
+    /// List the active profile's servers
+    List,
+    /// Check addresses (arguments, or one per line on stdin) without saving them
+    Validate { addresses: Vec<String> },
+    /// Replace the active profile's servers with the lines of a file ("-" for stdin)
+    Set { file: PathBuf },
+    /// Fetch subscriptions and the remote TLS host map now
+    Refresh,
+}
+
+#[derive(Subcommand)]
+enum TlsHostsCommand {
+    /// List the effective map and where each entry comes from
+    List,
+    /// Add "ip host" pairs from a file ("-" for stdin) to the user layer
+    Import { file: PathBuf },
+}
+
+fn read_input(path: &PathBuf) -> Result<String, String> {
+    if path.as_os_str() == "-" {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| e.to_string())?;
+        Ok(text)
+    } else {
+        std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+fn write_table(out: &mut impl Write, table: &Table) -> io::Result<()> {
+    let mut widths: Vec<usize> = table.headers.iter().map(|h| h.len()).collect();
+    for row in &table.rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    let header: Vec<String> = table.headers.iter().map(|h| h.to_string()).collect();
+    for cells in [&header, &rule].into_iter().chain(&table.rows) {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{:<width$}", c, width = *w))
+            .collect();
+        writeln!(out, "{}", padded.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+fn write_output<T: Serialize>(format: Format, items: &[T], table: &Table) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    match format {
+        Format::Table => write_table(&mut out, table)?,
+        Format::Csv => {
+            writeln!(out, "{}", table.headers.join(","))?;
+            for row in &table.rows {
+                let cells: Vec<String> = row.iter().map(|c| csv_escape(c)).collect();
+                writeln!(out, "{}", cells.join(","))?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, items)?;
+            writeln!(out)?;
+        }
+        Format::Ndjson => {
+            for item in items {
+                serde_json::to_writer(&mut out, item)?;
+                writeln!(out)?;
+            }
+        }
+    }
+    out.flush()
+}
+
+// Table and CSV use `table`; JSON and NDJSON serialize the items as they are.
+// A closed pipe (e.g. `| head`) is not an error.
+fn emit<T: Serialize>(format: Format, items: &[T], table: Table) -> Result<(), String> {
+    match write_output(format, items, &table) {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+// Returns whether every check passed.
+fn emit_verdict(format: Format, verdict: &Verdict) -> Result<bool, String> {
+    let table = Table {
+        headers: vec!["passed", "assertion", "server_address", "actual", "message"],
+        rows: verdict
+            .outcomes
+            .iter()
+            .map(|o| {
+                vec![
+                    if o.passed { "PASS" } else { "FAIL" }.to_string(),
+                    o.assertion.clone(),
+                    opt(o.server_address.as_ref()),
+                    opt(o.actual.as_ref()),
+                    o.message.clone(),
+                ]
+            })
+            .collect(),
+    };
+    match format {
+        Format::Json => emit(format, std::slice::from_ref(verdict), table)?,
+        _ => emit(format, &verdict.outcomes, table)?,
+    }
+    eprintln!("{}", verdict);
+    Ok(verdict.passed)
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn ms(value: Option<f64>) -> String {
+    value.map(|v| format!("{:.1}", v)).unwrap_or_default()
+}
+
+async fn run_dns(args: DnsArgs, format: Format) -> Result<bool, String> {
+    let checks = args.assert.load()?;
+    // A DNSSEC check is meaningless without validation
+    let validate_dnssec = args.validate_dnssec || assertions::needs_dnssec(&checks);
+    let report_format = args.report.as_deref().map(report_format_for).transpose()?;
+    let bench = DnsBenchmarkArgs {
+        domain_or_ip: args.domain_or_ip,
+        samples: args.samples,
+        timeout_secs: args.timeout_secs,
+        custom_servers: (!args.servers.is_empty()).then_some(args.servers),
+        validate_dnssec: validate_dnssec.then_some(true),
+        warm_up: args.warm_up.then_some(true),
+        inspect_tls: args.no_inspect_tls.then_some(false),
+        record_type: args.record_type,
+        include_system: args.no_system.then_some(false),
+    };
+    let params = serde_json::to_value(&bench).unwrap_or_default();
+    let started_at = history::now_ms();
+    let results = dns_tester::run_dns_benchmark(bench).await;
+    if let (Some(path), Some(report_format)) = (&args.report, report_format) {
+        let run = ReportRun {
+            title: None,
+            id: None,
+            started_at: Some(started_at),
+            params,
+            environment: history::environment().await,
+            results: RunResults::Dns(results.clone()),
+        };
+        report::write_report(&run, report_format, path)?;
+        eprintln!("Report written to {}", path.display());
+    }
+    if !checks.is_empty() {
+        return emit_verdict(format, &assertions::evaluate(&checks, &results));
+    }
+    let table = match format {
+        // Same columns as the app's CSV export
+        Format::Csv => csv_table(RunResults::Dns(results.clone())),
+        _ => {
+            let mut sorted: Vec<_> = results.iter().collect();
+            sorted.sort_by(|a, b| {
+                b.query_successful.cmp(&a.query_successful).then(
+                    a.latency_avg_ms
+                        .unwrap_or(f64::MAX)
+                        .total_cmp(&b.latency_avg_ms.unwrap_or(f64::MAX)),
+                )
+            });
+            Table {
+                headers: vec![
+                    "SERVER",
+                    "PROVIDER",
+                    "MEDIAN MS",
+                    "JITTER MS",
+                    "SUCCESS",
+                    "VS SYSTEM",
+                    "FAILURES",
+                    "ERROR",
+                ],
+                rows: sorted
+                    .iter()
+                    .map(|r| {
+                        vec![
+                            r.server_address.clone(),
+                            opt(r.provider.as_ref()),
+                            ms(r.latency_avg_ms),
+                            ms(r.jitter_avg_ms),
+                            format!("{:.0}%", r.success_percent),
+                            r.vs_system_percent
+                                .map(|p| format!("{:+.0}%", p))
+                                .unwrap_or_default(),
+                            error_breakdown(&r.error_counts),
+                            error_line(r.error_msg.as_ref()),
+                        ]
+                    })
+                    .collect(),
+            }
+        }
+    };
+    emit(format, &results, table).map(|()| true)
+}
+
+async fn run_speed(args: SpeedArgs, format: Format) -> Result<bool, String> {
+    let checks = args.assert.load()?;
+    let report_format = args.report.as_deref().map(report_format_for).transpose()?;
+    let speed = DownloadSpeedArgs {
+        url: args.url,
+        duration_secs: args.duration_secs,
+        timeout_secs: args.timeout_secs,
+        custom_servers: (!args.servers.is_empty()).then_some(args.servers),
+    };
+    let params = serde_json::to_value(&speed).unwrap_or_default();
+    let started_at = history::now_ms();
+    let results = speed_tester::perform_download_speed_test(speed).await;
+    if let (Some(path), Some(report_format)) = (&args.report, report_format) {
+        let run = ReportRun {
+            title: None,
+            id: None,
+            started_at: Some(started_at),
+            params,
+            environment: history::environment().await,
+            results: RunResults::Speed(results.clone()),
+        };
+        report::write_report(&run, report_format, path)?;
+        eprintln!("Report written to {}", path.display());
+    }
+    if !checks.is_empty() {
+        return emit_verdict(format, &assertions::evaluate(&checks, &results));
+    }
+    emit(format, &results, csv_table(RunResults::Speed(results.clone()))).map(|()| true)
+}
+
+fn csv_table(results: RunResults) -> Table {
+    let (headers, rows) = report::csv_table(&results);
+    Table { headers, rows }
+}
+
+// A stored run by id ("latest" for the newest), or a results file saved by the app or a report.
+async fn load_report_run(run: &str) -> Result<ReportRun, String> {
+    if run == "latest" {
+        let latest = history::list_runs(None, Some(1), None).await?;
+        let summary = latest.first().ok_or("The history is empty")?;
+        return report::load_run(summary.id).await;
+    }
+    match run.parse::<i64>() {
+        Ok(id) => report::load_run(id).await,
+        Err(_) => report::load_file(Path::new(run)),
+    }
+}
+
+async fn run_report(args: ReportArgs) -> Result<(), String> {
+    let run = load_report_run(&args.run).await?;
+    let report_format = match (args.report_format, &args.output) {
+        (Some(f), _) => f,
+        (None, Some(path)) => report_format_for(path)?,
+        (None, None) => ReportFormat::Markdown,
+    };
+    match &args.output {
+        Some(path) => {
+            report::write_report(&run, report_format, path)?;
+            eprintln!("Report written to {}", path.display());
+            Ok(())
+        }
+        None => {
+            let text = report::render(&run, report_format)?;
+            match io::stdout().lock().write_all(text.as_bytes()) {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.to_string()),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+fn compare_table(comparison: &RunComparison) -> Table {
+    let metrics: Vec<_> = comparison
+        .servers
+        .iter()
+        .flat_map(|s| s.metrics.iter().map(|m| m.metric))
+        .fold(vec![], |mut seen, m| {
+            if !seen.contains(&m) {
+                seen.push(m);
+            }
+            seen
+        });
+    let mut headers = vec!["server_address", "change"];
+    headers.extend(metrics.iter().map(|m| match m {
+        assertions::Metric::Median => "median_ms",
+        assertions::Metric::P95 => "p95_ms",
+        assertions::Metric::Jitter => "jitter_ms",
+        assertions::Metric::Success => "success_percent",
+        assertions::Metric::BandwidthMbps => "bandwidth_mbps",
+        _ => "metric",
+    }));
+    headers.push("answers");
+    let rows = comparison
+        .servers
+        .iter()
+        .map(|s| {
+            let mut row = vec![
+                s.server_address.clone(),
+                serde_json::to_value(s.change)
+                    .ok()
+                    .and_then(|v| v.as_str().map(str::to_string))
+                    .unwrap_or_default(),
+            ];
+            for metric in &metrics {
+                // "12.0 -> 9.5 (-21%)*", the star marking a significant change
+                row.push(
+                    s.metrics
+                        .iter()
+                        .find(|m| m.metric == *metric)
+                        .map(|m| {
+                            format!(
+                                "{} -> {}{}{}",
+                                ms(m.a),
+                                ms(m.b),
+                                m.percent
+                                    .map(|p| format!(" ({:+.0}%)", p))
+                                    .unwrap_or_default(),
+                                if m.significant { "*" } else { "" }
+                            )
+                        })
+                        .unwrap_or_default(),
+                );
+            }
+            let answers: Vec<String> = s
+                .answers_added
+                .iter()
+                .map(|a| format!("+{}", a))
+                .chain(s.answers_removed.iter().map(|a| format!("-{}", a)))
+                .collect();
+            row.push(answers.join(" "));
+            row
+        })
+        .collect();
+    Table { headers, rows }
+}
+
+async fn run_compare(a: &str, b: &str, format: Format) -> Result<(), String> {
+    let comparison = compare::compare(&load_report_run(a).await?, &load_report_run(b).await?)?;
+    let table = compare_table(&comparison);
+    match format {
+        Format::Json | Format::Ndjson => emit(format, std::slice::from_ref(&comparison), table)?,
+        _ => emit(format, &comparison.servers, table)?,
+    }
+    eprintln!(
+        "{} newly failing, {} recovered, {} answer changes, {} significant changes",
+        comparison.newly_failing.len(),
+        comparison.recovered.len(),
+        comparison.answer_changes.len(),
+        comparison.significant_changes.len()
+    );
+    Ok(())
+}
+
+// Runs until interrupted. The monitor config supplies domains, servers and rules even when
+// the app's own monitor is disabled.
+async fn run_serve(args: ServeArgs) -> Result<(), String> {
+    let mut config = ExporterConfig {
+        enabled: true,
+        ..exporter::get_config().await
+    };
+    if let Some(bind) = args.bind {
+        config.bind = bind;
+    }
+    exporter::listen(&config).await?;
+    if let Some(addr) = exporter::get_status().await.listening_on {
+        eprintln!("Serving metrics on http://{}/metrics", addr);
+    }
+    let mut checks = monitor::get_config().await;
+    if let Some(minutes) = args.interval_minutes {
+        checks.interval_minutes = minutes;
+    }
+    let interval = std::time::Duration::from_secs(checks.interval_minutes.max(1) as u64 * 60);
+    loop {
+        monitor::run_checks(&checks).await;
+        if let Some(error) = monitor::get_status().await.last_error {
+            eprintln!("{}", error);
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+// Runs in the foreground until interrupted, then prints the per-upstream counters.
+async fn run_proxy(args: ProxyArgs, format: Format) -> Result<(), String> {
+    let mut config = proxy::get_config().await;
+    if let Some(listen) = args.listen {
+        config.listen = listen;
+    }
+    if !args.upstreams.is_empty() {
+        config.upstreams = args.upstreams;
+    }
+    config.race |= args.race;
+    let status = proxy::launch(&config).await?;
+    eprintln!(
+        "Proxy listening on {} ({}), upstreams: {}",
+        status.listen.unwrap_or_default(),
+        if status.race { "racing" } else { "failover" },
+        status
+            .upstreams
+            .iter()
+            .map(|u| u.server_address.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    tokio::signal::ctrl_c().await.map_err(|e| e.to_string())?;
+    let status = proxy::get_status().await;
+    proxy::stop().await;
+    eprintln!(
+        "{} queries, {} from cache, {} failed",
+        status.queries, status.cache_hits, status.failures
+    );
+    let table = Table {
+        headers: vec!["server_address", "queries", "answers", "failures", "avg_ms"],
+        rows: status
+            .upstreams
+            .iter()
+            .map(|u| {
+                vec![
+                    u.server_address.clone(),
+                    u.queries.to_string(),
+                    u.answers.to_string(),
+                    u.failures.to_string(),
+                    ms(u.avg_ms),
+                ]
+            })
+            .collect(),
+    };
+    emit(format, &status.upstreams, table)
+}
+
+async fn run_apply(args: ApplyArgs) -> Result<(), String> {
+    let target = match args.backend {
+        Backend::Resolved => ApplyTarget::SystemdResolved {
+            drop_in_dir: args
+                .path
+                .unwrap_or_else(|| PathBuf::from("/etc/systemd/resolved.conf.d")),
+        },
+        Backend::NetworkManager => ApplyTarget::NetworkManager {
+            connection: args.connection,
+        },
+        Backend::ResolvConf => ApplyTarget::ResolvConf {
+            path: args.path.unwrap_or_else(|| PathBuf::from("/etc/resolv.conf")),
+        },
+    };
+    let outcome = system_dns::apply(ApplyRequest {
+        servers: args.servers,
+        target,
+        dry_run: args.dry_run,
+    })
+    .await?;
+    print!("{}", outcome.diff);
+    for note in &outcome.notes {
+        eprintln!("{}", note);
+    }
+    match outcome.backup_id {
+        Some(id) => eprintln!("Applied (backup {}); undo with `resoboost-cli revert`", id),
+        None if outcome.diff.is_empty() => eprintln!("Already configured, nothing to change"),
+        None => eprintln!("Dry run, nothing changed"),
+    }
+    Ok(())
+}
+
+async fn run_revert() -> Result<(), String> {
+    let reverted = system_dns::revert().await?;
+    if reverted.is_empty() {
+        eprintln!("Nothing to revert");
+    }
+    for servers in reverted {
+        eprintln!("Reverted {}", servers);
+    }
+    Ok(())
+}
+
+async fn run_snippets(args: SnippetsArgs, format: Format) -> Result<(), String> {
+    let servers = if args.servers.is_empty() {
+        proxy::best_from_history(args.best).await?
+    } else {
+        args.servers
+    };
+    let snippets = snippets::generate(&servers, &args.formats).await?;
+    for snippet in &snippets {
+        for skipped in &snippet.skipped {
+            eprintln!(
+                "{}: skipped {}: {}",
+                snippet.format.label(),
+                skipped.server,
+                skipped.reason
+            );
+        }
+    }
+    if matches!(format, Format::Json | Format::Ndjson) {
+        let table = Table {
+            headers: vec![],
+            rows: vec![],
+        };
+        return emit(format, &snippets, table);
+    }
+    let mut out = io::stdout().lock();
+    for snippet in snippets.iter().filter(|s| !s.content.is_empty()) {
+        let written = writeln!(
+            out,
+            "# {} ({})\n{}",
+            snippet.format.label(),
+            snippet.target,
+            snippet.content
+        );
+        if let Err(e) = written {
+            return match e.kind() {
+                io::ErrorKind::BrokenPipe => Ok(()),
+                _ => Err(e.to_string()),
+            };
+        }
+    }
+    Ok(())
+}
+
+// Ctrl-C ends the test after the current step instead of abandoning it.
+async fn run_load(args: LoadArgs, format: Format) -> Result<(), String> {
+    let run = load_test::run(LoadTestArgs {
+        servers: args.servers,
+        domain: args.domain,
+        record_type: args.record_type,
+        start_qps: args.start_qps,
+        step_qps: args.step_qps,
+        max_qps: args.max_qps,
+        step_secs: args.step_secs,
+        concurrency: args.concurrency,
+        timeout_secs: args.timeout,
+        error_threshold_percent: args.error_threshold,
+        confirm: args.confirm,
+    });
+    tokio::pin!(run);
+    let results = tokio::select! {
+        results = &mut run => results,
+        _ = tokio::signal::ctrl_c() => {
+            eprintln!("Stopping after the current step");
+            load_test::stop();
+            run.await
+        }
+    }?;
+    let table = Table {
+        headers: vec![
+            "server_address",
+            "target_qps",
+            "achieved_qps",
+            "sent",
+            "not_sent",
+            "servfail",
+            "refused",
+            "timeouts",
+            "other_errors",
+            "error_percent",
+            "p50_ms",
+            "p95_ms",
+            "p99_ms",
+        ],
+        rows: results
+            .iter()
+            .flat_map(|r| {
+                r.steps.iter().map(|s| {
+                    vec![
+                        r.server_address.clone(),
+                        s.target_qps.to_string(),
+                        format!("{:.1}", s.achieved_qps),
+                        s.sent.to_string(),
+                        s.not_sent.to_string(),
+                        s.servfail.to_string(),
+                        s.refused.to_string(),
+                        s.timeouts.to_string(),
+                        s.other_errors.to_string(),
+                        format!("{:.1}", s.error_percent),
+                        ms(s.latency_p50_ms),
+                        ms(s.latency_p95_ms),
+                        ms(s.latency_p99_ms),
+                    ]
+                })
+            })
+            .collect(),
+    };
+    emit(format, &results, table)?;
+    for r in &results {
+        match (&r.error_msg, r.saturation_qps) {
+            (Some(e), _) => eprintln!("{}: {}", r.server_address, e),
+            (None, Some(qps)) => eprintln!(
+                "{}: error rate crossed the threshold at {} qps ({})",
+                r.server_address,
+                qps,
+                match r.max_clean_qps {
+                    Some(best) => format!("best clean rate {:.1} qps", best),
+                    None => "no step stayed under it".to_string(),
+                }
+            ),
+            (None, None) => eprintln!(
+                "{}: no saturation{}",
+                r.server_address,
+                if r.stopped { " before stopping" } else { " up to the maximum rate" }
+            ),
+        }
+    }
+    Ok(())
+}
+
+async fn run_servers(command: ServersCommand, format: Format) -> Result<(), String> {
+    match command {
+        ServersCommand::List => {
+            let entries = servers::get_entries().await;
+            let table = Table {
+                headers: vec!["address", "provider", "protocol", "enabled", "region"],
+                rows: entries
+                    .iter()
+                    .map(|e| {
+                        vec![
+                            e.address.clone(),
+                            opt(e.provider.as_ref()),
+                            serde_json::to_value(e.protocol)
+                                .ok()
+                                .and_then(|v| v.as_str().map(str::to_string))
+                                .unwrap_or_default(),
+                            e.enabled.to_string(),
+                            opt(e.region.as_ref()),
+                        ]
+                    })
+                    .collect(),
+            };
+            emit(format, &entries, table)
+        }
+        ServersCommand::Validate { addresses } => {
+            let lines = if addresses.is_empty() {
+                read_input(&PathBuf::from("-"))?
+                    .lines()
+                    .map(str::to_string)
+                    .collect()
+            } else {
+                addresses
+            };
+            let report = spec::validate_lines(&lines);
+            let table = Table {
+                headers: vec!["line", "input", "canonical", "problem"],
+                rows: report
+                    .lines
+                    .iter()
+                    .map(|l| {
+                        let problem = match (&l.error, l.duplicate_of) {
+                            (Some(e), _) => e.to_string(),
+                            (None, Some(first)) => format!("duplicate of line {}", first),
+                            (None, None) => String::new(),
+                        };
+                        vec![
+                            l.line.to_string(),
+                            l.input.clone(),
+                            opt(l.canonical.as_ref()),
+                            problem,
+                        ]
+                    })
+                    .collect(),
+            };
+            emit(format, &report.lines, table)?;
+            if report.invalid > 0 {
+                return Err(format!("{} invalid address(es)", report.invalid));
+            }
+            Ok(())
+        }
+        ServersCommand::Set { file } => {
+            let lines: Vec<String> = read_input(&file)?.lines().map(str::to_string).collect();
+            servers::set_servers(lines).await?;
+            eprintln!("Saved {} servers", servers::get_entries().await.len());
+            Ok(())
+        }
+        ServersCommand::Refresh => {
+            let statuses = subscriptions::refresh_all().await;
+            if let Err(e) = tls_hosts::update_tls_hosts_from_url().await {
+                eprintln!("Could not update TLS hosts: {}", e);
+            }
+            let table = Table {
+                headers: vec!["url", "accepted", "rejected", "error"],
+                rows: statuses
+                    .iter()
+                    .map(|s| {
+                        vec![
+                            s.subscription.url.clone(),
+                            s.state.accepted.to_string(),
+                            s.state.rejected.len().to_string(),
+                            opt(s.state.last_error.as_ref()),
+                        ]
+                    })
+                    .collect(),
+            };
+            emit(format, &statuses, table)
+        }
+    }
+}
+
+async fn run_tls_hosts(command: TlsHostsCommand, format: Format) -> Result<(), String> {
+    match command {
+        TlsHostsCommand::List => {
+            let entries = tls_hosts::get_entries().await;
+            let table = Table {
+                headers: vec!["ip", "host", "source"],
+                rows: entries
+                    .iter()
+                    .map(|e| {
+                        vec![
+                            e.ip.clone(),
+                            e.host.clone(),
+                            serde_json::to_value(e.source)
+                                .ok()
+                                .and_then(|v| v.as_str().map(str::to_string))
+                                .unwrap_or_default(),
+                        ]
+                    })
+                    .collect(),
+            };
+            emit(format, &entries, table)
+        }
+        TlsHostsCommand::Import { file } => {
+            let result = tls_hosts::import_user_pairs(&read_input(&file)?).await?;
+            eprintln!("Imported {} mappings", result.imported);
+            for line in &result.rejected {
+                eprintln!("Rejected: {}", line);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match cli
+        .data_dir
+        .or_else(|| dirs::data_dir().map(|d| d.join(APP_IDENTIFIER)))
+    {
+        Some(dir) => storage::set_data_dir(dir),
+        None => eprintln!("No data dir, settings and history will not persist"),
+    }
+    dns_tester::load_configs();
+
+    let result = match cli.command {
+        Command::Dns(args) => run_dns(args, cli.format).await,
+        Command::Speed(args) => run_speed(args, cli.format).await,
+        Command::Servers(command) => run_servers(command, cli.format).await.map(|()| true),
+        Command::TlsHosts(command) => run_tls_hosts(command, cli.format).await.map(|()| true),
+        Command::Report(args) => run_report(args).await.map(|()| true),
+        Command::Compare { a, b } => run_compare(&a, &b, cli.format).await.map(|()| true),
+        Command::Serve(args) => run_serve(args).await.map(|()| true),
+        Command::Proxy(args) => run_proxy(args, cli.format).await.map(|()| true),
+        Command::Apply(args) => run_apply(args).await.map(|()| true),
+        Command::Revert => run_revert().await.map(|()| true),
+        Command::Snippets(args) => run_snippets(args, cli.format).await.map(|()| true),
+        Command::Load(args) => run_load(args, cli.format).await.map(|()| true),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(EXIT_ASSERTION_FAILED),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
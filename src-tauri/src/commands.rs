@@ -0,0 +1,288 @@
+// Tauri command wrappers. The logic lives in the core modules so the CLI can share it.
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::assertions::{self, Verdict};
+use crate::compare::{self, RunComparison, RunRef};
+use crate::dns_tester::bootstrap::{self, BootstrapStrategy};
+use crate::dns_tester::importers::{self, ImportFormat, ImportMode, ImportPreview};
+use crate::dns_tester::profiles::{self, ProfileSummary};
+use crate::dns_tester::spec::{self, ValidationReport};
+use crate::dns_tester::subscriptions::{self, Subscription, SubscriptionStatus};
+use crate::dns_tester::tls_hosts::{self, TlsHostEntry, TlsHostImport};
+use crate::dns_tester::{self, servers, DnsBenchmarkArgs, DnsTestResult, ServerEntry};
+use crate::exporter::{self, ExporterConfig, ExporterStatus};
+use crate::history::{self, RunDetail, RunKind, RunSummary, TrendBucket, TrendPoint};
+use crate::load_test::{self, LoadTestArgs, LoadTestResult};
+use crate::monitor::{self, MonitorConfig, MonitorStatus};
+use crate::proxy::{self, ProxyConfig, ProxyStatus};
+use crate::report::{self, ReportFormat, ReportRun};
+use crate::snippets::{self, Snippet, SnippetFormat};
+use crate::speed_tester::{self, DownloadSpeedArgs, DownloadTestResult};
+use crate::system_dns::{self, ApplyOutcome, ApplyRequest, DnsBackup};
+
+#[tauri::command]
+pub async fn run_dns_benchmark(args: DnsBenchmarkArgs) -> Vec<DnsTestResult> {
+    dns_tester::run_dns_benchmark(args).await
+}
+
+#[tauri::command]
+pub async fn perform_download_speed_test(args: DownloadSpeedArgs) -> Vec<DownloadTestResult> {
+    speed_tester::perform_download_speed_test(args).await
+}
+
+#[tauri::command]
+pub async fn get_dns_servers() -> Vec<String> {
+    servers::get_all_addresses().await
+}
+
+#[tauri::command]
+pub async fn set_dns_servers(servers: Vec<String>) -> Result<(), String> {
+    servers::set_servers(servers).await
+}
+
+#[tauri::command]
+pub async fn validate_servers(servers: Vec<String>) -> ValidationReport {
+    spec::validate_lines(&servers)
+}
+
+#[tauri::command]
+pub async fn get_server_entries() -> Vec<ServerEntry> {
+    servers::get_entries().await
+}
+
+#[tauri::command]
+pub async fn set_server_entries(entries: Vec<ServerEntry>) -> Result<(), String> {
+    servers::set_entries(entries).await
+}
+
+#[tauri::command]
+pub async fn preview_server_import(
+    format: ImportFormat,
+    text: String,
+    mode: Option<ImportMode>,
+) -> Result<ImportPreview, String> {
+    importers::preview_import(format, &text, mode.unwrap_or_default()).await
+}
+
+#[tauri::command]
+pub async fn apply_server_import(
+    format: ImportFormat,
+    text: String,
+    mode: Option<ImportMode>,
+) -> Result<ImportPreview, String> {
+    importers::apply_import(format, &text, mode.unwrap_or_default()).await
+}
+
+#[tauri::command]
+pub async fn list_server_profiles() -> Vec<ProfileSummary> {
+    profiles::list_profiles().await
+}
+
+#[tauri::command]
+pub async fn create_server_profile(name: String, copy_from: Option<String>) -> Result<(), String> {
+    profiles::create_profile(name, copy_from).await
+}
+
+#[tauri::command]
+pub async fn rename_server_profile(old_name: String, new_name: String) -> Result<(), String> {
+    profiles::rename_profile(old_name, new_name).await
+}
+
+#[tauri::command]
+pub async fn delete_server_profile(name: String) -> Result<(), String> {
+    profiles::delete_profile(name).await
+}
+
+#[tauri::command]
+pub async fn switch_server_profile(name: String) -> Result<(), String> {
+    profiles::switch_profile(name).await
+}
+
+#[tauri::command]
+pub async fn get_subscriptions() -> Vec<SubscriptionStatus> {
+    subscriptions::get_statuses().await
+}
+
+#[tauri::command]
+pub async fn set_subscriptions(subscriptions: Vec<Subscription>) -> Result<(), String> {
+    subscriptions::set_subscriptions(subscriptions).await
+}
+
+#[tauri::command]
+pub async fn refresh_subscriptions() -> Vec<SubscriptionStatus> {
+    subscriptions::refresh_all().await
+}
+
+#[tauri::command]
+pub async fn get_tls_host_map() -> Vec<TlsHostEntry> {
+    tls_hosts::get_entries().await
+}
+
+#[tauri::command]
+pub async fn set_tls_host_map(map: HashMap<String, String>) -> Result<(), String> {
+    tls_hosts::set_user_map(map).await
+}
+
+#[tauri::command]
+pub async fn import_tls_host_map(text: String) -> Result<TlsHostImport, String> {
+    tls_hosts::import_user_pairs(&text).await
+}
+
+#[tauri::command]
+pub async fn get_bootstrap_config() -> BootstrapStrategy {
+    bootstrap::get_strategy().await
+}
+
+#[tauri::command]
+pub async fn set_bootstrap_config(strategy: BootstrapStrategy) -> Result<(), String> {
+    bootstrap::set_strategy(strategy).await
+}
+
+#[tauri::command]
+pub async fn list_history_runs(
+    kind: Option<RunKind>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<Vec<RunSummary>, String> {
+    history::list_runs(kind, limit, offset).await
+}
+
+#[tauri::command]
+pub async fn get_history_run(id: i64) -> Result<RunDetail, String> {
+    history::get_run(id).await
+}
+
+#[tauri::command]
+pub async fn delete_history_runs(ids: Vec<i64>) -> Result<usize, String> {
+    history::delete_runs(ids).await
+}
+
+#[tauri::command]
+pub async fn get_server_trends(
+    servers: Option<Vec<String>>,
+    since_ms: Option<i64>,
+    bucket: Option<TrendBucket>,
+) -> Result<Vec<TrendPoint>, String> {
+    history::query_trends(servers, since_ms, bucket.unwrap_or_default()).await
+}
+
+#[tauri::command]
+pub async fn get_monitor_config() -> MonitorConfig {
+    monitor::get_config().await
+}
+
+#[tauri::command]
+pub async fn set_monitor_config(config: MonitorConfig) -> Result<(), String> {
+    monitor::set_config(config).await
+}
+
+#[tauri::command]
+pub async fn get_monitor_status() -> MonitorStatus {
+    monitor::get_status().await
+}
+
+#[tauri::command]
+pub async fn evaluate_dns_assertions(
+    rules: Vec<String>,
+    results: Vec<DnsTestResult>,
+) -> Result<Verdict, String> {
+    Ok(assertions::evaluate(&assertions::parse_all(&rules)?, &results))
+}
+
+#[tauri::command]
+pub async fn evaluate_speed_assertions(
+    rules: Vec<String>,
+    results: Vec<DownloadTestResult>,
+) -> Result<Verdict, String> {
+    Ok(assertions::evaluate(&assertions::parse_all(&rules)?, &results))
+}
+
+// Writes a report of a stored run (run_id) or of results the UI passes in (run).
+#[tauri::command]
+pub async fn export_report(
+    run_id: Option<i64>,
+    run: Option<ReportRun>,
+    format: ReportFormat,
+    path: PathBuf,
+) -> Result<(), String> {
+    let run = match (run_id, run) {
+        (Some(id), _) => report::load_run(id).await?,
+        (None, Some(run)) => run,
+        (None, None) => return Err("Nothing to export: pass a run id or results".to_string()),
+    };
+    report::write_report(&run, format, &path)
+}
+
+#[tauri::command]
+pub async fn compare_runs(a: RunRef, b: RunRef) -> Result<RunComparison, String> {
+    compare::compare_runs(a, b).await
+}
+
+#[tauri::command]
+pub async fn get_exporter_config() -> ExporterConfig {
+    exporter::get_config().await
+}
+
+#[tauri::command]
+pub async fn set_exporter_config(config: ExporterConfig) -> Result<(), String> {
+    exporter::set_config(config).await
+}
+
+#[tauri::command]
+pub async fn get_exporter_status() -> ExporterStatus {
+    exporter::get_status().await
+}
+
+#[tauri::command]
+pub async fn get_proxy_config() -> ProxyConfig {
+    proxy::get_config().await
+}
+
+#[tauri::command]
+pub async fn start_proxy(config: ProxyConfig) -> Result<ProxyStatus, String> {
+    proxy::start(config).await
+}
+
+#[tauri::command]
+pub async fn stop_proxy() {
+    proxy::stop().await
+}
+
+#[tauri::command]
+pub async fn get_proxy_status() -> ProxyStatus {
+    proxy::get_status().await
+}
+
+#[tauri::command]
+pub async fn apply_dns_servers(request: ApplyRequest) -> Result<ApplyOutcome, String> {
+    system_dns::apply(request).await
+}
+
+#[tauri::command]
+pub async fn revert_dns_changes() -> Result<Vec<String>, String> {
+    system_dns::revert().await
+}
+
+#[tauri::command]
+pub async fn get_dns_backups() -> Vec<DnsBackup> {
+    system_dns::list_backups().await
+}
+
+#[tauri::command]
+pub async fn generate_snippets(
+    servers: Vec<String>,
+    formats: Vec<SnippetFormat>,
+) -> Result<Vec<Snippet>, String> {
+    snippets::generate(&servers, &formats).await
+}
+
+#[tauri::command]
+pub async fn run_load_test(args: LoadTestArgs) -> Result<Vec<LoadTestResult>, String> {
+    load_test::run(args).await
+}
+
+#[tauri::command]
+pub fn stop_load_test() {
+    load_test::stop()
+}
@@ -0,0 +1,529 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use crate::assertions::{Measured, Metric};
+use crate::dns_tester::spec;
+use crate::dns_tester::DnsTestResult;
+use crate::history::RunKind;
+use crate::report::{self, ReportRun, RunResults};
+use crate::speed_tester::DownloadTestResult;
+
+// Two-sided p-value below which a latency or success change counts as significant
+const SIGNIFICANCE_LEVEL: f64 = 0.05;
+// Without samples to test (jitter, bandwidth, imported results without samples)
+const SIGNIFICANT_CHANGE_PERCENT: f64 = 10.0;
+// Fewer successful samples than this per side is not worth a rank test
+const MIN_TEST_SAMPLES: usize = 3;
+
+// Where one side of a comparison comes from.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum RunRef {
+    // History run id
+    Run(i64),
+    // JSON report, stored run or exported results array
+    File(PathBuf),
+    // Results held by the UI
+    Results(Box<ReportRun>),
+}
+
+impl RunRef {
+    pub async fn load(self) -> Result<ReportRun, String> {
+        match self {
+            RunRef::Run(id) => report::load_run(id).await,
+            RunRef::File(path) => report::load_file(&path),
+            RunRef::Results(run) => Ok(*run),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerChange {
+    Unchanged,
+    // Succeeded in A, failed in B
+    NewlyFailing,
+    // Failed in A, succeeded in B
+    Recovered,
+    StillFailing,
+    // Only in B
+    Added,
+    // Only in A
+    Removed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MetricDelta {
+    pub metric: Metric,
+    pub a: Option<f64>,
+    pub b: Option<f64>,
+    // b - a
+    pub delta: Option<f64>,
+    pub percent: Option<f64>,
+    // None when there were no samples to test
+    pub p_value: Option<f64>,
+    pub significant: bool,
+    // Lower latency / jitter, higher success / bandwidth
+    pub improved: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServerDiff {
+    pub server_address: String,
+    pub provider: Option<String>,
+    pub change: ServerChange,
+    pub metrics: Vec<MetricDelta>,
+    pub answers_added: Vec<String>,
+    pub answers_removed: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunLabel {
+    pub id: Option<i64>,
+    pub started_at: Option<i64>,
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunComparison {
+    pub kind: RunKind,
+    pub a: RunLabel,
+    pub b: RunLabel,
+    pub servers: Vec<ServerDiff>,
+    pub newly_failing: Vec<String>,
+    pub recovered: Vec<String>,
+    // Servers whose answer set differs between the runs
+    pub answer_changes: Vec<String>,
+    // Servers with at least one significant metric change
+    pub significant_changes: Vec<String>,
+}
+
+pub async fn compare_runs(a: RunRef, b: RunRef) -> Result<RunComparison, String> {
+    compare(&a.load().await?, &b.load().await?)
+}
+
+fn label(run: &ReportRun) -> RunLabel {
+    RunLabel {
+        id: run.id,
+        started_at: run.started_at,
+        title: run.title.clone(),
+    }
+}
+
+// Servers match on their canonical spec, so "1.1.1.1" and "udp://1.1.1.1:53" are the same.
+fn server_key(address: &str) -> String {
+    spec::parse(address)
+        .map(|p| p.to_string())
+        .unwrap_or_else(|_| address.trim().to_ascii_lowercase())
+}
+
+pub fn compare(a: &ReportRun, b: &ReportRun) -> Result<RunComparison, String> {
+    let (kind, servers) = match (&a.results, &b.results) {
+        (RunResults::Dns(ra), RunResults::Dns(rb)) => (RunKind::Dns, diff_results(ra, rb)),
+        (RunResults::Speed(ra), RunResults::Speed(rb)) => (RunKind::Speed, diff_results(ra, rb)),
+        _ => return Err("Cannot compare a DNS benchmark with a speed test".to_string()),
+    };
+    let names = |pred: &dyn Fn(&ServerDiff) -> bool| -> Vec<String> {
+        servers
+            .iter()
+            .filter(|s| pred(s))
+            .map(|s| s.server_address.clone())
+            .collect()
+    };
+    Ok(RunComparison {
+        kind,
+        a: label(a),
+        b: label(b),
+        newly_failing: names(&|s| s.change == ServerChange::NewlyFailing),
+        recovered: names(&|s| s.change == ServerChange::Recovered),
+        answer_changes: names(&|s| !s.answers_added.is_empty() || !s.answers_removed.is_empty()),
+        significant_changes: names(&|s| s.metrics.iter().any(|m| m.significant)),
+        servers,
+    })
+}
+
+// What the comparison needs from a result beyond `Measured`.
+trait Comparable: Measured {
+    fn provider(&self) -> Option<String>;
+    fn succeeded(&self) -> bool;
+    fn compared_metrics() -> &'static [Metric];
+    // Successful sample latencies and the (ok, total) sample counts
+    fn sample_latencies(&self) -> Vec<f64>;
+    fn sample_counts(&self) -> (usize, usize);
+}
+
+impl Comparable for DnsTestResult {
+    fn provider(&self) -> Option<String> {
+        self.provider.clone()
+    }
+
+    fn succeeded(&self) -> bool {
+        self.query_successful
+    }
+
+    fn compared_metrics() -> &'static [Metric] {
+        &[Metric::Median, Metric::P95, Metric::Jitter, Metric::Success]
+    }
+
+    fn sample_latencies(&self) -> Vec<f64> {
+        self.samples
+            .iter()
+            .filter(|s| s.ok)
+            .map(|s| s.latency_ms)
+            .collect()
+    }
+
+    fn sample_counts(&self) -> (usize, usize) {
+        let ok = self.samples.iter().filter(|s| s.ok).count();
+        (ok, self.samples.len())
+    }
+}
+
+impl Comparable for DownloadTestResult {
+    fn provider(&self) -> Option<String> {
+        self.provider.clone()
+    }
+
+    fn succeeded(&self) -> bool {
+        self.query_successful
+    }
+
+    fn compared_metrics() -> &'static [Metric] {
+        &[Metric::BandwidthMbps]
+    }
+
+    fn sample_latencies(&self) -> Vec<f64> {
+        vec![]
+    }
+
+    fn sample_counts(&self) -> (usize, usize) {
+        (0, 0)
+    }
+}
+
+fn diff_results<R: Comparable>(a: &[R], b: &[R]) -> Vec<ServerDiff> {
+    let mut diffs = vec![];
+    let mut matched = BTreeSet::new();
+    for ra in a {
+        let key = server_key(ra.server_address());
+        let found = b
+            .iter()
+            .enumerate()
+            .find(|(i, rb)| !matched.contains(i) && server_key(rb.server_address()) == key);
+        let Some((index, rb)) = found else {
+            diffs.push(one_sided(ra, ServerChange::Removed));
+            continue;
+        };
+        matched.insert(index);
+        let change = match (ra.succeeded(), rb.succeeded()) {
+            (true, true) => ServerChange::Unchanged,
+            (true, false) => ServerChange::NewlyFailing,
+            (false, true) => ServerChange::Recovered,
+            (false, false) => ServerChange::StillFailing,
+        };
+        let answers_a: BTreeSet<String> = ra.answers().into_iter().collect();
+        let answers_b: BTreeSet<String> = rb.answers().into_iter().collect();
+        // A failed side has no answers; that is a failure, not an answer change
+        let (added, removed) = if change == ServerChange::Unchanged {
+            (
+                answers_b.difference(&answers_a).cloned().collect(),
+                answers_a.difference(&answers_b).cloned().collect(),
+            )
+        } else {
+            (vec![], vec![])
+        };
+        diffs.push(ServerDiff {
+            server_address: rb.server_address().to_string(),
+            provider: rb.provider().or_else(|| ra.provider()),
+            change,
+            metrics: R::compared_metrics()
+                .iter()
+                .map(|m| metric_delta(*m, ra, rb))
+                .collect(),
+            answers_added: added,
+            answers_removed: removed,
+        });
+    }
+    for (index, rb) in b.iter().enumerate() {
+        if !matched.contains(&index) {
+            diffs.push(one_sided(rb, ServerChange::Added));
+        }
+    }
+    diffs
+}
+
+fn one_sided<R: Comparable>(result: &R, change: ServerChange) -> ServerDiff {
+    ServerDiff {
+        server_address: result.server_address().to_string(),
+        provider: result.provider(),
+        change,
+        metrics: vec![],
+        answers_added: vec![],
+        answers_removed: vec![],
+    }
+}
+
+fn metric_delta<R: Comparable>(metric: Metric, a: &R, b: &R) -> MetricDelta {
+    let (va, vb) = (a.metric(metric), b.metric(metric));
+    let delta = va.zip(vb).map(|(x, y)| y - x);
+    let percent = va
+        .zip(delta)
+        .filter(|(x, _)| *x != 0.0)
+        .map(|(x, d)| d / x * 100.0);
+    let p_value = match metric {
+        Metric::Median | Metric::P50 | Metric::P95 | Metric::P99 => {
+            mann_whitney_p(&a.sample_latencies(), &b.sample_latencies())
+        }
+        Metric::Success => two_proportion_p(a.sample_counts(), b.sample_counts()),
+        _ => None,
+    };
+    let significant = match p_value {
+        Some(p) => p < SIGNIFICANCE_LEVEL && delta.is_some_and(|d| d != 0.0),
+        None => percent.is_some_and(|p| p.abs() >= SIGNIFICANT_CHANGE_PERCENT),
+    };
+    let higher_is_better = matches!(metric, Metric::Success | Metric::BandwidthMbps);
+    MetricDelta {
+        metric,
+        a: va,
+        b: vb,
+        delta,
+        percent,
+        p_value,
+        significant,
+        improved: delta
+            .filter(|d| *d != 0.0)
+            .map(|d| (d > 0.0) == higher_is_better),
+    }
+}
+
+// Two-sided Mann-Whitney U test (normal approximation with tie correction).
+// Latencies are skewed, so a rank test fits better than a t-test.
+fn mann_whitney_p(a: &[f64], b: &[f64]) -> Option<f64> {
+    if a.len() < MIN_TEST_SAMPLES || b.len() < MIN_TEST_SAMPLES {
+        return None;
+    }
+    let mut all: Vec<(f64, bool)> = a
+        .iter()
+        .map(|v| (*v, true))
+        .chain(b.iter().map(|v| (*v, false)))
+        .collect();
+    all.sort_by(|x, y| x.0.total_cmp(&y.0));
+    let n = all.len() as f64;
+    let (mut rank_sum_a, mut tie_term) = (0.0, 0.0);
+    let mut i = 0;
+    while i < all.len() {
+        let mut j = i;
+        while j + 1 < all.len() && all[j + 1].0 == all[i].0 {
+            j += 1;
+        }
+        // Tied values share the average of their ranks (1-based)
+        let rank = (i + j) as f64 / 2.0 + 1.0;
+        let ties = (j - i + 1) as f64;
+        tie_term += ties.powi(3) - ties;
+        rank_sum_a += rank * all[i..=j].iter().filter(|(_, in_a)| *in_a).count() as f64;
+        i = j + 1;
+    }
+    let (na, nb) = (a.len() as f64, b.len() as f64);
+    let u = rank_sum_a - na * (na + 1.0) / 2.0;
+    let mean = na * nb / 2.0;
+    let variance = na * nb / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return Some(1.0);
+    }
+    let z = (u - mean).abs() / variance.sqrt();
+    Some(erfc(z / std::f64::consts::SQRT_2).min(1.0))
+}
+
+// Two-sided z-test for a change in success rate; counts are (successes, total).
+fn two_proportion_p((ok_a, n_a): (usize, usize), (ok_b, n_b): (usize, usize)) -> Option<f64> {
+    if n_a == 0 || n_b == 0 {
+        return None;
+    }
+    let (pa, pb) = (ok_a as f64 / n_a as f64, ok_b as f64 / n_b as f64);
+    let pooled = (ok_a + ok_b) as f64 / (n_a + n_b) as f64;
+    let se = (pooled * (1.0 - pooled) * (1.0 / n_a as f64 + 1.0 / n_b as f64)).sqrt();
+    if se == 0.0 {
+        return Some(1.0);
+    }
+    Some(erfc((pb - pa).abs() / se / std::f64::consts::SQRT_2).min(1.0))
+}
+
+// Complementary error function (Numerical Recipes erfcc, |error| < 1.2e-7).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t
+        * (-z * z - 1.265_512_23
+            + t * (1.000_023_68
+                + t * (0.374_091_96
+                    + t * (0.096_784_18
+                        + t * (-0.186_288_06
+                            + t * (0.278_868_07
+                                + t * (-1.135_203_98
+                                    + t * (1.488_515_87
+                                        + t * (-0.822_152_23 + t * 0.170_872_77)))))))))
+            .exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns_tester::{DnsError, DnsErrorKind, Sample};
+
+    fn result(server: &str, ok: bool, ips: &[&str]) -> DnsTestResult {
+        let mut result = DnsTestResult::failure(
+            server.to_string(),
+            false,
+            DnsError::new(DnsErrorKind::Timeout, "timed out"),
+        );
+        result.query_successful = ok;
+        result.success_percent = if ok { 100.0 } else { 0.0 };
+        result.ipv4_ips = ips.iter().map(|ip| ip.to_string()).collect();
+        result.samples = vec![
+            Sample {
+                latency_ms: 20.0,
+                ok,
+                error: None,
+            };
+            3
+        ];
+        result
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn erfc_matches_known_values() {
+        assert_close(erfc(0.0), 1.0, 1e-7);
+        assert_close(erfc(1.0), 0.157_299_207, 1e-7);
+        assert_close(erfc(-1.0), 1.842_700_793, 1e-7);
+        assert_close(erfc(3.0), 2.209e-5, 1e-7);
+    }
+
+    #[test]
+    fn mann_whitney_handles_ties() {
+        // U = 3 with ties at 12 across the samples; the tie-corrected variance is 15.972
+        let a = [10.0, 12.0, 12.0, 15.0];
+        let b = [12.0, 14.0, 16.0, 16.0, 18.0];
+        assert_close(mann_whitney_p(&a, &b).unwrap(), 0.079_856, 1e-5);
+        assert_close(mann_whitney_p(&b, &a).unwrap(), 0.079_856, 1e-5);
+        assert_eq!(mann_whitney_p(&a[..2], &b), None);
+    }
+
+    #[test]
+    fn identical_samples_are_not_significant() {
+        let samples = [18.0, 20.0, 25.0, 31.0];
+        assert_close(mann_whitney_p(&samples, &samples).unwrap(), 1.0, 1e-6);
+        assert_eq!(mann_whitney_p(&[5.0; 4], &[5.0; 4]), Some(1.0));
+        assert_eq!(two_proportion_p((10, 10), (10, 10)), Some(1.0));
+        assert_close(two_proportion_p((7, 10), (7, 10)).unwrap(), 1.0, 1e-6);
+        assert!(two_proportion_p((20, 20), (2, 20)).unwrap() < SIGNIFICANCE_LEVEL);
+    }
+
+    #[test]
+    fn diff_results_classifies_every_server() {
+        let a = vec![
+            result("1.1.1.1", true, &["192.0.2.1", "192.0.2.2"]),
+            result("8.8.8.8", true, &["192.0.2.1"]),
+            result("9.9.9.9", false, &[]),
+            result("208.67.222.222", true, &["192.0.2.1"]),
+        ];
+        let b = vec![
+            result("udp://1.1.1.1:53", true, &["192.0.2.2", "192.0.2.3"]),
+            result("8.8.8.8", false, &[]),
+            result("9.9.9.9", true, &["192.0.2.1"]),
+            result("94.140.14.14", true, &["192.0.2.1"]),
+        ];
+        let diffs = diff_results(&a, &b);
+        let changes: Vec<(&str, ServerChange)> = diffs
+            .iter()
+            .map(|d| (d.server_address.as_str(), d.change))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("udp://1.1.1.1:53", ServerChange::Unchanged),
+                ("8.8.8.8", ServerChange::NewlyFailing),
+                ("9.9.9.9", ServerChange::Recovered),
+                ("208.67.222.222", ServerChange::Removed),
+                ("94.140.14.14", ServerChange::Added),
+            ]
+        );
+        assert_eq!(diffs[0].answers_added, vec!["192.0.2.3"]);
+        assert_eq!(diffs[0].answers_removed, vec!["192.0.2.1"]);
+        // failing or recovering is not an answer change
+        assert!(diffs[1..3]
+            .iter()
+            .all(|d| d.answers_added.is_empty() && d.answers_removed.is_empty()));
+        let success = diffs[1]
+            .metrics
+            .iter()
+            .find(|m| m.metric == Metric::Success)
+            .unwrap();
+        assert_eq!(success.improved, Some(false));
+        assert!(success.significant, "{:?}", success);
+        assert!(diffs[3].metrics.is_empty() && diffs[4].metrics.is_empty());
+    }
+
+    // An app export from before per-sample results were recorded
+    fn exported(latency_ms: f64, jitter_ms: f64) -> String {
+        format!(
+            r#"[{{"server_address": "1.1.1.1", "resolution_time_ms": {latency}, "query_successful": true,
+                "latency_avg_ms": {latency}, "jitter_avg_ms": {jitter}, "success_percent": 100.0,
+                "dnssec_validated": false, "dnssec_enabled": false, "ipv4_ips": ["192.0.2.1"],
+                "ipv6_ips": [], "error_msg": null, "avg_time": {latency}}}]"#,
+            latency = latency_ms,
+            jitter = jitter_ms
+        )
+    }
+
+    #[tokio::test]
+    async fn imported_files_without_samples_use_the_percent_threshold() {
+        let dir = std::env::temp_dir().join(format!(
+            "resoboost-compare-{}-{}",
+            std::process::id(),
+            crate::history::now_ms()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("before.json"), dir.join("after.json"));
+        std::fs::write(&a, exported(20.0, 2.0)).unwrap();
+        std::fs::write(&b, exported(30.0, 2.1)).unwrap();
+
+        let comparison = compare_runs(RunRef::File(a), RunRef::File(b))
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(comparison.servers.len(), 1);
+        let metric = |m: Metric| {
+            comparison.servers[0]
+                .metrics
+                .iter()
+                .find(|d| d.metric == m)
+                .unwrap()
+                .clone()
+        };
+        // no samples, so no p-value: 50% slower is significant, 5% more jitter is not
+        let median = metric(Metric::Median);
+        assert_eq!((median.p_value, median.percent), (None, Some(50.0)));
+        assert!(median.significant);
+        assert_eq!(median.improved, Some(false));
+        let jitter = metric(Metric::Jitter);
+        assert!(jitter.p_value.is_none() && !jitter.significant);
+        let success = metric(Metric::Success);
+        assert!(success.p_value.is_none() && !success.significant);
+        assert_eq!(comparison.significant_changes, vec!["1.1.1.1"]);
+    }
+}
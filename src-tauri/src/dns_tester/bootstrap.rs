@@ -0,0 +1,230 @@
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig};
+use hickory_resolver::name_server::TokioConnectionProvider;
+use hickory_resolver::system_conf::read_system_conf;
+use hickory_resolver::{Resolver, TokioResolver};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{OnceCell, RwLock};
+use tracing::info;
+
+// How hostnames of DoH/DoT/DoQ (and hostname-based UDP) servers are turned into IPs
+// before the actual benchmark connection is made.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum BootstrapStrategy {
+    // Resolver(s) configured in the OS (`/etc/resolv.conf`, Windows adapters)
+    System,
+    // Plain UDP resolvers chosen by the user, "ip" or "ip:port"
+    Plain { servers: Vec<String> },
+    // Fixed answers per hostname; hosts missing from the map fail instead of leaking a query
+    Static { hosts: HashMap<String, Vec<String>> },
+}
+
+impl Default for BootstrapStrategy {
+    fn default() -> Self {
+        // Android has no readable resolv.conf; use an explicit, visible choice there.
+        if cfg!(target_os = "android") {
+            BootstrapStrategy::Plain {
+                servers: vec!["1.1.1.1".to_string(), "8.8.8.8".to_string()],
+            }
+        } else {
+            BootstrapStrategy::System
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BootstrapAnswer {
+    pub host: String,
+    pub ips: Vec<IpAddr>,
+    // Time spent on the actual lookup (cached answers keep the original measurement)
+    pub elapsed_ms: f64,
+    pub source: String,
+}
+
+pub static BOOTSTRAP_STRATEGY: Lazy<RwLock<BootstrapStrategy>> =
+    Lazy::new(|| RwLock::new(BootstrapStrategy::default()));
+
+// Answers shared by everything one run resolves (precheck, benchmark, TLS targets). Each run
+// owns its cache, so concurrent runs (UI, monitor, proxy) never see or clear each other's
+// entries. Concurrent lookups of one host wait for a single query; failures are not kept.
+#[derive(Default)]
+pub struct BootstrapCache {
+    entries: Mutex<HashMap<String, Arc<OnceCell<BootstrapAnswer>>>>,
+}
+
+impl BootstrapCache {
+    pub async fn resolve(
+        &self,
+        host: &str,
+    ) -> Result<BootstrapAnswer, Box<dyn std::error::Error + Send + Sync>> {
+        let key = cache_key(host);
+        let cell = self
+            .entries
+            .lock()
+            .map_err(|_| "Bootstrap cache lock poisoned")?
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        cell.get_or_try_init(|| resolve_host(&key)).await.cloned()
+    }
+}
+
+fn cache_key(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+pub async fn get_strategy() -> BootstrapStrategy {
+    BOOTSTRAP_STRATEGY.read().await.clone()
+}
+
+pub async fn set_strategy(strategy: BootstrapStrategy) -> Result<(), String> {
+    validate_strategy(&strategy)?;
+    *BOOTSTRAP_STRATEGY.write().await = strategy;
+    Ok(())
+}
+
+fn validate_strategy(strategy: &BootstrapStrategy) -> Result<(), String> {
+    match strategy {
+        BootstrapStrategy::System => Ok(()),
+        BootstrapStrategy::Plain { servers } => {
+            if servers.is_empty() {
+                return Err("Plain bootstrap needs at least one resolver".to_string());
+            }
+            for s in servers {
+                parse_plain_server(s)?;
+            }
+            Ok(())
+        }
+        BootstrapStrategy::Static { hosts } => {
+            for (host, ips) in hosts {
+                if ips.is_empty() {
+                    return Err(format!("No static IPs given for {}", host));
+                }
+                for ip in ips {
+                    ip.trim()
+                        .parse::<IpAddr>()
+                        .map_err(|_| format!("Invalid static IP for {}: {}", host, ip))?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn parse_plain_server(s: &str) -> Result<SocketAddr, String> {
+    let s = s.trim();
+    if let Ok(ip) = s.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, 53));
+    }
+    s.parse::<SocketAddr>()
+        .map_err(|_| format!("Invalid bootstrap resolver: {}", s))
+}
+
+// Resolve `host` with the configured strategy; runs go through a `BootstrapCache` instead.
+pub async fn resolve_host(
+    host: &str,
+) -> Result<BootstrapAnswer, Box<dyn std::error::Error + Send + Sync>> {
+    let key = cache_key(host);
+    let strategy = get_strategy().await;
+    let start = Instant::now();
+    let (ips, source): (Vec<IpAddr>, String) = match &strategy {
+        BootstrapStrategy::Static { hosts } => {
+            let ips = hosts
+                .iter()
+                .find(|(h, _)| h.trim_end_matches('.').eq_ignore_ascii_case(&key))
+                .map(|(_, ips)| ips.iter().filter_map(|ip| ip.trim().parse().ok()).collect())
+                .unwrap_or_else(Vec::new);
+            if ips.is_empty() {
+                return Err(format!("No static bootstrap IPs configured for {}", key).into());
+            }
+            (ips, "static".to_string())
+        }
+        BootstrapStrategy::System => {
+            let resolver = system_resolver()?;
+            let response = resolver.lookup_ip(key.as_str()).await?;
+            (response.iter().collect(), "system".to_string())
+        }
+        BootstrapStrategy::Plain { servers } => {
+            let addrs: Vec<SocketAddr> = servers
+                .iter()
+                .map(|s| parse_plain_server(s))
+                .collect::<Result<_, _>>()?;
+            let resolver = plain_resolver(&addrs);
+            let response = resolver.lookup_ip(key.as_str()).await?;
+            let used = addrs.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(",");
+            (response.iter().collect(), format!("plain:{}", used))
+        }
+    };
+    if ips.is_empty() {
+        return Err(format!("Bootstrap returned no addresses for {}", key).into());
+    }
+
+    let answer = BootstrapAnswer {
+        host: key.clone(),
+        ips,
+        elapsed_ms: start.elapsed().as_secs_f64() * 1000.0,
+        source,
+    };
+    info!(
+        "Bootstrap {} -> {:?} via {} in {:.1} ms",
+        answer.host, answer.ips, answer.source, answer.elapsed_ms
+    );
+    Ok(answer)
+}
+
+fn system_resolver() -> Result<TokioResolver, Box<dyn std::error::Error + Send + Sync>> {
+    let (config, opts) = read_system_conf()
+        .map_err(|e| format!("System resolver configuration unavailable: {}", e))?;
+    Ok(Resolver::builder_with_config(config, TokioConnectionProvider::default())
+        .with_options(opts)
+        .build())
+}
+
+fn plain_resolver(addrs: &[SocketAddr]) -> TokioResolver {
+    let mut group = NameServerConfigGroup::new();
+    for addr in addrs {
+        group.merge(NameServerConfigGroup::from_ips_clear(
+            &[addr.ip()],
+            addr.port(),
+            true,
+        ));
+    }
+    Resolver::builder_with_config(
+        ResolverConfig::from_parts(None, vec![], group),
+        TokioConnectionProvider::default(),
+    )
+    .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Static answers need no network, so the cache can be checked without mocks.
+    #[tokio::test]
+    async fn one_lookup_per_host_and_cache() {
+        *BOOTSTRAP_STRATEGY.write().await = BootstrapStrategy::Static {
+            hosts: HashMap::from([("dns.test".to_string(), vec!["192.0.2.7".to_string()])]),
+        };
+        let cache = BootstrapCache::default();
+
+        let (a, b) = tokio::join!(cache.resolve("dns.test"), cache.resolve("DNS.test."));
+        let (a, b) = (a.unwrap(), b.unwrap());
+        assert_eq!(a.ips, vec!["192.0.2.7".parse::<IpAddr>().unwrap()]);
+        assert_eq!(a.host, "dns.test");
+        // the second caller got the first caller's answer, measurement included
+        assert_eq!(a.elapsed_ms, b.elapsed_ms);
+        assert_eq!(cache.entries.lock().unwrap().len(), 1);
+
+        // failures are not kept, and another cache starts empty
+        assert!(cache.resolve("missing.test").await.is_err());
+        assert!(cache.entries.lock().unwrap()["missing.test"].get().is_none());
+        assert!(BootstrapCache::default().entries.lock().unwrap().is_empty());
+        *BOOTSTRAP_STRATEGY.write().await = BootstrapStrategy::default();
+    }
+}
@@ -0,0 +1,508 @@
+use hickory_resolver::TokioResolver;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::bootstrap::{BootstrapAnswer, BootstrapCache};
+use super::errors::DnsError;
+use super::pinning::PinningVerifier;
+use super::tls_inspect::TlsTarget;
+use super::{build_resolver_for_server, run_query, BenchQuery, QueryAnswer};
+
+// Seams of the per-server benchmark loop: where resolvers come from, how one query runs
+// and what time it is. Production uses hickory and the system clock; tests use `sim`.
+
+pub(crate) trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+pub(crate) trait QueryExecutor: Send + Sync {
+    // Err is the classified error reported for the sample (a timeout once the limit passes).
+    fn query(
+        &self,
+        query: &BenchQuery,
+        timeout_secs: u64,
+    ) -> impl Future<Output = Result<QueryAnswer, DnsError>> + Send;
+
+    fn validates_dnssec(&self) -> bool;
+}
+
+impl QueryExecutor for TokioResolver {
+    fn query(
+        &self,
+        query: &BenchQuery,
+        timeout_secs: u64,
+    ) -> impl Future<Output = Result<QueryAnswer, DnsError>> + Send {
+        run_query(self, query, timeout_secs)
+    }
+
+    fn validates_dnssec(&self) -> bool {
+        self.options().validate
+    }
+}
+
+// An executor for one server plus what it took to set it up.
+pub(crate) struct Built<E> {
+    pub executor: E,
+    pub bootstrap: Option<BootstrapAnswer>,
+    pub tls_target: Option<TlsTarget>,
+    pub pin_verifier: Option<Arc<PinningVerifier>>,
+}
+
+pub(crate) trait ResolverFactory: Send + Sync {
+    type Executor: QueryExecutor;
+
+    fn build(
+        &self,
+        server_address: &str,
+        timeout_secs: u64,
+        validate_dnssec: bool,
+    ) -> impl Future<Output = Result<Built<Self::Executor>, DnsError>> + Send;
+}
+
+pub(crate) struct HickoryFactory<'a> {
+    pub bootstrap_cache: &'a BootstrapCache,
+}
+
+impl ResolverFactory for HickoryFactory<'_> {
+    type Executor = TokioResolver;
+
+    async fn build(
+        &self,
+        server_address: &str,
+        timeout_secs: u64,
+        validate_dnssec: bool,
+    ) -> Result<Built<TokioResolver>, DnsError> {
+        let built = build_resolver_for_server(
+            server_address,
+            timeout_secs,
+            validate_dnssec,
+            self.bootstrap_cache,
+        )
+        .await?;
+        Ok(Built {
+            executor: built.resolver,
+            bootstrap: built.bootstrap,
+            tls_target: built.tls_target,
+            pin_verifier: built.pin_verifier,
+        })
+    }
+}
+
+pub(crate) fn median(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    Some(if sorted.len() % 2 == 1 {
+        sorted[mid]
+    } else {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    })
+}
+
+// Sample (n - 1) standard deviation. A single value has no spread, which the UI shows as 0.
+pub(crate) fn sample_stddev(values: &[f64]) -> Option<f64> {
+    match values.len() {
+        0 => None,
+        1 => Some(0.0),
+        len => {
+            let n = len as f64;
+            let mean = values.iter().sum::<f64>() / n;
+            let var = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (n - 1.0);
+            Some(var.sqrt())
+        }
+    }
+}
+
+// Scripted servers on virtual time. A query moves the clock forward by its scripted latency,
+// or by the whole timeout when it gets no reply, so runs are instant and exactly repeatable.
+#[cfg(test)]
+pub(crate) mod sim {
+    use super::*;
+    use crate::dns_tester::errors::DnsErrorKind;
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[derive(Clone)]
+    pub struct SimClock {
+        start: Instant,
+        elapsed_us: Arc<AtomicU64>,
+    }
+
+    impl SimClock {
+        pub fn new() -> Self {
+            SimClock {
+                start: Instant::now(),
+                elapsed_us: Arc::new(AtomicU64::new(0)),
+            }
+        }
+
+        pub fn advance(&self, by: Duration) {
+            self.elapsed_us
+                .fetch_add(by.as_micros() as u64, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for SimClock {
+        fn now(&self) -> Instant {
+            self.start + Duration::from_micros(self.elapsed_us.load(Ordering::SeqCst))
+        }
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub enum Outcome {
+        // An A answer after this many milliseconds
+        Reply(u64),
+        // NOERROR without records
+        Empty(u64),
+        // A resolver error such as SERVFAIL, reported by its label
+        Fail(u64, DnsErrorKind),
+        // No reply at all
+        Lost,
+    }
+
+    pub const ANSWER: &str = "192.0.2.1";
+
+    pub struct SimExecutor {
+        outcomes: Mutex<VecDeque<Outcome>>,
+        clock: SimClock,
+        validate: bool,
+    }
+
+    impl QueryExecutor for SimExecutor {
+        async fn query(
+            &self,
+            _query: &BenchQuery,
+            timeout_secs: u64,
+        ) -> Result<QueryAnswer, DnsError> {
+            // A script that runs out behaves like a server that went away
+            let outcome = self
+                .outcomes
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Outcome::Lost);
+            let limit = Duration::from_secs(timeout_secs);
+            let took = match outcome {
+                Outcome::Reply(ms) | Outcome::Empty(ms) | Outcome::Fail(ms, _) => {
+                    Duration::from_millis(ms)
+                }
+                Outcome::Lost => Duration::MAX,
+            };
+            if took >= limit {
+                self.clock.advance(limit);
+                return Err(DnsError::timeout());
+            }
+            self.clock.advance(took);
+            match outcome {
+                Outcome::Reply(_) => Ok(QueryAnswer {
+                    ipv4: vec![ANSWER.to_string()],
+                    ..QueryAnswer::default()
+                }),
+                Outcome::Fail(_, kind) => Err(DnsError::new(kind, kind.label())),
+                _ => Ok(QueryAnswer::default()),
+            }
+        }
+
+        fn validates_dnssec(&self) -> bool {
+            self.validate
+        }
+    }
+
+    // Servers unknown to the factory fail to build, like an unparsable address.
+    pub struct SimFactory {
+        clock: SimClock,
+        scripts: Mutex<HashMap<String, Vec<Outcome>>>,
+    }
+
+    impl SimFactory {
+        pub fn new(clock: &SimClock) -> Self {
+            SimFactory {
+                clock: clock.clone(),
+                scripts: Mutex::new(HashMap::new()),
+            }
+        }
+
+        pub fn server(self, address: &str, outcomes: impl IntoIterator<Item = Outcome>) -> Self {
+            self.scripts
+                .lock()
+                .unwrap()
+                .insert(address.to_string(), outcomes.into_iter().collect());
+            self
+        }
+    }
+
+    impl ResolverFactory for SimFactory {
+        type Executor = SimExecutor;
+
+        async fn build(
+            &self,
+            server_address: &str,
+            _timeout_secs: u64,
+            validate_dnssec: bool,
+        ) -> Result<Built<SimExecutor>, DnsError> {
+            let outcomes = self
+                .scripts
+                .lock()
+                .unwrap()
+                .remove(server_address)
+                .ok_or_else(|| {
+                    DnsError::invalid_server(format!("Unknown server {}", server_address))
+                })?;
+            Ok(Built {
+                executor: SimExecutor {
+                    outcomes: Mutex::new(outcomes.into()),
+                    clock: self.clock.clone(),
+                    validate: validate_dnssec,
+                },
+                bootstrap: None,
+                tls_target: None,
+                pin_verifier: None,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sim::{Outcome, SimClock, SimFactory, ANSWER};
+    use super::*;
+    use crate::dns_tester::errors::DnsErrorKind;
+    use crate::dns_tester::{benchmark_single_server, DnsTestResult};
+    use std::collections::BTreeMap;
+
+    const SERVER: &str = "192.0.2.53";
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("value should be present");
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    async fn run(
+        outcomes: impl IntoIterator<Item = Outcome>,
+        samples: usize,
+        timeout_secs: u64,
+        warm_up: bool,
+    ) -> DnsTestResult {
+        let clock = SimClock::new();
+        let factory = SimFactory::new(&clock).server(SERVER, outcomes);
+        let query = BenchQuery {
+            name: "example.test".to_string(),
+            record_type: None,
+        };
+        benchmark_single_server(
+            &factory,
+            &clock,
+            query,
+            SERVER.to_string(),
+            timeout_secs,
+            samples,
+            false,
+            warm_up,
+            true,
+        )
+        .await
+    }
+
+    fn latencies(result: &DnsTestResult) -> Vec<f64> {
+        result.samples.iter().map(|s| s.latency_ms).collect()
+    }
+
+    #[test]
+    fn median_takes_the_middle_or_the_mean_of_the_two_middles() {
+        assert_eq!(median(&[]), None);
+        assert_close(median(&[7.0]), 7.0);
+        assert_close(median(&[30.0, 10.0, 20.0]), 20.0);
+        assert_close(median(&[40.0, 10.0, 30.0, 20.0]), 25.0);
+    }
+
+    #[test]
+    fn stddev_is_the_sample_standard_deviation() {
+        assert_eq!(sample_stddev(&[]), None);
+        assert_close(sample_stddev(&[42.0]), 0.0);
+        assert_close(sample_stddev(&[5.0, 5.0, 5.0]), 0.0);
+        // squared deviations sum to 32 over n - 1 = 7
+        assert_close(
+            sample_stddev(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]),
+            (32.0f64 / 7.0).sqrt(),
+        );
+    }
+
+    #[tokio::test]
+    async fn latency_and_jitter_come_from_the_clock() {
+        let result = run(
+            [Outcome::Reply(10), Outcome::Reply(30), Outcome::Reply(20)],
+            3,
+            2,
+            false,
+        )
+        .await;
+
+        assert_eq!(latencies(&result), vec![10.0, 30.0, 20.0]);
+        assert_close(result.latency_avg_ms, 20.0);
+        assert_close(result.jitter_avg_ms, 10.0);
+        assert_eq!(result.resolution_time_ms, Some(20));
+        assert_eq!(result.success_percent, 100.0);
+        assert_eq!(result.ipv4_ips, vec![ANSWER.to_string()]);
+        assert_eq!(result.error_msg, None);
+        assert!(result.error_counts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn lost_queries_cost_the_full_timeout() {
+        let result = run(
+            [
+                Outcome::Reply(10),
+                Outcome::Lost,
+                Outcome::Reply(30),
+                Outcome::Lost,
+            ],
+            4,
+            2,
+            false,
+        )
+        .await;
+
+        assert_eq!(latencies(&result), vec![10.0, 2000.0, 30.0, 2000.0]);
+        assert_eq!(
+            result.samples.iter().map(|s| s.ok).collect::<Vec<_>>(),
+            vec![true, false, true, false]
+        );
+        assert_eq!(result.success_percent, 50.0);
+        assert!(result.query_successful);
+        assert_eq!(result.error_msg.as_deref(), Some("Timeout"));
+        // timed-out samples count towards the median and jitter
+        assert_close(result.latency_avg_ms, 1015.0);
+        assert_close(
+            result.jitter_avg_ms,
+            sample_stddev(&[10.0, 2000.0, 30.0, 2000.0]).unwrap(),
+        );
+    }
+
+    #[tokio::test]
+    async fn replies_slower_than_the_timeout_are_timeouts() {
+        let result = run([Outcome::Reply(1500), Outcome::Reply(999)], 2, 1, false).await;
+
+        assert_eq!(latencies(&result), vec![1000.0, 999.0]);
+        assert_eq!(result.success_percent, 50.0);
+        assert_eq!(result.error_msg.as_deref(), Some("Timeout"));
+    }
+
+    #[tokio::test]
+    async fn only_the_first_error_is_reported() {
+        let result = run(
+            [
+                Outcome::Fail(5, DnsErrorKind::ServFail),
+                Outcome::Lost,
+                Outcome::Empty(8),
+            ],
+            3,
+            1,
+            false,
+        )
+        .await;
+
+        assert_eq!(latencies(&result), vec![5.0, 1000.0, 8.0]);
+        // an empty answer is not an error, but not a success either
+        assert_eq!(result.success_percent, 0.0);
+        assert!(!result.query_successful);
+        assert_eq!(result.error_msg.as_deref(), Some("SERVFAIL"));
+        assert_eq!(result.error_kind, Some(DnsErrorKind::ServFail));
+    }
+
+    #[tokio::test]
+    async fn failed_samples_are_counted_by_kind() {
+        let result = run(
+            [
+                Outcome::Reply(10),
+                Outcome::Lost,
+                Outcome::Fail(5, DnsErrorKind::ServFail),
+                Outcome::Empty(8),
+                Outcome::Fail(5, DnsErrorKind::ServFail),
+                Outcome::Reply(1200),
+            ],
+            6,
+            1,
+            false,
+        )
+        .await;
+
+        assert_eq!(
+            result.samples.iter().map(|s| s.error).collect::<Vec<_>>(),
+            vec![
+                None,
+                Some(DnsErrorKind::Timeout),
+                Some(DnsErrorKind::ServFail),
+                Some(DnsErrorKind::NoData),
+                Some(DnsErrorKind::ServFail),
+                Some(DnsErrorKind::Timeout),
+            ]
+        );
+        assert_eq!(
+            result.error_counts,
+            BTreeMap::from([
+                (DnsErrorKind::Timeout, 2),
+                (DnsErrorKind::ServFail, 2),
+                (DnsErrorKind::NoData, 1),
+            ])
+        );
+        // the first real error wins over an empty answer
+        assert_eq!(result.error_kind, Some(DnsErrorKind::Timeout));
+    }
+
+    #[tokio::test]
+    async fn warm_up_is_not_measured() {
+        let result = run([Outcome::Lost, Outcome::Reply(12)], 1, 2, true).await;
+
+        assert_eq!(latencies(&result), vec![12.0]);
+        assert_close(result.jitter_avg_ms, 0.0);
+        assert_eq!(result.error_msg, None);
+    }
+
+    #[tokio::test]
+    async fn build_errors_become_a_failed_result() {
+        let clock = SimClock::new();
+        let factory = SimFactory::new(&clock);
+        let query = BenchQuery {
+            name: "example.test".to_string(),
+            record_type: None,
+        };
+
+        let result = benchmark_single_server(
+            &factory,
+            &clock,
+            query,
+            SERVER.to_string(),
+            2,
+            3,
+            true,
+            false,
+            true,
+        )
+        .await;
+
+        assert!(!result.query_successful);
+        assert!(result.samples.is_empty());
+        assert!(result.dnssec_enabled);
+        assert_eq!(result.error_msg, Some(format!("Unknown server {}", SERVER)));
+        assert_eq!(result.error_kind, Some(DnsErrorKind::InvalidServer));
+    }
+}
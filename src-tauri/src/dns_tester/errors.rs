@@ -0,0 +1,255 @@
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::proto::{ProtoError, ProtoErrorKind};
+use hickory_resolver::{ResolveError, ResolveErrorKind};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::spec::SpecError;
+
+// Why a query or a server failed, so results can be broken down by cause.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsErrorKind {
+    Timeout,
+    ConnectionRefused,
+    TlsHandshake,
+    // Untrusted, expired or wrong-name certificate
+    CertificateInvalid,
+    // The chain matches none of the server's SPKI/stamp pins
+    PinMismatch,
+    // The server's own hostname could not be resolved
+    Bootstrap,
+    #[serde(rename = "servfail")]
+    ServFail,
+    Refused,
+    #[serde(rename = "nxdomain")]
+    NxDomain,
+    // NOERROR without records of the asked type
+    #[serde(rename = "nodata")]
+    NoData,
+    DnssecBogus,
+    // The address itself can't be used (syntax, unsupported protocol)
+    InvalidServer,
+    // Local failure: runtime or task setup
+    Runtime,
+    Other,
+}
+
+impl DnsErrorKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DnsErrorKind::Timeout => "timeout",
+            DnsErrorKind::ConnectionRefused => "connection refused",
+            DnsErrorKind::TlsHandshake => "TLS handshake",
+            DnsErrorKind::CertificateInvalid => "certificate invalid",
+            DnsErrorKind::PinMismatch => "pin mismatch",
+            DnsErrorKind::Bootstrap => "bootstrap",
+            DnsErrorKind::ServFail => "SERVFAIL",
+            DnsErrorKind::Refused => "REFUSED",
+            DnsErrorKind::NxDomain => "NXDOMAIN",
+            DnsErrorKind::NoData => "NODATA",
+            DnsErrorKind::DnssecBogus => "DNSSEC bogus",
+            DnsErrorKind::InvalidServer => "invalid server",
+            DnsErrorKind::Runtime => "runtime",
+            DnsErrorKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsError {
+    pub kind: DnsErrorKind,
+    pub message: String,
+}
+
+impl DnsError {
+    pub fn new(kind: DnsErrorKind, message: impl Into<String>) -> Self {
+        DnsError {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn timeout() -> Self {
+        DnsError::new(DnsErrorKind::Timeout, "Timeout")
+    }
+
+    pub fn invalid_server(message: impl fmt::Display) -> Self {
+        DnsError::new(DnsErrorKind::InvalidServer, message.to_string())
+    }
+
+    pub fn bootstrap(message: impl fmt::Display) -> Self {
+        DnsError::new(DnsErrorKind::Bootstrap, message.to_string())
+    }
+}
+
+impl fmt::Display for DnsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for DnsError {}
+
+impl From<SpecError> for DnsError {
+    fn from(error: SpecError) -> Self {
+        DnsError::invalid_server(error)
+    }
+}
+
+impl From<ResolveError> for DnsError {
+    fn from(error: ResolveError) -> Self {
+        let kind = match error.kind() {
+            ResolveErrorKind::Proto(proto) => proto_kind(proto),
+            _ => DnsErrorKind::Other,
+        };
+        DnsError::new(kind, error.to_string())
+    }
+}
+
+impl From<ProtoError> for DnsError {
+    fn from(error: ProtoError) -> Self {
+        DnsError::new(proto_kind(&error), error.to_string())
+    }
+}
+
+fn proto_kind(error: &ProtoError) -> DnsErrorKind {
+    match error.kind() {
+        ProtoErrorKind::NoRecordsFound { response_code, .. } => match *response_code {
+            ResponseCode::NoError => DnsErrorKind::NoData,
+            ResponseCode::NXDomain => DnsErrorKind::NxDomain,
+            ResponseCode::ServFail => DnsErrorKind::ServFail,
+            ResponseCode::Refused => DnsErrorKind::Refused,
+            _ => DnsErrorKind::Other,
+        },
+        ProtoErrorKind::Timeout => DnsErrorKind::Timeout,
+        ProtoErrorKind::RequestRefused => DnsErrorKind::Refused,
+        ProtoErrorKind::Nsec { proof, .. } if proof.is_bogus() => DnsErrorKind::DnssecBogus,
+        ProtoErrorKind::RustlsError(e) => transport_kind(&format!("tls error: {}", e), None),
+        ProtoErrorKind::Io(e) => transport_kind(&e.to_string(), Some(e.kind())),
+        ProtoErrorKind::QuinnConnection(e) => transport_kind(&e.to_string(), None),
+        ProtoErrorKind::QuinnConnect(e) => transport_kind(&e.to_string(), None),
+        // hickory's validator reports missing or broken proofs as plain messages
+        ProtoErrorKind::Message(m) if is_dnssec_message(m) => DnsErrorKind::DnssecBogus,
+        ProtoErrorKind::Msg(m) if is_dnssec_message(m) => DnsErrorKind::DnssecBogus,
+        _ => DnsErrorKind::Other,
+    }
+}
+
+// TLS failures arrive as I/O or QUIC errors wrapping rustls' message.
+fn transport_kind(text: &str, io_kind: Option<std::io::ErrorKind>) -> DnsErrorKind {
+    let text = text.to_ascii_lowercase();
+    if text.contains("pin mismatch") {
+        DnsErrorKind::PinMismatch
+    } else if text.contains("certificate") {
+        DnsErrorKind::CertificateInvalid
+    } else if text.contains("tls") || text.contains("handshake") || text.contains("crypto") {
+        DnsErrorKind::TlsHandshake
+    } else if io_kind == Some(std::io::ErrorKind::ConnectionRefused) || text.contains("refused") {
+        DnsErrorKind::ConnectionRefused
+    } else if io_kind == Some(std::io::ErrorKind::TimedOut) || text.contains("timed out") {
+        DnsErrorKind::Timeout
+    } else {
+        DnsErrorKind::Other
+    }
+}
+
+fn is_dnssec_message(message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    ["validat", "rrsig", "dnskey", "dnssec", "nsec"]
+        .iter()
+        .any(|word| message.contains(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::proto::op::Query;
+    use hickory_resolver::proto::rr::{Name, RecordType};
+    use std::io;
+    use std::str::FromStr;
+
+    fn no_records(response_code: ResponseCode) -> DnsErrorKind {
+        let query = Query::query(Name::from_str("example.test.").unwrap(), RecordType::A);
+        let error = ProtoError::nx_error(
+            Box::new(query),
+            None,
+            None,
+            None,
+            response_code,
+            false,
+            None,
+        );
+        DnsError::from(error).kind
+    }
+
+    fn io_error(error: io::Error) -> DnsErrorKind {
+        DnsError::from(ProtoError::from(error)).kind
+    }
+
+    #[test]
+    fn response_codes_map_to_their_kinds() {
+        assert_eq!(no_records(ResponseCode::NoError), DnsErrorKind::NoData);
+        assert_eq!(no_records(ResponseCode::NXDomain), DnsErrorKind::NxDomain);
+        assert_eq!(no_records(ResponseCode::ServFail), DnsErrorKind::ServFail);
+        assert_eq!(no_records(ResponseCode::Refused), DnsErrorKind::Refused);
+        assert_eq!(no_records(ResponseCode::FormErr), DnsErrorKind::Other);
+    }
+
+    #[test]
+    fn transport_failures_are_told_apart() {
+        assert_eq!(
+            DnsError::from(ProtoError::from(ProtoErrorKind::Timeout)).kind,
+            DnsErrorKind::Timeout
+        );
+        assert_eq!(
+            io_error(io::ErrorKind::ConnectionRefused.into()),
+            DnsErrorKind::ConnectionRefused
+        );
+        // how hickory wraps a rejected DoT certificate
+        assert_eq!(
+            io_error(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                "tls error: invalid peer certificate: UnknownIssuer",
+            )),
+            DnsErrorKind::CertificateInvalid
+        );
+        // how the pinning verifier's rejection arrives
+        assert_eq!(
+            io_error(io::Error::other(
+                "tls error: Pin mismatch for \"dns.example\": presented SPKI [AAAA] match none of the configured pins",
+            )),
+            DnsErrorKind::PinMismatch
+        );
+        assert_eq!(
+            io_error(io::Error::other(
+                "tls error: received fatal alert: HandshakeFailure"
+            )),
+            DnsErrorKind::TlsHandshake
+        );
+    }
+
+    #[test]
+    fn validator_messages_are_dnssec_bogus() {
+        let error = ProtoError::from("could not validate negative response missing SOA");
+        assert_eq!(DnsError::from(error).kind, DnsErrorKind::DnssecBogus);
+        assert_eq!(
+            DnsError::from(ProtoError::from("unexpected reply")).kind,
+            DnsErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn kinds_serialize_as_snake_case() {
+        let kinds = [
+            DnsErrorKind::ConnectionRefused,
+            DnsErrorKind::ServFail,
+            DnsErrorKind::NxDomain,
+            DnsErrorKind::NoData,
+        ];
+        assert_eq!(
+            serde_json::to_string(&kinds).unwrap(),
+            r#"["connection_refused","servfail","nxdomain","nodata"]"#
+        );
+    }
+}
@@ -0,0 +1,703 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+use super::servers::{self, validate_address, ServerEntry, ServerTag};
+use super::spec::split_host_port;
+use super::stamps::{decode_stamp, StampProtocol};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat {
+    // dnscrypt-proxy public-resolvers.md
+    DnscryptMd,
+    AdguardJson,
+    ResolvConf,
+    Unbound,
+    Dnsmasq,
+    // systemd-resolved resolved.conf
+    ResolvedConf,
+    Csv,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    #[default]
+    Append,
+    Replace,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportPreview {
+    pub format: ImportFormat,
+    pub mode: ImportMode,
+    // The list as it would be after applying
+    pub entries: Vec<ServerEntry>,
+    pub added: Vec<String>,
+    pub already_present: Vec<String>,
+    pub removed: Vec<String>,
+    // "what: why" for everything that could not be imported
+    pub skipped: Vec<String>,
+}
+
+// Collects entries, validating each address and dropping duplicates.
+#[derive(Default)]
+struct Collector {
+    entries: Vec<ServerEntry>,
+    skipped: Vec<String>,
+    seen: HashSet<String>,
+}
+
+impl Collector {
+    fn push(&mut self, entry: ServerEntry) {
+        if let Err(e) = validate_address(&entry.address) {
+            self.skipped.push(format!("{}: {}", entry.address, e));
+        } else if self.seen.insert(entry.address.clone()) {
+            self.entries.push(entry);
+        }
+    }
+
+    fn push_address(&mut self, address: String, provider: Option<String>) {
+        let mut entry = ServerEntry::from_address(&address);
+        entry.provider = provider;
+        self.push(entry);
+    }
+
+    fn skip(&mut self, what: &str, why: &str) {
+        self.skipped.push(format!("{}: {}", what, why));
+    }
+}
+
+pub fn parse(format: ImportFormat, text: &str) -> Result<(Vec<ServerEntry>, Vec<String>), String> {
+    let mut out = Collector::default();
+    match format {
+        ImportFormat::DnscryptMd => parse_dnscrypt_md(text, &mut out),
+        ImportFormat::AdguardJson => parse_adguard_json(text, &mut out)?,
+        ImportFormat::ResolvConf => parse_resolv_conf(text, &mut out)?,
+        ImportFormat::Unbound => parse_unbound(text, &mut out),
+        ImportFormat::Dnsmasq => parse_dnsmasq(text, &mut out),
+        ImportFormat::ResolvedConf => parse_resolved_conf(text, &mut out),
+        ImportFormat::Csv => parse_csv(text, &mut out)?,
+    }
+    if out.entries.is_empty() {
+        return Err(format!(
+            "No importable servers found ({} skipped)",
+            out.skipped.len()
+        ));
+    }
+    Ok((out.entries, out.skipped))
+}
+
+// Address in the form `spec::parse` accepts; default ports are left implicit.
+fn plain_or_tls(ip: &str, port: Option<u16>, tls: bool, name: Option<&str>) -> String {
+    let host = if ip.contains(':') && port.is_some() {
+        format!("[{}]", ip)
+    } else {
+        ip.to_string()
+    };
+    if tls {
+        let port = match port {
+            Some(p) if p != 853 => format!(":{}", p),
+            _ => String::new(),
+        };
+        let sni = name.map(|n| format!("@{}", n)).unwrap_or_default();
+        return format!("tls://{}{}{}", host, port, sni);
+    }
+    match port {
+        Some(p) if p != 53 => format!("udp://{}:{}", host, p),
+        _ => ip.to_string(),
+    }
+}
+
+// "## Name" sections followed by a description and one or more sdns:// stamps.
+fn parse_dnscrypt_md(text: &str, out: &mut Collector) {
+    let mut name: Option<String> = None;
+    for line in text.lines().map(str::trim) {
+        if let Some(header) = line.strip_prefix("## ") {
+            name = Some(header.trim().to_string());
+            continue;
+        }
+        if !line.starts_with("sdns://") {
+            continue;
+        }
+        match decode_stamp(line) {
+            Ok(stamp) if stamp.protocol == StampProtocol::DnsCrypt => {
+                out.skip(name.as_deref().unwrap_or(line), "DNSCrypt is not supported")
+            }
+            Ok(stamp) => {
+                let mut entry = ServerEntry::from_address(line);
+                entry.provider = name.clone();
+                if stamp.dnssec {
+                    entry.tags.push(ServerTag::Dnssec);
+                }
+                if stamp.no_logs {
+                    entry.tags.push(ServerTag::NoLog);
+                }
+                if !stamp.no_filter {
+                    entry.tags.push(ServerTag::Filtering);
+                }
+                out.push(entry);
+            }
+            Err(e) => out.skip(line, &e),
+        }
+    }
+}
+
+// AdGuard's providers JSON: a list of providers (or {"providers": [...]}), each with a
+// name and servers given as upstream strings or objects with an "upstream" field.
+fn parse_adguard_json(text: &str, out: &mut Collector) -> Result<(), String> {
+    let root: Value = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let providers = match &root {
+        Value::Array(items) => items.clone(),
+        Value::Object(map) => match map.get("providers") {
+            Some(Value::Array(items)) => items.clone(),
+            Some(Value::Object(by_id)) => by_id.values().cloned().collect(),
+            _ => return Err("Expected a \"providers\" list".to_string()),
+        },
+        _ => return Err("Expected a JSON list or object".to_string()),
+    };
+    for provider in &providers {
+        let name = ["name", "title"]
+            .iter()
+            .find_map(|k| provider.get(k).and_then(Value::as_str))
+            .map(str::to_string);
+        let servers = ["servers", "upstreams", "dns_servers"]
+            .iter()
+            .find_map(|k| provider.get(k).and_then(Value::as_array))
+            .cloned()
+            .unwrap_or_default();
+        for server in &servers {
+            let upstream = match server {
+                Value::String(s) => Some(s.as_str()),
+                other => ["upstream", "address", "url"]
+                    .iter()
+                    .find_map(|k| other.get(k).and_then(Value::as_str)),
+            };
+            let Some(upstream) = upstream.map(str::trim) else {
+                out.skip(
+                    name.as_deref().unwrap_or("provider"),
+                    "server without upstream",
+                );
+                continue;
+            };
+            let address = upstream
+                .strip_prefix("udp://")
+                .or_else(|| upstream.strip_prefix("tcp://"))
+                .unwrap_or(upstream);
+            if address.starts_with("sdns://")
+                && decode_stamp(address).map(|s| s.protocol) == Ok(StampProtocol::DnsCrypt)
+            {
+                out.skip(address, "DNSCrypt is not supported");
+                continue;
+            }
+            out.push_address(address.to_string(), name.clone());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn parse_resolv_conf(text: &str, out: &mut Collector) -> Result<(), String> {
+    let (config, _) = hickory_resolver::system_conf::parse_resolv_conf(text)
+        .map_err(|e| format!("Invalid resolv.conf: {}", e))?;
+    // Each nameserver appears once per protocol (UDP and TCP)
+    for ns in config.name_servers() {
+        let addr = ns.socket_addr;
+        let address = plain_or_tls(&addr.ip().to_string(), Some(addr.port()), false, None);
+        if !out.seen.contains(&address) {
+            out.push_address(address, None);
+        }
+    }
+    Ok(())
+}
+
+// hickory only parses resolv.conf on Unix; elsewhere read the nameserver lines directly.
+#[cfg(not(unix))]
+fn parse_resolv_conf(text: &str, out: &mut Collector) -> Result<(), String> {
+    for line in text.lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("nameserver") {
+            let value = value.trim();
+            match value.parse::<std::net::IpAddr>() {
+                Ok(ip) => out.push_address(ip.to_string(), None),
+                Err(_) => out.skip(value, "not an IP address"),
+            }
+        }
+    }
+    Ok(())
+}
+
+// forward-zone blocks: forward-addr ip[@port][#name], forward-host, forward-tls-upstream.
+fn parse_unbound(text: &str, out: &mut Collector) {
+    let mut zone: Vec<String> = Vec::new();
+    let mut tls = false;
+    for line in text.lines().map(strip_unbound_comment) {
+        if line.starts_with("forward-zone:") {
+            flush_unbound_zone(&mut zone, tls, out);
+            tls = false;
+        } else if let Some(v) =
+            unbound_value(line, "forward-addr:").or_else(|| unbound_value(line, "forward-host:"))
+        {
+            zone.push(v);
+        } else if let Some(v) = unbound_value(line, "forward-tls-upstream:")
+            .or_else(|| unbound_value(line, "forward-ssl-upstream:"))
+        {
+            tls = v == "yes";
+        }
+    }
+    flush_unbound_zone(&mut zone, tls, out);
+}
+
+// '#' starts a comment only at the start of a line or after whitespace;
+// in "1.1.1.1@853#cloudflare-dns.com" it introduces the TLS name.
+fn strip_unbound_comment(line: &str) -> &str {
+    let bytes = line.as_bytes();
+    let end = (0..bytes.len())
+        .find(|&i| bytes[i] == b'#' && (i == 0 || bytes[i - 1].is_ascii_whitespace()))
+        .unwrap_or(bytes.len());
+    line[..end].trim()
+}
+
+fn unbound_value(line: &str, key: &str) -> Option<String> {
+    line.strip_prefix(key)
+        .map(|v| v.trim().trim_matches('"').to_string())
+}
+
+fn flush_unbound_zone(zone: &mut Vec<String>, tls: bool, out: &mut Collector) {
+    for value in zone.drain(..) {
+        let (rest, name) = match value.split_once('#') {
+            Some((r, n)) => (r, Some(n)),
+            None => (value.as_str(), None),
+        };
+        let (ip, port) = match rest.split_once('@') {
+            Some((ip, p)) => match p.parse::<u16>() {
+                Ok(port) => (ip, Some(port)),
+                Err(_) => {
+                    out.skip(&value, "invalid port");
+                    continue;
+                }
+            },
+            None => (rest, None),
+        };
+        out.push_address(plain_or_tls(ip, port, tls, name), None);
+    }
+}
+
+// server=ip[#port][@source]. Domain-scoped entries (server=/domain/ip) only answer for
+// those domains, so they are skipped like local-only ones.
+fn parse_dnsmasq(text: &str, out: &mut Collector) {
+    for line in text.lines().map(str::trim) {
+        let Some(value) = line.strip_prefix("server=") else {
+            continue;
+        };
+        if let Some(scoped) = value.strip_prefix('/') {
+            let domains = scoped.rsplit_once('/').map_or(scoped, |(d, _)| d);
+            out.skip(
+                value,
+                &format!("only used for {}", domains.replace('/', ", ")),
+            );
+            continue;
+        }
+        if value.is_empty() || value == "#" {
+            out.skip(line, "no upstream address");
+            continue;
+        }
+        let target = value.split('@').next().unwrap_or(value);
+        let (ip, port) = match target.split_once('#') {
+            Some((ip, p)) => match p.parse::<u16>() {
+                Ok(port) => (ip, Some(port)),
+                Err(_) => {
+                    out.skip(value, "invalid port");
+                    continue;
+                }
+            },
+            None => (target, None),
+        };
+        out.push_address(plain_or_tls(ip, port, false, None), None);
+    }
+}
+
+// [Resolve] DNS= / FallbackDNS= entries: ip[:port][%iface][#name]; DNSOverTLS=yes makes them DoT.
+fn parse_resolved_conf(text: &str, out: &mut Collector) {
+    let mut values: Vec<String> = Vec::new();
+    let mut tls = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key.trim() {
+            "DNS" | "FallbackDNS" => values.extend(value.split_whitespace().map(str::to_string)),
+            "DNSOverTLS" => tls = matches!(value.trim(), "yes" | "true" | "1"),
+            _ => {}
+        }
+    }
+    for value in values {
+        let (rest, name) = match value.split_once('#') {
+            Some((r, n)) => (r, Some(n)),
+            None => (value.as_str(), None),
+        };
+        let rest = rest.split('%').next().unwrap_or(rest);
+        let parsed = if rest.starts_with('[') || rest.matches(':').count() == 1 {
+            split_host_port(rest, if tls { 853 } else { 53 }).map(|(ip, port)| (ip, Some(port)))
+        } else {
+            Ok((rest.to_string(), None))
+        };
+        match parsed {
+            Ok((ip, port)) => out.push_address(plain_or_tls(&ip, port, tls, name), None),
+            Err(e) => out.skip(&value, &e),
+        }
+    }
+}
+
+// Splits one CSV record, honouring double quotes ("" inside quotes is a literal quote).
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+// With a header row, columns are matched by name (address/server/upstream/url, provider/name,
+// region/country, tags, notes). Without one, the first column is the address and the second
+// the provider.
+fn parse_csv(text: &str, out: &mut Collector) -> Result<(), String> {
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .peekable();
+    let first = lines.peek().map(|l| csv_fields(l)).ok_or("Empty CSV")?;
+    let find = |names: &[&str]| {
+        first
+            .iter()
+            .position(|h| names.contains(&h.to_ascii_lowercase().as_str()))
+    };
+    let address_col = find(&["address", "server", "upstream", "url"]);
+    let (address_col, provider_col, region_col, tags_col, notes_col) = match address_col {
+        Some(col) => {
+            lines.next();
+            (
+                col,
+                find(&["provider", "name"]),
+                find(&["region", "country"]),
+                find(&["tags"]),
+                find(&["notes", "comment"]),
+            )
+        }
+        None => (0, Some(1), None, None, None),
+    };
+
+    for line in lines {
+        let fields = csv_fields(line);
+        let get = |col: Option<usize>| {
+            col.and_then(|c| fields.get(c))
+                .filter(|v| !v.is_empty())
+                .cloned()
+        };
+        let Some(address) = get(Some(address_col)) else {
+            out.skip(line, "no address column");
+            continue;
+        };
+        let mut entry = ServerEntry::from_address(&address);
+        entry.provider = get(provider_col);
+        entry.region = get(region_col);
+        entry.notes = get(notes_col);
+        if let Some(tags) = get(tags_col) {
+            for tag in tags.split([';', '|', ' ']).filter(|t| !t.is_empty()) {
+                match serde_json::from_value::<ServerTag>(Value::String(tag.to_ascii_lowercase())) {
+                    Ok(tag) => entry.tags.push(tag),
+                    Err(_) => out.skip(&address, &format!("unknown tag '{}' ignored", tag)),
+                }
+            }
+        }
+        out.push(entry);
+    }
+    Ok(())
+}
+
+fn preview_against(
+    format: ImportFormat,
+    mode: ImportMode,
+    imported: Vec<ServerEntry>,
+    skipped: Vec<String>,
+    current: Vec<ServerEntry>,
+) -> ImportPreview {
+    let current_addrs: HashSet<&str> = current.iter().map(|e| e.address.as_str()).collect();
+    let imported_addrs: HashSet<&str> = imported.iter().map(|e| e.address.as_str()).collect();
+    let (added, already_present): (Vec<String>, Vec<String>) = imported
+        .iter()
+        .map(|e| e.address.clone())
+        .partition(|a| !current_addrs.contains(a.as_str()));
+
+    let (entries, removed) = match mode {
+        ImportMode::Append => {
+            let mut entries = current.clone();
+            entries.extend(
+                imported
+                    .iter()
+                    .filter(|e| !current_addrs.contains(e.address.as_str()))
+                    .cloned(),
+            );
+            (entries, vec![])
+        }
+        ImportMode::Replace => {
+            let removed = current
+                .iter()
+                .filter(|e| !imported_addrs.contains(e.address.as_str()))
+                .map(|e| e.address.clone())
+                .collect();
+            (imported.clone(), removed)
+        }
+    };
+    ImportPreview {
+        format,
+        mode,
+        entries,
+        added,
+        already_present,
+        removed,
+        skipped,
+    }
+}
+
+pub async fn preview_import(
+    format: ImportFormat,
+    text: &str,
+    mode: ImportMode,
+) -> Result<ImportPreview, String> {
+    let (imported, skipped) = parse(format, text)?;
+    Ok(preview_against(
+        format,
+        mode,
+        imported,
+        skipped,
+        servers::get_entries().await,
+    ))
+}
+
+// Same result as the preview, written to the active profile.
+pub async fn apply_import(
+    format: ImportFormat,
+    text: &str,
+    mode: ImportMode,
+) -> Result<ImportPreview, String> {
+    let preview = preview_import(format, text, mode).await?;
+    servers::set_entries(preview.entries.clone()).await?;
+    Ok(preview)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns_tester::stamps::{encode_stamp, DnsStamp};
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+
+    fn addresses(format: ImportFormat, text: &str) -> (Vec<String>, Vec<String>) {
+        let (entries, skipped) = parse(format, text).unwrap();
+        (entries.into_iter().map(|e| e.address).collect(), skipped)
+    }
+
+    #[test]
+    fn dnscrypt_md_keeps_stamp_properties() {
+        let doh = encode_stamp(&DnsStamp {
+            protocol: StampProtocol::Doh,
+            dnssec: true,
+            no_logs: true,
+            no_filter: false,
+            addr: "9.9.9.9".to_string(),
+            hashes: vec![],
+            hostname: "dns.quad9.net".to_string(),
+            path: "/dns-query".to_string(),
+        })
+        .unwrap();
+        // protocol, properties, address, provider key, provider name
+        let mut raw = vec![0x01];
+        raw.extend_from_slice(&[0; 8]);
+        raw.push(7);
+        raw.extend_from_slice(b"1.2.3.4");
+        raw.push(32);
+        raw.extend_from_slice(&[7; 32]);
+        raw.push(11);
+        raw.extend_from_slice(b"2.dnscrypt.");
+        let dnscrypt = format!("sdns://{}", URL_SAFE_NO_PAD.encode(raw));
+        let text = format!(
+            "# public-resolvers\n\n## quad9-doh\nQuad9 over DoH\n\n{}\n\n## crypt\n{}\n\nsdns://!!\n",
+            doh, dnscrypt
+        );
+
+        let (entries, skipped) = parse(ImportFormat::DnscryptMd, &text).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].address, doh);
+        assert_eq!(entries[0].provider.as_deref(), Some("quad9-doh"));
+        assert_eq!(
+            entries[0].tags,
+            vec![ServerTag::Dnssec, ServerTag::NoLog, ServerTag::Filtering]
+        );
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(skipped[0], "crypt: DNSCrypt is not supported");
+        assert!(skipped[1].starts_with("sdns://!!: "), "{}", skipped[1]);
+    }
+
+    #[test]
+    fn adguard_json_reads_strings_and_objects() {
+        let text = r#"{"providers": [{
+            "name": "AdGuard",
+            "servers": [
+                "udp://94.140.14.14",
+                "tls://dns.adguard-dns.com",
+                {"upstream": "https://dns.adguard-dns.com/dns-query"},
+                {"protocol": "dns"},
+                "tcp://94.140.14.14"
+            ]
+        }]}"#;
+        let (entries, skipped) = parse(ImportFormat::AdguardJson, text).unwrap();
+        let addresses: Vec<&str> = entries.iter().map(|e| e.address.as_str()).collect();
+        assert_eq!(
+            addresses,
+            vec![
+                "94.140.14.14",
+                "tls://dns.adguard-dns.com",
+                "https://dns.adguard-dns.com/dns-query"
+            ]
+        );
+        assert!(entries
+            .iter()
+            .all(|e| e.provider.as_deref() == Some("AdGuard")));
+        assert_eq!(skipped, vec!["AdGuard: server without upstream"]);
+        assert!(parse(ImportFormat::AdguardJson, "{").is_err());
+        assert!(parse(ImportFormat::AdguardJson, "{\"servers\": []}").is_err());
+    }
+
+    #[test]
+    fn resolv_conf_reads_nameservers() {
+        let text = "search lan\nnameserver 1.1.1.1\nnameserver 2606:4700:4700::1111\n\
+                    options ndots:2\n";
+        let (found, skipped) = addresses(ImportFormat::ResolvConf, text);
+        assert_eq!(found, vec!["1.1.1.1", "2606:4700:4700::1111"]);
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn unbound_reads_forward_zones() {
+        let text = r#"
+server:
+    verbosity: 1
+forward-zone:
+    name: "."
+    forward-tls-upstream: yes
+    forward-addr: 1.1.1.1@853#cloudflare-dns.com  # primary
+    forward-addr: 2606:4700:4700::1111@8853#cloudflare-dns.com
+forward-zone:
+    name: "."
+    forward-addr: 9.9.9.9
+    forward-addr: 8.8.8.8@x
+"#;
+        let (found, skipped) = addresses(ImportFormat::Unbound, text);
+        assert_eq!(
+            found,
+            vec![
+                "tls://1.1.1.1@cloudflare-dns.com",
+                "tls://[2606:4700:4700::1111]:8853@cloudflare-dns.com",
+                "9.9.9.9",
+            ]
+        );
+        assert_eq!(skipped, vec!["8.8.8.8@x: invalid port"]);
+    }
+
+    #[test]
+    fn dnsmasq_skips_domain_scoped_servers() {
+        let text = "\
+# upstreams
+server=1.1.1.1
+server=2001:db8::1#5353
+server=9.9.9.9@eth0
+server=/corp.example/10.0.0.1
+server=/lan/home.arpa/192.168.1.1#5353
+server=/local/
+server=8.8.8.8#x
+no-resolv
+";
+        let (found, skipped) = addresses(ImportFormat::Dnsmasq, text);
+        assert_eq!(
+            found,
+            vec!["1.1.1.1", "udp://[2001:db8::1]:5353", "9.9.9.9"]
+        );
+        assert_eq!(
+            skipped,
+            vec![
+                "/corp.example/10.0.0.1: only used for corp.example",
+                "/lan/home.arpa/192.168.1.1#5353: only used for lan, home.arpa",
+                "/local/: only used for local",
+                "8.8.8.8#x: invalid port",
+            ]
+        );
+        assert!(parse(ImportFormat::Dnsmasq, "server=/corp/10.0.0.1").is_err());
+    }
+
+    #[test]
+    fn resolved_conf_applies_dns_over_tls() {
+        let text = "\
+[Resolve]
+#DNS=8.8.8.8
+DNS=1.1.1.1#cloudflare-dns.com [2606:4700:4700::1111]:853#cloudflare-dns.com
+FallbackDNS=9.9.9.9%eth0 [::1
+DNSOverTLS=yes
+";
+        let (found, skipped) = addresses(ImportFormat::ResolvedConf, text);
+        assert_eq!(
+            found,
+            vec![
+                "tls://1.1.1.1@cloudflare-dns.com",
+                "tls://[2606:4700:4700::1111]@cloudflare-dns.com",
+                "tls://9.9.9.9",
+            ]
+        );
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].starts_with("[::1: "), "{}", skipped[0]);
+    }
+
+    #[test]
+    fn csv_matches_columns_by_header() {
+        let text = "\
+provider,address,tags,region,notes
+\"Cloudflare, Inc.\",1.1.1.1,no_log;dnssec,US,\"says \"\"hi\"\"\"
+Quad9,tls://dns.quad9.net,bogus,CH,
+Nobody,,,,
+";
+        let (entries, skipped) = parse(ImportFormat::Csv, text).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].address, "1.1.1.1");
+        assert_eq!(entries[0].provider.as_deref(), Some("Cloudflare, Inc."));
+        assert_eq!(entries[0].tags, vec![ServerTag::NoLog, ServerTag::Dnssec]);
+        assert_eq!(entries[0].region.as_deref(), Some("US"));
+        assert_eq!(entries[0].notes.as_deref(), Some("says \"hi\""));
+        assert_eq!(entries[1].address, "tls://dns.quad9.net");
+        assert_eq!(
+            skipped,
+            vec![
+                "tls://dns.quad9.net: unknown tag 'bogus' ignored",
+                "Nobody,,,,: no address column",
+            ]
+        );
+
+        let (found, _) = addresses(ImportFormat::Csv, "8.8.8.8,Google\nnot a server,X\n");
+        assert_eq!(found, vec!["8.8.8.8"]);
+    }
+}
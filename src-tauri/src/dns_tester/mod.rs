@@ -2,62 +2,122 @@ use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOp
 use hickory_resolver::TokioResolver;
 use hickory_resolver::Resolver;
 use hickory_resolver::name_server::TokioConnectionProvider;
+use hickory_resolver::proto::rr::{RData, RecordType};
+use hickory_resolver::system_conf::read_system_conf;
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
 use futures::{stream, StreamExt};
-use url::Url;
 use tokio::time::timeout;
 use tracing::{error, info, warn};
 use idna::domain_to_ascii;
+use std::collections::BTreeMap;
 use std::net::IpAddr;
 use tokio::runtime::Builder as TokioRtBuilder; // for isolated runtimes with larger stacks
 // reverted: removed host-IP cache to restore direct resolution behavior
 
-mod servers;
+pub mod servers;
 pub use servers::get_servers;
-use servers::set_servers as set_servers_inner;
-mod tls_hosts;
+pub use servers::ServerEntry;
+pub mod tls_hosts;
+pub mod bootstrap;
+pub mod tls_inspect;
+pub mod pinning;
+pub mod stamps;
+pub mod profiles;
+pub mod subscriptions;
+pub mod importers;
+pub mod spec;
+mod sni_discovery;
+mod engine;
+pub mod errors;
 
 use crate::dns_tester::tls_hosts::TLS_HOST_MAP;
-use servers::{init_servers, update_servers_from_url};
+use bootstrap::{BootstrapAnswer, BootstrapCache};
+use engine::{Clock, HickoryFactory, QueryExecutor, ResolverFactory, SystemClock};
+pub use errors::{DnsError, DnsErrorKind};
+use pinning::{PinSet, PinningVerifier};
+use spec::{ServerSpec, SYSTEM_SERVER};
+use stamps::StampProtocol;
+use std::sync::Arc;
+use tls_inspect::{TlsDetails, TlsTarget, TlsTransport};
+use servers::init_servers;
 use tls_hosts::{init_tls_hosts, update_tls_hosts_from_url};
+use crate::exporter;
+use crate::history;
 
 // (no host-IP cache)
 
 // Made sync to avoid creating a temporary runtime in main; it only spawns async work.
 pub fn init_configs() {
-    init_servers();
-    init_tls_hosts();
+    load_configs();
 
     // Kick off remote updates in background to avoid blocking startup
-    let dns_list_url =
-        "https://raw.githubusercontent.com/ednoct/DNS_SERVERS/main/servers.txt".to_string();
-    tauri::async_runtime::spawn(async move {
-        if let Err(e) = update_servers_from_url(&dns_list_url).await {
-            warn!("Could not update DNS servers from URL: {}", e);
-        }
+    crate::spawn(async move {
+        subscriptions::refresh_all().await;
     });
 
-    tauri::async_runtime::spawn(async move {
+    crate::spawn(async move {
         if let Err(e) = update_tls_hosts_from_url().await {
             warn!("Could not update TLS hosts from URL: {}", e);
         }
     });
 }
 
-#[tauri::command]
-pub async fn get_dns_servers() -> Vec<String> {
-    get_servers().await
+// Saved servers and TLS names only, without network updates (used by the CLI).
+pub fn load_configs() {
+    init_servers();
+    init_tls_hosts();
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DnsBenchmarkArgs {
+    #[serde(alias = "domainOrIp")]
+    pub domain_or_ip: String,
+    pub samples: Option<u32>,
+    #[serde(alias = "timeoutSecs")]
+    pub timeout_secs: Option<u64>,
+    #[serde(alias = "customServers")]
+    pub custom_servers: Option<Vec<String>>,
+    #[serde(alias = "validateDnssec")]
+    pub validate_dnssec: Option<bool>,
+    #[serde(alias = "warmUp")]
+    pub warm_up: Option<bool>,
+    #[serde(alias = "inspectTls")]
+    pub inspect_tls: Option<bool>,
+    #[serde(alias = "recordType")]
+    pub record_type: Option<String>,
+    // Measure the system resolvers as the vs_system baseline (default true)
+    #[serde(alias = "includeSystem")]
+    pub include_system: Option<bool>,
 }
 
-#[tauri::command]
-pub async fn set_dns_servers(servers: Vec<String>) -> Result<(), String> {
-    set_servers_inner(servers).await;
-    Ok(())
+// Runs a benchmark and stores it in the history database.
+pub async fn run_dns_benchmark(args: DnsBenchmarkArgs) -> Vec<DnsTestResult> {
+    let started_at = history::now_ms();
+    let params = serde_json::to_value(&args).unwrap_or_default();
+    let (domain, record_type) = (args.domain_or_ip.clone(), args.record_type.clone());
+    let results = perform_dns_benchmark(
+        args.domain_or_ip,
+        args.custom_servers,
+        args.timeout_secs,
+        args.samples,
+        args.validate_dnssec,
+        args.warm_up,
+        args.inspect_tls,
+        args.record_type,
+        args.include_system,
+    )
+    .await;
+    history::record_dns_run(history::RunSource::Manual, started_at, params, &results).await;
+    exporter::observe_dns(&domain, record_type.as_deref(), &results).await;
+    results
 }
+
+// Fields added after the first release default when missing, so older exports still load.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DnsTestResult {
     pub server_address: String,
+    #[serde(default)]
+    pub provider: Option<String>,
     // Back-compat single-measurement fields (now represent averages)
     pub resolution_time_ms: Option<u128>,
     pub query_successful: bool,
@@ -71,9 +131,169 @@ pub struct DnsTestResult {
     pub ipv6_ips: Vec<String>,
     pub error_msg: Option<String>,
     pub avg_time: Option<f64>,
+    // Hostname -> IP resolution done before connecting (None for IP-based servers)
+    #[serde(default)]
+    pub bootstrap_ms: Option<f64>,
+    #[serde(default)]
+    pub bootstrap_ips: Vec<String>,
+    // Certificate chain and negotiated session for DoT/DoH/DoQ servers
+    #[serde(default)]
+    pub tls_details: Option<TlsDetails>,
+    // Average latency relative to the "system" baseline (negative = faster)
+    #[serde(default)]
+    pub vs_system_ms: Option<f64>,
+    #[serde(default)]
+    pub vs_system_percent: Option<f64>,
+    // Every measured query in order, including failed ones
+    #[serde(default)]
+    pub samples: Vec<Sample>,
+    // Unique answer data other than A/AAAA (PTR names, MX, TXT, ...)
+    #[serde(default)]
+    pub answers: Vec<String>,
+    // An answer failed DNSSEC validation (only meaningful with validation enabled)
+    #[serde(default)]
+    pub dnssec_bogus: bool,
+    // Validation was on and every answer record was proven secure
+    #[serde(default)]
+    pub dnssec_secure: bool,
+    // Cause of `error_msg`
+    #[serde(default)]
+    pub error_kind: Option<DnsErrorKind>,
+    // Failed samples by cause, e.g. {"timeout": 3, "servfail": 1}
+    #[serde(default)]
+    pub error_counts: BTreeMap<DnsErrorKind, u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Sample {
+    pub latency_ms: f64,
+    pub ok: bool,
+    #[serde(default)]
+    pub error: Option<DnsErrorKind>,
+}
+
+impl DnsTestResult {
+    pub(crate) fn failure(server_address: String, dnssec_enabled: bool, error: DnsError) -> Self {
+        DnsTestResult {
+            server_address,
+            provider: None,
+            resolution_time_ms: None,
+            query_successful: false,
+            latency_avg_ms: None,
+            jitter_avg_ms: None,
+            success_percent: 0.0,
+            dnssec_validated: false,
+            dnssec_enabled,
+            ipv4_ips: vec![],
+            ipv6_ips: vec![],
+            error_msg: Some(error.message),
+            avg_time: None,
+            bootstrap_ms: None,
+            bootstrap_ips: vec![],
+            tls_details: None,
+            vs_system_ms: None,
+            vs_system_percent: None,
+            samples: vec![],
+            answers: vec![],
+            dnssec_bogus: false,
+            dnssec_secure: false,
+            error_kind: Some(error.kind),
+            error_counts: BTreeMap::new(),
+        }
+    }
+}
+
+// What each sample asks. Without an explicit record type, names are resolved with
+// lookup_ip (A/AAAA) and IP addresses with a reverse (PTR) lookup.
+#[derive(Clone)]
+struct BenchQuery {
+    name: String,
+    record_type: Option<RecordType>,
 }
 
-#[tauri::command]
+// "", "A/AAAA" and "IP" mean the default lookup_ip behaviour.
+pub fn parse_record_type(s: &str) -> Result<Option<RecordType>, String> {
+    let s = s.trim().to_ascii_uppercase();
+    if s.is_empty() || s == "A/AAAA" || s == "IP" {
+        return Ok(None);
+    }
+    s.parse::<RecordType>()
+        .map(Some)
+        .map_err(|_| format!("Unsupported record type '{}'", s))
+}
+
+// Data from one successful query.
+#[derive(Default)]
+struct QueryAnswer {
+    ipv4: Vec<String>,
+    ipv6: Vec<String>,
+    other: Vec<String>,
+    bogus: bool,
+    // Some record was not proven secure (unsigned, Insecure or Indeterminate)
+    unproven: bool,
+}
+
+impl QueryAnswer {
+    fn is_empty(&self) -> bool {
+        self.ipv4.is_empty() && self.ipv6.is_empty() && self.other.is_empty()
+    }
+
+    fn push_record(&mut self, record: &hickory_resolver::proto::rr::Record) {
+        match record.data() {
+            RData::A(a) => self.ipv4.push(a.to_string()),
+            RData::AAAA(a) => self.ipv6.push(a.to_string()),
+            data => self.other.push(data.to_string()),
+        }
+        self.bogus |= record.proof().is_bogus();
+        self.unproven |= !record.proof().is_secure();
+    }
+}
+
+async fn run_query(
+    resolver: &TokioResolver,
+    query: &BenchQuery,
+    timeout_secs: u64,
+) -> Result<QueryAnswer, DnsError> {
+    let limit = std::time::Duration::from_secs(timeout_secs);
+    let reverse_ip = query
+        .name
+        .parse::<IpAddr>()
+        .ok()
+        .filter(|_| matches!(query.record_type, None | Some(RecordType::PTR)));
+    let lookup = match (reverse_ip, query.record_type) {
+        (Some(ip), _) => timeout(limit, resolver.reverse_lookup(ip))
+            .await
+            .map(|r| r.map(|l| l.as_lookup().clone())),
+        (None, Some(record_type)) => {
+            timeout(limit, resolver.lookup(query.name.as_str(), record_type)).await
+        }
+        (None, None) => timeout(limit, resolver.lookup_ip(query.name.as_str()))
+            .await
+            .map(|r| r.map(|l| l.as_lookup().clone())),
+    };
+    let lookup = match lookup {
+        Ok(Ok(lookup)) => lookup,
+        Ok(Err(e)) => return Err(e.into()),
+        Err(_) => return Err(DnsError::timeout()),
+    };
+    let mut answer = QueryAnswer::default();
+    for record in lookup.records() {
+        answer.push_record(record);
+    }
+    Ok(answer)
+}
+
+// A resolver bound to one benchmarked server, plus how its hostname was bootstrapped.
+pub struct BuiltResolver {
+    pub resolver: TokioResolver,
+    pub bootstrap: Option<BootstrapAnswer>,
+    // Endpoint dialled for DoT/DoH/DoQ, used for certificate/session inspection
+    pub tls_target: Option<TlsTarget>,
+    // Present when the entry carries SPKI/stamp pins
+    pub pin_verifier: Option<Arc<PinningVerifier>>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn perform_dns_benchmark(
     domain_or_ip: String,
     custom_servers: Option<Vec<String>>,
@@ -81,10 +301,24 @@ pub async fn perform_dns_benchmark(
     samples: Option<u32>,
     validate_dnssec: Option<bool>,
     warm_up: Option<bool>,
+    inspect_tls: Option<bool>,
+    record_type: Option<String>,
+    include_system: Option<bool>,
 ) -> Vec<DnsTestResult> {
     // Accept domain or IP. Validate/convert domain (IDNA) off the worker thread.
     let validate_dnssec_flag = validate_dnssec.unwrap_or(false);
     let warm_up_flag = warm_up.unwrap_or(false);
+    let inspect_tls_flag = inspect_tls.unwrap_or(true);
+    let record_type = match parse_record_type(record_type.as_deref().unwrap_or("")) {
+        Ok(t) => t,
+        Err(e) => {
+            return vec![DnsTestResult::failure(
+                "invalid_record_type".to_string(),
+                validate_dnssec_flag,
+                DnsError::new(DnsErrorKind::Other, e),
+            )];
+        }
+    };
     let input_is_ip = domain_or_ip.parse::<IpAddr>().is_ok();
     let ascii_domain: Option<String> = if !input_is_ip {
         match tokio::task::spawn_blocking({
@@ -95,20 +329,11 @@ pub async fn perform_dns_benchmark(
         {
             Ok(Ok(d)) => Some(d),
             _ => {
-                return vec![DnsTestResult {
-                    server_address: "invalid_domain".to_string(),
-                    resolution_time_ms: None,
-                    query_successful: false,
-                    latency_avg_ms: None,
-                    jitter_avg_ms: None,
-                    success_percent: 0.0,
-                    dnssec_validated: false,
-                    dnssec_enabled: validate_dnssec_flag,
-                    ipv4_ips: vec![],
-                    ipv6_ips: vec![],
-                    error_msg: Some("Invalid domain format".to_string()),
-                    avg_time: None,
-                }];
+                return vec![DnsTestResult::failure(
+                    "invalid_domain".to_string(),
+                    validate_dnssec_flag,
+                    DnsError::new(DnsErrorKind::Other, "Invalid domain format"),
+                )];
             }
         }
     } else {
@@ -120,51 +345,74 @@ pub async fn perform_dns_benchmark(
 
     // If a domain is entered, benchmark standard forward lookups (A/AAAA) for that domain.
     // If an IP is entered, benchmark a reverse (PTR) lookup for that IP.
-    let query_norm = if input_is_ip {
-        domain_or_ip.clone()
-    } else {
-        ascii_domain.unwrap()
+    let query_norm = BenchQuery {
+        name: if input_is_ip {
+            domain_or_ip.clone()
+        } else {
+            ascii_domain.unwrap()
+        },
+        record_type,
     };
 
+    let explicit_servers = custom_servers.is_some();
     let mut servers_list = match custom_servers {
         Some(s) => s,
         None => get_servers().await,
     };
+    // The system's own resolvers are measured as the baseline of every run unless opted out.
+    if include_system.unwrap_or(true)
+        && !servers_list
+            .iter()
+            .any(|s| s.trim().eq_ignore_ascii_case(SYSTEM_SERVER))
+    {
+        servers_list.insert(0, SYSTEM_SERVER.to_string());
+    }
+    // Hostnames are bootstrapped once per run and shared by precheck and benchmark.
+    let bootstrap_cache = Arc::new(BootstrapCache::default());
     // Soft cap to avoid extremely long runs when user has a huge list
     if servers_list.len() > 120 {
         servers_list.truncate(120);
     }
     // Early reachability precheck: quickly test servers with a shorter timeout and skip unresponsive ones.
-    let precheck_timeout = std::cmp::min(3, timeout);
-    let query_for_pre = query_norm.clone();
-    let prechecked: Vec<(String, bool)> = stream::iter(servers_list.iter().cloned().map(|server| {
-        let q = query_for_pre.clone();
-        let validate = validate_dnssec_flag;
-        async move {
-            let ok = precheck_server(&q, &server, precheck_timeout, validate).await;
-            (server, ok)
+    // Only the default list is trimmed; servers asked for explicitly (monitor, CLI) are always
+    // benchmarked, so a server that is down shows up as failed instead of disappearing.
+    if !explicit_servers {
+        let precheck_timeout = std::cmp::min(3, timeout);
+        let query_for_pre = query_norm.clone();
+        let prechecked: Vec<(String, bool)> = stream::iter(servers_list.iter().cloned().map(|server| {
+            let q = query_for_pre.clone();
+            let validate = validate_dnssec_flag;
+            let bootstrap_cache = bootstrap_cache.clone();
+            async move {
+                let ok =
+                    precheck_server(&q, &server, precheck_timeout, validate, &bootstrap_cache).await;
+                (server, ok)
+            }
+        }))
+        .buffer_unordered(20)
+        .collect()
+        .await;
+
+        // The baseline stays in even when unreachable, so its failure is reported.
+        let filtered: Vec<String> = prechecked
+            .into_iter()
+            .filter(|(s, ok)| *ok || s.trim().eq_ignore_ascii_case(SYSTEM_SERVER))
+            .map(|(s, _)| s)
+            .collect();
+
+        if !filtered.is_empty() {
+            servers_list = filtered;
         }
-    }))
-    .buffer_unordered(20)
-    .collect()
-    .await;
-
-    let filtered: Vec<String> = prechecked
-        .into_iter()
-        .filter(|(_, ok)| *ok)
-        .map(|(s, _)| s)
-        .collect();
-
-    if !filtered.is_empty() {
-        servers_list = filtered;
     }
     // Process servers with bounded concurrency, offloading each server's work
     // into an isolated Tokio runtime with a larger thread stack to avoid worker overflows.
     const CONCURRENCY: usize = 10;
-    stream::iter(servers_list.into_iter().map(|server| {
+    let providers = servers::provider_map().await;
+    let mut results: Vec<DnsTestResult> = stream::iter(servers_list.into_iter().map(|server| {
         let query_clone = query_norm.clone();
         let validate_dnssec_flag = validate_dnssec_flag;
         let warm_up_flag = warm_up_flag;
+        let bootstrap_cache = bootstrap_cache.clone();
         async move {
             tokio::task::spawn_blocking(move || {
                 run_server_benchmark_in_isolated_rt(
@@ -174,65 +422,92 @@ pub async fn perform_dns_benchmark(
                     sample_count,
                     validate_dnssec_flag,
                     warm_up_flag,
+                    inspect_tls_flag,
+                    bootstrap_cache,
                 )
             })
             .await
-            .unwrap_or_else(|e| DnsTestResult {
-                server_address: "unknown".to_string(),
-                resolution_time_ms: None,
-                query_successful: false,
-                latency_avg_ms: None,
-                jitter_avg_ms: None,
-                success_percent: 0.0,
-                dnssec_validated: false,
-                dnssec_enabled: validate_dnssec_flag,
-                ipv4_ips: vec![],
-                ipv6_ips: vec![],
-                error_msg: Some(format!("Task error: {}", e)),
-                avg_time: None,
+            .unwrap_or_else(|e| {
+                DnsTestResult::failure(
+                    "unknown".to_string(),
+                    validate_dnssec_flag,
+                    DnsError::new(DnsErrorKind::Runtime, format!("Task error: {}", e)),
+                )
             })
         }
     }))
     .buffer_unordered(CONCURRENCY)
     .collect()
-    .await
+    .await;
+
+    for r in results.iter_mut() {
+        r.provider = if r.server_address.trim().eq_ignore_ascii_case(SYSTEM_SERVER) {
+            Some("System resolver".to_string())
+        } else {
+            providers.get(&r.server_address).cloned()
+        };
+    }
+    compare_with_system(&mut results);
+    results
+}
+
+fn compare_with_system(results: &mut [DnsTestResult]) {
+    // A baseline that never answered only measured its timeouts
+    let baseline = results
+        .iter()
+        .find(|r| r.server_address.trim().eq_ignore_ascii_case(SYSTEM_SERVER))
+        .filter(|r| r.query_successful)
+        .and_then(|r| r.latency_avg_ms)
+        .filter(|ms| *ms > 0.0);
+    let Some(baseline) = baseline else {
+        return;
+    };
+    for r in results
+        .iter_mut()
+        .filter(|r| !r.server_address.trim().eq_ignore_ascii_case(SYSTEM_SERVER))
+    {
+        if let Some(ms) = r.latency_avg_ms {
+            r.vs_system_ms = Some(ms - baseline);
+            r.vs_system_percent = Some((ms - baseline) / baseline * 100.0);
+        }
+    }
 }
 
 // Quick reachability check with short timeout, returns true if a basic query succeeds.
 async fn precheck_server(
-    query: &str,
+    query: &BenchQuery,
     server: &str,
     timeout_secs: u64,
     validate_dnssec: bool,
+    bootstrap_cache: &BootstrapCache,
 ) -> bool {
     // Build resolver and attempt one lookup within timeout.
-    let resolver = match build_resolver_for_server(server, timeout_secs, validate_dnssec).await {
-        Ok(r) => r,
-        Err(_) => return false,
-    };
-    // Treat IP input as reverse lookup, otherwise forward lookup.
-    if let Ok(ip) = query.parse::<IpAddr>() {
-        match timeout(std::time::Duration::from_secs(timeout_secs), resolver.reverse_lookup(ip)).await {
-            Ok(Ok(lookup)) => lookup.iter().next().is_some(),
-            _ => false,
-        }
-    } else {
-        match timeout(std::time::Duration::from_secs(timeout_secs), resolver.lookup_ip(query)).await {
-            Ok(Ok(lookup)) => lookup.iter().next().is_some(),
-            _ => false,
-        }
-    }
+    let (resolver, pin_verifier) =
+        match build_resolver_for_server(server, timeout_secs, validate_dnssec, bootstrap_cache)
+            .await
+        {
+            Ok(built) => (built.resolver, built.pin_verifier),
+            Err(_) => return false,
+        };
+    let ok = run_query(&resolver, query, timeout_secs)
+        .await
+        .is_ok_and(|answer| !answer.is_empty());
+    // Keep pin mismatches in the run so they are reported rather than silently dropped.
+    ok || pin_verifier.and_then(|v| v.mismatch()).is_some()
 }
 
 // Runs the async per-server benchmark inside a dedicated Tokio runtime with a larger
 // thread stack size. This avoids deep stack use on shared worker threads.
+#[allow(clippy::too_many_arguments)]
 fn run_server_benchmark_in_isolated_rt(
-    query: String,
+    query: BenchQuery,
     server_address: String,
     timeout_secs: u64,
     samples: usize,
     validate_dnssec: bool,
     warm_up: bool,
+    inspect_tls: bool,
+    bootstrap_cache: Arc<BootstrapCache>,
 ) -> DnsTestResult {
     // 4 MiB stack to be safe on Windows for TLS/ASN.1/h3 parsing paths
     let rt = TokioRtBuilder::new_current_thread()
@@ -242,179 +517,123 @@ fn run_server_benchmark_in_isolated_rt(
     let rt = match rt {
         Ok(rt) => rt,
         Err(e) => {
-            return DnsTestResult {
+            return DnsTestResult::failure(
                 server_address,
-                resolution_time_ms: None,
-                query_successful: false,
-                latency_avg_ms: None,
-                jitter_avg_ms: None,
-                success_percent: 0.0,
-                dnssec_validated: false,
-                dnssec_enabled: validate_dnssec,
-                ipv4_ips: vec![],
-                ipv6_ips: vec![],
-                error_msg: Some(format!("Runtime build error: {}", e)),
-                avg_time: None,
-            };
+                validate_dnssec,
+                DnsError::new(DnsErrorKind::Runtime, format!("Runtime build error: {}", e)),
+            );
         }
     };
 
     rt.block_on(async move {
+        let factory = HickoryFactory {
+            bootstrap_cache: &bootstrap_cache,
+        };
         benchmark_single_server(
+            &factory,
+            &SystemClock,
             query,
             server_address,
             timeout_secs,
             samples,
             validate_dnssec,
             warm_up,
+            inspect_tls,
         )
         .await
     })
 }
 
 
-async fn benchmark_single_server(
-    query: String,
+// One server's samples. Resolvers, queries and time come through the engine traits so the
+// timing and statistics can be driven by a simulation in tests.
+#[allow(clippy::too_many_arguments)]
+async fn benchmark_single_server<F: ResolverFactory, C: Clock>(
+    factory: &F,
+    clock: &C,
+    query: BenchQuery,
     server_address: String,
     timeout_secs: u64,
     samples: usize,
     validate_dnssec: bool,
     warm_up: bool,
+    inspect_tls: bool,
 ) -> DnsTestResult {
     info!("Testing server: {}", server_address);
-    let resolver_result = build_resolver_for_server(&server_address, timeout_secs, validate_dnssec).await;
+    let resolver_result = factory.build(&server_address, timeout_secs, validate_dnssec).await;
 
-    let resolver = match resolver_result {
-        Ok(r) => r,
+    let (resolver, bootstrap, tls_target, pin_verifier) = match resolver_result {
+        Ok(built) => (built.executor, built.bootstrap, built.tls_target, built.pin_verifier),
         Err(e) => {
             error!("Resolver build error: {}", e);
-            return DnsTestResult {
-                server_address,
-                resolution_time_ms: None,
-                query_successful: false,
-                latency_avg_ms: None,
-                jitter_avg_ms: None,
-                success_percent: 0.0,
-                dnssec_validated: false,
-                dnssec_enabled: validate_dnssec,
-                ipv4_ips: vec![],
-                ipv6_ips: vec![],
-                error_msg: Some(e.to_string()),
-                avg_time: None,
-            };
+            return DnsTestResult::failure(server_address, validate_dnssec, e);
         }
     };
 
-    let is_ip = query.parse::<IpAddr>().is_ok();
-
     // Optional warm-up query to establish connections (not measured)
     if warm_up {
         let warm_to = std::cmp::min(timeout_secs, 3);
-        if is_ip {
-            if let Ok(ip) = query.parse::<IpAddr>() {
-                let _ = timeout(std::time::Duration::from_secs(warm_to), resolver.reverse_lookup(ip)).await;
-            }
-        } else {
-            let _ = timeout(std::time::Duration::from_secs(warm_to), resolver.lookup_ip(&query)).await;
-        }
+        let _ = resolver.query(&query, warm_to).await;
     }
     let mut latencies_ms: Vec<f64> = Vec::with_capacity(samples);
+    let mut sample_log: Vec<Sample> = Vec::with_capacity(samples);
     let mut successes = 0usize;
-    let mut last_error: Option<String> = None;
+    let mut last_error: Option<DnsError> = None;
+    let mut error_counts: BTreeMap<DnsErrorKind, u32> = BTreeMap::new();
     let mut ipv4_all = Vec::new();
     let mut ipv6_all = Vec::new();
-    // per-record security not available in all versions; aggregate via resolver options below
+    let mut answers_all = Vec::new();
+    let mut dnssec_bogus = false;
+    // Cleared by any answer record without a Secure proof
+    let mut all_secure = true;
 
     for _ in 0..samples {
-        let start = Instant::now();
-        let mut sample_success = false;
-
-        if is_ip {
-            // Proper reverse lookup for IPs
-            match query.parse::<IpAddr>() {
-                Ok(ip) => match timeout(std::time::Duration::from_secs(timeout_secs), resolver.reverse_lookup(ip)).await {
-                    Ok(Ok(lookup)) => {
-                        sample_success = !lookup.iter().next().is_none();
-                        // ReverseLookup security indicator not consistently available across versions.
-                    }
-                    Ok(Err(e)) => {
-                        last_error = Some(e.to_string());
-                    }
-                    Err(_) => {
-                        // timeout
-                        if last_error.is_none() { last_error = Some("Timeout".to_string()); }
-                    }
-                },
-                Err(e) => {
-                    last_error = Some(e.to_string());
+        let start = clock.now();
+        let mut sample_error = None;
+
+        match resolver.query(&query, timeout_secs).await {
+            Ok(mut answer) => {
+                if answer.is_empty() {
+                    sample_error = Some(DnsErrorKind::NoData);
                 }
+                dnssec_bogus |= answer.bogus;
+                all_secure &= !answer.unproven;
+                ipv4_all.append(&mut answer.ipv4);
+                ipv6_all.append(&mut answer.ipv6);
+                answers_all.append(&mut answer.other);
             }
-        } else {
-            // For domains, prefer a single lookup_ip (gathers A/AAAA) with a hard timeout
-            match timeout(std::time::Duration::from_secs(timeout_secs), resolver.lookup_ip(&query)).await {
-                Ok(Ok(lookup)) => {
-                    let mut v4: Vec<String> = Vec::new();
-                    let mut v6: Vec<String> = Vec::new();
-                    for ip in lookup.iter() {
-                        if ip.is_ipv4() { v4.push(ip.to_string()); } else { v6.push(ip.to_string()); }
-                    }
-                    if !v4.is_empty() { sample_success = true; ipv4_all.append(&mut v4); }
-                    if !v6.is_empty() { sample_success = true; ipv6_all.append(&mut v6); }
-                }
-                Ok(Err(e)) => {
-                    if last_error.is_none() { last_error = Some(e.to_string()); }
-                }
-                Err(_) => {
-                    if last_error.is_none() { last_error = Some("Timeout".to_string()); }
+            Err(e) => {
+                sample_error = Some(e.kind);
+                if last_error.is_none() {
+                    last_error = Some(e);
                 }
             }
         }
 
-        let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+        let elapsed_ms = clock.now().duration_since(start).as_secs_f64() * 1000.0;
         latencies_ms.push(elapsed_ms);
-        if sample_success {
-            successes += 1;
+        sample_log.push(Sample {
+            latency_ms: elapsed_ms,
+            ok: sample_error.is_none(),
+            error: sample_error,
+        });
+        match sample_error {
+            Some(kind) => *error_counts.entry(kind).or_default() += 1,
+            None => successes += 1,
         }
     }
 
-    // Compute metrics (median latency + standard deviation for jitter)
-    let latency_avg_ms = if !latencies_ms.is_empty() {
-        let mut sorted = latencies_ms.clone();
-        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        let mid = sorted.len() / 2;
-        let median = if sorted.len() % 2 == 1 {
-            sorted[mid]
-        } else {
-            (sorted[mid - 1] + sorted[mid]) / 2.0
-        };
-        Some(median)
-    } else {
-        None
-    };
-    let jitter_avg_ms = if latencies_ms.len() > 1 {
-        let n = latencies_ms.len() as f64;
-        let mean = latencies_ms.iter().sum::<f64>() / n;
-        let var = latencies_ms
-            .iter()
-            .map(|v| {
-                let d = *v - mean;
-                d * d
-            })
-            .sum::<f64>()
-            / (n - 1.0); // sample standard deviation
-        Some(var.sqrt())
-    } else if latencies_ms.len() == 1 {
-        // With a single sample, define jitter as 0 instead of null for clearer UI
-        Some(0.0)
-    } else {
-        None
-    };
+    // Median latency, and the sample standard deviation as jitter
+    let latency_avg_ms = engine::median(&latencies_ms);
+    let jitter_avg_ms = engine::sample_stddev(&latencies_ms);
 
     // unique IPs
     ipv4_all.sort();
     ipv4_all.dedup();
     ipv6_all.sort();
     ipv6_all.dedup();
+    answers_all.sort();
+    answers_all.dedup();
 
     let success_percent = if samples > 0 {
         (successes as f64) * 100.0 / (samples as f64)
@@ -424,29 +643,55 @@ async fn benchmark_single_server(
 
     let avg_u128 = latency_avg_ms.map(|v| v as u128);
 
+    // A pin mismatch explains every failure; surface it instead of the generic TLS error.
+    if let Some(mismatch) = pin_verifier.as_ref().and_then(|v| v.mismatch()) {
+        last_error = Some(DnsError::new(DnsErrorKind::PinMismatch, mismatch));
+    }
+
+    // Inspect the certificate/session after sampling so the extra handshake is not timed.
+    let tls_details = match tls_target.as_ref() {
+        Some(target) if inspect_tls => Some(tls_inspect::inspect_tls(target, timeout_secs).await),
+        _ => None,
+    };
+
     DnsTestResult {
         server_address,
+        provider: None,
         resolution_time_ms: avg_u128,
         query_successful: successes > 0,
         latency_avg_ms,
         jitter_avg_ms,
         success_percent,
-        // this flag indicates whether validation is enabled; per-record security would require
-        // checking lookup.is_secure(), which is partially accounted for during lookups above.
-        dnssec_validated: resolver.options().validate && successes > 0,
-        dnssec_enabled: resolver.options().validate,
+        // Validation was on and something answered; `dnssec_secure` says whether it was signed
+        dnssec_validated: resolver.validates_dnssec() && successes > 0,
+        dnssec_enabled: resolver.validates_dnssec(),
         ipv4_ips: ipv4_all,
         ipv6_ips: ipv6_all,
-        error_msg: last_error,
+        error_kind: last_error.as_ref().map(|e| e.kind),
+        error_msg: last_error.map(|e| e.message),
         avg_time: latency_avg_ms,
+        bootstrap_ms: bootstrap.as_ref().map(|b| b.elapsed_ms),
+        bootstrap_ips: bootstrap
+            .map(|b| b.ips.iter().map(|ip| ip.to_string()).collect())
+            .unwrap_or_default(),
+        tls_details,
+        vs_system_ms: None,
+        vs_system_percent: None,
+        samples: sample_log,
+        answers: answers_all,
+        dnssec_bogus,
+        dnssec_secure: resolver.validates_dnssec() && successes > 0 && all_secure,
+        error_counts,
     }
 }
 
+// Hostnames are bootstrapped through `bootstrap_cache`; callers outside a run pass a fresh one.
 pub async fn build_resolver_for_server(
     server_address: &str,
     timeout_secs: u64,
     validate_dnssec: bool,
-) -> Result<TokioResolver, Box<dyn std::error::Error + Send + Sync>> {
+    bootstrap_cache: &BootstrapCache,
+) -> Result<BuiltResolver, DnsError> {
     let mut opts = ResolverOpts::default();
     opts.timeout = std::time::Duration::from_secs(timeout_secs);
     opts.validate = validate_dnssec;
@@ -455,197 +700,323 @@ pub async fn build_resolver_for_server(
     // Disable resolver cache to avoid near-zero times after warm-up
     // and measure real network latency rather than in-process cache hits.
     opts.cache_size = 0;
+    let mut bootstrap_answer: Option<BootstrapAnswer> = None;
+    let mut tls_target: Option<TlsTarget> = None;
+    let mut name_from_host_map = false;
+    // Addresses are parsed into a typed spec first, so malformed entries fail here with
+    // a clear message. Optional pins ride along as a '#spki-sha256=...' suffix.
+    let parsed = spec::parse(server_address)?;
+    let mut pins = parsed.pins;
+    let transport = parsed.spec.tls_transport();
+
+    let config = match parsed.spec {
+        ServerSpec::Stamp { decoded: stamp, .. } => {
+            pins.tbs.extend(stamp.hashes.iter().copied());
+            let default_port = match stamp.protocol {
+                StampProtocol::Plain => 53,
+                StampProtocol::Doh => 443,
+                StampProtocol::Dot | StampProtocol::Doq => 853,
+                StampProtocol::DnsCrypt => {
+                    return Err(DnsError::invalid_server("DNSCrypt stamps are not supported"))
+                },
+            };
+            let (hostname, addr_host, port) =
+                stamp.endpoint(default_port).map_err(DnsError::invalid_server)?;
+            let ips = if let Ok(ip) = addr_host.parse::<IpAddr>() {
+                vec![ip]
+            } else {
+                let answer = bootstrap_cache.resolve(&addr_host)
+                    .await
+                    .map_err(DnsError::bootstrap)?;
+                let ips = answer.ips.clone();
+                bootstrap_answer = Some(answer);
+                ips
+            };
 
-    let config = if server_address.starts_with("https://") || server_address.starts_with("h3://") {
-        // Parse full URL, capture host/port/path, and build DoH/H3 with SNI + path
-        let is_h3 = server_address.starts_with("h3://");
-        let url = Url::parse(server_address)?;
-        let host_raw = url.host_str().ok_or("No host in URL")?.to_string();
-        let port = url.port().unwrap_or(443);
-
-        // Build endpoint path (path + optional query); default to /dns-query if empty
-        let mut endpoint = url.path().to_string();
-        if let Some(q) = url.query() {
-            if !endpoint.is_empty() {
-                endpoint.push('?');
-                endpoint.push_str(q);
-            }
-        }
-        if endpoint.is_empty() || endpoint == "/" {
-            endpoint = "/dns-query".to_string();
+            let group = match stamp.protocol {
+                StampProtocol::Doh => {
+                    let endpoint = if stamp.path.is_empty() {
+                        "/dns-query".to_string()
+                    } else {
+                        stamp.path.clone()
+                    };
+                    let mut group =
+                        NameServerConfigGroup::from_ips_https(&ips, port, hostname.clone(), true);
+                    for ns in group.iter_mut() {
+                        ns.http_endpoint = Some(endpoint.clone());
+                    }
+                    group
+                }
+                StampProtocol::Dot => {
+                    NameServerConfigGroup::from_ips_tls(&ips, port, hostname.clone(), true)
+                }
+                StampProtocol::Doq => {
+                    NameServerConfigGroup::from_ips_quic(&ips, port, hostname.clone(), true)
+                }
+                _ => NameServerConfigGroup::from_ips_clear(&ips, port, true),
+            };
+            let transport = match stamp.protocol {
+                StampProtocol::Doh => Some(TlsTransport::Doh),
+                StampProtocol::Dot => Some(TlsTransport::Dot),
+                StampProtocol::Doq => Some(TlsTransport::Doq),
+                _ => None,
+            };
+            tls_target = transport.zip(ips.first()).map(|(transport, ip)| TlsTarget {
+                transport,
+                ip: *ip,
+                port,
+                tls_name: hostname.clone(),
+                name_from_host_map: false,
+                pins: PinSet::default(),
+            });
+            ResolverConfig::from_parts(None, vec![], group)
         }
-
-        // Determine SNI name and IPs for the target host
-        let (tls_dns_name, ips): (String, Vec<IpAddr>) = if let Ok(ip) = host_raw.parse::<IpAddr>() {
-            // IP-based DoH/H3: use TLS_HOST_MAP to get SNI name when possible
-            let tls_name = match TLS_HOST_MAP.get() {
-                Some(cell) => {
-                    let map = cell.read().await;
-                    map.get(&ip.to_string()).cloned().unwrap_or_else(|| {
+        ServerSpec::Doh {
+            host: host_raw,
+            port,
+            endpoint,
+            h3: is_h3,
+        } => {
+            // Determine SNI name and IPs for the target host
+            let (tls_dns_name, ips): (String, Vec<IpAddr>) = if let Ok(ip) = host_raw.parse::<IpAddr>() {
+                // IP-based DoH/H3: use TLS_HOST_MAP (or SNI discovery) to get SNI name when possible
+                let tls_name = match tls_name_for_ip(&ip, TlsTransport::Doh, port, timeout_secs).await {
+                    Some(name) => {
+                        name_from_host_map = true;
+                        name
+                    }
+                    None => {
                         warn!(
-                            "No TLS host map for IP: {}. Using IP as SNI name; TLS may fail.",
+                            "No TLS name known or discovered for IP: {}. Using IP as SNI name; TLS may fail.",
                             host_raw
                         );
                         host_raw.clone()
-                    })
-                }
-                None => {
-                    warn!("TLS host map not initialized. Using IP as SNI name.");
-                    host_raw.clone()
-                }
+                    }
+                };
+                (tls_name, vec![ip])
+            } else {
+                // Domain host: SNI is host itself; resolve to IPs with the bootstrap strategy
+                let answer = bootstrap_cache.resolve(&host_raw)
+                    .await
+                    .map_err(DnsError::bootstrap)?;
+                let ips = answer.ips.clone();
+                bootstrap_answer = Some(answer);
+                (host_raw.clone(), ips)
             };
-            (tls_name, vec![ip])
-        } else {
-            // Domain host: SNI is host itself; resolve to IPs with system resolver
-            let sys_resolver = Resolver::builder_with_config(
-                ResolverConfig::default(),
-                TokioConnectionProvider::default(),
-            )
-            .build();
-            let response = sys_resolver.lookup_ip(&host_raw).await?;
-            let ips: Vec<IpAddr> = response.iter().collect();
-            (host_raw.clone(), ips)
-        };
-
-        // Build NameServerConfigGroup for DoH (HTTPS). Treat any h3:// as HTTPS fallback.
-        if is_h3 {
-            warn!("H3 scheme provided but H3 support is disabled; using HTTPS fallback");
-        }
-        let mut group = NameServerConfigGroup::from_ips_https(&ips, port, tls_dns_name, true);
-        for ns in group.iter_mut() {
-            ns.http_endpoint = Some(endpoint.clone());
-        }
 
-        ResolverConfig::from_parts(None, vec![], group)
-    } else if let Some(stripped) = server_address.strip_prefix("tls://") {
-        // Support optional SNI override via '@': tls://<host>[:port]@<sni>
-        let (host_part, sni_override) = match stripped.split_once('@') {
-            Some((left, right)) => (left, Some(right.to_string())),
-            None => (stripped, None),
-        };
-        let (host_str, port) = host_part.split_once(':').unwrap_or((host_part, "853"));
-        let port_num: u16 = port.parse()?;
-
-        let tls_dns_name = if let Some(sni) = sni_override {
-            sni
-        } else if let Ok(ip) = host_str.parse::<IpAddr>() {
-            match TLS_HOST_MAP.get() {
-                Some(cell) => {
-                    let map = cell.read().await;
-                    map.get(&ip.to_string()).cloned().unwrap_or_else(|| {
-                        warn!(
-                            "No TLS host map for IP: {}. Using IP as name, may fail.",
-                            host_str
-                        );
-                        host_str.to_string()
-                    })
-                }
-                None => {
-                    warn!("TLS host map not initialized. Using IP as TLS name.");
-                    host_str.to_string()
-                }
+            // Build NameServerConfigGroup for DoH (HTTPS). Treat any h3:// as HTTPS fallback.
+            if is_h3 {
+                warn!("H3 scheme provided but H3 support is disabled; using HTTPS fallback");
+            }
+            tls_target = ips.first().map(|ip| TlsTarget {
+                transport: TlsTransport::Doh,
+                ip: *ip,
+                port,
+                tls_name: tls_dns_name.clone(),
+                name_from_host_map,
+                pins: PinSet::default(),
+            });
+            let mut group = NameServerConfigGroup::from_ips_https(&ips, port, tls_dns_name, true);
+            for ns in group.iter_mut() {
+                ns.http_endpoint = Some(endpoint.clone());
             }
-        } else {
-            host_str.to_string()
-        };
-
-        let ips = if let Ok(ip) = host_str.parse::<IpAddr>() {
-            vec![ip]
-        } else {
-            let sys_resolver = Resolver::builder_with_config(
-                ResolverConfig::default(),
-                TokioConnectionProvider::default(),
-            )
-            .build();
-            let response = sys_resolver.lookup_ip(host_str).await?;
-            response.iter().collect()
-        };
 
-        ResolverConfig::from_parts(
-            None,
-            vec![],
-            NameServerConfigGroup::from_ips_tls(&ips, port_num, tls_dns_name, true),
-        )
-    } else if let Some(stripped) = server_address.strip_prefix("quic://") {
-        // Support optional SNI override via '@': quic://<host>[:port]@<sni>
-        let (host_part, sni_override) = match stripped.split_once('@') {
-            Some((left, right)) => (left, Some(right.to_string())),
-            None => (stripped, None),
-        };
-        let (host_str, port) = host_part.split_once(':').unwrap_or((host_part, "853"));
-        let port_num: u16 = port.parse()?;
-
-        // Use TLS host mapping for QUIC as well when given an IP
-        let tls_dns_name = if let Some(sni) = sni_override {
-            sni
-        } else if let Ok(ip) = host_str.parse::<IpAddr>() {
-            match TLS_HOST_MAP.get() {
-                Some(cell) => {
-                    let map = cell.read().await;
-                    map.get(&ip.to_string()).cloned().unwrap_or_else(|| {
+            ResolverConfig::from_parts(None, vec![], group)
+        }
+        // tls://<host>[:port][@<sni>] and quic://<host>[:port][@<sni>]
+        ServerSpec::Dot {
+            host: host_str,
+            port: port_num,
+            tls_name: sni_override,
+        }
+        | ServerSpec::Doq {
+            host: host_str,
+            port: port_num,
+            tls_name: sni_override,
+        } => {
+            let transport = transport.unwrap_or(TlsTransport::Dot);
+            let tls_dns_name = if let Some(sni) = sni_override {
+                sni
+            } else if let Ok(ip) = host_str.parse::<IpAddr>() {
+                match tls_name_for_ip(&ip, transport, port_num, timeout_secs).await {
+                    Some(name) => {
+                        name_from_host_map = true;
+                        name
+                    }
+                    None => {
                         warn!(
-                            "No TLS host map for IP: {}. Using IP as name, may fail.",
+                            "No TLS name known or discovered for IP: {}. Using IP as name, may fail.",
                             host_str
                         );
-                        host_str.to_string()
-                    })
-                }
-                None => {
-                    warn!("TLS host map not initialized. Using IP as TLS name.");
-                    host_str.to_string()
+                        host_str.clone()
+                    }
                 }
-            }
-        } else {
-            host_str.to_string()
-        };
+            } else {
+                host_str.clone()
+            };
 
-        let ips = if let Ok(ip) = host_str.parse::<IpAddr>() {
-            vec![ip]
-        } else {
-            let sys_resolver = Resolver::builder_with_config(
-                ResolverConfig::default(),
-                TokioConnectionProvider::default(),
-            )
-            .build();
-            let response = sys_resolver.lookup_ip(host_str).await?;
-            response.iter().collect()
-        };
+            let ips = if let Ok(ip) = host_str.parse::<IpAddr>() {
+                vec![ip]
+            } else {
+                let answer = bootstrap_cache.resolve(&host_str)
+                    .await
+                    .map_err(DnsError::bootstrap)?;
+                let ips = answer.ips.clone();
+                bootstrap_answer = Some(answer);
+                ips
+            };
 
-        ResolverConfig::from_parts(
-            None,
-            vec![],
-            NameServerConfigGroup::from_ips_quic(&ips, port_num, tls_dns_name, true),
-        )
-    } else {
-        // UDP: accept either an IP or a hostname and resolve it first
-        if let Ok(ip) = server_address.parse::<IpAddr>() {
-            ResolverConfig::from_parts(
-                None,
-                vec![],
-                NameServerConfigGroup::from_ips_clear(&[ip], 53, true),
-            )
-        } else {
-            let sys_resolver = Resolver::builder_with_config(
-                ResolverConfig::default(),
-                TokioConnectionProvider::default(),
-            )
-            .build();
-            let response = sys_resolver.lookup_ip(server_address).await?;
-            let ips: Vec<IpAddr> = response.iter().collect();
+            tls_target = ips.first().map(|ip| TlsTarget {
+                transport,
+                ip: *ip,
+                port: port_num,
+                tls_name: tls_dns_name.clone(),
+                name_from_host_map,
+                pins: PinSet::default(),
+            });
+            let group = if transport == TlsTransport::Doq {
+                NameServerConfigGroup::from_ips_quic(&ips, port_num, tls_dns_name, true)
+            } else {
+                NameServerConfigGroup::from_ips_tls(&ips, port_num, tls_dns_name, true)
+            };
+            ResolverConfig::from_parts(None, vec![], group)
+        }
+        ServerSpec::System => {
+            // Nameservers, search domains and ndots/edns0 from the OS configuration
+            // (/etc/resolv.conf on Linux); timeouts and caching stay as configured above.
+            let (config, system_opts) = read_system_conf()
+                .map_err(|e| {
+                    DnsError::new(
+                        DnsErrorKind::Other,
+                        format!("System resolver configuration unavailable: {}", e),
+                    )
+                })?;
+            opts.ndots = system_opts.ndots;
+            opts.edns0 = system_opts.edns0;
+            info!(
+                "System resolver: {:?}",
+                config
+                    .name_servers()
+                    .iter()
+                    .map(|ns| ns.socket_addr)
+                    .collect::<std::collections::HashSet<_>>()
+            );
+            config
+        }
+        ServerSpec::Udp { host, port } => {
+            // UDP: accept either an IP or a hostname and resolve it first
+            let ips = if let Ok(ip) = host.parse::<IpAddr>() {
+                vec![ip]
+            } else {
+                let answer = bootstrap_cache.resolve(&host)
+                    .await
+                    .map_err(DnsError::bootstrap)?;
+                let ips = answer.ips.clone();
+                bootstrap_answer = Some(answer);
+                ips
+            };
             ResolverConfig::from_parts(
                 None,
                 vec![],
-                NameServerConfigGroup::from_ips_clear(&ips, 53, true),
+                NameServerConfigGroup::from_ips_clear(&ips, port, true),
             )
         }
     };
 
+    let pin_verifier = if pins.is_empty() {
+        #[cfg(feature = "test-support")]
+        if tls_inspect::has_extra_roots() {
+            opts.tls_config = tls_inspect::resolver_client_config(tls_inspect::platform_verifier())
+                .map_err(|e| DnsError::new(DnsErrorKind::Runtime, e.to_string()))?;
+        }
+        None
+    } else {
+        let target = tls_target
+            .as_mut()
+            .ok_or_else(|| {
+                DnsError::invalid_server("Pins can only be used with DoT/DoH/DoQ servers")
+            })?;
+        target.pins = pins.clone();
+        let verifier = Arc::new(PinningVerifier::new(pins));
+        opts.tls_config = tls_inspect::resolver_client_config(verifier.clone())
+            .map_err(|e| DnsError::new(DnsErrorKind::Runtime, e.to_string()))?;
+        Some(verifier)
+    };
+
     let resolver_builder = Resolver::builder_with_config(config, TokioConnectionProvider::default())
         .with_options(opts);
 
     // Offload resolver build (may do heavier sync init) to a blocking thread.
     let built = tokio::task::spawn_blocking(move || resolver_builder.build())
         .await
-        .map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { Box::new(e) })?;
-    Ok(built)
+        .map_err(|e| DnsError::new(DnsErrorKind::Runtime, e.to_string()))?;
+    Ok(BuiltResolver {
+        resolver: built,
+        bootstrap: bootstrap_answer,
+        tls_target,
+        pin_verifier,
+    })
+}
+
+// SNI/verification name for an IP-only encrypted server: TLS_HOST_MAP first,
+// then a discovery probe of the server's certificate.
+pub(crate) async fn tls_name_for_ip(
+    ip: &IpAddr,
+    transport: TlsTransport,
+    port: u16,
+    timeout_secs: u64,
+) -> Option<String> {
+    let known = match TLS_HOST_MAP.get() {
+        Some(cell) => cell.read().await.get(&ip.to_string()).cloned(),
+        None => {
+            warn!("TLS host map not initialized.");
+            None
+        }
+    };
+    match known {
+        Some(name) => Some(name),
+        None => sni_discovery::discover_tls_name(transport, *ip, port, timeout_secs).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measured(server: &str, latency_ms: f64, successful: bool) -> DnsTestResult {
+        let mut result = DnsTestResult::failure(
+            server.to_string(),
+            false,
+            DnsError::new(DnsErrorKind::Other, ""),
+        );
+        result.latency_avg_ms = Some(latency_ms);
+        result.query_successful = successful;
+        result
+    }
+
+    #[test]
+    fn servers_are_compared_with_a_working_baseline() {
+        let mut results = vec![
+            measured(SYSTEM_SERVER, 40.0, true),
+            measured("1.1.1.1", 30.0, true),
+        ];
+        compare_with_system(&mut results);
+
+        assert_eq!(results[0].vs_system_ms, None);
+        assert_eq!(results[1].vs_system_ms, Some(-10.0));
+        assert_eq!(results[1].vs_system_percent, Some(-25.0));
+    }
+
+    #[test]
+    fn a_failed_baseline_is_not_compared_against() {
+        // all samples timed out at 2 s
+        let mut results = vec![
+            measured(SYSTEM_SERVER, 2000.0, false),
+            measured("1.1.1.1", 30.0, true),
+        ];
+        compare_with_system(&mut results);
+
+        assert_eq!(results[1].vs_system_ms, None);
+        assert_eq!(results[1].vs_system_percent, None);
+    }
 }
@@ -0,0 +1,264 @@
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use super::tls_inspect::platform_verifier;
+
+// Pins are attached to DoT/DoH/DoQ entries as a fragment:
+//   tls://1.1.1.1@cloudflare-dns.com#spki-sha256=<base64>
+//   https://dns.google/dns-query#spki-sha256=<b64>&spki-sha256=<backup b64>
+// DNS stamps carry SHA-256 hashes of the TBS certificate instead, kept as `tbs-sha256`.
+const SPKI_KEY: &str = "spki-sha256";
+const TBS_KEY: &str = "tbs-sha256";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PinSet {
+    pub spki: Vec<[u8; 32]>,
+    pub tbs: Vec<[u8; 32]>,
+}
+
+impl PinSet {
+    pub fn is_empty(&self) -> bool {
+        self.spki.is_empty() && self.tbs.is_empty()
+    }
+
+    // Inverse of `split_pins` (without the leading '#'), base64-encoded.
+    pub fn to_fragment(&self) -> String {
+        let spki = self.spki.iter().map(|d| (SPKI_KEY, d));
+        let tbs = self.tbs.iter().map(|d| (TBS_KEY, d));
+        spki.chain(tbs)
+            .map(|(key, digest)| format!("{}={}", key, STANDARD.encode(digest)))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    // True if the leaf or any intermediate carries a pinned key (or TBS hash).
+    pub fn matches(&self, chain: &[CertificateDer<'_>]) -> bool {
+        chain.iter().any(|der| match cert_digests(der.as_ref()) {
+            Some((spki, tbs)) => self.spki.contains(&spki) || self.tbs.contains(&tbs),
+            None => false,
+        })
+    }
+}
+
+// Splits `address#spki-sha256=...` into the bare address and its pins.
+pub fn split_pins(address: &str) -> Result<(&str, PinSet), String> {
+    let Some((bare, fragment)) = address.split_once('#') else {
+        return Ok((address, PinSet::default()));
+    };
+    let mut pins = PinSet::default();
+    for part in fragment.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid pin parameter: {}", part))?;
+        let digest = parse_digest(value).ok_or_else(|| format!("Invalid pin value: {}", value))?;
+        match key {
+            SPKI_KEY => pins.spki.push(digest),
+            TBS_KEY => pins.tbs.push(digest),
+            _ => return Err(format!("Unknown pin parameter: {}", key)),
+        }
+    }
+    Ok((bare, pins))
+}
+
+// Accepts base64 (standard or URL-safe, padded or not) or 64 hex characters.
+pub fn parse_digest(value: &str) -> Option<[u8; 32]> {
+    let value = value.trim();
+    if value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit()) {
+        let mut out = [0u8; 32];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&value[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        return Some(out);
+    }
+    [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD]
+        .iter()
+        .find_map(|engine| engine.decode(value).ok())
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes.as_slice()).ok())
+}
+
+// (SHA-256 of SubjectPublicKeyInfo, SHA-256 of TBSCertificate)
+fn cert_digests(der: &[u8]) -> Option<([u8; 32], [u8; 32])> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    let spki: [u8; 32] = Sha256::digest(cert.public_key().raw).into();
+    let tbs: [u8; 32] = Sha256::digest(cert.tbs_certificate.as_ref()).into();
+    Some((spki, tbs))
+}
+
+// Normal chain validation first, then the pin check. A failed pin check is remembered
+// so callers can report "pin mismatch" instead of hickory's generic connection error.
+#[derive(Debug)]
+pub struct PinningVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    pins: PinSet,
+    mismatch: Mutex<Option<String>>,
+}
+
+impl PinningVerifier {
+    pub fn new(pins: PinSet) -> Self {
+        PinningVerifier {
+            inner: platform_verifier(),
+            pins,
+            mismatch: Mutex::new(None),
+        }
+    }
+
+    pub fn mismatch(&self) -> Option<String> {
+        self.mismatch.lock().ok().and_then(|m| m.clone())
+    }
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        let chain: Vec<CertificateDer<'_>> = std::iter::once(end_entity.clone())
+            .chain(intermediates.iter().cloned())
+            .collect();
+        if self.pins.matches(&chain) {
+            return Ok(verified);
+        }
+        let presented = chain
+            .iter()
+            .filter_map(|c| cert_digests(c.as_ref()))
+            .map(|(spki, _)| STANDARD.encode(spki))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let msg = format!(
+            "Pin mismatch for {:?}: presented SPKI [{}] match none of the configured pins",
+            server_name, presented
+        );
+        if let Ok(mut m) = self.mismatch.lock() {
+            *m = Some(msg.clone());
+        }
+        Err(rustls::Error::General(msg))
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The integration-test fixtures (see tests/support/mod.rs)
+    const CA_CERT: &[u8] = include_bytes!("../../tests/support/certs/ca.der");
+    const SERVER_CERT: &[u8] = include_bytes!("../../tests/support/certs/server.der");
+    // openssl x509 -pubkey | openssl pkey -pubin -outform DER | openssl dgst -sha256
+    const SERVER_SPKI: &str = "mekxhb/J2JcyQHyp/fsOU7JAw0hZBnsYo5PagRUvqVM=";
+    const CA_SPKI: &str = "1XAH38EA/3JQ7OIYDULh6lefgaJP17eH7Zkh0Tqrm2w=";
+    const SERVER_TBS: &str = "3739544781b60986a4732ddef77680dc174b9967348b3dfdb17ff00b3d9194e9";
+
+    fn digest(value: &str) -> [u8; 32] {
+        parse_digest(value).expect("valid digest")
+    }
+
+    #[test]
+    fn parse_digest_accepts_hex_and_every_base64_flavour() {
+        let expected = digest(SERVER_SPKI);
+        let hex: String = expected.iter().map(|b| format!("{:02X}", b)).collect();
+        for value in [
+            hex.as_str(),
+            &hex.to_ascii_lowercase(),
+            "mekxhb/J2JcyQHyp/fsOU7JAw0hZBnsYo5PagRUvqVM",
+            "mekxhb_J2JcyQHyp_fsOU7JAw0hZBnsYo5PagRUvqVM=",
+            "mekxhb_J2JcyQHyp_fsOU7JAw0hZBnsYo5PagRUvqVM",
+            " mekxhb/J2JcyQHyp/fsOU7JAw0hZBnsYo5PagRUvqVM= ",
+        ] {
+            assert_eq!(parse_digest(value), Some(expected), "{}", value);
+        }
+        // 31 bytes, a 63-character hex string and plain garbage
+        assert_eq!(parse_digest(&STANDARD.encode([0u8; 31])), None);
+        assert_eq!(parse_digest(&hex[1..]), None);
+        assert_eq!(parse_digest("not a digest"), None);
+    }
+
+    #[test]
+    fn split_pins_reads_the_fragment() {
+        assert_eq!(
+            split_pins("tls://1.1.1.1").unwrap(),
+            ("tls://1.1.1.1", PinSet::default())
+        );
+        let address = format!(
+            "https://dns.example/dns-query#spki-sha256={}&&tbs-sha256={}&spki-sha256={}",
+            SERVER_SPKI, SERVER_TBS, CA_SPKI
+        );
+        let (bare, pins) = split_pins(&address).unwrap();
+        assert_eq!(bare, "https://dns.example/dns-query");
+        assert_eq!(pins.spki, vec![digest(SERVER_SPKI), digest(CA_SPKI)]);
+        assert_eq!(pins.tbs, vec![digest(SERVER_TBS)]);
+        // to_fragment writes base64 and parses back to the same set
+        let fragment = format!("x#{}", pins.to_fragment());
+        assert_eq!(split_pins(&fragment).unwrap().1, pins);
+
+        for (address, error) in [
+            ("x#spki-sha256", "Invalid pin parameter"),
+            ("x#spki-sha256=abc", "Invalid pin value"),
+            ("x#sha1=abc", "Invalid pin value"),
+            (
+                &format!("x#md5={}", SERVER_SPKI),
+                "Unknown pin parameter: md5",
+            ),
+        ] {
+            let e = split_pins(address).unwrap_err();
+            assert!(e.contains(error), "{}: {}", address, e);
+        }
+    }
+
+    #[test]
+    fn pins_match_any_certificate_in_the_chain() {
+        let server = CertificateDer::from(SERVER_CERT.to_vec());
+        let ca = CertificateDer::from(CA_CERT.to_vec());
+        let chain = [server.clone(), ca];
+        let pinned = |spki: &[&str], tbs: &[&str]| PinSet {
+            spki: spki.iter().map(|d| digest(d)).collect(),
+            tbs: tbs.iter().map(|d| digest(d)).collect(),
+        };
+
+        assert!(pinned(&[SERVER_SPKI], &[]).matches(&chain));
+        assert!(pinned(&[CA_SPKI], &[]).matches(&chain));
+        assert!(!pinned(&[CA_SPKI], &[]).matches(std::slice::from_ref(&server)));
+        assert!(pinned(&[], &[SERVER_TBS]).matches(&chain));
+        // a TBS hash is not an SPKI hash
+        assert!(!pinned(&[SERVER_TBS], &[]).matches(&chain));
+        assert!(!PinSet::default().matches(&chain));
+        assert!(!pinned(&[SERVER_SPKI], &[]).matches(&[CertificateDer::from(vec![0x30, 0x00])]));
+    }
+}
@@ -0,0 +1,345 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, warn};
+
+use super::servers::{default_entries, ServerEntry, ServerProtocol, DNS_SERVERS};
+use crate::storage;
+
+const PROFILES_FILE: &str = "server_profiles.json";
+pub const DEFAULT_PROFILE: &str = "Default";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServerProfile {
+    pub name: String,
+    pub entries: Vec<ServerEntry>,
+    // Receives the remote server list, until the user edits it
+    #[serde(default)]
+    pub follow_remote: bool,
+    #[serde(default)]
+    pub user_edited: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProfileStore {
+    pub active: String,
+    pub profiles: Vec<ServerProfile>,
+    // Set when the file on disk could not be parsed; the store then runs on the seeds
+    // and is never saved, so the user's file isn't replaced by them.
+    #[serde(skip)]
+    unreadable: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProfileSummary {
+    pub name: String,
+    pub active: bool,
+    pub server_count: usize,
+    pub enabled_count: usize,
+    pub follow_remote: bool,
+    pub user_edited: bool,
+}
+
+impl ProfileStore {
+    fn find(&self, name: &str) -> Option<&ServerProfile> {
+        self.profiles
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    fn find_mut(&mut self, name: &str) -> Option<&mut ServerProfile> {
+        self.profiles
+            .iter_mut()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    fn active_entries(&self) -> Vec<ServerEntry> {
+        self.find(&self.active)
+            .map(|p| p.entries.clone())
+            .unwrap_or_default()
+    }
+}
+
+static PROFILES: Lazy<RwLock<ProfileStore>> =
+    Lazy::new(|| RwLock::new(load_store(storage::load_json(PROFILES_FILE))));
+// Serializes saves, so the last write to disk is always the latest state.
+static SAVING: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+fn seed_store() -> ProfileStore {
+    let defaults = default_entries();
+    let profile = |name: &str, entries: Vec<ServerEntry>, follow_remote: bool| ServerProfile {
+        name: name.to_string(),
+        entries,
+        follow_remote,
+        user_edited: false,
+    };
+    ProfileStore {
+        active: DEFAULT_PROFILE.to_string(),
+        profiles: vec![
+            profile(DEFAULT_PROFILE, defaults.clone(), true),
+            profile(
+                "Encrypted only",
+                defaults
+                    .iter()
+                    .filter(|e| e.protocol != ServerProtocol::Udp)
+                    .cloned()
+                    .collect(),
+                false,
+            ),
+            profile(
+                "China",
+                defaults
+                    .iter()
+                    .filter(|e| e.region.as_deref() == Some("CN"))
+                    .cloned()
+                    .collect(),
+                false,
+            ),
+        ],
+        unreadable: None,
+    }
+}
+
+fn load_store(loaded: Result<Option<ProfileStore>, String>) -> ProfileStore {
+    match loaded {
+        Ok(Some(mut store)) if !store.profiles.is_empty() => {
+            if store.find(&store.active).is_none() {
+                store.active = store.profiles[0].name.clone();
+            }
+            store
+        }
+        Ok(_) => seed_store(),
+        Err(e) => {
+            warn!("Server profiles: {}; using the built-in profiles", e);
+            let backup = storage::data_file(PROFILES_FILE).map(|path| {
+                let backup = path.with_extension("json.bak");
+                if let Err(e) = std::fs::copy(&path, &backup) {
+                    warn!("Could not back up {}: {}", path.display(), e);
+                }
+                backup
+            });
+            let mut store = seed_store();
+            store.unreadable = Some(match backup {
+                Some(backup) => format!(
+                    "The server profiles file could not be read ({}). Changes are not saved \
+                     until it is fixed or removed; a copy was kept as {}",
+                    e,
+                    backup.display()
+                ),
+                None => format!("The server profiles file could not be read ({})", e),
+            });
+            store
+        }
+    }
+}
+
+// Writes the current store outside the store lock; callers release it first.
+async fn persist() -> Result<(), String> {
+    let _saving = SAVING.lock().await;
+    let store = PROFILES.read().await.clone();
+    if let Some(e) = store.unreadable {
+        return Err(e);
+    }
+    tokio::task::spawn_blocking(move || storage::write_json(PROFILES_FILE, &store))
+        .await
+        .map_err(|e| format!("Could not save server profiles: {}", e))?
+}
+
+// Loads the profile file and returns the active profile's entries.
+pub fn init_profiles() -> Vec<ServerEntry> {
+    Lazy::force(&PROFILES);
+    PROFILES
+        .try_read()
+        .map(|store| store.active_entries())
+        .unwrap_or_else(|_| default_entries())
+}
+
+async fn load_into_active_list(entries: Vec<ServerEntry>) {
+    *DNS_SERVERS.write().await = entries;
+}
+
+pub async fn active_profile_name() -> String {
+    PROFILES.read().await.active.clone()
+}
+
+pub async fn list_profiles() -> Vec<ProfileSummary> {
+    let store = PROFILES.read().await;
+    store
+        .profiles
+        .iter()
+        .map(|p| ProfileSummary {
+            name: p.name.clone(),
+            active: p.name == store.active,
+            server_count: p.entries.len(),
+            enabled_count: p.entries.iter().filter(|e| e.enabled).count(),
+            follow_remote: p.follow_remote,
+            user_edited: p.user_edited,
+        })
+        .collect()
+}
+
+fn check_name(store: &ProfileStore, name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Profile name cannot be empty".to_string());
+    }
+    if store.find(name).is_some() {
+        return Err(format!("A profile named '{}' already exists", name));
+    }
+    Ok(name.to_string())
+}
+
+pub async fn create_profile(name: String, copy_from: Option<String>) -> Result<(), String> {
+    {
+        let mut store = PROFILES.write().await;
+        let name = check_name(&store, &name)?;
+        let entries = match copy_from {
+            Some(src) => store
+                .find(&src)
+                .map(|p| p.entries.clone())
+                .ok_or_else(|| format!("Unknown profile: {}", src))?,
+            None => default_entries(),
+        };
+        store.profiles.push(ServerProfile {
+            name,
+            entries,
+            follow_remote: false,
+            user_edited: false,
+        });
+    }
+    persist().await
+}
+
+pub async fn rename_profile(old_name: String, new_name: String) -> Result<(), String> {
+    {
+        let mut store = PROFILES.write().await;
+        let new_name = if old_name.trim().eq_ignore_ascii_case(new_name.trim()) {
+            new_name.trim().to_string()
+        } else {
+            check_name(&store, &new_name)?
+        };
+        let was_active = store.active.eq_ignore_ascii_case(old_name.trim());
+        let profile = store
+            .find_mut(old_name.trim())
+            .ok_or_else(|| format!("Unknown profile: {}", old_name))?;
+        profile.name = new_name.clone();
+        if was_active {
+            store.active = new_name;
+        }
+    }
+    persist().await
+}
+
+pub async fn delete_profile(name: String) -> Result<(), String> {
+    let switched = {
+        let mut store = PROFILES.write().await;
+        if store.profiles.len() <= 1 {
+            return Err("Cannot delete the last profile".to_string());
+        }
+        let before = store.profiles.len();
+        store
+            .profiles
+            .retain(|p| !p.name.eq_ignore_ascii_case(name.trim()));
+        if store.profiles.len() == before {
+            return Err(format!("Unknown profile: {}", name));
+        }
+        let switched = if store.find(&store.active).is_none() {
+            store.active = store.profiles[0].name.clone();
+            Some(store.active_entries())
+        } else {
+            None
+        };
+        switched
+    };
+    persist().await?;
+    if let Some(entries) = switched {
+        load_into_active_list(entries).await;
+    }
+    Ok(())
+}
+
+pub async fn switch_profile(name: String) -> Result<(), String> {
+    let entries = {
+        let mut store = PROFILES.write().await;
+        let profile = store
+            .find(name.trim())
+            .ok_or_else(|| format!("Unknown profile: {}", name))?;
+        let (active, entries) = (profile.name.clone(), profile.entries.clone());
+        store.active = active;
+        entries
+    };
+    persist().await?;
+    info!("Switched to server profile '{}'", name.trim());
+    load_into_active_list(entries).await;
+    Ok(())
+}
+
+// Stores a user edit of the active list; the profile stops following remote updates.
+pub async fn save_active_entries(entries: Vec<ServerEntry>) -> Result<(), String> {
+    {
+        let mut store = PROFILES.write().await;
+        let active = store.active.clone();
+        if let Some(profile) = store.find_mut(&active) {
+            profile.entries = entries;
+            profile.user_edited = true;
+        }
+    }
+    persist().await
+}
+
+// Applies a remote list to every profile that follows it and was never edited.
+// Returns the new active entries if the active profile was among them.
+pub async fn apply_remote_entries(entries: Vec<ServerEntry>) -> Option<Vec<ServerEntry>> {
+    let active_updated = {
+        let mut store = PROFILES.write().await;
+        let active = store.active.clone();
+        let mut active_updated = false;
+        for profile in store
+            .profiles
+            .iter_mut()
+            .filter(|p| p.follow_remote && !p.user_edited)
+        {
+            profile.entries = entries.clone();
+            active_updated |= profile.name == active;
+        }
+        active_updated
+    };
+    if let Err(e) = persist().await {
+        warn!("Could not save server profiles: {}", e);
+    }
+    if active_updated {
+        load_into_active_list(entries.clone()).await;
+        Some(entries)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_missing_file_starts_from_the_seeds() {
+        let store = load_store(Ok(None));
+        assert_eq!(store.active, DEFAULT_PROFILE);
+        assert_eq!(store.profiles.len(), 3);
+        assert!(store.unreadable.is_none());
+    }
+
+    #[test]
+    fn an_unreadable_file_is_never_overwritten() {
+        let store = load_store(Err("unreadable server_profiles.json: EOF".to_string()));
+        assert_eq!(store.profiles.len(), 3);
+        let reason = store.unreadable.expect("store should refuse to save");
+        assert!(reason.contains("EOF"), "{}", reason);
+    }
+
+    #[test]
+    fn the_active_profile_falls_back_to_the_first() {
+        let mut saved = seed_store();
+        saved.active = "Gone".to_string();
+        let store = load_store(Ok(Some(saved)));
+        assert_eq!(store.active, DEFAULT_PROFILE);
+    }
+}
@@ -1,161 +1,425 @@
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tracing::info;
+
+use super::profiles;
+use super::spec;
+use super::stamps::{decode_stamp, StampProtocol};
+use ServerTag::*;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerProtocol {
+    Udp,
+    Dot,
+    Doh,
+    Doq,
+    DnsCrypt,
+}
+
+impl ServerProtocol {
+    pub fn from_address(address: &str) -> Self {
+        if address.starts_with("tls://") {
+            ServerProtocol::Dot
+        } else if address.starts_with("https://") || address.starts_with("h3://") {
+            ServerProtocol::Doh
+        } else if address.starts_with("quic://") {
+            ServerProtocol::Doq
+        } else if address.starts_with("sdns://") {
+            match decode_stamp(address).map(|s| s.protocol) {
+                Ok(StampProtocol::Doh) => ServerProtocol::Doh,
+                Ok(StampProtocol::Dot) => ServerProtocol::Dot,
+                Ok(StampProtocol::Doq) => ServerProtocol::Doq,
+                Ok(StampProtocol::DnsCrypt) => ServerProtocol::DnsCrypt,
+                _ => ServerProtocol::Udp,
+            }
+        } else {
+            ServerProtocol::Udp
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerTag {
+    Filtering,
+    Ecs,
+    Dnssec,
+    NoLog,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ServerEntry {
+    pub address: String,
+    #[serde(default)]
+    pub provider: Option<String>,
+    // Derived from `address` whenever entries are stored
+    #[serde(default = "default_protocol")]
+    pub protocol: ServerProtocol,
+    #[serde(default)]
+    pub region: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<ServerTag>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+fn default_protocol() -> ServerProtocol {
+    ServerProtocol::Udp
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl ServerEntry {
+    pub fn from_address(address: &str) -> Self {
+        let address = address.trim().to_string();
+        ServerEntry {
+            protocol: ServerProtocol::from_address(&address),
+            address,
+            provider: None,
+            region: None,
+            tags: vec![],
+            enabled: true,
+            notes: None,
+        }
+    }
+
+    // Trims text fields, drops empty optionals and duplicate tags, and re-derives the protocol.
+    fn normalized(mut self) -> Self {
+        self.address = self.address.trim().to_string();
+        self.protocol = ServerProtocol::from_address(&self.address);
+        let clean = |v: Option<String>| v.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        self.provider = clean(self.provider);
+        self.region = clean(self.region);
+        self.notes = clean(self.notes);
+        self.tags.sort();
+        self.tags.dedup();
+        self
+    }
+}
+
+struct DefaultServer {
+    address: &'static str,
+    provider: &'static str,
+    region: &'static str,
+    tags: &'static [ServerTag],
+    notes: &'static str,
+}
+
+const fn default_server(
+    address: &'static str,
+    provider: &'static str,
+    region: &'static str,
+    tags: &'static [ServerTag],
+    notes: &'static str,
+) -> DefaultServer {
+    DefaultServer {
+        address,
+        provider,
+        region,
+        tags,
+        notes,
+    }
+}
+
+impl DefaultServer {
+    fn to_entry(&self) -> ServerEntry {
+        let mut entry = ServerEntry::from_address(self.address);
+        entry.provider = Some(self.provider.to_string());
+        entry.region = Some(self.region.to_string());
+        entry.tags = self.tags.to_vec();
+        entry.notes = Some(self.notes.to_string()).filter(|n| !n.is_empty());
+        entry
+    }
+}
+
+#[rustfmt::skip]
+const DEFAULT_DNS_SERVERS: &[DefaultServer] = &[
+    // Standard DNS (UDP/53)
+    default_server("8.8.8.8", "Google Public DNS", "Global", &[Dnssec, Ecs], "General purpose"),
+    default_server("8.8.4.4", "Google Public DNS", "Global", &[Dnssec, Ecs], "General purpose"),
+    default_server("1.1.1.1", "Cloudflare", "Global", &[Dnssec, NoLog], "Unfiltered, privacy-focused"),
+    default_server("1.0.0.1", "Cloudflare", "Global", &[Dnssec, NoLog], "Unfiltered, privacy-focused"),
+    default_server("208.67.222.222", "OpenDNS Home", "Global", &[Filtering], "Phishing protection"),
+    default_server("208.67.220.220", "OpenDNS Home", "Global", &[Filtering], "Phishing protection"),
+    default_server("208.67.220.2", "OpenDNS Sandbox", "Global", &[], "Unfiltered"),
+    default_server("208.67.222.2", "OpenDNS Sandbox", "Global", &[], "Unfiltered"),
+    default_server("9.9.9.9", "Quad9", "Global", &[Filtering, Dnssec, NoLog], "Malware blocking, DNSSEC validation"),
+    default_server("149.112.112.112", "Quad9", "Global", &[Filtering, Dnssec, NoLog], "Malware blocking, DNSSEC validation"),
+    default_server("9.9.9.11", "Quad9 ECS", "Global", &[Filtering, Dnssec, Ecs, NoLog], "Malware blocking, DNSSEC validation, ECS enabled"),
+    default_server("149.112.112.11", "Quad9 ECS", "Global", &[Filtering, Dnssec, Ecs, NoLog], "Malware blocking, DNSSEC validation, ECS enabled"),
+    default_server("9.9.9.10", "Quad9 Unsecured", "Global", &[NoLog], "No malware blocking, no DNSSEC validation"),
+    default_server("149.112.112.10", "Quad9 Unsecured", "Global", &[NoLog], "No malware blocking, no DNSSEC validation"),
+    default_server("94.140.14.14", "AdGuard DNS", "Global", &[Filtering], "Ads, trackers, malware, phishing blocking"),
+    default_server("94.140.15.15", "AdGuard DNS", "Global", &[Filtering], "Ads, trackers, malware, phishing blocking"),
+    default_server("94.140.14.140", "AdGuard DNS Non-filtering", "Global", &[], ""),
+    default_server("94.140.14.141", "AdGuard DNS Non-filtering", "Global", &[], ""),
+    default_server("77.88.8.8", "Yandex DNS Basic", "RU", &[], "General purpose"),
+    default_server("77.88.8.1", "Yandex DNS Basic", "RU", &[], "General purpose"),
+    default_server("77.88.8.88", "Yandex DNS Safe", "RU", &[Filtering], "Protection from dangerous websites"),
+    default_server("77.88.8.2", "Yandex DNS Safe", "RU", &[Filtering], "Protection from dangerous websites"),
+    default_server("185.228.168.9", "CleanBrowsing Security", "Global", &[Filtering], "Malware, phishing, spam blocking"),
+    default_server("185.228.169.9", "CleanBrowsing Security", "Global", &[Filtering], "Malware, phishing, spam blocking"),
+    default_server("76.76.2.0", "Control D", "Global", &[], "General purpose / customizable"),
+    default_server("76.76.10.0", "Control D", "Global", &[], "General purpose / customizable"),
+    default_server("138.197.140.189", "OpenNIC", "Global", &[], "Community-run, neutral"),
+    default_server("168.235.111.72", "OpenNIC", "Global", &[], "Community-run, neutral"),
+    default_server("76.76.19.19", "Alternate DNS", "Global", &[Filtering], "Ad-blocking"),
+    default_server("76.223.122.150", "Alternate DNS", "Global", &[Filtering], "Ad-blocking"),
+    default_server("216.146.35.35", "Dyn", "US", &[], "General purpose"),
+    default_server("216.146.36.36", "Dyn", "US", &[], "General purpose"),
+    default_server("74.82.42.42", "Hurricane Electric", "US", &[], "General purpose"),
+    default_server("149.112.121.10", "CIRA Canadian Shield", "CA", &[Filtering], "Malware and phishing protection"),
+    default_server("149.112.122.10", "CIRA Canadian Shield", "CA", &[Filtering], "Malware and phishing protection"),
+    default_server("8.26.56.26", "Comodo Secure DNS", "Global", &[Filtering], "Security, fraudulent website protection"),
+    default_server("8.20.247.20", "Comodo Secure DNS", "Global", &[Filtering], "Security, fraudulent website protection"),
+    default_server("205.171.3.65", "CenturyLink (Level3)", "US", &[], "General purpose"),
+    default_server("205.171.2.65", "CenturyLink (Level3)", "US", &[], "General purpose"),
+    default_server("223.5.5.5", "AliDNS", "CN", &[], ""),
+    default_server("223.6.6.6", "AliDNS", "CN", &[], ""),
+    default_server("185.222.222.222", "DNS.SB", "Global", &[Dnssec, NoLog], ""),
+    default_server("45.11.45.11", "DNS.SB", "Global", &[Dnssec, NoLog], ""),
+    default_server("119.29.29.29", "DNSPod", "CN", &[], ""),
+    default_server("182.254.116.116", "DNSPod", "CN", &[], ""),
+    default_server("194.242.2.2", "Mullvad", "Global", &[NoLog], ""),
+    default_server("194.242.2.4", "Mullvad Base", "Global", &[Filtering, NoLog], ""),
+    default_server("45.90.28.0", "NextDNS", "Global", &[], ""),
+    default_server("45.90.30.0", "NextDNS", "Global", &[], ""),
+    default_server("146.112.41.2", "OpenBLD", "Global", &[Filtering], ""),
+    default_server("146.112.41.102", "OpenBLD", "Global", &[Filtering], ""),
+    default_server("193.110.81.9", "DNS0.EU", "EU", &[Filtering, Dnssec], ""),
+    default_server("185.253.5.9", "DNS0.EU", "EU", &[Filtering, Dnssec], ""),
+    default_server("101.226.4.6", "360", "CN", &[], ""),
+    default_server("180.163.224.54", "360", "CN", &[], ""),
+    default_server("185.95.218.42", "Digitale Gesellschaft", "CH", &[NoLog], ""),
+    default_server("185.95.218.43", "Digitale Gesellschaft", "CH", &[NoLog], ""),
+    default_server("158.64.1.29", "Restena", "LU", &[], ""),
+    default_server("203.180.164.45", "IIJ", "JP", &[], ""),
+    default_server("203.180.166.45", "IIJ", "JP", &[], ""),
+    default_server("116.202.176.26", "LibreDNS", "GR", &[NoLog], ""),
+    default_server("147.135.76.183", "LibreDNS", "GR", &[NoLog], ""),
+    default_server("130.59.31.248", "Switch", "CH", &[], ""),
+    default_server("130.59.31.251", "Switch", "CH", &[], ""),
+    default_server("146.255.56.98", "Foundation for Applied Privacy", "AT", &[NoLog], ""),
+    default_server("91.239.100.100", "UncensoredDNS", "DK", &[NoLog], ""),
+    default_server("89.233.43.71", "UncensoredDNS", "DK", &[NoLog], ""),
+    default_server("104.21.83.62", "RethinkDNS", "Global", &[], ""),
+    default_server("172.67.214.246", "RethinkDNS", "Global", &[], ""),
+
+    // DNS-over-TLS (DoT)
+    default_server("tls://cloudflare-dns.com:853", "Cloudflare", "Global", &[Dnssec, NoLog], ""),
+    default_server("tls://dns.google:853", "Google Public DNS", "Global", &[Dnssec, Ecs], ""),
+    default_server("tls://dns.quad9.net:853", "Quad9", "Global", &[Filtering, Dnssec, NoLog], ""),
+    default_server("tls://dns.adguard.com:853", "AdGuard DNS", "Global", &[Filtering], ""),
+    default_server("tls://max.rethinkdns.com:853", "RethinkDNS", "Global", &[], ""),
+    default_server("tls://dns.alidns.com:853", "AliDNS", "CN", &[], ""),
+
+    // DNS-over-HTTPS (DoH)
+    default_server("https://cloudflare-dns.com/dns-query", "Cloudflare", "Global", &[Dnssec, NoLog], ""),
+    default_server("https://security.cloudflare-dns.com/dns-query", "Cloudflare Security", "Global", &[Filtering, Dnssec, NoLog], "Malware blocking"),
+    default_server("https://dns.google/dns-query", "Google Public DNS", "Global", &[Dnssec, Ecs], ""),
+    default_server("https://dns.quad9.net/dns-query", "Quad9", "Global", &[Filtering, Dnssec, NoLog], ""),
+    default_server("https://doh.dns.sb/dns-query", "DNS.SB", "Global", &[Dnssec, NoLog], ""),
+    default_server("https://doh.cleanbrowsing.org/doh/family-filter/", "CleanBrowsing Family", "Global", &[Filtering], "Family filter"),
+    default_server("https://dns.adguard-dns.com/dns-query", "AdGuard DNS", "Global", &[Filtering], ""),
+    default_server("https://dns-family.adguard-dns.com/dns-query", "AdGuard DNS Family", "Global", &[Filtering], "Family protection"),
+    default_server("https://dns-unfiltered.adguard-dns.com/dns-query", "AdGuard DNS Non-filtering", "Global", &[], ""),
+    default_server("https://doh.opendns.com/dns-query", "OpenDNS Home", "Global", &[Filtering], ""),
+    default_server("https://freedns.controld.com/x-goodbyeads", "Control D", "Global", &[Filtering], "GoodbyeAds list"),
+    default_server("https://blitz.ahadns.com/1:17", "AhaDNS Blitz", "Global", &[Filtering], ""),
+    default_server("https://doh.blahdns.com/dns-query", "BlahDNS", "Global", &[NoLog], ""),
+    default_server("https://doh.uncensoreddns.org/dns-query", "UncensoredDNS", "DK", &[NoLog], ""),
+    default_server("https://dns.fdn.org/dns-query", "FDN", "FR", &[], ""),
+    default_server("https://doh.dns.watch/dns-query", "DNS.WATCH", "DE", &[Dnssec, NoLog], ""),
+    default_server("https://sky.rethinkdns.com/dns-query", "RethinkDNS", "Global", &[], ""),
+    default_server("https://dns.alidns.com/dns-query", "AliDNS", "CN", &[], ""),
+    default_server("https://doh.libredns.gr/dns-query", "LibreDNS", "GR", &[NoLog], ""),
+    default_server("https://doh.tiar.app/dns-query", "Tiarap", "SG", &[NoLog], ""),
+    default_server("https://dns.aa.net.uk/dns-query", "Andrews & Arnold", "UK", &[], ""),
+    default_server("https://dnsforge.de/dns-query", "dnsforge", "DE", &[Filtering], ""),
+
+    // DNS-over-QUIC (DoQ)
+    default_server("quic://dns.adguard.com", "AdGuard DNS", "Global", &[Filtering], ""),
+    default_server("quic://family.adguard-dns.com", "AdGuard DNS Family", "Global", &[Filtering], ""),
+    default_server("quic://unfiltered.adguard-dns.com", "AdGuard DNS Non-filtering", "Global", &[], ""),
+    default_server("quic://x-goodbyeads.freedns.controld.com", "Control D", "Global", &[Filtering], "GoodbyeAds list"),
 
-const DEFAULT_DNS_SERVERS: &[&'static str] = &[
-        // Standard DNS (UDP/53)
-        "8.8.8.8",          // Google Public DNS (General Purpose)
-        "8.8.4.4",          // Google Public DNS (General Purpose)
-        "1.1.1.1",          // Cloudflare DNS (Unfiltered, Privacy-Focused)
-        "1.0.0.1",          // Cloudflare DNS (Unfiltered, Privacy-Focused)
-        "208.67.222.222", // OpenDNS Home (Phishing Protection)
-        "208.67.220.220", // OpenDNS Home (Phishing Protection)
-        "208.67.220.2",   // OpenDNS Sandbox (Unfiltered)
-        "208.67.222.2",   // OpenDNS Sandbox (Unfiltered)
-        "9.9.9.9",          // Quad9 (Malware Blocking, DNSSEC Validation)
-        "149.112.112.112", // Quad9 (Malware Blocking, DNSSEC Validation)
-        "9.9.9.11",         // Quad9 (Malware Blocking, DNSSEC Validation, ECS Enabled)
-        "149.112.112.11", // Quad9 (Malware Blocking, DNSSEC Validation, ECS Enabled)
-        "9.9.9.10",         // Quad9 (Unsecured - No Malware Blocking, No DNSSEC Validation)
-        "149.112.112.10", // Quad9 (Unsecured - No Malware Blocking, No DNSSEC Validation)
-        "94.140.14.14", // AdGuard DNS (Ads, Trackers, Malware, Phishing Blocking)
-        "94.140.15.15", // AdGuard DNS (Ads, Trackers, Malware, Phishing Blocking)
-        "94.140.14.140", // AdGuard DNS (Non-filtering)
-        "94.140.14.141", // AdGuard DNS (Non-filtering)
-        "77.88.8.8",        // Yandex DNS Basic (General Purpose)
-        "77.88.8.1",        // Yandex DNS Basic (General Purpose)
-        "77.88.8.88",   // Yandex DNS Safe (Protection from Dangerous Websites)
-        "77.88.8.2",        // Yandex DNS Safe (Protection from Dangerous Websites)
-        "185.228.168.9", // CleanBrowse Security Filter (Malware, Phishing, Spam Blocking)
-        "185.228.169.9", // CleanBrowse Security Filter (Malware, Phishing, Spam Blocking)
-        "76.76.2.0",        // Control D (General Purpose / Customizable)
-        "76.76.10.0",   // Control D (General Purpose / Customizable)
-        "138.197.140.189", // OpenNIC (Community-run, Neutral)
-        "168.235.111.72", // OpenNIC (Community-run, Neutral)
-        "76.76.19.19",  // Alternate DNS (Ad-blocking)
-        "76.223.122.150", // Alternate DNS (Ad-blocking)
-        "216.146.35.35", // Dyn (General Purpose)
-        "216.146.36.36", // Dyn (General Purpose)
-        "74.82.42.42",  // Hurricane Electric (General Purpose)
-        "149.112.121.10", // CIRA Canadian Shield (Malware and Phishing Protection)
-        "149.112.122.10", // CIRA Canadian Shield (Malware and Phishing Protection)
-        "8.26.56.26",   // Comodo Secure DNS (Security, Fraudulent Website Protection)
-        "8.20.247.20",  // Comodo Secure DNS (Security, Fraudulent Website Protection)
-        "205.171.3.65", // CenturyLink (Level3) (General Purpose)
-        "205.171.2.65", // CenturyLink (Level3) (General Purpose)
-        "223.5.5.5",        // AliDNS
-        "223.6.6.6",        // AliDNS
-        "185.222.222.222", // DNS.SB
-        "45.11.45.11",  // DNS.SB
-        "119.29.29.29", // DNSPod
-        "182.254.116.116", // DNSPod
-        "194.242.2.2",  // Mullvad
-        "194.242.2.4",  // Mullvad Base
-        "45.90.28.0",   // NextDNS
-        "45.90.30.0",   // NextDNS
-        "146.112.41.2", // OpenBLD
-        "146.112.41.102", // OpenBLD
-        "193.110.81.9", // DNS0.EU
-        "185.253.5.9",  // DNS0.EU
-        "101.226.4.6",  // 360
-        "180.163.224.54", // 360
-        "185.95.218.42", // Digitale Gesellschaft
-        "185.95.218.43", // Digitale Gesellschaft
-        "158.64.1.29",  // Restena
-        "203.180.164.45", // IIJ
-        "203.180.166.45", // IIJ
-        "116.202.176.26", // LibreDNS
-        "147.135.76.183", // LibreDNS
-        "130.59.31.248", // Switch
-        "130.59.31.251", // Switch
-        "146.255.56.98", // Foundation for Applied Privacy
-        "91.239.100.100", // UncensoredDNS
-        "89.233.43.71", // UncensoredDNS
-        "104.21.83.62", // RethinkDNS
-        "172.67.214.246", // RethinkDNS
-
-        // DNS-over-TLS (DoT)
-        "tls://cloudflare-dns.com:853",
-        "tls://dns.google:853",
-        "tls://dns.quad9.net:853",
-        "tls://dns.adguard.com:853",
-        "tls://max.rethinkdns.com:853",
-        "tls://dns.alidns.com:853",
-        
-        // DNS-over-HTTPS (DoH)
-        "https://cloudflare-dns.com/dns-query",
-        "https://security.cloudflare-dns.com/dns-query",
-        "https://dns.google/dns-query",
-        "https://dns.quad9.net/dns-query",
-        "https://doh.dns.sb/dns-query",
-        "https://doh.cleanbrowsing.org/doh/family-filter/",
-        "https://dns.adguard-dns.com/dns-query",
-        "https://dns-family.adguard-dns.com/dns-query",
-        "https://dns-unfiltered.adguard-dns.com/dns-query",
-        "https://doh.opendns.com/dns-query",
-        "https://freedns.controld.com/x-goodbyeads",
-        "https://blitz.ahadns.com/1:17",
-        "https://doh.blahdns.com/dns-query",
-        "https://doh.uncensoreddns.org/dns-query",
-        "https://dns.fdn.org/dns-query",
-        "https://doh.dns.watch/dns-query",
-        "https://sky.rethinkdns.com/dns-query",
-        "https://dns.alidns.com/dns-query",
-        "https://doh.libredns.gr/dns-query",
-        "https://doh.tiar.app/dns-query",
-        "https://dns.aa.net.uk/dns-query",
-        "https://dnsforge.de/dns-query",
-        
-        // DNS-over-QUIC (DoQ)
-        "quic://dns.adguard.com",
-        "quic://family.adguard-dns.com",
-        "quic://unfiltered.adguard-dns.com",
-        "quic://x-goodbyeads.freedns.controld.com",
 ];
 
-pub static DNS_SERVERS: Lazy<Arc<RwLock<Vec<String>>>> = Lazy::new(|| {
-    Arc::new(RwLock::new(
-        DEFAULT_DNS_SERVERS.iter().map(|s| s.to_string()).collect(),
-    ))
-});
+pub fn default_entries() -> Vec<ServerEntry> {
+    DEFAULT_DNS_SERVERS.iter().map(|d| d.to_entry()).collect()
+}
+
+pub static DNS_SERVERS: Lazy<Arc<RwLock<Vec<ServerEntry>>>> =
+    Lazy::new(|| Arc::new(RwLock::new(default_entries())));
 
+// Loads the active profile from disk into the working list.
 pub fn init_servers() {
-    Lazy::force(&DNS_SERVERS);
+    let entries = profiles::init_profiles();
+    if let Ok(mut guard) = DNS_SERVERS.try_write() {
+        *guard = entries;
+    }
 }
 
+// Enabled server addresses, as used by the benchmarks.
 pub async fn get_servers() -> Vec<String> {
+    DNS_SERVERS
+        .read()
+        .await
+        .iter()
+        .filter(|e| e.enabled)
+        .map(|e| e.address.clone())
+        .collect()
+}
+
+// Every stored address (including disabled ones) for the plain-text editor.
+pub async fn get_all_addresses() -> Vec<String> {
+    DNS_SERVERS
+        .read()
+        .await
+        .iter()
+        .map(|e| e.address.clone())
+        .collect()
+}
+
+pub async fn get_entries() -> Vec<ServerEntry> {
     DNS_SERVERS.read().await.clone()
 }
 
-pub async fn set_servers(new_servers: Vec<String>) {
-    let filtered: Vec<String> = new_servers
+pub async fn set_entries(entries: Vec<ServerEntry>) -> Result<(), String> {
+    let unique = unique_entries(entries)?;
+    *DNS_SERVERS.write().await = unique.clone();
+    profiles::save_active_entries(unique).await
+}
+
+// Same rules as `set_servers`: any malformed address rejects the list, and later
+// entries with the canonical form of an earlier one are dropped.
+fn unique_entries(entries: Vec<ServerEntry>) -> Result<Vec<ServerEntry>, String> {
+    let mut seen = HashSet::new();
+    let mut unique = vec![];
+    for entry in entries.into_iter().map(ServerEntry::normalized) {
+        if entry.address.is_empty() {
+            continue;
+        }
+        let canonical = spec::parse(&entry.address)
+            .map_err(|e| format!("{}: {}", entry.address, e))?
+            .to_string();
+        if seen.insert(canonical) {
+            unique.push(entry);
+        }
+    }
+    Ok(unique)
+}
+
+// Plain addresses keep the metadata of matching current or default entries.
+pub fn entries_from_addresses(addresses: Vec<String>, known: &[ServerEntry]) -> Vec<ServerEntry> {
+    let defaults = default_entries();
+    addresses
         .into_iter()
         .map(|s| s.trim().to_string())
         .filter(|s| !s.is_empty())
-        .collect();
-    let mut servers_guard = DNS_SERVERS.write().await;
-    *servers_guard = filtered;
+        .map(|address| {
+            known
+                .iter()
+                .chain(defaults.iter())
+                .find(|e| e.address == address)
+                .cloned()
+                .unwrap_or_else(|| ServerEntry::from_address(&address))
+        })
+        .collect()
 }
 
-pub async fn update_servers_from_url(url: &str) -> Result<(), reqwest::Error> {
-    println!("Updating DNS servers from: {}", url);
-    let response_text = reqwest::get(url).await?.text().await?;
-    let new_servers: Vec<String> = response_text
-        .lines()
-        .map(|line| line.trim().to_string())
-        .filter(|line| !line.is_empty())
+// Rejects the whole list if any line is malformed; later duplicates of an entry
+// (same canonical form, e.g. `1.1.1.1` and `udp://1.1.1.1:53`) are dropped.
+pub async fn set_servers(new_servers: Vec<String>) -> Result<(), String> {
+    let report = spec::validate_lines(&new_servers);
+    let errors: Vec<String> = report
+        .lines
+        .iter()
+        .filter_map(|l| l.error.as_ref().map(|e| format!("Line {}, {}", l.line, e)))
+        .collect();
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    let unique: Vec<String> = report
+        .lines
+        .into_iter()
+        .filter(|l| l.duplicate_of.is_none())
+        .map(|l| l.input)
         .collect();
 
-    if !new_servers.is_empty() {
+    let entries = {
         let mut servers_guard = DNS_SERVERS.write().await;
-        *servers_guard = new_servers;
-        println!(
-            "Successfully updated DNS servers. Total count: {}",
-            servers_guard.len()
-        );
-    } else {
-        println!("No new servers found in the response.");
+        *servers_guard = entries_from_addresses(unique, &servers_guard);
+        servers_guard.clone()
+    };
+    profiles::save_active_entries(entries).await
+}
+
+// Syntax check matching what `build_resolver_for_server` accepts (no network access).
+pub fn validate_address(address: &str) -> Result<(), String> {
+    spec::parse(address).map(|_| ()).map_err(|e| e.message)
+}
+
+// Address -> provider name for labelling results.
+pub async fn provider_map() -> HashMap<String, String> {
+    let current = DNS_SERVERS.read().await;
+    default_entries()
+        .iter()
+        .chain(current.iter())
+        .filter_map(|e| e.provider.clone().map(|p| (e.address.clone(), p)))
+        .collect()
+}
+
+// Pushes a remote list to the profiles that follow it; user-edited profiles are left alone.
+pub async fn apply_remote_addresses(addresses: Vec<String>) {
+    let known = DNS_SERVERS.read().await.clone();
+    let entries = entries_from_addresses(addresses, &known);
+    match profiles::apply_remote_entries(entries).await {
+        Some(active) => info!("Updated DNS servers. Total count: {}", active.len()),
+        None => info!("Active profile does not follow remote lists; not applied."),
     }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(address: &str, tags: &[ServerTag]) -> ServerEntry {
+        ServerEntry {
+            tags: tags.to_vec(),
+            ..ServerEntry::from_address(address)
+        }
+    }
+
+    #[test]
+    fn entries_are_deduplicated_by_canonical_address() {
+        let unique = unique_entries(vec![
+            entry(" 1.1.1.1 ", &[NoLog, Dnssec, NoLog]),
+            entry("udp://1.1.1.1:53", &[Filtering]),
+            entry("", &[]),
+            entry("tls://1.1.1.1", &[]),
+        ])
+        .unwrap();
+
+        assert_eq!(unique.len(), 2);
+        assert_eq!(unique[0].address, "1.1.1.1");
+        assert_eq!(unique[0].tags, vec![Dnssec, NoLog]);
+        assert_eq!(unique[1].address, "tls://1.1.1.1");
+        assert_eq!(unique[1].protocol, ServerProtocol::Dot);
+    }
+
+    #[test]
+    fn an_invalid_address_rejects_the_list() {
+        let err =
+            unique_entries(vec![entry("1.1.1.1", &[]), entry("not a server", &[])]).unwrap_err();
+        assert!(err.starts_with("not a server: "), "{}", err);
+    }
 }
@@ -0,0 +1,157 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use super::engine::{Clock, SystemClock};
+use super::tls_hosts;
+use super::tls_inspect::{probe_dns_sans, verify_tls_name, TlsTransport};
+
+// Failed endpoints are not probed again for a while, so repeated runs stay fast.
+const RETRY_AFTER: Duration = Duration::from_secs(10 * 60);
+// Each candidate costs one verified handshake
+const MAX_CANDIDATES: usize = 3;
+
+// The endpoint probed: the same IP can serve DoT, DoH and DoQ on different ports
+type Endpoint = (IpAddr, u16, TlsTransport);
+
+// When discovery last failed for each endpoint.
+#[derive(Default)]
+struct FailureMemory {
+    failed: HashMap<Endpoint, Instant>,
+}
+
+impl FailureMemory {
+    fn failed_recently(&self, endpoint: &Endpoint, clock: &impl Clock) -> bool {
+        self.failed
+            .get(endpoint)
+            .is_some_and(|at| clock.now().duration_since(*at) < RETRY_AFTER)
+    }
+
+    fn record(&mut self, endpoint: Endpoint, clock: &impl Clock) {
+        self.failed.insert(endpoint, clock.now());
+    }
+}
+
+static FAILED: Lazy<Mutex<FailureMemory>> = Lazy::new(|| Mutex::new(FailureMemory::default()));
+
+// Finds a verifiable TLS name for an IP-only server: read the certificate SANs on an
+// unverified handshake, then confirm a candidate with a normal verified handshake.
+// A confirmed name is stored in the TLS host map as "discovered".
+pub async fn discover_tls_name(
+    transport: TlsTransport,
+    ip: IpAddr,
+    port: u16,
+    timeout_secs: u64,
+) -> Option<String> {
+    let endpoint = (ip, port, transport);
+    if FAILED
+        .lock()
+        .is_ok_and(|failed| failed.failed_recently(&endpoint, &SystemClock))
+    {
+        return None;
+    }
+
+    match try_discover(transport, ip, port, timeout_secs).await {
+        Ok(name) => {
+            info!("Discovered TLS name {} for {}", name, ip);
+            if let Err(e) = tls_hosts::record_discovered(ip, &name).await {
+                warn!("Could not save discovered TLS name for {}: {}", ip, e);
+            }
+            Some(name)
+        }
+        Err(e) => {
+            warn!("SNI discovery failed for {}:{}: {}", ip, port, e);
+            if let Ok(mut failed) = FAILED.lock() {
+                failed.record(endpoint, &SystemClock);
+            }
+            None
+        }
+    }
+}
+
+// Wildcards cannot be sent as SNI; prefer names that look like resolver hosts.
+fn candidates(sans: Vec<String>) -> Vec<String> {
+    let mut candidates: Vec<String> = sans
+        .into_iter()
+        .filter(|san| !san.starts_with("*."))
+        .map(|san| san.trim_end_matches('.').to_ascii_lowercase())
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    candidates.sort_by_key(|name| !name.contains("dns"));
+    candidates.truncate(MAX_CANDIDATES);
+    candidates
+}
+
+async fn try_discover(
+    transport: TlsTransport,
+    ip: IpAddr,
+    port: u16,
+    timeout_secs: u64,
+) -> Result<String, String> {
+    let candidates = candidates(probe_dns_sans(transport, ip, port, timeout_secs).await?);
+    if candidates.is_empty() {
+        return Err("Certificate has no usable DNS names".to_string());
+    }
+
+    let mut last_error = String::new();
+    for name in candidates {
+        match verify_tls_name(transport, ip, port, &name, timeout_secs).await {
+            Ok(()) => return Ok(name),
+            Err(e) => last_error = format!("{}: {}", name, e),
+        }
+    }
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns_tester::engine::sim::SimClock;
+
+    fn names(sans: &[&str]) -> Vec<String> {
+        candidates(sans.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn candidates_skip_wildcards_and_prefer_dns_names() {
+        assert_eq!(
+            names(&[
+                "*.example.com",
+                "www.example.com",
+                "DNS.example.com.",
+                "dns.example.com",
+                "api.example.com",
+                "doh-dns.example.net",
+            ]),
+            vec!["dns.example.com", "doh-dns.example.net", "api.example.com"]
+        );
+        assert_eq!(
+            names(&["c.example", "b.example", "a.example", "d.example"]),
+            vec!["a.example", "b.example", "c.example"]
+        );
+        assert!(names(&["*.example.com"]).is_empty());
+    }
+
+    #[test]
+    fn failures_are_retried_after_ten_minutes() {
+        let clock = SimClock::new();
+        let mut memory = FailureMemory::default();
+        let endpoint: Endpoint = ("192.0.2.1".parse().unwrap(), 853, TlsTransport::Dot);
+        assert!(!memory.failed_recently(&endpoint, &clock));
+
+        memory.record(endpoint, &clock);
+        assert!(memory.failed_recently(&endpoint, &clock));
+        // the same IP on another port or transport is still probed
+        assert!(!memory.failed_recently(&(endpoint.0, 443, TlsTransport::Doh), &clock));
+        assert!(!memory.failed_recently(&(endpoint.0, 853, TlsTransport::Doq), &clock));
+
+        clock.advance(RETRY_AFTER - Duration::from_secs(1));
+        assert!(memory.failed_recently(&endpoint, &clock));
+        clock.advance(Duration::from_secs(1));
+        assert!(!memory.failed_recently(&endpoint, &clock));
+    }
+}
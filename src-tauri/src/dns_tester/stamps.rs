@@ -0,0 +1,324 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use super::spec::split_host_port;
+
+// DNS stamps (https://dnscrypt.info/stamps-specifications): "sdns://" + base64url of
+// a protocol byte, 8 bytes of little-endian properties and length-prefixed fields.
+const PROP_DNSSEC: u64 = 1;
+const PROP_NO_LOGS: u64 = 2;
+const PROP_NO_FILTER: u64 = 4;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StampProtocol {
+    Plain,
+    DnsCrypt,
+    Doh,
+    Dot,
+    Doq,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DnsStamp {
+    pub protocol: StampProtocol,
+    pub dnssec: bool,
+    pub no_logs: bool,
+    pub no_filter: bool,
+    // "ip", "ip:port" or "[ipv6]:port"; may be empty for DoH/DoT/DoQ (resolve hostname)
+    pub addr: String,
+    // SHA-256 digests of TBS certificates in the server's chain
+    pub hashes: Vec<[u8; 32]>,
+    // "host" or "host:port" (provider name for DNSCrypt)
+    pub hostname: String,
+    pub path: String,
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let b = *self.buf.get(self.pos).ok_or("Truncated stamp")?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos + n;
+        let slice = self.buf.get(self.pos..end).ok_or("Truncated stamp")?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn lp(&mut self) -> Result<&'a [u8], String> {
+        let len = self.byte()? as usize;
+        self.take(len)
+    }
+
+    fn lp_string(&mut self) -> Result<String, String> {
+        String::from_utf8(self.lp()?.to_vec()).map_err(|_| "Invalid UTF-8 in stamp".to_string())
+    }
+
+    // Variable-length set: every length byte except the last has its 0x80 bit set.
+    fn vlp(&mut self) -> Result<Vec<&'a [u8]>, String> {
+        let mut items = Vec::new();
+        loop {
+            let b = self.byte()?;
+            let item = self.take((b & 0x7f) as usize)?;
+            if !item.is_empty() {
+                items.push(item);
+            }
+            if b & 0x80 == 0 {
+                return Ok(items);
+            }
+        }
+    }
+
+    fn remaining(&self) -> bool {
+        self.pos < self.buf.len()
+    }
+}
+
+pub fn decode_stamp(stamp: &str) -> Result<DnsStamp, String> {
+    let payload = stamp
+        .trim()
+        .strip_prefix("sdns://")
+        .ok_or("Stamp must start with sdns://")?;
+    let bytes = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|e| format!("Invalid stamp encoding: {}", e))?;
+    let mut r = Reader {
+        buf: &bytes,
+        pos: 0,
+    };
+
+    let protocol = match r.byte()? {
+        0x00 => StampProtocol::Plain,
+        0x01 => StampProtocol::DnsCrypt,
+        0x02 => StampProtocol::Doh,
+        0x03 => StampProtocol::Dot,
+        0x04 => StampProtocol::Doq,
+        other => return Err(format!("Unsupported stamp protocol 0x{:02x}", other)),
+    };
+    let props = u64::from_le_bytes(
+        r.take(8)?
+            .try_into()
+            .map_err(|_| "Truncated stamp".to_string())?,
+    );
+    let mut stamp = DnsStamp {
+        protocol,
+        dnssec: props & PROP_DNSSEC != 0,
+        no_logs: props & PROP_NO_LOGS != 0,
+        no_filter: props & PROP_NO_FILTER != 0,
+        addr: r.lp_string()?,
+        hashes: vec![],
+        hostname: String::new(),
+        path: String::new(),
+    };
+
+    match protocol {
+        StampProtocol::Plain => {}
+        StampProtocol::DnsCrypt => {
+            // public key, then provider name
+            r.lp()?;
+            stamp.hostname = r.lp_string()?;
+        }
+        StampProtocol::Doh | StampProtocol::Dot | StampProtocol::Doq => {
+            for hash in r.vlp()? {
+                let digest = <[u8; 32]>::try_from(hash)
+                    .map_err(|_| "Stamp hash is not a SHA-256 digest".to_string())?;
+                stamp.hashes.push(digest);
+            }
+            stamp.hostname = r.lp_string()?;
+            if protocol == StampProtocol::Doh {
+                stamp.path = r.lp_string()?;
+            }
+            // Optional bootstrap IPs are ignored; the bootstrap strategy decides.
+            if r.remaining() {
+                r.vlp()?;
+            }
+        }
+    }
+    Ok(stamp)
+}
+
+fn push_lp(out: &mut Vec<u8>, field: &[u8]) -> Result<(), String> {
+    let len = u8::try_from(field.len()).map_err(|_| "Stamp field longer than 255 bytes")?;
+    out.push(len);
+    out.extend_from_slice(field);
+    Ok(())
+}
+
+// Inverse of decode_stamp. DNSCrypt stamps need the provider key, which DnsStamp does not keep.
+pub fn encode_stamp(stamp: &DnsStamp) -> Result<String, String> {
+    let protocol = match stamp.protocol {
+        StampProtocol::Plain => 0x00,
+        StampProtocol::DnsCrypt => return Err("DNSCrypt stamps can't be re-encoded".to_string()),
+        StampProtocol::Doh => 0x02,
+        StampProtocol::Dot => 0x03,
+        StampProtocol::Doq => 0x04,
+    };
+    let mut props = 0;
+    if stamp.dnssec {
+        props |= PROP_DNSSEC;
+    }
+    if stamp.no_logs {
+        props |= PROP_NO_LOGS;
+    }
+    if stamp.no_filter {
+        props |= PROP_NO_FILTER;
+    }
+    let mut out = vec![protocol];
+    out.extend_from_slice(&props.to_le_bytes());
+    push_lp(&mut out, stamp.addr.as_bytes())?;
+    if stamp.protocol != StampProtocol::Plain {
+        if stamp.hashes.is_empty() {
+            out.push(0);
+        }
+        for (i, hash) in stamp.hashes.iter().enumerate() {
+            let more = if i + 1 < stamp.hashes.len() { 0x80 } else { 0 };
+            out.push(hash.len() as u8 | more);
+            out.extend_from_slice(hash);
+        }
+        push_lp(&mut out, stamp.hostname.as_bytes())?;
+        if stamp.protocol == StampProtocol::Doh {
+            push_lp(&mut out, stamp.path.as_bytes())?;
+        }
+    }
+    Ok(format!("sdns://{}", URL_SAFE_NO_PAD.encode(out)))
+}
+
+impl DnsStamp {
+    // (hostname, host to connect to, port). The address, when present, overrides the
+    // hostname's host and port; the hostname is empty for plain and DNSCrypt stamps.
+    pub fn endpoint(&self, default_port: u16) -> Result<(String, String, u16), String> {
+        let (hostname, host_port) =
+            if self.hostname.is_empty() || self.protocol == StampProtocol::DnsCrypt {
+                (String::new(), default_port)
+            } else {
+                split_host_port(&self.hostname, default_port)?
+            };
+        let (host, port) = if self.addr.is_empty() {
+            (hostname.clone(), host_port)
+        } else {
+            split_host_port(&self.addr, host_port)?
+        };
+        Ok((hostname, host, port))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cloudflare's entry in dnscrypt-proxy's public-resolvers.md
+    const CLOUDFLARE: &str =
+        "sdns://AgcAAAAAAAAABzEuMC4wLjEAEmRucy5jbG91ZGZsYXJlLmNvbQovZG5zLXF1ZXJ5";
+
+    fn stamp(protocol: StampProtocol, addr: &str, hostname: &str) -> DnsStamp {
+        DnsStamp {
+            protocol,
+            dnssec: false,
+            no_logs: false,
+            no_filter: false,
+            addr: addr.to_string(),
+            hashes: vec![],
+            hostname: hostname.to_string(),
+            path: String::new(),
+        }
+    }
+
+    #[test]
+    fn decodes_a_published_stamp() {
+        let decoded = decode_stamp(CLOUDFLARE).unwrap();
+        assert_eq!(
+            decoded,
+            DnsStamp {
+                protocol: StampProtocol::Doh,
+                dnssec: true,
+                no_logs: true,
+                no_filter: true,
+                addr: "1.0.0.1".to_string(),
+                hashes: vec![],
+                hostname: "dns.cloudflare.com".to_string(),
+                path: "/dns-query".to_string(),
+            }
+        );
+        assert_eq!(encode_stamp(&decoded).unwrap(), CLOUDFLARE);
+    }
+
+    #[test]
+    fn every_protocol_round_trips() {
+        let mut dot = stamp(StampProtocol::Dot, "[2001:db8::1]:853", "dns.example");
+        dot.hashes = vec![[1; 32], [2; 32]];
+        dot.no_logs = true;
+        let mut doh = stamp(StampProtocol::Doh, "", "dns.example:8443");
+        doh.path = "/q".to_string();
+        doh.dnssec = true;
+        for original in [
+            stamp(StampProtocol::Plain, "192.0.2.1:5353", ""),
+            dot,
+            doh,
+            stamp(StampProtocol::Doq, "192.0.2.1", "dns.example"),
+        ] {
+            let encoded = encode_stamp(&original).unwrap();
+            assert_eq!(decode_stamp(&encoded).unwrap(), original, "{}", encoded);
+        }
+        assert!(encode_stamp(&stamp(StampProtocol::DnsCrypt, "192.0.2.1", "x")).is_err());
+        let long = stamp(StampProtocol::Dot, "", &"a".repeat(256));
+        assert!(encode_stamp(&long).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_stamps() {
+        let encode = |bytes: &[u8]| format!("sdns://{}", URL_SAFE_NO_PAD.encode(bytes));
+        let mut short_hash = vec![0x03];
+        short_hash.extend_from_slice(&[0; 8]);
+        short_hash.extend_from_slice(&[0, 2, 1, 2, 0]);
+        for (stamp, error) in [
+            ("https://dns.example".to_string(), "must start with sdns://"),
+            ("sdns://!!".to_string(), "Invalid stamp encoding"),
+            (encode(&[0x05, 0, 0, 0, 0, 0, 0, 0, 0, 0]), "protocol 0x05"),
+            (encode(&[0x00, 0, 0]), "Truncated stamp"),
+            (
+                encode(&[0x00, 0, 0, 0, 0, 0, 0, 0, 0, 4, b'1']),
+                "Truncated",
+            ),
+            (encode(&short_hash), "not a SHA-256 digest"),
+        ] {
+            let e = decode_stamp(&stamp).unwrap_err();
+            assert!(e.contains(error), "{}: {}", stamp, e);
+        }
+    }
+
+    #[test]
+    fn endpoint_prefers_the_address_and_inherits_the_port() {
+        let plain = stamp(StampProtocol::Plain, "[2001:db8::1]:5353", "");
+        assert_eq!(
+            plain.endpoint(53),
+            Ok((String::new(), "2001:db8::1".to_string(), 5353))
+        );
+        let by_name = stamp(StampProtocol::Dot, "", "DNS.example:8853");
+        assert_eq!(
+            by_name.endpoint(853),
+            Ok(("dns.example".to_string(), "dns.example".to_string(), 8853))
+        );
+        let by_addr = stamp(StampProtocol::Dot, "192.0.2.1", "dns.example:8853");
+        assert_eq!(
+            by_addr.endpoint(853),
+            Ok(("dns.example".to_string(), "192.0.2.1".to_string(), 8853))
+        );
+        let dnscrypt = stamp(StampProtocol::DnsCrypt, "192.0.2.1", "2.dnscrypt.example");
+        assert_eq!(
+            dnscrypt.endpoint(443),
+            Ok((String::new(), "192.0.2.1".to_string(), 443))
+        );
+        assert!(stamp(StampProtocol::Dot, "192.0.2.1:0", "x")
+            .endpoint(853)
+            .is_err());
+    }
+}
@@ -0,0 +1,536 @@
+use minisign_verify::{PublicKey, Signature};
+use once_cell::sync::Lazy;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use super::servers::{self, default_entries, validate_address};
+use crate::storage;
+
+const SUBSCRIPTIONS_FILE: &str = "subscriptions.json";
+const DEFAULT_SUBSCRIPTION_URL: &str =
+    "https://raw.githubusercontent.com/ednoct/DNS_SERVERS/main/servers.txt";
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+// Keep the status payload small when a source is mostly garbage
+const MAX_REPORTED_REJECTS: usize = 20;
+
+// How a source combines with the list built so far (built-in defaults, then each
+// enabled subscription in order).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeRule {
+    Append,
+    #[default]
+    Replace,
+    Intersect,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Subscription {
+    pub url: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub merge: MergeRule,
+    // minisign public key (base64). When set, the list must carry a valid signature.
+    #[serde(default)]
+    pub public_key: Option<String>,
+    // Defaults to `<url>.minisig`
+    #[serde(default)]
+    pub signature_url: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SubscriptionState {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    // Unix seconds
+    pub last_checked: Option<u64>,
+    pub last_updated: Option<u64>,
+    pub accepted: usize,
+    pub rejected: Vec<String>,
+    // True when the entries came from the last-good copy on disk
+    pub from_cache: bool,
+    pub last_error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SubscriptionStatus {
+    #[serde(flatten)]
+    pub subscription: Subscription,
+    #[serde(flatten)]
+    pub state: SubscriptionState,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SubscriptionStore {
+    subscriptions: Vec<Subscription>,
+    #[serde(default)]
+    state: HashMap<String, SubscriptionState>,
+}
+
+impl Default for SubscriptionStore {
+    fn default() -> Self {
+        SubscriptionStore {
+            subscriptions: vec![Subscription {
+                url: DEFAULT_SUBSCRIPTION_URL.to_string(),
+                enabled: true,
+                merge: MergeRule::Replace,
+                public_key: None,
+                signature_url: None,
+            }],
+            state: HashMap::new(),
+        }
+    }
+}
+
+static SUBSCRIPTIONS: Lazy<RwLock<SubscriptionStore>> =
+    Lazy::new(|| RwLock::new(storage::read_json(SUBSCRIPTIONS_FILE).unwrap_or_default()));
+
+fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Last-good copy of a source, keyed by a hash of its URL. Signed sources keep their
+// detached signature next to it (`.minisig`).
+fn cache_path(url: &str) -> Option<std::path::PathBuf> {
+    let digest = Sha256::digest(url.as_bytes());
+    let key: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    storage::data_file(&format!("subscriptions/{}.txt", key))
+}
+
+// The cached copy is re-verified on every read, so a key added or changed after the copy
+// was stored can't let unsigned or foreign-signed entries through. Copies that fail are dropped.
+fn read_cache(sub: &Subscription) -> Option<String> {
+    let path = cache_path(&sub.url)?;
+    let text = std::fs::read_to_string(&path).ok()?;
+    let signature = std::fs::read_to_string(path.with_extension("minisig")).ok();
+    match verify_cached(text, signature, sub.public_key.as_deref()) {
+        Ok(text) => Some(text),
+        Err(e) => {
+            warn!("Dropping cached copy of {}: {}", sub.url, e);
+            drop_cache(&sub.url);
+            None
+        }
+    }
+}
+
+fn verify_cached(
+    text: String,
+    signature: Option<String>,
+    public_key: Option<&str>,
+) -> Result<String, String> {
+    if let Some(key) = public_key {
+        let signature = signature.ok_or("Cached copy has no signature")?;
+        verify_signature(key, &signature, text.as_bytes())?;
+    }
+    Ok(text)
+}
+
+fn write_cache(url: &str, text: &str, signature: Option<&str>) {
+    let Some(path) = cache_path(url) else {
+        return;
+    };
+    let sig_path = path.with_extension("minisig");
+    let written = storage::write_atomic(&path, text.as_bytes()).and_then(|()| match signature {
+        Some(signature) => storage::write_atomic(&sig_path, signature.as_bytes()),
+        None => match std::fs::remove_file(&sig_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("{}: {}", sig_path.display(), e))
+            }
+            _ => Ok(()),
+        },
+    });
+    if let Err(e) = written {
+        warn!("Could not cache {}: {}", url, e);
+    }
+}
+
+fn drop_cache(url: &str) {
+    if let Some(path) = cache_path(url) {
+        let _ = std::fs::remove_file(path.with_extension("minisig"));
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+// Valid addresses in file order (deduplicated) plus "line N: reason" for the rest.
+fn parse_entries(text: &str) -> (Vec<String>, Vec<String>) {
+    let mut seen = HashSet::new();
+    let mut accepted = Vec::new();
+    let mut rejected = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match validate_address(line) {
+            Ok(()) => {
+                if seen.insert(line.to_string()) {
+                    accepted.push(line.to_string());
+                }
+            }
+            Err(e) => rejected.push(format!("line {}: {}", n + 1, e)),
+        }
+    }
+    (accepted, rejected)
+}
+
+fn verify_signature(public_key: &str, signature: &str, body: &[u8]) -> Result<(), String> {
+    let key = PublicKey::from_base64(public_key.trim())
+        .map_err(|e| format!("Invalid public key: {}", e))?;
+    let sig = Signature::decode(signature).map_err(|e| format!("Invalid signature file: {}", e))?;
+    key.verify(body, &sig, false)
+        .map_err(|e| format!("Signature verification failed: {}", e))
+}
+
+enum Fetched {
+    NotModified,
+    Body {
+        text: String,
+        // Detached minisign signature, when the source has a key
+        signature: Option<String>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+async fn fetch(
+    client: &reqwest::Client,
+    sub: &Subscription,
+    state: &SubscriptionState,
+    has_cache: bool,
+) -> Result<Fetched, String> {
+    let mut request = client.get(&sub.url);
+    // Conditional headers only make sense if a 304 can be served from disk
+    if has_cache {
+        if let Some(etag) = &state.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(modified) = &state.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, modified);
+        }
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Fetched::NotModified);
+    }
+    let response = response.error_for_status().map_err(|e| e.to_string())?;
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+    let text = response.text().await.map_err(|e| e.to_string())?;
+
+    let mut verified_signature = None;
+    if let Some(key) = &sub.public_key {
+        let sig_url = sub
+            .signature_url
+            .clone()
+            .unwrap_or_else(|| format!("{}.minisig", sub.url));
+        let signature = client
+            .get(&sig_url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Could not fetch signature {}: {}", sig_url, e))?
+            .text()
+            .await
+            .map_err(|e| e.to_string())?;
+        verify_signature(key, &signature, text.as_bytes())?;
+        verified_signature = Some(signature);
+    }
+    Ok(Fetched::Body {
+        text,
+        signature: verified_signature,
+        etag,
+        last_modified,
+    })
+}
+
+// Fetches one source. On any failure the last-good copy is used and the error recorded.
+async fn refresh_one(
+    client: &reqwest::Client,
+    sub: &Subscription,
+    mut state: SubscriptionState,
+) -> (Option<Vec<String>>, SubscriptionState) {
+    state.last_checked = Some(now_unix());
+    let cached = read_cache(sub);
+
+    let fresh = match fetch(client, sub, &state, cached.is_some()).await {
+        Ok(Fetched::NotModified) => Ok(None),
+        Ok(Fetched::Body {
+            text,
+            signature,
+            etag,
+            last_modified,
+        }) => {
+            let (accepted, rejected) = parse_entries(&text);
+            if accepted.is_empty() {
+                Err(format!(
+                    "No valid entries in response ({} rejected)",
+                    rejected.len()
+                ))
+            } else {
+                write_cache(&sub.url, &text, signature.as_deref());
+                state.etag = etag;
+                state.last_modified = last_modified;
+                state.last_updated = Some(now_unix());
+                Ok(Some((accepted, rejected)))
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    let parsed = match fresh {
+        Ok(Some(parsed)) => {
+            state.from_cache = false;
+            state.last_error = None;
+            Some(parsed)
+        }
+        Ok(None) => {
+            state.from_cache = true;
+            state.last_error = None;
+            cached.as_deref().map(parse_entries)
+        }
+        Err(e) => {
+            warn!("Subscription {} failed: {}", sub.url, e);
+            state.from_cache = true;
+            state.last_error = Some(e);
+            cached.as_deref().map(parse_entries)
+        }
+    };
+
+    match parsed {
+        Some((accepted, mut rejected)) => {
+            state.accepted = accepted.len();
+            rejected.truncate(MAX_REPORTED_REJECTS);
+            state.rejected = rejected;
+            (Some(accepted), state)
+        }
+        None => {
+            state.accepted = 0;
+            (None, state)
+        }
+    }
+}
+
+fn merge(base: Vec<String>, incoming: Vec<String>, rule: MergeRule) -> Vec<String> {
+    match rule {
+        MergeRule::Replace => incoming,
+        MergeRule::Append => {
+            let mut out = base;
+            for address in incoming {
+                if !out.contains(&address) {
+                    out.push(address);
+                }
+            }
+            out
+        }
+        MergeRule::Intersect => {
+            let keep: HashSet<&String> = incoming.iter().collect();
+            base.into_iter().filter(|a| keep.contains(a)).collect()
+        }
+    }
+}
+
+// Refreshes every enabled subscription and pushes the merged list to the profiles
+// that follow remote updates. Nothing is applied if no source produced entries.
+pub async fn refresh_all() -> Vec<SubscriptionStatus> {
+    let (subscriptions, mut states) = {
+        let store = SUBSCRIPTIONS.read().await;
+        (store.subscriptions.clone(), store.state.clone())
+    };
+    let client = match reqwest::Client::builder().timeout(FETCH_TIMEOUT).build() {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("Could not build HTTP client: {}", e);
+            return get_statuses().await;
+        }
+    };
+
+    let mut merged: Vec<String> = default_entries().into_iter().map(|e| e.address).collect();
+    let mut applied_any = false;
+    for sub in subscriptions.iter().filter(|s| s.enabled) {
+        let state = states.remove(&sub.url).unwrap_or_default();
+        let (addresses, state) = refresh_one(&client, sub, state).await;
+        states.insert(sub.url.clone(), state);
+        if let Some(addresses) = addresses {
+            merged = merge(merged, addresses, sub.merge);
+            applied_any = true;
+        }
+    }
+
+    {
+        let mut store = SUBSCRIPTIONS.write().await;
+        // The list may have been edited while fetching; only keep state for current URLs.
+        let urls: HashSet<&String> = store.subscriptions.iter().map(|s| &s.url).collect();
+        states.retain(|url, _| urls.contains(url));
+        store.state = states;
+        if let Err(e) = storage::write_json(SUBSCRIPTIONS_FILE, &*store) {
+            warn!("Could not save subscriptions: {}", e);
+        }
+    }
+
+    if applied_any && !merged.is_empty() {
+        info!("Subscriptions produced {} servers", merged.len());
+        servers::apply_remote_addresses(merged).await;
+    }
+    get_statuses().await
+}
+
+pub async fn get_statuses() -> Vec<SubscriptionStatus> {
+    let store = SUBSCRIPTIONS.read().await;
+    store
+        .subscriptions
+        .iter()
+        .map(|s| SubscriptionStatus {
+            subscription: s.clone(),
+            state: store.state.get(&s.url).cloned().unwrap_or_default(),
+        })
+        .collect()
+}
+
+pub async fn set_subscriptions(subscriptions: Vec<Subscription>) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    let mut cleaned = Vec::with_capacity(subscriptions.len());
+    for mut sub in subscriptions {
+        sub.url = sub.url.trim().to_string();
+        let url = url::Url::parse(&sub.url).map_err(|e| format!("{}: {}", sub.url, e))?;
+        if url.scheme() != "https" && url.scheme() != "http" {
+            return Err(format!("Subscription must be an http(s) URL: {}", sub.url));
+        }
+        if !seen.insert(sub.url.clone()) {
+            return Err(format!("Duplicate subscription: {}", sub.url));
+        }
+        sub.public_key = sub
+            .public_key
+            .map(|k| k.trim().to_string())
+            .filter(|k| !k.is_empty());
+        if let Some(key) = &sub.public_key {
+            PublicKey::from_base64(key).map_err(|e| format!("Invalid public key: {}", e))?;
+        }
+        sub.signature_url = sub
+            .signature_url
+            .map(|u| u.trim().to_string())
+            .filter(|u| !u.is_empty());
+        cleaned.push(sub);
+    }
+
+    let mut store = SUBSCRIPTIONS.write().await;
+    // A new or removed key invalidates what was cached under the old one; forgetting the
+    // validators too makes the next fetch unconditional instead of a 304 with nothing to serve.
+    for sub in &cleaned {
+        let previous = store.subscriptions.iter().find(|s| s.url == sub.url);
+        if previous.is_some_and(|p| p.public_key != sub.public_key) {
+            drop_cache(&sub.url);
+            if let Some(state) = store.state.get_mut(&sub.url) {
+                state.etag = None;
+                state.last_modified = None;
+            }
+        }
+    }
+    store.subscriptions = cleaned;
+    storage::write_json(SUBSCRIPTIONS_FILE, &*store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example pair from the minisign-verify documentation, signing the bytes "test".
+    const KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    // Same key id, different key
+    const OTHER_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51THPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1633700835\tfile:test\tprehashed
+wLMDjy9FLAuxZ3q4NlEvkgtyhrr0gtTu6KC4KBJdITbbOeAi1zBIYo0v4iTgt8jJpIidRJnp94ABQkJAgAooBQ==";
+
+    fn cached(signature: Option<&str>, key: Option<&str>) -> Result<String, String> {
+        verify_cached("test".to_string(), signature.map(str::to_string), key)
+    }
+
+    #[test]
+    fn unsigned_sources_use_the_cache_as_is() {
+        assert_eq!(cached(None, None).as_deref(), Ok("test"));
+    }
+
+    #[test]
+    fn signed_caches_are_verified_on_read() {
+        assert_eq!(cached(Some(SIGNATURE), Some(KEY)).as_deref(), Ok("test"));
+        // cached before the key was configured
+        assert!(cached(None, Some(KEY)).is_err());
+        // signed for a key that has since been replaced
+        assert!(cached(Some(SIGNATURE), Some(OTHER_KEY)).is_err());
+        assert!(verify_cached(
+            "tampered".to_string(),
+            Some(SIGNATURE.to_string()),
+            Some(KEY)
+        )
+        .is_err());
+    }
+
+    fn list(addresses: &[&str]) -> Vec<String> {
+        addresses.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_rules() {
+        let base = list(&["1.1.1.1", "8.8.8.8", "9.9.9.9"]);
+        let incoming = list(&["9.9.9.9", "94.140.14.14", "1.1.1.1"]);
+        assert_eq!(
+            merge(base.clone(), incoming.clone(), MergeRule::Replace),
+            incoming
+        );
+        // base order first, then new addresses in the order they came
+        assert_eq!(
+            merge(base.clone(), incoming.clone(), MergeRule::Append),
+            list(&["1.1.1.1", "8.8.8.8", "9.9.9.9", "94.140.14.14"])
+        );
+        // only what both lists have, in base order
+        assert_eq!(
+            merge(base.clone(), incoming, MergeRule::Intersect),
+            list(&["1.1.1.1", "9.9.9.9"])
+        );
+        assert!(merge(base, vec![], MergeRule::Intersect).is_empty());
+    }
+
+    #[test]
+    fn parse_entries_skips_comments_and_reports_bad_lines() {
+        let text = "# resolvers\n\
+                    \n\
+                    1.1.1.1\n\
+                    \x20 tls://dns.google \n\
+                    not a server\n\
+                    1.1.1.1\n\
+                    https://\n\
+                    \x20 # indented comment\n\
+                    quic://dns.adguard-dns.com";
+        let (accepted, rejected) = parse_entries(text);
+        assert_eq!(
+            accepted,
+            list(&["1.1.1.1", "tls://dns.google", "quic://dns.adguard-dns.com"])
+        );
+        assert_eq!(rejected.len(), 2, "{:?}", rejected);
+        assert!(rejected[0].starts_with("line 5: "), "{:?}", rejected);
+        assert!(rejected[1].starts_with("line 7: "), "{:?}", rejected);
+
+        assert_eq!(parse_entries("# nothing here\n\n"), (vec![], vec![]));
+    }
+}
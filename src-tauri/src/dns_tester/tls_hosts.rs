@@ -1,41 +1,262 @@
-use once_cell::sync::OnceCell;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tokio::sync::RwLock;
+use std::net::IpAddr;
+use tokio::sync::{Mutex, RwLock};
 use reqwest::Client;
 
-// Maps IP -> TLS DNS name (hostname for SNI)
+use super::spec::is_valid_hostname;
+use crate::storage;
+
+const USER_FILE: &str = "tls_hosts_user.json";
+const REMOTE_FILE: &str = "tls_hosts_remote.json";
+const DISCOVERED_FILE: &str = "tls_hosts_discovered.json";
+
+// Maps IP -> TLS DNS name (hostname for SNI).
+// Effective view of the layers below, lowest precedence first:
+// defaults, discovered (by SNI discovery), remote, user overrides.
 pub static TLS_HOST_MAP: OnceCell<RwLock<HashMap<String, String>>> = OnceCell::new();
 
-pub fn init_tls_hosts() {
-    let default = DEFAULT_TLS_HOST_MAP
+#[derive(Default)]
+struct HostLayers {
+    discovered: HashMap<String, String>,
+    remote: HashMap<String, String>,
+    user: HashMap<String, String>,
+}
+
+// The layers that are saved to a file of their own.
+#[derive(Clone, Copy)]
+enum Layer {
+    Discovered,
+    Remote,
+    User,
+}
+
+impl Layer {
+    fn file(self) -> &'static str {
+        match self {
+            Layer::Discovered => DISCOVERED_FILE,
+            Layer::Remote => REMOTE_FILE,
+            Layer::User => USER_FILE,
+        }
+    }
+}
+
+impl HostLayers {
+    fn get(&self, layer: Layer) -> &HashMap<String, String> {
+        match layer {
+            Layer::Discovered => &self.discovered,
+            Layer::Remote => &self.remote,
+            Layer::User => &self.user,
+        }
+    }
+
+    fn get_mut(&mut self, layer: Layer) -> &mut HashMap<String, String> {
+        match layer {
+            Layer::Discovered => &mut self.discovered,
+            Layer::Remote => &mut self.remote,
+            Layer::User => &mut self.user,
+        }
+    }
+}
+
+static LAYERS: Lazy<RwLock<HostLayers>> = Lazy::new(|| {
+    RwLock::new(HostLayers {
+        discovered: storage::read_json(DISCOVERED_FILE).unwrap_or_default(),
+        remote: storage::read_json(REMOTE_FILE).unwrap_or_default(),
+        user: storage::read_json(USER_FILE).unwrap_or_default(),
+    })
+});
+// Serializes updates, so one update cannot overwrite another between its read and its swap.
+static SAVING: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HostSource {
+    Default,
+    Discovered,
+    Remote,
+    User,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TlsHostEntry {
+    pub ip: String,
+    pub host: String,
+    pub source: HostSource,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TlsHostImport {
+    pub imported: usize,
+    pub rejected: Vec<String>,
+}
+
+fn default_map() -> HashMap<String, String> {
+    DEFAULT_TLS_HOST_MAP
         .iter()
         .map(|(ip, host)| (ip.to_string(), host.to_string()))
-        .collect::<HashMap<_, _>>();
-    TLS_HOST_MAP.set(RwLock::new(default)).ok();
+        .collect()
 }
 
-pub async fn update_tls_hosts_from_url() -> Result<(), reqwest::Error> {
-    // Remote file format: "host ip" per line.
-    let url = "https://raw.githubusercontent.com/ednoct/DNS_SERVERS/main/tls-host-map.txt";
-    let client = Client::new();
-    let response = client.get(url).send().await?.text().await?;
+fn effective_entries(layers: &HostLayers) -> Vec<TlsHostEntry> {
+    let mut merged: HashMap<String, (String, HostSource)> = HashMap::new();
+    let sources = [
+        (default_map(), HostSource::Default),
+        (layers.discovered.clone(), HostSource::Discovered),
+        (layers.remote.clone(), HostSource::Remote),
+        (layers.user.clone(), HostSource::User),
+    ];
+    for (map, source) in sources {
+        for (ip, host) in map {
+            merged.insert(ip, (host, source));
+        }
+    }
+    let mut entries: Vec<TlsHostEntry> = merged
+        .into_iter()
+        .map(|(ip, (host, source))| TlsHostEntry { ip, host, source })
+        .collect();
+    entries.sort_by(|a, b| a.ip.cmp(&b.ip));
+    entries
+}
+
+async fn rebuild_effective(layers: &HostLayers) {
+    let map = effective_entries(layers)
+        .into_iter()
+        .map(|e| (e.ip, e.host))
+        .collect();
+    match TLS_HOST_MAP.get() {
+        Some(cell) => *cell.write().await = map,
+        None => {
+            TLS_HOST_MAP.set(RwLock::new(map)).ok();
+        }
+    }
+}
+
+pub fn init_tls_hosts() {
+    Lazy::force(&LAYERS);
+    let map = LAYERS
+        .try_read()
+        .map(|layers| {
+            effective_entries(&layers)
+                .into_iter()
+                .map(|e| (e.ip, e.host))
+                .collect()
+        })
+        .unwrap_or_else(|_| default_map());
+    TLS_HOST_MAP.set(RwLock::new(map)).ok();
+}
 
-    let mut new_map = HashMap::new();
-    for line in response.lines() {
+// Canonical (ip, host) pair, or why it was rejected.
+fn validate_pair(ip: &str, host: &str) -> Result<(String, String), String> {
+    let ip: IpAddr = ip
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid IP address: {}", ip.trim()))?;
+    let host = host.trim().trim_end_matches('.').to_ascii_lowercase();
+    if !is_valid_hostname(&host) {
+        return Err(format!("Invalid hostname: {}", host));
+    }
+    Ok((ip.to_string(), host))
+}
+
+// One pair per line, "host ip" (the remote format) or "ip host"; '#' starts a comment.
+fn parse_pairs(text: &str) -> (HashMap<String, String>, Vec<String>) {
+    let mut pairs = HashMap::new();
+    let mut rejected = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
         let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() == 2 {
-            // store as IP -> host
-            let host = parts[0].to_string();
-            let ip = parts[1].to_string();
-            new_map.insert(ip, host);
+        let result = match parts.as_slice() {
+            [a, b] if a.parse::<IpAddr>().is_ok() => validate_pair(a, b),
+            [a, b] => validate_pair(b, a),
+            _ => Err("expected a hostname and an IP".to_string()),
+        };
+        match result {
+            Ok((ip, host)) => {
+                pairs.insert(ip, host);
+            }
+            Err(e) => rejected.push(format!("line {}: {}", n + 1, e)),
         }
     }
+    (pairs, rejected)
+}
+
+// Builds the new layer from the current one and saves it outside the layers lock; the
+// effective map only changes once the file is written.
+async fn update_layer(
+    layer: Layer,
+    update: impl FnOnce(&mut HashMap<String, String>),
+) -> Result<(), String> {
+    let _saving = SAVING.lock().await;
+    let mut map = LAYERS.read().await.get(layer).clone();
+    update(&mut map);
+    let saved = map.clone();
+    tokio::task::spawn_blocking(move || storage::write_json(layer.file(), &saved))
+        .await
+        .map_err(|e| format!("Could not save TLS hosts: {}", e))??;
+    let mut layers = LAYERS.write().await;
+    *layers.get_mut(layer) = map;
+    rebuild_effective(&layers).await;
+    Ok(())
+}
+
+pub async fn get_entries() -> Vec<TlsHostEntry> {
+    effective_entries(&*LAYERS.read().await)
+}
 
-    if let Some(map) = TLS_HOST_MAP.get() {
-        let mut locked = map.write().await;
-        *locked = new_map;
+// Replaces the user layer (IP -> host). Remote data and defaults are untouched.
+pub async fn set_user_map(map: HashMap<String, String>) -> Result<(), String> {
+    let mut user = HashMap::with_capacity(map.len());
+    for (ip, host) in &map {
+        let (ip, host) = validate_pair(ip, host)?;
+        user.insert(ip, host);
     }
+    update_layer(Layer::User, |layer| *layer = user).await
+}
 
+// Adds pairs from text to the user layer; invalid lines are reported, not fatal.
+pub async fn import_user_pairs(text: &str) -> Result<TlsHostImport, String> {
+    let (pairs, rejected) = parse_pairs(text);
+    if pairs.is_empty() {
+        return Err(format!("No valid entries ({} rejected)", rejected.len()));
+    }
+    let imported = pairs.len();
+    update_layer(Layer::User, |user| user.extend(pairs)).await?;
+    Ok(TlsHostImport { imported, rejected })
+}
+
+// Stores a name confirmed by SNI discovery (see `sni_discovery`).
+pub async fn record_discovered(ip: IpAddr, host: &str) -> Result<(), String> {
+    let (ip, host) = validate_pair(&ip.to_string(), host)?;
+    update_layer(Layer::Discovered, |discovered| {
+        discovered.insert(ip, host);
+    })
+    .await
+}
+
+pub async fn update_tls_hosts_from_url() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Remote file format: "host ip" per line.
+    let url = "https://raw.githubusercontent.com/ednoct/DNS_SERVERS/main/tls-host-map.txt";
+    let client = Client::new();
+    let response = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    let (new_map, rejected) = parse_pairs(&response);
+    if new_map.is_empty() {
+        // Keep the previous remote layer rather than wiping it with a bad download
+        return Err(format!("No valid TLS host entries ({} rejected)", rejected.len()).into());
+    }
+
+    update_layer(Layer::Remote, |remote| *remote = new_map).await?;
     Ok(())
 }
 
@@ -50,3 +271,117 @@ pub const DEFAULT_TLS_HOST_MAP: &[(&str, &str)] = &[
     ("8.26.56.26", "cdns.comodo.com"),
     ("137.66.7.89", "max.rethinkdns.com"),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(ip, host)| (ip.to_string(), host.to_string()))
+            .collect()
+    }
+
+    fn entry<'a>(entries: &'a [TlsHostEntry], ip: &str) -> (&'a str, HostSource) {
+        let e = entries.iter().find(|e| e.ip == ip).unwrap();
+        (e.host.as_str(), e.source)
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let layers = HostLayers {
+            discovered: map(&[("1.1.1.1", "found.example"), ("192.0.2.1", "found.example")]),
+            remote: map(&[
+                ("1.1.1.1", "remote.example"),
+                ("192.0.2.2", "remote.example"),
+            ]),
+            user: map(&[("8.8.8.8", "mine.example"), ("192.0.2.2", "mine.example")]),
+        };
+        let entries = effective_entries(&layers);
+        assert_eq!(
+            entry(&entries, "1.1.1.1"),
+            ("remote.example", HostSource::Remote)
+        );
+        assert_eq!(
+            entry(&entries, "8.8.8.8"),
+            ("mine.example", HostSource::User)
+        );
+        assert_eq!(
+            entry(&entries, "9.9.9.9"),
+            ("dns.quad9.net", HostSource::Default)
+        );
+        assert_eq!(
+            entry(&entries, "192.0.2.1"),
+            ("found.example", HostSource::Discovered)
+        );
+        assert_eq!(
+            entry(&entries, "192.0.2.2"),
+            ("mine.example", HostSource::User)
+        );
+        assert_eq!(entries.len(), DEFAULT_TLS_HOST_MAP.len() + 2);
+        assert!(entries.windows(2).all(|w| w[0].ip < w[1].ip));
+    }
+
+    #[test]
+    fn pairs_are_validated_and_canonical() {
+        assert_eq!(
+            validate_pair(" 2001:DB8:0::1 ", "DNS.Example.com."),
+            Ok(("2001:db8::1".to_string(), "dns.example.com".to_string()))
+        );
+        assert_eq!(
+            validate_pair("dns.example.com", "1.1.1.1"),
+            Err("Invalid IP address: dns.example.com".to_string())
+        );
+        assert_eq!(
+            validate_pair("1.1.1.1", "not a host"),
+            Err("Invalid hostname: not a host".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_pairs_in_either_order() {
+        let text = "# remote format\n\
+                    dns.google 8.8.8.8\n\
+                    \n\
+                    9.9.9.9   dns.quad9.net   # ip first\n\
+                    1.1.1.1\n\
+                    bad_host! 1.0.0.1\n\
+                    one.example two.example\n";
+        let (pairs, rejected) = parse_pairs(text);
+        assert_eq!(
+            pairs,
+            map(&[("8.8.8.8", "dns.google"), ("9.9.9.9", "dns.quad9.net")])
+        );
+        assert_eq!(
+            rejected,
+            vec![
+                "line 5: expected a hostname and an IP",
+                "line 6: Invalid hostname: bad_host!",
+                "line 7: Invalid IP address: two.example",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn updates_reach_the_effective_map() {
+        record_discovered("198.51.100.53".parse().unwrap(), "Found.Example")
+            .await
+            .unwrap();
+        let entries = get_entries().await;
+        assert_eq!(
+            entry(&entries, "198.51.100.53"),
+            ("found.example", HostSource::Discovered)
+        );
+
+        let import = import_user_pairs("198.51.100.53 mine.example\nnonsense\n")
+            .await
+            .unwrap();
+        assert_eq!((import.imported, import.rejected.len()), (1, 1));
+        let effective = TLS_HOST_MAP.get().unwrap().read().await;
+        assert_eq!(
+            effective.get("198.51.100.53").map(String::as_str),
+            Some("mine.example")
+        );
+    }
+}
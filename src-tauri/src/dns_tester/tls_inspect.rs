@@ -0,0 +1,697 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+#[cfg(feature = "test-support")]
+use once_cell::sync::Lazy;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+#[cfg(feature = "test-support")]
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "test-support")]
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tokio_rustls::TlsConnector;
+use tracing::warn;
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+use super::pinning::PinSet;
+
+// Certificates expiring sooner than this are flagged in `TlsDetails::warnings`.
+const EXPIRY_WARNING_DAYS: i64 = 14;
+
+// Private CAs trusted on top of the platform roots, for the loopback servers in tests/support.
+// Only exists with the `test-support` feature, which the integration tests turn on.
+#[cfg(feature = "test-support")]
+static EXTRA_ROOTS: Lazy<RwLock<Option<Arc<WebPkiServerVerifier>>>> = Lazy::new(|| RwLock::new(None));
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsTransport {
+    Dot,
+    Doh,
+    Doq,
+}
+
+impl TlsTransport {
+    fn alpn(&self) -> Vec<Vec<u8>> {
+        match self {
+            TlsTransport::Dot => vec![],
+            TlsTransport::Doh => vec![b"h2".to_vec()],
+            TlsTransport::Doq => vec![b"doq".to_vec()],
+        }
+    }
+}
+
+// Where an encrypted server was actually dialled, as decided by `build_resolver_for_server`.
+#[derive(Debug, Clone)]
+pub struct TlsTarget {
+    pub transport: TlsTransport,
+    pub ip: IpAddr,
+    pub port: u16,
+    pub tls_name: String,
+    // true when `tls_name` was taken from TLS_HOST_MAP for an IP-only server
+    pub name_from_host_map: bool,
+    pub pins: PinSet,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CertInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub sans: Vec<String>,
+    pub serial: String,
+    pub not_before: String,
+    pub not_after: String,
+    pub days_until_expiry: i64,
+    // base64 SHA-256 of the SubjectPublicKeyInfo (usable as a pin)
+    pub spki_sha256: String,
+    pub sha256_fingerprint: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TlsDetails {
+    pub transport: TlsTransport,
+    pub connected_ip: String,
+    pub port: u16,
+    pub tls_name: String,
+    pub name_from_host_map: bool,
+    pub handshake_ms: Option<f64>,
+    pub verified: bool,
+    pub tls_version: Option<String>,
+    pub cipher_suite: Option<String>,
+    pub alpn: Option<String>,
+    pub ocsp_stapled: bool,
+    // None when the entry has no pins
+    pub pin_matched: Option<bool>,
+    // Leaf first, as presented by the server
+    pub chain: Vec<CertInfo>,
+    pub warnings: Vec<String>,
+    pub error: Option<String>,
+}
+
+// Records what the server presented, then defers to the real verifier.
+// With `skip_chain_check` the chain is only recorded (used for SNI discovery);
+// handshake signatures are still checked against the presented key.
+#[derive(Debug)]
+struct CapturingVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    skip_chain_check: bool,
+    chain: Mutex<Vec<CertificateDer<'static>>>,
+    ocsp: Mutex<Vec<u8>>,
+}
+
+impl CapturingVerifier {
+    fn new(skip_chain_check: bool) -> Arc<Self> {
+        Arc::new(CapturingVerifier {
+            inner: platform_verifier(),
+            skip_chain_check,
+            chain: Mutex::new(Vec::new()),
+            ocsp: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+impl ServerCertVerifier for CapturingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Ok(mut chain) = self.chain.lock() {
+            *chain = std::iter::once(end_entity)
+                .chain(intermediates.iter())
+                .map(|c| c.clone().into_owned())
+                .collect();
+        }
+        if let Ok(mut ocsp) = self.ocsp.lock() {
+            *ocsp = ocsp_response.to_vec();
+        }
+        if self.skip_chain_check {
+            return Ok(ServerCertVerified::assertion());
+        }
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+pub fn crypto_provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+// Same trust model hickory uses for its own connections (platform roots).
+pub fn platform_verifier() -> Arc<dyn ServerCertVerifier> {
+    let platform: Arc<dyn ServerCertVerifier> =
+        Arc::new(rustls_platform_verifier::Verifier::new().with_provider(crypto_provider()));
+    #[cfg(feature = "test-support")]
+    if let Some(extra) = EXTRA_ROOTS.read().ok().and_then(|extra| extra.clone()) {
+        return Arc::new(ExtraRootsVerifier { platform, extra });
+    }
+    platform
+}
+
+// Trusts `roots` in addition to the platform store for every DoT/DoH/DoQ connection.
+// An empty list goes back to platform roots only.
+#[cfg(feature = "test-support")]
+pub fn set_extra_roots(roots: Vec<CertificateDer<'static>>) -> Result<(), String> {
+    let verifier = if roots.is_empty() {
+        None
+    } else {
+        let mut store = rustls::RootCertStore::empty();
+        for root in roots {
+            store
+                .add(root)
+                .map_err(|e| format!("Invalid root certificate: {}", e))?;
+        }
+        let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(store), crypto_provider())
+            .build()
+            .map_err(|e| format!("Invalid root certificates: {}", e))?;
+        Some(verifier)
+    };
+    *EXTRA_ROOTS.write().map_err(|_| "Trust store lock poisoned")? = verifier;
+    Ok(())
+}
+
+#[cfg(feature = "test-support")]
+pub fn has_extra_roots() -> bool {
+    EXTRA_ROOTS.read().is_ok_and(|extra| extra.is_some())
+}
+
+// hickory builds its own platform-root config unless given one, so resolvers get this
+// for pins (and extra test roots); hickory fills in the ALPN.
+pub fn resolver_client_config(verifier: Arc<dyn ServerCertVerifier>) -> Result<ClientConfig, rustls::Error> {
+    Ok(ClientConfig::builder_with_provider(crypto_provider())
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier)
+        .with_no_client_auth())
+}
+
+// Accepts a chain the platform trusts, otherwise one that ends in an extra root.
+#[cfg(feature = "test-support")]
+#[derive(Debug)]
+struct ExtraRootsVerifier {
+    platform: Arc<dyn ServerCertVerifier>,
+    extra: Arc<WebPkiServerVerifier>,
+}
+
+#[cfg(feature = "test-support")]
+impl ServerCertVerifier for ExtraRootsVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.platform
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+            .or_else(|platform_err| {
+                self.extra
+                    .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+                    .map_err(|_| platform_err)
+            })
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.platform.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.platform.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.platform.supported_verify_schemes()
+    }
+}
+
+// Performs one extra handshake against `target` and reports what was negotiated.
+pub async fn inspect_tls(target: &TlsTarget, timeout_secs: u64) -> TlsDetails {
+    let capture = CapturingVerifier::new(false);
+    let mut details = TlsDetails {
+        transport: target.transport,
+        connected_ip: target.ip.to_string(),
+        port: target.port,
+        tls_name: target.tls_name.clone(),
+        name_from_host_map: target.name_from_host_map,
+        handshake_ms: None,
+        verified: false,
+        tls_version: None,
+        cipher_suite: None,
+        alpn: None,
+        ocsp_stapled: false,
+        pin_matched: None,
+        chain: vec![],
+        warnings: vec![],
+        error: None,
+    };
+
+    let started = std::time::Instant::now();
+    let limit = Duration::from_secs(timeout_secs.max(1));
+    let outcome = match timeout(limit, handshake(target, capture.clone())).await {
+        Ok(r) => r,
+        Err(_) => Err("TLS handshake timeout".to_string()),
+    };
+    match outcome {
+        Ok(session) => {
+            details.handshake_ms = Some(started.elapsed().as_secs_f64() * 1000.0);
+            details.verified = true;
+            details.tls_version = session.tls_version;
+            details.cipher_suite = session.cipher_suite;
+            details.alpn = session.alpn;
+        }
+        Err(e) => details.error = Some(e),
+    }
+
+    let chain = capture.chain.lock().map(|c| c.clone()).unwrap_or_default();
+    details.ocsp_stapled = capture.ocsp.lock().map(|o| !o.is_empty()).unwrap_or(false);
+    details.chain = chain
+        .iter()
+        .filter_map(|der| cert_info(der.as_ref()))
+        .collect();
+    if !target.pins.is_empty() && !chain.is_empty() {
+        details.pin_matched = Some(target.pins.matches(&chain));
+    }
+    details.warnings = collect_warnings(target, &chain, &details.chain);
+    if details.pin_matched == Some(false) {
+        details
+            .warnings
+            .push("Pin mismatch: no certificate in the chain matches the configured pins (possible TLS interception)".to_string());
+    }
+    for w in &details.warnings {
+        warn!("{}:{} {}", target.ip, target.port, w);
+    }
+    details
+}
+
+// Unverified handshake with the IP as name; returns the DNS names in the leaf's SANs.
+pub async fn probe_dns_sans(
+    transport: TlsTransport,
+    ip: IpAddr,
+    port: u16,
+    timeout_secs: u64,
+) -> Result<Vec<String>, String> {
+    let target = TlsTarget {
+        transport,
+        ip,
+        port,
+        tls_name: ip.to_string(),
+        name_from_host_map: false,
+        pins: PinSet::default(),
+    };
+    let capture = CapturingVerifier::new(true);
+    let limit = Duration::from_secs(timeout_secs.max(1));
+    timeout(limit, handshake(&target, capture.clone()))
+        .await
+        .map_err(|_| "TLS handshake timeout".to_string())??;
+    let chain = capture.chain.lock().map(|c| c.clone()).unwrap_or_default();
+    let leaf = chain.first().ok_or("Server presented no certificate")?;
+    let (_, cert) = X509Certificate::from_der(leaf.as_ref()).map_err(|e| e.to_string())?;
+    Ok(cert_sans(&cert)
+        .into_iter()
+        .filter(|san| san.parse::<IpAddr>().is_err())
+        .collect())
+}
+
+// Fully verified handshake using `tls_name`, as hickory would perform it.
+pub async fn verify_tls_name(
+    transport: TlsTransport,
+    ip: IpAddr,
+    port: u16,
+    tls_name: &str,
+    timeout_secs: u64,
+) -> Result<(), String> {
+    let target = TlsTarget {
+        transport,
+        ip,
+        port,
+        tls_name: tls_name.to_string(),
+        name_from_host_map: false,
+        pins: PinSet::default(),
+    };
+    let limit = Duration::from_secs(timeout_secs.max(1));
+    timeout(limit, handshake(&target, CapturingVerifier::new(false)))
+        .await
+        .map_err(|_| "TLS handshake timeout".to_string())?
+        .map(|_| ())
+}
+
+struct Session {
+    tls_version: Option<String>,
+    cipher_suite: Option<String>,
+    alpn: Option<String>,
+}
+
+fn client_config(
+    transport: TlsTransport,
+    verifier: Arc<dyn ServerCertVerifier>,
+) -> Result<ClientConfig, String> {
+    let builder = ClientConfig::builder_with_provider(crypto_provider());
+    let builder = if transport == TlsTransport::Doq {
+        builder.with_protocol_versions(&[&rustls::version::TLS13])
+    } else {
+        builder.with_safe_default_protocol_versions()
+    }
+    .map_err(|e| e.to_string())?;
+    let mut config = builder
+        .dangerous()
+        .with_custom_certificate_verifier(verifier)
+        .with_no_client_auth();
+    config.alpn_protocols = transport.alpn();
+    // Mirror hickory: DoT connections are made without SNI
+    config.enable_sni = transport != TlsTransport::Dot;
+    Ok(config)
+}
+
+async fn handshake(
+    target: &TlsTarget,
+    verifier: Arc<CapturingVerifier>,
+) -> Result<Session, String> {
+    let config = client_config(target.transport, verifier)?;
+    let server_name = ServerName::try_from(target.tls_name.clone())
+        .map_err(|e| format!("Invalid TLS name {}: {}", target.tls_name, e))?;
+    let addr = SocketAddr::new(target.ip, target.port);
+
+    if target.transport == TlsTransport::Doq {
+        let quic_config = quinn::crypto::rustls::QuicClientConfig::try_from(config)
+            .map_err(|e| format!("QUIC config error: {}", e))?;
+        let bind: SocketAddr = if addr.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        let mut endpoint =
+            quinn::Endpoint::client(bind).map_err(|e| format!("QUIC socket error: {}", e))?;
+        endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(quic_config)));
+        let connection = endpoint
+            .connect(addr, &target.tls_name)
+            .map_err(|e| format!("QUIC connect error: {}", e))?
+            .await
+            .map_err(|e| format!("QUIC handshake error: {}", e))?;
+        let alpn = connection
+            .handshake_data()
+            .and_then(|d| d.downcast::<quinn::crypto::rustls::HandshakeData>().ok())
+            .and_then(|d| d.protocol)
+            .map(|p| String::from_utf8_lossy(&p).to_string());
+        connection.close(0u32.into(), b"");
+        endpoint.close(0u32.into(), b"");
+        return Ok(Session {
+            // QUIC always runs TLS 1.3; quinn does not expose the negotiated suite
+            tls_version: Some("TLSv1_3".to_string()),
+            cipher_suite: None,
+            alpn,
+        });
+    }
+
+    let tcp = TcpStream::connect(addr)
+        .await
+        .map_err(|e| format!("TCP connect error: {}", e))?;
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(server_name, tcp)
+        .await
+        .map_err(|e| format!("TLS handshake error: {}", e))?;
+    let (_, conn) = stream.get_ref();
+    Ok(Session {
+        tls_version: conn.protocol_version().map(|v| format!("{:?}", v)),
+        cipher_suite: conn
+            .negotiated_cipher_suite()
+            .map(|cs| format!("{:?}", cs.suite())),
+        alpn: conn
+            .alpn_protocol()
+            .map(|p| String::from_utf8_lossy(p).to_string()),
+    })
+}
+
+fn cert_info(der: &[u8]) -> Option<CertInfo> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    let validity = cert.validity();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    Some(CertInfo {
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        sans: cert_sans(&cert),
+        serial: cert.raw_serial_as_string(),
+        not_before: validity
+            .not_before
+            .to_rfc2822()
+            .unwrap_or_else(|_| validity.not_before.to_string()),
+        not_after: validity
+            .not_after
+            .to_rfc2822()
+            .unwrap_or_else(|_| validity.not_after.to_string()),
+        days_until_expiry: (validity.not_after.timestamp() - now).div_euclid(86_400),
+        spki_sha256: BASE64.encode(Sha256::digest(cert.public_key().raw)),
+        sha256_fingerprint: hex_colon(&Sha256::digest(der)),
+    })
+}
+
+fn cert_sans(cert: &X509Certificate<'_>) -> Vec<String> {
+    let mut sans = Vec::new();
+    if let Ok(Some(ext)) = cert.subject_alternative_name() {
+        for name in &ext.value.general_names {
+            match name {
+                GeneralName::DNSName(d) => sans.push(d.to_string()),
+                GeneralName::IPAddress(raw) => {
+                    if let Some(ip) = ip_from_bytes(raw) {
+                        sans.push(ip.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    sans
+}
+
+fn ip_from_bytes(raw: &[u8]) -> Option<IpAddr> {
+    match raw.len() {
+        4 => <[u8; 4]>::try_from(raw).ok().map(IpAddr::from),
+        16 => <[u8; 16]>::try_from(raw).ok().map(IpAddr::from),
+        _ => None,
+    }
+}
+
+fn hex_colon(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+// RFC 6125 style matching: exact, or a single left-most wildcard label.
+pub fn san_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    if let Some(suffix) = pattern.strip_prefix("*.") {
+        match name.split_once('.') {
+            Some((label, rest)) => !label.is_empty() && rest == suffix,
+            None => false,
+        }
+    } else {
+        pattern == name
+    }
+}
+
+fn collect_warnings(
+    target: &TlsTarget,
+    chain_der: &[CertificateDer<'static>],
+    chain: &[CertInfo],
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for cert in chain {
+        if cert.days_until_expiry < 0 {
+            warnings.push(format!("Certificate '{}' has expired", cert.subject));
+        } else if cert.days_until_expiry < EXPIRY_WARNING_DAYS {
+            warnings.push(format!(
+                "Certificate '{}' expires in {} day(s)",
+                cert.subject, cert.days_until_expiry
+            ));
+        }
+    }
+    if let Some(leaf) = chain.first() {
+        if !leaf
+            .sans
+            .iter()
+            .any(|san| san_matches(san, &target.tls_name))
+        {
+            let origin = if target.name_from_host_map {
+                "SNI from TLS_HOST_MAP"
+            } else {
+                "TLS name"
+            };
+            warnings.push(format!(
+                "{} '{}' does not match certificate SANs [{}]",
+                origin,
+                target.tls_name,
+                leaf.sans.join(", ")
+            ));
+        }
+    } else if chain_der.is_empty() {
+        warnings.push("Server presented no certificate".to_string());
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns_tester::pinning::PinSet;
+
+    const SERVER_CERT: &[u8] = include_bytes!("../../tests/support/certs/server.der");
+    const CA_CERT: &[u8] = include_bytes!("../../tests/support/certs/ca.der");
+
+    fn target(tls_name: &str, name_from_host_map: bool) -> TlsTarget {
+        TlsTarget {
+            transport: TlsTransport::Dot,
+            ip: "127.0.0.1".parse().unwrap(),
+            port: 853,
+            tls_name: tls_name.to_string(),
+            name_from_host_map,
+            pins: PinSet::default(),
+        }
+    }
+
+    fn chain() -> (Vec<CertificateDer<'static>>, Vec<CertInfo>) {
+        let der = vec![
+            CertificateDer::from(SERVER_CERT.to_vec()),
+            CertificateDer::from(CA_CERT.to_vec()),
+        ];
+        let info = der.iter().map(|d| cert_info(d.as_ref()).unwrap()).collect();
+        (der, info)
+    }
+
+    #[test]
+    fn san_wildcards_cover_exactly_one_label() {
+        assert!(san_matches("dns.example.com", "DNS.Example.com."));
+        assert!(san_matches("*.example.com", "a.example.com"));
+        assert!(san_matches("*.Example.com.", "A.EXAMPLE.COM"));
+        assert!(!san_matches("*.example.com", "a.b.example.com"));
+        assert!(!san_matches("*.example.com", "example.com"));
+        assert!(!san_matches("*.example.com", ".example.com"));
+        assert!(!san_matches("a.*.example.com", "a.b.example.com"));
+        assert!(!san_matches("dns.example.com", "dns.example.net"));
+        assert!(san_matches("127.0.0.1", "127.0.0.1"));
+    }
+
+    #[test]
+    fn cert_info_reads_the_fixture_leaf() {
+        let info = cert_info(SERVER_CERT).unwrap();
+        assert_eq!(info.subject, "CN=mock.test");
+        assert_eq!(info.issuer, "CN=ResoBoost Test CA");
+        assert_eq!(info.sans, vec!["mock.test", "127.0.0.1"]);
+        assert_eq!(
+            info.serial,
+            "10:f6:46:09:a1:20:42:23:05:ff:55:da:91:a6:6e:e7:5e:9b:95:7f"
+        );
+        assert!(info.not_after.contains("2126"), "{}", info.not_after);
+        // valid for 100 years from when the fixtures were made
+        assert!(
+            info.days_until_expiry > 36_000,
+            "{}",
+            info.days_until_expiry
+        );
+        assert_eq!(
+            info.spki_sha256,
+            "mekxhb/J2JcyQHyp/fsOU7JAw0hZBnsYo5PagRUvqVM="
+        );
+        assert_eq!(
+            info.sha256_fingerprint,
+            "3F:B5:DC:46:01:13:DE:00:42:59:38:8A:5B:F2:B7:8F:\
+             35:88:71:8D:DD:94:D3:CB:32:8F:67:19:EB:5E:0A:78"
+        );
+        assert!(cert_info(b"not a certificate").is_none());
+    }
+
+    #[test]
+    fn expiry_warnings_start_at_the_threshold() {
+        let (der, mut info) = chain();
+        let mut warnings_at = |days: i64| {
+            info[1].days_until_expiry = days;
+            collect_warnings(&target("mock.test", false), &der, &info)
+        };
+        assert!(warnings_at(EXPIRY_WARNING_DAYS).is_empty());
+        assert_eq!(
+            warnings_at(EXPIRY_WARNING_DAYS - 1),
+            vec![format!(
+                "Certificate 'CN=ResoBoost Test CA' expires in {} day(s)",
+                EXPIRY_WARNING_DAYS - 1
+            )]
+        );
+        assert_eq!(
+            warnings_at(0),
+            vec!["Certificate 'CN=ResoBoost Test CA' expires in 0 day(s)"]
+        );
+        assert_eq!(
+            warnings_at(-1),
+            vec!["Certificate 'CN=ResoBoost Test CA' has expired"]
+        );
+    }
+
+    #[test]
+    fn names_outside_the_leaf_sans_are_flagged() {
+        let (der, info) = chain();
+        assert!(collect_warnings(&target("MOCK.test", false), &der, &info).is_empty());
+        assert!(collect_warnings(&target("127.0.0.1", false), &der, &info).is_empty());
+        assert_eq!(
+            collect_warnings(&target("dns.example", false), &der, &info),
+            vec!["TLS name 'dns.example' does not match certificate SANs [mock.test, 127.0.0.1]"]
+        );
+        assert_eq!(
+            collect_warnings(&target("dns.example", true), &der, &info),
+            vec![
+                "SNI from TLS_HOST_MAP 'dns.example' does not match certificate SANs \
+                 [mock.test, 127.0.0.1]"
+            ]
+        );
+        assert_eq!(
+            collect_warnings(&target("mock.test", false), &[], &[]),
+            vec!["Server presented no certificate"]
+        );
+    }
+}
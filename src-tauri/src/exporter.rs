@@ -0,0 +1,513 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{oneshot, Mutex, RwLock};
+use tracing::{info, warn};
+
+use crate::assertions::{Measured, Metric};
+use crate::dns_tester::servers::ServerProtocol;
+use crate::dns_tester::spec::SYSTEM_SERVER;
+use crate::dns_tester::DnsTestResult;
+use crate::history;
+use crate::speed_tester::DownloadTestResult;
+use crate::storage;
+
+const EXPORTER_FILE: &str = "exporter.json";
+// Distinct targets (domain + record type, or download URL) kept; the oldest is dropped first
+const MAX_TARGETS: usize = 100;
+const MAX_REQUEST_BYTES: usize = 8192;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ExporterConfig {
+    pub enabled: bool,
+    // Loopback by default; use 0.0.0.0 only on a trusted network
+    pub bind: String,
+}
+
+impl Default for ExporterConfig {
+    fn default() -> Self {
+        ExporterConfig {
+            enabled: false,
+            bind: "127.0.0.1:9577".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ExporterStatus {
+    // Address actually bound (differs from the config with port 0)
+    pub listening_on: Option<String>,
+    pub last_error: Option<String>,
+    pub targets: usize,
+}
+
+struct Target<R> {
+    // Unix milliseconds
+    at: i64,
+    results: Vec<R>,
+}
+
+#[derive(Default)]
+struct Latest {
+    // (domain, record type) -> last benchmark or monitor check
+    dns: BTreeMap<(String, String), Target<DnsTestResult>>,
+    // URL -> last speed test
+    speed: BTreeMap<String, Target<DownloadTestResult>>,
+}
+
+struct Running {
+    addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+}
+
+static CONFIG: Lazy<RwLock<ExporterConfig>> =
+    Lazy::new(|| RwLock::new(storage::read_json(EXPORTER_FILE).unwrap_or_default()));
+static LATEST: Lazy<RwLock<Latest>> = Lazy::new(|| RwLock::new(Latest::default()));
+static SERVER: Lazy<Mutex<Option<Running>>> = Lazy::new(|| Mutex::new(None));
+static LAST_ERROR: Lazy<RwLock<Option<String>>> = Lazy::new(|| RwLock::new(None));
+
+fn evict_oldest<K: Clone + Ord, R>(map: &mut BTreeMap<K, Target<R>>) {
+    while map.len() > MAX_TARGETS {
+        let oldest = map.iter().min_by_key(|(_, t)| t.at).map(|(k, _)| k.clone());
+        match oldest {
+            Some(key) => map.remove(&key),
+            None => break,
+        };
+    }
+}
+
+// Called after every DNS benchmark and monitor check; replaces that target's series.
+pub async fn observe_dns(domain: &str, record_type: Option<&str>, results: &[DnsTestResult]) {
+    let key = (
+        domain.trim().to_string(),
+        record_type.unwrap_or("A/AAAA").to_string(),
+    );
+    let mut latest = LATEST.write().await;
+    latest.dns.insert(
+        key,
+        Target {
+            at: history::now_ms(),
+            results: results.to_vec(),
+        },
+    );
+    evict_oldest(&mut latest.dns);
+}
+
+pub async fn observe_speed(url: &str, results: &[DownloadTestResult]) {
+    let mut latest = LATEST.write().await;
+    latest.speed.insert(
+        url.to_string(),
+        Target {
+            at: history::now_ms(),
+            results: results.to_vec(),
+        },
+    );
+    evict_oldest(&mut latest.speed);
+}
+
+pub async fn get_config() -> ExporterConfig {
+    CONFIG.read().await.clone()
+}
+
+pub async fn get_status() -> ExporterStatus {
+    let latest = LATEST.read().await;
+    ExporterStatus {
+        listening_on: SERVER.lock().await.as_ref().map(|r| r.addr.to_string()),
+        last_error: LAST_ERROR.read().await.clone(),
+        targets: latest.dns.len() + latest.speed.len(),
+    }
+}
+
+// Saves the config and (re)binds; a bind error is returned and nothing is left listening.
+pub async fn set_config(config: ExporterConfig) -> Result<(), String> {
+    config
+        .bind
+        .parse::<SocketAddr>()
+        .map_err(|e| format!("Invalid bind address '{}': {}", config.bind, e))?;
+    storage::write_json(EXPORTER_FILE, &config)?;
+    *CONFIG.write().await = config.clone();
+    listen(&config).await
+}
+
+// Started once at launch with the saved config.
+pub fn start() {
+    crate::spawn(async {
+        let config = get_config().await;
+        if let Err(e) = listen(&config).await {
+            warn!("Metrics exporter not started: {}", e);
+        }
+    });
+}
+
+// Applies a config without saving it (the CLI's `serve` uses this).
+pub async fn listen(config: &ExporterConfig) -> Result<(), String> {
+    let mut server = SERVER.lock().await;
+    if let Some(running) = server.take() {
+        let _ = running.shutdown.send(());
+    }
+    *LAST_ERROR.write().await = None;
+    if !config.enabled {
+        return Ok(());
+    }
+    let listener = match TcpListener::bind(&config.bind).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            let message = format!("Could not listen on {}: {}", config.bind, e);
+            *LAST_ERROR.write().await = Some(message.clone());
+            return Err(message);
+        }
+    };
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    let (shutdown, mut stop) = oneshot::channel();
+    info!("Metrics exporter listening on http://{}/metrics", addr);
+    crate::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = &mut stop => break,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => crate::spawn(async move {
+                        if let Err(e) = handle(stream).await {
+                            warn!("Metrics request failed: {}", e);
+                        }
+                    }),
+                    Err(e) => warn!("Metrics exporter accept failed: {}", e),
+                },
+            }
+        }
+    });
+    *server = Some(Running { addr, shutdown });
+    Ok(())
+}
+
+// Just enough HTTP/1.1 for a Prometheus scrape: GET /metrics, then close.
+async fn handle(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut buf = vec![0u8; MAX_REQUEST_BYTES];
+    let mut len = 0;
+    let head = loop {
+        let read = tokio::time::timeout(REQUEST_TIMEOUT, stream.read(&mut buf[len..]))
+            .await
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+        len += read;
+        let text = String::from_utf8_lossy(&buf[..len]).to_string();
+        if text.contains("\r\n\r\n") || read == 0 || len == buf.len() {
+            break text;
+        }
+    };
+    let mut words = head.lines().next().unwrap_or_default().split_whitespace();
+    let (method, path) = (
+        words.next().unwrap_or_default(),
+        words.next().unwrap_or("/"),
+    );
+    let openmetrics = head.lines().any(|l| {
+        let l = l.to_ascii_lowercase();
+        l.starts_with("accept:") && l.contains("application/openmetrics-text")
+    });
+    let (status, content_type, body) = match (method, path.split('?').next()) {
+        ("GET", Some("/metrics")) => {
+            let body = render(openmetrics).await;
+            let content_type = if openmetrics {
+                "application/openmetrics-text; version=1.0.0; charset=utf-8"
+            } else {
+                "text/plain; version=0.0.4; charset=utf-8"
+            };
+            ("200 OK", content_type, body)
+        }
+        ("GET", Some("/")) => (
+            "200 OK",
+            "text/html; charset=utf-8",
+            "<a href=\"/metrics\">ResoBoost metrics</a>\n".to_string(),
+        ),
+        ("GET", _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed\n".to_string(),
+        ),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn protocol(server: &str) -> String {
+    if server.eq_ignore_ascii_case(SYSTEM_SERVER) {
+        return SYSTEM_SERVER.to_string();
+    }
+    serde_json::to_value(ServerProtocol::from_address(server))
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+// Collects samples per metric family so each family is written once with its HELP/TYPE.
+struct Families {
+    // name -> (help, type, samples)
+    families: BTreeMap<&'static str, (&'static str, &'static str, Vec<String>)>,
+}
+
+impl Families {
+    fn add(&mut self, name: &'static str, help: &'static str, labels: &[(&str, &str)], value: f64) {
+        self.add_typed(name, "gauge", help, labels, value);
+    }
+
+    fn add_typed(
+        &mut self,
+        name: &'static str,
+        kind: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+            .collect();
+        self.families
+            .entry(name)
+            .or_insert((help, kind, vec![]))
+            .2
+            .push(format!("{}{{{}}} {}", name, labels.join(","), value));
+    }
+}
+
+async fn render(openmetrics: bool) -> String {
+    let latest = LATEST.read().await;
+    let mut f = Families {
+        families: BTreeMap::new(),
+    };
+    for ((domain, record_type), target) in &latest.dns {
+        f.add(
+            "resoboost_dns_last_run_timestamp_seconds",
+            "When the latest benchmark of this domain finished",
+            &[("domain", domain), ("record_type", record_type)],
+            target.at as f64 / 1000.0,
+        );
+        for r in &target.results {
+            let protocol = protocol(&r.server_address);
+            let labels = [
+                ("server", r.server_address.as_str()),
+                ("protocol", protocol.as_str()),
+                ("domain", domain.as_str()),
+                ("record_type", record_type.as_str()),
+            ];
+            f.add(
+                "resoboost_dns_up",
+                "1 if the server answered in the latest benchmark",
+                &labels,
+                if r.query_successful { 1.0 } else { 0.0 },
+            );
+            f.add(
+                "resoboost_dns_success_ratio",
+                "Share of successful queries in the latest benchmark",
+                &labels,
+                r.success_percent / 100.0,
+            );
+            for (quantile, metric) in [
+                ("0.5", Metric::P50),
+                ("0.95", Metric::P95),
+                ("0.99", Metric::P99),
+            ] {
+                if let Some(ms) = r.metric(metric) {
+                    let mut with_quantile = labels.to_vec();
+                    with_quantile.push(("quantile", quantile));
+                    // Quantiles only (no _sum/_count), which both text formats allow
+                    f.add_typed(
+                        "resoboost_dns_latency_seconds",
+                        "summary",
+                        "Query latency quantiles over successful samples",
+                        &with_quantile,
+                        ms / 1000.0,
+                    );
+                }
+            }
+            if let Some(jitter) = r.jitter_avg_ms {
+                f.add(
+                    "resoboost_dns_jitter_seconds",
+                    "Sample standard deviation of successful query latencies",
+                    &labels,
+                    jitter / 1000.0,
+                );
+            }
+            if let Some(secure) = r.dnssec_secure() {
+                f.add(
+                    "resoboost_dns_dnssec_secure",
+                    "1 if answers validated with DNSSEC (only when validation was enabled)",
+                    &labels,
+                    if secure { 1.0 } else { 0.0 },
+                );
+                f.add(
+                    "resoboost_dns_dnssec_bogus",
+                    "1 if an answer failed DNSSEC validation",
+                    &labels,
+                    if r.dnssec_bogus { 1.0 } else { 0.0 },
+                );
+            }
+        }
+    }
+    for (url, target) in &latest.speed {
+        let domain = url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+        f.add(
+            "resoboost_download_last_run_timestamp_seconds",
+            "When the latest speed test of this URL finished",
+            &[("url", url), ("domain", &domain)],
+            target.at as f64 / 1000.0,
+        );
+        for r in &target.results {
+            let protocol = protocol(&r.server_address);
+            let labels = [
+                ("server", r.server_address.as_str()),
+                ("protocol", protocol.as_str()),
+                ("domain", domain.as_str()),
+                ("url", url.as_str()),
+            ];
+            f.add(
+                "resoboost_download_up",
+                "1 if the download through this server's answer succeeded",
+                &labels,
+                if r.query_successful { 1.0 } else { 0.0 },
+            );
+            if r.query_successful {
+                f.add(
+                    "resoboost_download_bandwidth_bytes_per_second",
+                    "Download throughput in the latest speed test",
+                    &labels,
+                    r.bandwidth_mbps * 1_000_000.0 / 8.0,
+                );
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for (name, (help, kind, samples)) in f.families {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for sample in samples {
+            let _ = writeln!(out, "{}", sample);
+        }
+    }
+    if openmetrics {
+        out.push_str("# EOF\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns_tester::{DnsError, DnsErrorKind, Sample};
+    use std::collections::BTreeSet;
+
+    // Checks the shape both formats share: one HELP and TYPE per family, written before the
+    // family's samples, and every sample belonging to the family above it.
+    fn assert_valid_exposition(body: &str) {
+        let mut seen = BTreeSet::new();
+        let mut current: Option<String> = None;
+        let mut help_of: Option<String> = None;
+        for line in body.lines().filter(|l| *l != "# EOF") {
+            if let Some(rest) = line.strip_prefix("# HELP ") {
+                let name = rest.split(' ').next().unwrap().to_string();
+                assert!(seen.insert(name.clone()), "family {} written twice", name);
+                help_of = Some(name);
+            } else if let Some(rest) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = rest.split_once(' ').unwrap();
+                assert_eq!(
+                    help_of.as_deref(),
+                    Some(name),
+                    "TYPE without HELP: {}",
+                    line
+                );
+                assert!(
+                    ["gauge", "summary"].contains(&kind),
+                    "unexpected type: {}",
+                    line
+                );
+                current = Some(name.to_string());
+            } else {
+                let family = current.as_deref().expect("sample before any TYPE");
+                let (series, value) = line.rsplit_once(' ').unwrap();
+                let (name, labels) = series.split_once('{').unwrap();
+                assert_eq!(name, family, "sample outside its family: {}", line);
+                assert!(labels.ends_with('}'), "unterminated labels: {}", line);
+                value.parse::<f64>().unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn render_writes_each_family_once_in_both_formats() {
+        let mut ok = DnsTestResult::failure(
+            "tls://1.1.1.1@one.one.one.one".to_string(),
+            true,
+            DnsError::new(DnsErrorKind::Other, ""),
+        );
+        ok.query_successful = true;
+        ok.success_percent = 100.0;
+        ok.jitter_avg_ms = Some(1.5);
+        ok.dnssec_validated = true;
+        ok.samples = [10.0, 12.0, 14.0]
+            .map(|latency_ms| Sample {
+                latency_ms,
+                ok: true,
+                error: None,
+            })
+            .to_vec();
+        let failed = DnsTestResult::failure(
+            "192.0.2.1".to_string(),
+            false,
+            DnsError::new(DnsErrorKind::Timeout, "timed out"),
+        );
+        observe_dns("example.test", None, &[ok.clone(), failed.clone()]).await;
+        observe_dns("quote\"d.test", Some("MX"), &[ok, failed]).await;
+        observe_speed(
+            "https://example.test/file",
+            &[DownloadTestResult {
+                server_address: "8.8.8.8".to_string(),
+                provider: None,
+                resolved_ip: Some("192.0.2.80".to_string()),
+                duration_ms: 1000,
+                bytes_read: 1_000_000,
+                bandwidth_mbps: 8.0,
+                query_successful: true,
+                http_status: Some(200),
+                error_msg: None,
+            }],
+        )
+        .await;
+
+        let text = render(false).await;
+        assert_valid_exposition(&text);
+        assert!(!text.contains("# EOF"));
+        assert!(text.contains("domain=\"quote\\\"d.test\""));
+        assert!(text.contains("# TYPE resoboost_dns_latency_seconds summary"));
+        assert!(text.contains("quantile=\"0.95\""));
+        assert!(text.contains("resoboost_download_bandwidth_bytes_per_second"));
+
+        let openmetrics = render(true).await;
+        assert_valid_exposition(&openmetrics);
+        assert!(openmetrics.ends_with("\n# EOF\n"));
+        assert_eq!(openmetrics.matches("# EOF").count(), 1);
+    }
+}
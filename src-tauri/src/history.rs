@@ -0,0 +1,662 @@
+use once_cell::sync::Lazy;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::dns_tester::{profiles, DnsTestResult};
+use crate::speed_tester::DownloadTestResult;
+use crate::storage;
+
+const DB_FILE: &str = "history.sqlite3";
+const SCHEMA_VERSION: i32 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,
+    source TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    finished_at INTEGER NOT NULL,
+    params TEXT NOT NULL,
+    environment TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS results (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id INTEGER NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+    server_address TEXT NOT NULL,
+    provider TEXT,
+    success INTEGER NOT NULL,
+    latency_ms REAL,
+    success_percent REAL,
+    bandwidth_mbps REAL,
+    error TEXT,
+    data TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS samples (
+    result_id INTEGER NOT NULL REFERENCES results(id) ON DELETE CASCADE,
+    seq INTEGER NOT NULL,
+    latency_ms REAL NOT NULL,
+    ok INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_runs_started ON runs(started_at);
+CREATE INDEX IF NOT EXISTS idx_results_run ON results(run_id);
+CREATE INDEX IF NOT EXISTS idx_results_server ON results(server_address);
+CREATE INDEX IF NOT EXISTS idx_samples_result ON samples(result_id);
+";
+
+// rusqlite connections are not Sync; all access goes through this mutex on blocking threads.
+// None when there is no data directory or the database could not be opened.
+static DB: Lazy<Mutex<Option<Connection>>> = Lazy::new(|| Mutex::new(open_db()));
+
+fn open_db() -> Option<Connection> {
+    // Unit tests get a private in-memory database instead of the app data dir
+    if cfg!(test) {
+        return Connection::open_in_memory().and_then(prepare).ok();
+    }
+    let path = storage::data_file(DB_FILE)?;
+    match Connection::open(&path).and_then(prepare) {
+        Ok(conn) => Some(conn),
+        Err(e) => {
+            warn!("History database unavailable ({}): {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// Enables foreign keys and creates or upgrades the schema.
+fn prepare(conn: Connection) -> rusqlite::Result<Connection> {
+    conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+    let version: i32 = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+    if version < SCHEMA_VERSION {
+        conn.execute_batch(SCHEMA)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    }
+    Ok(conn)
+}
+
+async fn with_db<T, F>(f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        let mut guard = DB
+            .lock()
+            .map_err(|_| "History database lock poisoned".to_string())?;
+        let conn = guard.as_mut().ok_or("History database is not available")?;
+        f(conn).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RunKind {
+    Dns,
+    Speed,
+}
+
+impl RunKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RunKind::Dns => "dns",
+            RunKind::Speed => "speed",
+        }
+    }
+}
+
+// Where a run came from: the UI, or the background monitor.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RunSource {
+    Manual,
+    Monitor,
+}
+
+impl RunSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RunSource::Manual => "manual",
+            RunSource::Monitor => "monitor",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunSummary {
+    pub id: i64,
+    pub kind: String,
+    pub source: String,
+    // Unix milliseconds
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub params: Value,
+    pub server_count: i64,
+    pub success_count: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunDetail {
+    #[serde(flatten)]
+    pub summary: RunSummary,
+    pub environment: Value,
+    // DnsTestResult or DownloadTestResult, as returned when the run finished
+    pub results: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrendPoint {
+    pub server_address: String,
+    // Start of the bucket, unix milliseconds (UTC)
+    pub bucket_start: i64,
+    pub runs: usize,
+    pub samples: usize,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub success_rate: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TrendBucket {
+    Hour,
+    #[default]
+    Day,
+}
+
+impl TrendBucket {
+    fn millis(&self) -> i64 {
+        match self {
+            TrendBucket::Hour => 3_600_000,
+            TrendBucket::Day => 86_400_000,
+        }
+    }
+}
+
+// What a result looks like to the results table, independent of the run kind.
+struct ResultRow {
+    server_address: String,
+    provider: Option<String>,
+    success: bool,
+    latency_ms: Option<f64>,
+    success_percent: Option<f64>,
+    bandwidth_mbps: Option<f64>,
+    error: Option<String>,
+    data: String,
+    samples: Vec<(f64, bool)>,
+}
+
+// App and OS details stored with every run.
+pub async fn environment() -> Value {
+    serde_json::json!({
+        "app_version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "profile": profiles::active_profile_name().await,
+    })
+}
+
+async fn insert_run(
+    kind: RunKind,
+    source: RunSource,
+    started_at: i64,
+    params: Value,
+    rows: Vec<ResultRow>,
+) -> Result<i64, String> {
+    let environment = environment().await.to_string();
+    let finished_at = now_ms();
+    with_db(move |conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO runs (kind, source, started_at, finished_at, params, environment)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                kind.as_str(),
+                source.as_str(),
+                started_at,
+                finished_at,
+                params.to_string(),
+                environment
+            ],
+        )?;
+        let run_id = tx.last_insert_rowid();
+        for row in rows {
+            tx.execute(
+                "INSERT INTO results (run_id, server_address, provider, success, latency_ms,
+                 success_percent, bandwidth_mbps, error, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    run_id,
+                    row.server_address,
+                    row.provider,
+                    row.success,
+                    row.latency_ms,
+                    row.success_percent,
+                    row.bandwidth_mbps,
+                    row.error,
+                    row.data
+                ],
+            )?;
+            let result_id = tx.last_insert_rowid();
+            for (seq, (latency_ms, ok)) in row.samples.into_iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO samples (result_id, seq, latency_ms, ok) VALUES (?1, ?2, ?3, ?4)",
+                    params![result_id, seq as i64, latency_ms, ok],
+                )?;
+            }
+        }
+        tx.commit()?;
+        Ok(run_id)
+    })
+    .await
+}
+
+// Failures are logged, never surfaced: history must not break a benchmark.
+pub async fn record_dns_run(
+    source: RunSource,
+    started_at: i64,
+    params: Value,
+    results: &[DnsTestResult],
+) -> Option<i64> {
+    let rows = results
+        .iter()
+        .map(|r| ResultRow {
+            server_address: r.server_address.clone(),
+            provider: r.provider.clone(),
+            success: r.query_successful,
+            latency_ms: r.latency_avg_ms,
+            success_percent: Some(r.success_percent),
+            bandwidth_mbps: None,
+            error: r.error_msg.clone(),
+            data: serde_json::to_string(r).unwrap_or_default(),
+            samples: r.samples.iter().map(|s| (s.latency_ms, s.ok)).collect(),
+        })
+        .collect();
+    match insert_run(RunKind::Dns, source, started_at, params, rows).await {
+        Ok(id) => Some(id),
+        Err(e) => {
+            warn!("Could not record DNS run: {}", e);
+            None
+        }
+    }
+}
+
+pub async fn record_speed_run(
+    started_at: i64,
+    params: Value,
+    results: &[DownloadTestResult],
+) -> Option<i64> {
+    let rows = results
+        .iter()
+        .map(|r| ResultRow {
+            server_address: r.server_address.clone(),
+            provider: r.provider.clone(),
+            success: r.query_successful,
+            latency_ms: None,
+            success_percent: None,
+            bandwidth_mbps: Some(r.bandwidth_mbps),
+            error: r.error_msg.clone(),
+            data: serde_json::to_string(r).unwrap_or_default(),
+            samples: vec![],
+        })
+        .collect();
+    match insert_run(RunKind::Speed, RunSource::Manual, started_at, params, rows).await {
+        Ok(id) => Some(id),
+        Err(e) => {
+            warn!("Could not record speed run: {}", e);
+            None
+        }
+    }
+}
+
+const SUMMARY_COLUMNS: &str = "
+    runs.id, runs.kind, runs.source, runs.started_at, runs.finished_at, runs.params,
+    (SELECT COUNT(*) FROM results WHERE results.run_id = runs.id),
+    (SELECT COUNT(*) FROM results WHERE results.run_id = runs.id AND results.success = 1)";
+
+fn summary_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<RunSummary> {
+    let params: String = row.get(5)?;
+    Ok(RunSummary {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        source: row.get(2)?,
+        started_at: row.get(3)?,
+        finished_at: row.get(4)?,
+        params: serde_json::from_str(&params).unwrap_or(Value::Null),
+        server_count: row.get(6)?,
+        success_count: row.get(7)?,
+    })
+}
+
+// Newest first.
+pub async fn list_runs(
+    kind: Option<RunKind>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<Vec<RunSummary>, String> {
+    let limit = limit.unwrap_or(50).min(1000) as i64;
+    let offset = offset.unwrap_or(0) as i64;
+    with_db(move |conn| {
+        let sql = format!(
+            "SELECT {} FROM runs WHERE (?1 IS NULL OR kind = ?1)
+             ORDER BY started_at DESC LIMIT ?2 OFFSET ?3",
+            SUMMARY_COLUMNS
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(
+            params![kind.map(|k| k.as_str()), limit, offset],
+            summary_from_row,
+        )?;
+        rows.collect()
+    })
+    .await
+}
+
+pub async fn get_run(id: i64) -> Result<RunDetail, String> {
+    with_db(move |conn| {
+        let sql = format!(
+            "SELECT {}, runs.environment FROM runs WHERE id = ?1",
+            SUMMARY_COLUMNS
+        );
+        let found = conn
+            .query_row(&sql, params![id], |row| {
+                let environment: String = row.get(8)?;
+                Ok((summary_from_row(row)?, environment))
+            })
+            .optional()?;
+        let Some((summary, environment)) = found else {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        };
+        let mut stmt = conn.prepare("SELECT data FROM results WHERE run_id = ?1 ORDER BY id")?;
+        let results = stmt
+            .query_map(params![id], |row| row.get::<_, String>(0))?
+            .map(|data| data.map(|d| serde_json::from_str(&d).unwrap_or(Value::Null)))
+            .collect::<rusqlite::Result<Vec<Value>>>()?;
+        Ok(RunDetail {
+            summary,
+            environment: serde_json::from_str(&environment).unwrap_or(Value::Null),
+            results,
+        })
+    })
+    .await
+    .map_err(|e| {
+        if e == rusqlite::Error::QueryReturnedNoRows.to_string() {
+            format!("No run with id {}", id)
+        } else {
+            e
+        }
+    })
+}
+
+// Returns the number of runs removed (their results and samples go with them).
+pub async fn delete_runs(ids: Vec<i64>) -> Result<usize, String> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    with_db(move |conn| {
+        let placeholders = vec!["?"; ids.len()].join(",");
+        conn.execute(
+            &format!("DELETE FROM runs WHERE id IN ({})", placeholders),
+            params_from_iter(ids.iter()),
+        )
+    })
+    .await
+}
+
+// Percentile of an ascending slice as the sample at the rounded index p% * (n - 1), without
+// interpolation (trends, load tests and assertions).
+pub(crate) fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p / 100.0 * (sorted.len() - 1) as f64).round() as usize;
+    sorted.get(rank.min(sorted.len() - 1)).copied()
+}
+
+// Per-server latency percentiles (successful samples only) and success rate per bucket.
+pub async fn query_trends(
+    servers: Option<Vec<String>>,
+    since_ms: Option<i64>,
+    bucket: TrendBucket,
+) -> Result<Vec<TrendPoint>, String> {
+    let since = since_ms.unwrap_or_else(|| now_ms() - 30 * 86_400_000);
+    let rows: Vec<(String, i64, i64, f64, bool)> = with_db(move |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT results.server_address, runs.id, runs.started_at, samples.latency_ms, samples.ok
+             FROM samples
+             JOIN results ON results.id = samples.result_id
+             JOIN runs ON runs.id = results.run_id
+             WHERE runs.kind = 'dns' AND runs.started_at >= ?1",
+        )?;
+        let rows = stmt.query_map(params![since], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })?;
+        rows.collect()
+    })
+    .await?;
+
+    struct Acc {
+        runs: std::collections::HashSet<i64>,
+        ok_latencies: Vec<f64>,
+        total: usize,
+        ok: usize,
+    }
+    let width = bucket.millis();
+    let mut buckets: BTreeMap<(String, i64), Acc> = BTreeMap::new();
+    for (server, run_id, started_at, latency_ms, ok) in rows {
+        if let Some(filter) = &servers {
+            if !filter.contains(&server) {
+                continue;
+            }
+        }
+        let acc = buckets
+            .entry((server, started_at - started_at.rem_euclid(width)))
+            .or_insert_with(|| Acc {
+                runs: Default::default(),
+                ok_latencies: vec![],
+                total: 0,
+                ok: 0,
+            });
+        acc.runs.insert(run_id);
+        acc.total += 1;
+        if ok {
+            acc.ok += 1;
+            acc.ok_latencies.push(latency_ms);
+        }
+    }
+
+    Ok(buckets
+        .into_iter()
+        .map(|((server_address, bucket_start), mut acc)| {
+            acc.ok_latencies.sort_by(|a, b| a.total_cmp(b));
+            TrendPoint {
+                server_address,
+                bucket_start,
+                runs: acc.runs.len(),
+                samples: acc.total,
+                p50_ms: percentile(&acc.ok_latencies, 50.0),
+                p95_ms: percentile(&acc.ok_latencies, 95.0),
+                success_rate: acc.ok as f64 * 100.0 / acc.total.max(1) as f64,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns_tester::{DnsError, DnsErrorKind, Sample};
+
+    // 2024-01-01 00:00:00 UTC
+    const NEW_YEAR: i64 = 1_704_067_200_000;
+    const MINUTE: i64 = 60_000;
+    const HOUR: i64 = 60 * MINUTE;
+
+    // Failed samples have a latency too; trends must leave them out of the percentiles.
+    fn result(server: &str, samples: &[(f64, bool)]) -> DnsTestResult {
+        let mut result = DnsTestResult::failure(
+            server.to_string(),
+            false,
+            DnsError::new(DnsErrorKind::Timeout, "timed out"),
+        );
+        result.query_successful = samples.iter().any(|s| s.1);
+        result.samples = samples
+            .iter()
+            .map(|&(latency_ms, ok)| Sample {
+                latency_ms,
+                ok,
+                error: None,
+            })
+            .collect();
+        result
+    }
+
+    #[test]
+    fn percentile_uses_the_rounded_index() {
+        let sorted = [10.0, 20.0, 30.0, 40.0, 50.0, 100.0];
+        // index 0.5 * 5 = 2.5 rounds up to 3
+        assert_eq!(percentile(&sorted, 50.0), Some(40.0));
+        assert_eq!(percentile(&sorted, 95.0), Some(100.0));
+        assert_eq!(percentile(&sorted, 0.0), Some(10.0));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[tokio::test]
+    async fn runs_round_trip_and_delete_with_their_samples() {
+        let results = vec![
+            result("roundtrip.test", &[(12.0, true), (1000.0, false)]),
+            result("roundtrip-down.test", &[(1000.0, false)]),
+        ];
+        let params = serde_json::json!({ "domain_or_ip": "example.com" });
+        let id = record_dns_run(RunSource::Monitor, NEW_YEAR, params.clone(), &results)
+            .await
+            .unwrap();
+
+        let detail = get_run(id).await.unwrap();
+        assert_eq!(detail.summary.kind, "dns");
+        assert_eq!(detail.summary.source, "monitor");
+        assert_eq!(detail.summary.started_at, NEW_YEAR);
+        assert_eq!(detail.summary.params, params);
+        assert_eq!(
+            (detail.summary.server_count, detail.summary.success_count),
+            (2, 1)
+        );
+        let stored: Vec<DnsTestResult> = detail
+            .results
+            .into_iter()
+            .map(|v| serde_json::from_value(v).unwrap())
+            .collect();
+        assert_eq!(stored[0].server_address, "roundtrip.test");
+        assert_eq!(stored[0].samples.len(), 2);
+        assert_eq!(stored[1].error_kind, Some(DnsErrorKind::Timeout));
+        assert!(list_runs(Some(RunKind::Dns), Some(1000), None)
+            .await
+            .unwrap()
+            .iter()
+            .any(|r| r.id == id));
+        assert!(!list_runs(Some(RunKind::Speed), None, None)
+            .await
+            .unwrap()
+            .iter()
+            .any(|r| r.id == id));
+
+        let trend =
+            |server: &str| query_trends(Some(vec![server.to_string()]), Some(0), TrendBucket::Day);
+        assert_eq!(trend("roundtrip.test").await.unwrap().len(), 1);
+        assert_eq!(delete_runs(vec![id]).await.unwrap(), 1);
+        assert_eq!(
+            get_run(id).await.unwrap_err(),
+            format!("No run with id {}", id)
+        );
+        assert!(trend("roundtrip.test").await.unwrap().is_empty());
+        assert_eq!(delete_runs(vec![id]).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn trends_bucket_by_hour_and_day() {
+        let server = "trends.test";
+        let runs = [
+            (
+                10 * HOUR + 10 * MINUTE,
+                vec![(10.0, true), (20.0, true), (30.0, true), (900.0, false)],
+            ),
+            (10 * HOUR + 40 * MINUTE, vec![(40.0, true), (50.0, true)]),
+            (13 * HOUR + 5 * MINUTE, vec![(100.0, true), (900.0, false)]),
+        ];
+        for (offset, samples) in &runs {
+            record_dns_run(
+                RunSource::Manual,
+                NEW_YEAR + offset,
+                Value::Null,
+                &[result(server, samples)],
+            )
+            .await
+            .unwrap();
+        }
+        let summary = |p: &TrendPoint| {
+            (
+                p.bucket_start - NEW_YEAR,
+                p.runs,
+                p.samples,
+                p.p50_ms,
+                p.p95_ms,
+                (p.success_rate * 10.0).round() / 10.0,
+            )
+        };
+
+        let hourly = query_trends(
+            Some(vec![server.to_string()]),
+            Some(NEW_YEAR),
+            TrendBucket::Hour,
+        )
+        .await
+        .unwrap();
+        let hourly: Vec<_> = hourly.iter().map(summary).collect();
+        assert_eq!(
+            hourly,
+            vec![
+                (10 * HOUR, 2, 6, Some(30.0), Some(50.0), 83.3),
+                (13 * HOUR, 1, 2, Some(100.0), Some(100.0), 50.0),
+            ]
+        );
+
+        let daily = query_trends(
+            Some(vec![server.to_string()]),
+            Some(NEW_YEAR),
+            TrendBucket::Day,
+        )
+        .await
+        .unwrap();
+        let daily: Vec<_> = daily.iter().map(summary).collect();
+        assert_eq!(daily, vec![(0, 3, 8, Some(40.0), Some(100.0), 75.0)]);
+
+        // runs before `since_ms` are left out
+        let later = query_trends(
+            Some(vec![server.to_string()]),
+            Some(NEW_YEAR + 11 * HOUR),
+            TrendBucket::Day,
+        )
+        .await
+        .unwrap();
+        assert_eq!(later.len(), 1);
+        assert_eq!((later[0].runs, later[0].samples), (1, 2));
+    }
+}
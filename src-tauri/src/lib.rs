@@ -1,14 +1,136 @@
-// Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
-#[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
+// Core benchmarking logic shared by the desktop app and the `resoboost-cli` binary.
+// Everything Tauri-specific sits behind the "gui" feature.
+pub mod assertions;
+pub mod compare;
+pub mod dns_tester;
+pub mod exporter;
+pub mod history;
+pub mod load_test;
+pub mod monitor;
+pub mod proxy;
+pub mod report;
+pub mod snippets;
+pub mod speed_tester;
+pub mod storage;
+pub mod system_dns;
+
+#[cfg(feature = "gui")]
+mod commands;
+
+use std::future::Future;
+#[cfg(feature = "gui")]
+use tauri::Manager;
+
+// Background work runs on Tauri's runtime in the app and on the ambient Tokio runtime in the CLI.
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    #[cfg(feature = "gui")]
+    tauri::async_runtime::spawn(future);
+    #[cfg(not(feature = "gui"))]
+    tokio::spawn(future);
+}
+
+#[cfg(feature = "gui")]
+mod gui {
+    use super::monitor;
+    use tauri::{AppHandle, Emitter};
+    use tauri_plugin_notification::NotificationExt;
+
+    // Monitor alerts reach the UI as "monitor-alert" events and, if enabled, as desktop notifications.
+    pub fn forward_monitor_alerts(app: AppHandle) {
+        let mut alerts = monitor::subscribe();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let alert = match alerts.recv().await {
+                    Ok(alert) => alert,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                };
+                if let Err(e) = app.emit("monitor-alert", &alert) {
+                    tracing::warn!("Could not emit monitor alert: {}", e);
+                }
+                if monitor::get_config().await.desktop_notifications {
+                    let _ = app
+                        .notification()
+                        .builder()
+                        .title(format!("ResoBoost: {}", alert.domain))
+                        .body(&alert.message)
+                        .show();
+                }
+            }
+        });
+    }
 }
 
+#[cfg(feature = "gui")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![greet])
+        .plugin(tauri_plugin_notification::init())
+        .setup(|app| {
+            // Saved server profiles live in the app data dir, so init waits for the app handle.
+            // init is sync and spawns its own async work.
+            match app.path().app_data_dir() {
+                Ok(dir) => storage::set_data_dir(dir),
+                Err(e) => tracing::warn!("No app data dir, settings will not persist: {}", e),
+            }
+            dns_tester::init_configs();
+            gui::forward_monitor_alerts(app.handle().clone());
+            monitor::start();
+            exporter::start();
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            commands::run_dns_benchmark,
+            commands::perform_download_speed_test,
+            commands::get_dns_servers,
+            commands::set_dns_servers,
+            commands::validate_servers,
+            commands::get_server_entries,
+            commands::set_server_entries,
+            commands::get_bootstrap_config,
+            commands::set_bootstrap_config,
+            commands::preview_server_import,
+            commands::apply_server_import,
+            commands::list_server_profiles,
+            commands::create_server_profile,
+            commands::rename_server_profile,
+            commands::delete_server_profile,
+            commands::switch_server_profile,
+            commands::get_subscriptions,
+            commands::set_subscriptions,
+            commands::refresh_subscriptions,
+            commands::get_tls_host_map,
+            commands::set_tls_host_map,
+            commands::import_tls_host_map,
+            commands::list_history_runs,
+            commands::get_history_run,
+            commands::delete_history_runs,
+            commands::get_server_trends,
+            commands::get_monitor_config,
+            commands::set_monitor_config,
+            commands::get_monitor_status,
+            commands::evaluate_dns_assertions,
+            commands::evaluate_speed_assertions,
+            commands::export_report,
+            commands::compare_runs,
+            commands::get_exporter_config,
+            commands::set_exporter_config,
+            commands::get_exporter_status,
+            commands::get_proxy_config,
+            commands::start_proxy,
+            commands::stop_proxy,
+            commands::get_proxy_status,
+            commands::apply_dns_servers,
+            commands::revert_dns_changes,
+            commands::get_dns_backups,
+            commands::generate_snippets,
+            commands::run_load_test,
+            commands::stop_load_test,
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,453 @@
+use hickory_resolver::lookup::Lookup;
+use hickory_resolver::proto::rr::RecordType;
+use hickory_resolver::{ResolveError, TokioResolver};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{error::Elapsed, MissedTickBehavior};
+use tracing::info;
+
+use crate::dns_tester::bootstrap::BootstrapCache;
+use crate::dns_tester::{build_resolver_for_server, parse_record_type, DnsError, DnsErrorKind};
+use crate::history::{self, percentile};
+
+// Safety caps no request can raise
+pub const MAX_QPS: u32 = 1000;
+pub const MAX_CONCURRENCY: usize = 256;
+// All steps against one resolver
+pub const MAX_DURATION_SECS: u64 = 300;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoadTestArgs {
+    pub servers: Vec<String>,
+    // Every query asks for a fresh random name under this domain, so caches can't answer
+    pub domain: String,
+    pub record_type: Option<String>,
+    pub start_qps: Option<u32>,
+    pub step_qps: Option<u32>,
+    pub max_qps: Option<u32>,
+    pub step_secs: Option<u64>,
+    // Queries in flight at once; ticks beyond it are counted as not sent
+    pub concurrency: Option<usize>,
+    pub timeout_secs: Option<u64>,
+    // Error rate (percent) that counts as saturation and ends the ramp for a server
+    pub error_threshold_percent: Option<f64>,
+    // Nothing is sent unless this is set
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoadStep {
+    pub target_qps: u32,
+    pub sent: u64,
+    // Ticks skipped because `concurrency` queries were still in flight
+    pub not_sent: u64,
+    // NOERROR or NXDOMAIN answers per second of the step
+    pub achieved_qps: f64,
+    pub answered: u64,
+    pub servfail: u64,
+    pub refused: u64,
+    pub timeouts: u64,
+    pub other_errors: u64,
+    pub error_percent: f64,
+    // Over every response, answered or not (timeouts excluded)
+    pub latency_avg_ms: Option<f64>,
+    pub latency_p50_ms: Option<f64>,
+    pub latency_p95_ms: Option<f64>,
+    pub latency_p99_ms: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoadTestResult {
+    pub server_address: String,
+    pub steps: Vec<LoadStep>,
+    // Best achieved QPS among steps under the error threshold
+    pub max_clean_qps: Option<f64>,
+    // Target QPS of the first step at or over the error threshold
+    pub saturation_qps: Option<u32>,
+    pub stopped: bool,
+    pub error_msg: Option<String>,
+}
+
+#[derive(Clone)]
+struct Plan {
+    domain: String,
+    record_type: RecordType,
+    steps: Vec<u32>,
+    step: Duration,
+    concurrency: usize,
+    timeout_secs: u64,
+    threshold: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Answered,
+    ServFail,
+    Refused,
+    Timeout,
+    Other,
+}
+
+// One load test at a time, whoever starts it
+static RUNNING: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+static STOP: AtomicBool = AtomicBool::new(false);
+
+// Ends the running test after the current step's in-flight queries.
+pub fn stop() {
+    STOP.store(true, Ordering::Relaxed);
+}
+
+fn plan(args: &LoadTestArgs) -> Result<Plan, String> {
+    if !args.confirm {
+        return Err(format!(
+            "A load test sends up to {} queries per second to each server; confirm to run it",
+            args.max_qps.unwrap_or(200)
+        ));
+    }
+    let domain = args.domain.trim().trim_matches('.').to_ascii_lowercase();
+    if domain.is_empty() {
+        return Err("A domain is required".to_string());
+    }
+    let record_type =
+        parse_record_type(args.record_type.as_deref().unwrap_or("A"))?.unwrap_or(RecordType::A);
+    let start = args.start_qps.unwrap_or(10).max(1);
+    let step_qps = args.step_qps.unwrap_or(start).max(1);
+    let max = args.max_qps.unwrap_or(200);
+    if max > MAX_QPS {
+        return Err(format!(
+            "max_qps {} is above the safety cap of {}",
+            max, MAX_QPS
+        ));
+    }
+    if start > max {
+        return Err(format!("start_qps {} is above max_qps {}", start, max));
+    }
+    let concurrency = args.concurrency.unwrap_or(64).max(1);
+    if concurrency > MAX_CONCURRENCY {
+        return Err(format!(
+            "concurrency {} is above the safety cap of {}",
+            concurrency, MAX_CONCURRENCY
+        ));
+    }
+    let steps: Vec<u32> = (start..=max).step_by(step_qps as usize).collect();
+    let step_secs = args.step_secs.unwrap_or(5).max(1);
+    if steps.len() as u64 * step_secs > MAX_DURATION_SECS {
+        return Err(format!(
+            "{} steps of {}s exceed the safety cap of {}s per server; raise step_qps or shorten steps",
+            steps.len(),
+            step_secs,
+            MAX_DURATION_SECS
+        ));
+    }
+    Ok(Plan {
+        domain,
+        record_type,
+        steps,
+        step: Duration::from_secs(step_secs),
+        concurrency,
+        timeout_secs: args.timeout_secs.unwrap_or(2).max(1),
+        threshold: args.error_threshold_percent.unwrap_or(5.0),
+    })
+}
+
+// Ramps each server in turn (never in parallel, so they don't skew each other).
+pub async fn run(args: LoadTestArgs) -> Result<Vec<LoadTestResult>, String> {
+    let plan = plan(&args)?;
+    if args.servers.is_empty() {
+        return Err("No servers selected".to_string());
+    }
+    let _running = RUNNING
+        .try_lock()
+        .map_err(|_| "A load test is already running".to_string())?;
+    STOP.store(false, Ordering::Relaxed);
+    let mut results = vec![];
+    for server in args.servers {
+        if STOP.load(Ordering::Relaxed) {
+            break;
+        }
+        info!(
+            "Load testing {} up to {} qps",
+            server,
+            plan.steps.last().unwrap_or(&0)
+        );
+        let plan = plan.clone();
+        let result = tokio::task::spawn_blocking(move || run_in_isolated_rt(server, plan))
+            .await
+            .map_err(|e| e.to_string())?;
+        results.push(result);
+    }
+    Ok(results)
+}
+
+fn failure(server_address: String, error: String) -> LoadTestResult {
+    LoadTestResult {
+        server_address,
+        steps: vec![],
+        max_clean_qps: None,
+        saturation_qps: None,
+        stopped: false,
+        error_msg: Some(error),
+    }
+}
+
+// Same reason as the benchmark's isolated runtimes, with workers to keep up with high rates.
+fn run_in_isolated_rt(server: String, plan: Plan) -> LoadTestResult {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .thread_stack_size(4 * 1024 * 1024)
+        .build();
+    match rt {
+        Ok(rt) => rt.block_on(load_server(server, plan)),
+        Err(e) => failure(server, format!("Runtime build error: {}", e)),
+    }
+}
+
+async fn load_server(server: String, plan: Plan) -> LoadTestResult {
+    let resolver = match build_resolver_for_server(&server, plan.timeout_secs, false, &BootstrapCache::default()).await {
+        Ok(built) => Arc::new(built.resolver),
+        Err(e) => return failure(server, e.to_string()),
+    };
+    // Unique per run, so a rerun never hits names cached by the previous one
+    let tag = format!("rb{:x}", history::now_ms());
+    // Like the benchmark's warm-up: one-time connection setup must not land in the first step
+    let warm_up = format!("{}-0.{}.", tag, plan.domain);
+    let _ = tokio::time::timeout(
+        Duration::from_secs(plan.timeout_secs),
+        resolver.lookup(warm_up, plan.record_type),
+    )
+    .await;
+    let mut sequence = 0u64;
+    let mut steps = vec![];
+    let mut saturation_qps = None;
+    for &qps in &plan.steps {
+        if STOP.load(Ordering::Relaxed) {
+            break;
+        }
+        let step = run_step(&resolver, &plan, qps, &tag, &mut sequence).await;
+        let saturated = step.error_percent >= plan.threshold;
+        steps.push(step);
+        if saturated {
+            saturation_qps = Some(qps);
+            break;
+        }
+    }
+    let max_clean_qps = steps
+        .iter()
+        .filter(|s| s.error_percent < plan.threshold)
+        .map(|s| s.achieved_qps)
+        .reduce(f64::max);
+    LoadTestResult {
+        server_address: server,
+        stopped: STOP.load(Ordering::Relaxed),
+        steps,
+        max_clean_qps,
+        saturation_qps,
+        error_msg: None,
+    }
+}
+
+fn classify(result: Result<Result<Lookup, ResolveError>, Elapsed>) -> Outcome {
+    let error = match result {
+        Err(_) => return Outcome::Timeout,
+        Ok(Ok(_)) => return Outcome::Answered,
+        Ok(Err(e)) => e,
+    };
+    match DnsError::from(error).kind {
+        // Random names are expected not to exist
+        DnsErrorKind::NoData | DnsErrorKind::NxDomain => Outcome::Answered,
+        DnsErrorKind::ServFail => Outcome::ServFail,
+        DnsErrorKind::Refused => Outcome::Refused,
+        DnsErrorKind::Timeout => Outcome::Timeout,
+        _ => Outcome::Other,
+    }
+}
+
+async fn run_step(
+    resolver: &Arc<TokioResolver>,
+    plan: &Plan,
+    qps: u32,
+    tag: &str,
+    sequence: &mut u64,
+) -> LoadStep {
+    let slots = Arc::new(Semaphore::new(plan.concurrency));
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / qps as f64));
+    // A stalled scheduler must not turn into a burst above the target rate
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let limit = Duration::from_secs(plan.timeout_secs);
+    let mut queries = JoinSet::new();
+    let (mut sent, mut not_sent) = (0, 0);
+    let started = Instant::now();
+    while started.elapsed() < plan.step && !STOP.load(Ordering::Relaxed) {
+        ticker.tick().await;
+        let Ok(slot) = slots.clone().try_acquire_owned() else {
+            not_sent += 1;
+            continue;
+        };
+        *sequence += 1;
+        let name = format!("{}-{}.{}.", tag, sequence, plan.domain);
+        let (resolver, record_type) = (resolver.clone(), plan.record_type);
+        sent += 1;
+        queries.spawn(async move {
+            let start = Instant::now();
+            let result = tokio::time::timeout(limit, resolver.lookup(name, record_type)).await;
+            drop(slot);
+            (classify(result), start.elapsed().as_secs_f64() * 1000.0)
+        });
+    }
+    let window = started.elapsed().as_secs_f64();
+    // In-flight queries finish (or time out) before the next step starts
+    let mut outcomes = vec![];
+    while let Some(joined) = queries.join_next().await {
+        if let Ok(outcome) = joined {
+            outcomes.push(outcome);
+        }
+    }
+
+    let count = |kind: Outcome| outcomes.iter().filter(|(o, _)| *o == kind).count() as u64;
+    let answered = count(Outcome::Answered);
+    let mut latencies: Vec<f64> = outcomes
+        .iter()
+        .filter(|(o, _)| *o != Outcome::Timeout)
+        .map(|(_, ms)| *ms)
+        .collect();
+    latencies.sort_by(f64::total_cmp);
+    let errors = sent - answered;
+    LoadStep {
+        target_qps: qps,
+        sent,
+        not_sent,
+        achieved_qps: answered as f64 / window.max(f64::EPSILON),
+        answered,
+        servfail: count(Outcome::ServFail),
+        refused: count(Outcome::Refused),
+        timeouts: count(Outcome::Timeout),
+        other_errors: count(Outcome::Other),
+        error_percent: if sent > 0 {
+            errors as f64 * 100.0 / sent as f64
+        } else {
+            0.0
+        },
+        latency_avg_ms: (!latencies.is_empty())
+            .then(|| latencies.iter().sum::<f64>() / latencies.len() as f64),
+        latency_p50_ms: percentile(&latencies, 50.0),
+        latency_p95_ms: percentile(&latencies, 95.0),
+        latency_p99_ms: percentile(&latencies, 99.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args() -> LoadTestArgs {
+        LoadTestArgs {
+            servers: vec!["192.0.2.1".to_string()],
+            domain: "Example.TEST.".to_string(),
+            record_type: None,
+            start_qps: None,
+            step_qps: None,
+            max_qps: None,
+            step_secs: None,
+            concurrency: None,
+            timeout_secs: None,
+            error_threshold_percent: None,
+            confirm: true,
+        }
+    }
+
+    #[test]
+    fn defaults_ramp_from_ten_to_two_hundred() {
+        let plan = plan(&args()).unwrap();
+        assert_eq!(plan.domain, "example.test");
+        assert_eq!(plan.record_type, RecordType::A);
+        assert_eq!(plan.steps, (10..=200).step_by(10).collect::<Vec<u32>>());
+        assert_eq!(plan.step, Duration::from_secs(5));
+        assert_eq!(plan.concurrency, 64);
+    }
+
+    #[test]
+    fn nothing_runs_without_confirm() {
+        let args = LoadTestArgs {
+            confirm: false,
+            max_qps: Some(50),
+            ..args()
+        };
+        let err = plan(&args).err().unwrap();
+        assert!(err.contains("up to 50 queries per second"), "{}", err);
+        assert!(err.contains("confirm"), "{}", err);
+    }
+
+    #[test]
+    fn safety_caps_reject_the_request() {
+        let too_fast = LoadTestArgs {
+            max_qps: Some(MAX_QPS + 1),
+            ..args()
+        };
+        assert_eq!(
+            plan(&too_fast).err().unwrap(),
+            format!(
+                "max_qps {} is above the safety cap of {}",
+                MAX_QPS + 1,
+                MAX_QPS
+            )
+        );
+        assert!(plan(&LoadTestArgs {
+            max_qps: Some(MAX_QPS),
+            step_qps: Some(100),
+            ..args()
+        })
+        .is_ok());
+
+        let too_wide = LoadTestArgs {
+            concurrency: Some(MAX_CONCURRENCY + 1),
+            ..args()
+        };
+        assert!(plan(&too_wide).err().unwrap().contains("concurrency"));
+
+        // 61 steps of 5s is just over the 300s cap
+        let too_long = LoadTestArgs {
+            start_qps: Some(1),
+            step_qps: Some(1),
+            max_qps: Some(61),
+            ..args()
+        };
+        assert!(plan(&too_long)
+            .err()
+            .unwrap()
+            .starts_with("61 steps of 5s exceed the safety cap of 300s"));
+        assert_eq!(
+            plan(&LoadTestArgs {
+                max_qps: Some(60),
+                ..too_long
+            })
+            .unwrap()
+            .steps
+            .len(),
+            60
+        );
+    }
+
+    #[test]
+    fn inconsistent_requests_are_rejected() {
+        let backwards = LoadTestArgs {
+            start_qps: Some(300),
+            ..args()
+        };
+        assert_eq!(
+            plan(&backwards).err().unwrap(),
+            "start_qps 300 is above max_qps 200"
+        );
+        let no_domain = LoadTestArgs {
+            domain: " . ".to_string(),
+            ..args()
+        };
+        assert_eq!(plan(&no_domain).err().unwrap(), "A domain is required");
+    }
+}
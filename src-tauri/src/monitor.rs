@@ -0,0 +1,325 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::{broadcast, Notify, RwLock};
+use tracing::info;
+
+use crate::assertions::{Measured, Metric};
+use crate::dns_tester::{self, DnsTestResult};
+use crate::exporter;
+use crate::history::{self, RunSource};
+use crate::storage;
+
+const MONITOR_FILE: &str = "monitor.json";
+// Alerts kept for get_monitor_status
+const RECENT_ALERTS: usize = 50;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertRule {
+    SuccessRateBelow { percent: f64 },
+    P95Above { ms: f64 },
+    // The sorted answer set differs from the previous check
+    AnswerChanged,
+    DnssecBogus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MonitorConfig {
+    pub enabled: bool,
+    pub interval_minutes: u32,
+    // Empty means the active server list
+    pub servers: Vec<String>,
+    pub domains: Vec<String>,
+    // "A/AAAA" (default lookup), "A", "MX", "TXT", ...
+    pub record_types: Vec<String>,
+    pub samples: u32,
+    pub timeout_secs: u64,
+    pub validate_dnssec: bool,
+    // Also check the system resolvers, which gives results a vs_system baseline
+    pub include_system: bool,
+    pub rules: Vec<AlertRule>,
+    pub desktop_notifications: bool,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        MonitorConfig {
+            enabled: false,
+            interval_minutes: 15,
+            servers: vec![],
+            domains: vec!["example.com".to_string()],
+            record_types: vec!["A/AAAA".to_string()],
+            samples: 3,
+            timeout_secs: 5,
+            validate_dnssec: false,
+            include_system: true,
+            rules: vec![
+                AlertRule::SuccessRateBelow { percent: 80.0 },
+                AlertRule::P95Above { ms: 500.0 },
+            ],
+            desktop_notifications: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Alert {
+    pub rule: AlertRule,
+    pub server_address: String,
+    pub domain: String,
+    pub record_type: String,
+    pub message: String,
+    // Unix milliseconds
+    pub at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MonitorStatus {
+    pub running: bool,
+    pub last_run_at: Option<i64>,
+    pub next_run_at: Option<i64>,
+    pub last_error: Option<String>,
+    pub recent_alerts: Vec<Alert>,
+}
+
+#[derive(Default)]
+struct MonitorState {
+    status: MonitorStatus,
+    // (server, domain, record type) -> answers seen on the last successful check
+    last_answers: HashMap<(String, String, String), Vec<String>>,
+    // Threshold conditions currently failing, so they alert once when they start
+    active: HashSet<(usize, String, String, String)>,
+}
+
+static CONFIG: Lazy<RwLock<MonitorConfig>> =
+    Lazy::new(|| RwLock::new(storage::read_json(MONITOR_FILE).unwrap_or_default()));
+static STATE: Lazy<RwLock<MonitorState>> = Lazy::new(|| RwLock::new(MonitorState::default()));
+// Wakes the scheduler early when the config changes
+static WAKE: Lazy<Notify> = Lazy::new(Notify::new);
+static ALERTS: Lazy<broadcast::Sender<Alert>> = Lazy::new(|| broadcast::channel(64).0);
+
+// Alerts as they fire; the GUI forwards them as events and notifications.
+pub fn subscribe() -> broadcast::Receiver<Alert> {
+    ALERTS.subscribe()
+}
+
+pub async fn get_config() -> MonitorConfig {
+    CONFIG.read().await.clone()
+}
+
+fn validate_config(config: &MonitorConfig) -> Result<(), String> {
+    if config.interval_minutes == 0 {
+        return Err("Interval must be at least one minute".to_string());
+    }
+    if config.domains.iter().all(|d| d.trim().is_empty()) {
+        return Err("At least one domain is required".to_string());
+    }
+    for t in &config.record_types {
+        dns_tester::parse_record_type(t)?;
+    }
+    for server in &config.servers {
+        dns_tester::servers::validate_address(server)
+            .map_err(|e| format!("Invalid server '{}': {}", server, e))?;
+    }
+    for rule in &config.rules {
+        match rule {
+            AlertRule::SuccessRateBelow { percent } if !(0.0..=100.0).contains(percent) => {
+                return Err("Success rate threshold must be between 0 and 100".to_string())
+            }
+            AlertRule::P95Above { ms } if *ms <= 0.0 => {
+                return Err("p95 threshold must be positive".to_string())
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Saves the config and restarts the schedule, so a newly enabled monitor runs right away.
+pub async fn set_config(config: MonitorConfig) -> Result<(), String> {
+    validate_config(&config)?;
+    storage::write_json(MONITOR_FILE, &config)?;
+    *CONFIG.write().await = config;
+    {
+        let mut state = STATE.write().await;
+        state.active.clear();
+        state.last_answers.clear();
+    }
+    WAKE.notify_one();
+    Ok(())
+}
+
+pub async fn get_status() -> MonitorStatus {
+    STATE.read().await.status.clone()
+}
+
+// Started once at app launch; sleeps while the monitor is disabled.
+pub fn start() {
+    crate::spawn(async move {
+        loop {
+            let config = get_config().await;
+            if !config.enabled {
+                {
+                    let mut state = STATE.write().await;
+                    state.status.running = false;
+                    state.status.next_run_at = None;
+                }
+                WAKE.notified().await;
+                continue;
+            }
+            run_checks(&config).await;
+            let interval = Duration::from_secs(config.interval_minutes as u64 * 60);
+            STATE.write().await.status.next_run_at =
+                Some(history::now_ms() + interval.as_millis() as i64);
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = WAKE.notified() => {}
+            }
+        }
+    });
+}
+
+// One benchmark per domain and record type.
+pub async fn run_checks(config: &MonitorConfig) {
+    STATE.write().await.status.running = true;
+    let record_types = if config.record_types.is_empty() {
+        vec!["A/AAAA".to_string()]
+    } else {
+        config.record_types.clone()
+    };
+    let mut last_error = None;
+    for domain in config.domains.iter().filter(|d| !d.trim().is_empty()) {
+        for record_type in &record_types {
+            let started_at = history::now_ms();
+            let (results, _) = check(config, domain.trim(), record_type).await;
+            if results.iter().all(|r| !r.query_successful) {
+                last_error = Some(format!("No server answered {} {}", domain, record_type));
+            }
+            let params = serde_json::json!({
+                "domain_or_ip": domain,
+                "record_type": record_type,
+                "samples": config.samples,
+                "timeout_secs": config.timeout_secs,
+                "validate_dnssec": config.validate_dnssec,
+            });
+            history::record_dns_run(RunSource::Monitor, started_at, params, &results).await;
+            exporter::observe_dns(domain, Some(record_type), &results).await;
+        }
+    }
+    let mut state = STATE.write().await;
+    state.status.running = false;
+    state.status.last_run_at = Some(history::now_ms());
+    state.status.last_error = last_error;
+}
+
+// Benchmarks one domain and record type and applies the alert rules, returning the results
+// and the alerts that fired. The servers always go in as an explicit list, so the benchmark's
+// reachability precheck can't drop a server that is down before the rules see it.
+pub async fn check(
+    config: &MonitorConfig,
+    domain: &str,
+    record_type: &str,
+) -> (Vec<DnsTestResult>, Vec<Alert>) {
+    let servers = if config.servers.is_empty() {
+        dns_tester::get_servers().await
+    } else {
+        config.servers.clone()
+    };
+    let results = dns_tester::perform_dns_benchmark(
+        domain.to_string(),
+        Some(servers),
+        Some(config.timeout_secs),
+        Some(config.samples),
+        Some(config.validate_dnssec),
+        Some(true),
+        Some(false),
+        Some(record_type.to_string()),
+        Some(config.include_system),
+    )
+    .await;
+    let alerts = evaluate(config, domain, record_type, &results).await;
+    (results, alerts)
+}
+
+async fn evaluate(
+    config: &MonitorConfig,
+    domain: &str,
+    record_type: &str,
+    results: &[DnsTestResult],
+) -> Vec<Alert> {
+    let mut fired = vec![];
+    let mut state = STATE.write().await;
+    for r in results {
+        let key = (
+            r.server_address.clone(),
+            domain.to_string(),
+            record_type.to_string(),
+        );
+        for (index, rule) in config.rules.iter().enumerate() {
+            let message = match rule {
+                AlertRule::SuccessRateBelow { percent } => {
+                    (r.success_percent < *percent).then(|| {
+                        format!(
+                            "success rate {:.0}% is below {:.0}%",
+                            r.success_percent, percent
+                        )
+                    })
+                }
+                AlertRule::P95Above { ms } => r
+                    .metric(Metric::P95)
+                    .filter(|p| p > ms)
+                    .map(|p| format!("p95 latency {:.0} ms is above {:.0} ms", p, ms)),
+                AlertRule::DnssecBogus => r
+                    .dnssec_bogus
+                    .then(|| "answer failed DNSSEC validation".to_string()),
+                AlertRule::AnswerChanged => {
+                    let mut answers = r.answers();
+                    answers.sort();
+                    if answers.is_empty() {
+                        None
+                    } else {
+                        let previous = state.last_answers.insert(key.clone(), answers.clone());
+                        previous.filter(|p| *p != answers).map(|p| {
+                            format!(
+                                "answer changed from [{}] to [{}]",
+                                p.join(", "),
+                                answers.join(", ")
+                            )
+                        })
+                    }
+                }
+            };
+            let active_key = (index, key.0.clone(), key.1.clone(), key.2.clone());
+            let Some(message) = message else {
+                state.active.remove(&active_key);
+                continue;
+            };
+            // Answer changes are events; threshold rules alert when they start failing.
+            if *rule != AlertRule::AnswerChanged && !state.active.insert(active_key) {
+                continue;
+            }
+            fired.push(Alert {
+                rule: rule.clone(),
+                server_address: r.server_address.clone(),
+                domain: domain.to_string(),
+                record_type: record_type.to_string(),
+                message: format!("{}: {}", r.server_address, message),
+                at: history::now_ms(),
+            });
+        }
+    }
+    for alert in &fired {
+        info!(
+            "Monitor alert for {} {}: {}",
+            alert.domain, alert.record_type, alert.message
+        );
+        state.status.recent_alerts.insert(0, alert.clone());
+        state.status.recent_alerts.truncate(RECENT_ALERTS);
+        // No subscribers is fine; alerts still show up in the status.
+        let _ = ALERTS.send(alert.clone());
+    }
+    fired
+}
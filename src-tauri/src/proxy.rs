@@ -0,0 +1,544 @@
+use futures::future::select_ok;
+use hickory_resolver::dns_lru::{DnsLru, TtlConfig};
+use hickory_resolver::lookup::Lookup;
+use hickory_resolver::proto::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+use hickory_resolver::proto::rr::Record;
+use hickory_resolver::proto::ProtoErrorKind;
+use hickory_resolver::{ResolveErrorKind, TokioResolver};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::runtime::Runtime;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::dns_tester::bootstrap::BootstrapCache;
+use crate::dns_tester::spec::SYSTEM_SERVER;
+use crate::dns_tester::{build_resolver_for_server, DnsTestResult};
+use crate::history::{self, RunKind};
+use crate::report::{self, RunResults};
+use crate::storage;
+
+const PROXY_FILE: &str = "proxy.json";
+// Recent queries kept for the live view
+const RECENT_QUERIES: usize = 100;
+// Benchmark runs searched for one with usable upstreams
+const RUNS_SEARCHED: u32 = 20;
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+// Same reason as the benchmark's isolated runtimes: resolver futures are stack-hungry
+const WORKER_STACK_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ProxyConfig {
+    // UDP and TCP on the same address
+    pub listen: String,
+    // Explicit upstreams; empty means the best servers of the latest DNS benchmark
+    pub upstreams: Vec<String>,
+    // How many benchmark results to use when upstreams is empty
+    pub upstream_count: usize,
+    // Ask every upstream at once and take the first answer, instead of failing over in order
+    pub race: bool,
+    pub cache_size: usize,
+    pub timeout_secs: u64,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        ProxyConfig {
+            listen: "127.0.0.1:5300".to_string(),
+            upstreams: vec![],
+            upstream_count: 3,
+            race: false,
+            cache_size: 4096,
+            timeout_secs: 3,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UpstreamStats {
+    pub server_address: String,
+    pub queries: u64,
+    pub answers: u64,
+    pub failures: u64,
+    pub avg_ms: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueryLogEntry {
+    // Unix milliseconds
+    pub at: i64,
+    pub name: String,
+    pub record_type: String,
+    pub response_code: String,
+    // None for cache hits and failures
+    pub upstream: Option<String>,
+    pub cached: bool,
+    pub elapsed_ms: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProxyStatus {
+    pub running: bool,
+    pub listen: Option<String>,
+    pub race: bool,
+    pub started_at: Option<i64>,
+    pub queries: u64,
+    pub cache_hits: u64,
+    pub failures: u64,
+    pub upstreams: Vec<UpstreamStats>,
+    pub recent: Vec<QueryLogEntry>,
+}
+
+struct Upstream {
+    address: String,
+    resolver: TokioResolver,
+    queries: AtomicU64,
+    answers: AtomicU64,
+    failures: AtomicU64,
+    total_us: AtomicU64,
+}
+
+struct Proxy {
+    upstreams: Vec<Upstream>,
+    cache: DnsLru,
+    race: bool,
+    timeout: Duration,
+    queries: AtomicU64,
+    cache_hits: AtomicU64,
+    failures: AtomicU64,
+    recent: StdMutex<VecDeque<QueryLogEntry>>,
+}
+
+struct Running {
+    proxy: Arc<Proxy>,
+    runtime: Runtime,
+    listen: SocketAddr,
+    started_at: i64,
+}
+
+static CONFIG: Lazy<Mutex<ProxyConfig>> =
+    Lazy::new(|| Mutex::new(storage::read_json(PROXY_FILE).unwrap_or_default()));
+static RUNNING: Lazy<Mutex<Option<Running>>> = Lazy::new(|| Mutex::new(None));
+
+pub async fn get_config() -> ProxyConfig {
+    CONFIG.lock().await.clone()
+}
+
+// Successful servers, most reliable then fastest; the system resolver is never an upstream.
+pub fn rank_upstreams(results: &[DnsTestResult]) -> Vec<String> {
+    let mut usable: Vec<&DnsTestResult> = results
+        .iter()
+        .filter(|r| r.query_successful && !r.server_address.eq_ignore_ascii_case(SYSTEM_SERVER))
+        .collect();
+    usable.sort_by(|a, b| {
+        b.success_percent.total_cmp(&a.success_percent).then(
+            a.latency_avg_ms
+                .unwrap_or(f64::MAX)
+                .total_cmp(&b.latency_avg_ms.unwrap_or(f64::MAX)),
+        )
+    });
+    usable.iter().map(|r| r.server_address.clone()).collect()
+}
+
+// The top `count` servers of the newest DNS benchmark that had a working one.
+pub async fn best_from_history(count: usize) -> Result<Vec<String>, String> {
+    for run in history::list_runs(Some(RunKind::Dns), Some(RUNS_SEARCHED), None).await? {
+        if run.success_count == 0 {
+            continue;
+        }
+        if let Ok(RunResults::Dns(results)) = report::load_run(run.id).await.map(|r| r.results) {
+            let ranked = rank_upstreams(&results);
+            if !ranked.is_empty() {
+                return Ok(ranked.into_iter().take(count.max(1)).collect());
+            }
+        }
+    }
+    Err("No DNS benchmark with a working server yet; run one or set upstreams".to_string())
+}
+
+// (Re)starts the proxy and saves the config once it is up.
+pub async fn start(config: ProxyConfig) -> Result<ProxyStatus, String> {
+    let status = launch(&config).await?;
+    storage::write_json(PROXY_FILE, &config)?;
+    *CONFIG.lock().await = config;
+    Ok(status)
+}
+
+// Starts without touching the saved config (the CLI's one-off overrides).
+pub async fn launch(config: &ProxyConfig) -> Result<ProxyStatus, String> {
+    let listen: SocketAddr = config
+        .listen
+        .parse()
+        .map_err(|e| format!("Invalid listen address '{}': {}", config.listen, e))?;
+    let addresses = if config.upstreams.is_empty() {
+        best_from_history(config.upstream_count).await?
+    } else {
+        config.upstreams.clone()
+    };
+    // The running proxy keeps serving until the new one is ready
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_stack_size(WORKER_STACK_BYTES)
+        .thread_name("resoboost-proxy")
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+    // Resolvers and sockets must be created on the runtime that drives them
+    let timeout_secs = config.timeout_secs.max(1);
+    let setup = async {
+        let upstreams = runtime
+            .spawn(connect_upstreams(addresses, timeout_secs))
+            .await
+            .map_err(|e| e.to_string())??;
+        let mut sockets = runtime.spawn(bind(listen)).await.map_err(|e| e.to_string())?;
+        // Restarting on the same port: only now make way for the new sockets
+        let same_port = RUNNING
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|r| r.listen.port() == listen.port());
+        if sockets.is_err() && same_port {
+            stop().await;
+            sockets = runtime.spawn(bind(listen)).await.map_err(|e| e.to_string())?;
+        }
+        sockets.map(|sockets| (upstreams, sockets))
+    };
+    let (upstreams, (udp, tcp, bound)) = match setup.await {
+        Ok(ready) => ready,
+        Err(e) => {
+            runtime.shutdown_background();
+            return Err(e);
+        }
+    };
+    let proxy = Arc::new(Proxy {
+        upstreams,
+        cache: DnsLru::new(config.cache_size.max(1), TtlConfig::default()),
+        race: config.race,
+        timeout: Duration::from_secs(timeout_secs),
+        queries: AtomicU64::new(0),
+        cache_hits: AtomicU64::new(0),
+        failures: AtomicU64::new(0),
+        recent: StdMutex::new(VecDeque::new()),
+    });
+    runtime.spawn(serve_udp(Arc::new(udp), proxy.clone()));
+    runtime.spawn(serve_tcp(tcp, proxy.clone()));
+    info!(
+        "DNS proxy listening on {} with {} upstream(s)",
+        bound,
+        proxy.upstreams.len()
+    );
+    let replaced = RUNNING.lock().await.replace(Running {
+        proxy,
+        runtime,
+        listen: bound,
+        started_at: history::now_ms(),
+    });
+    // The previous proxy, or one a concurrent start set up; a runtime must not be dropped
+    // from async code
+    if let Some(old) = replaced {
+        old.runtime.shutdown_background();
+        info!("DNS proxy on {} replaced", old.listen);
+    }
+    Ok(get_status().await)
+}
+
+async fn connect_upstreams(
+    addresses: Vec<String>,
+    timeout_secs: u64,
+) -> Result<Vec<Upstream>, String> {
+    let mut upstreams = vec![];
+    let bootstrap_cache = BootstrapCache::default();
+    for address in addresses {
+        match build_resolver_for_server(&address, timeout_secs, false, &bootstrap_cache).await {
+            Ok(built) => upstreams.push(Upstream {
+                address,
+                resolver: built.resolver,
+                queries: AtomicU64::new(0),
+                answers: AtomicU64::new(0),
+                failures: AtomicU64::new(0),
+                total_us: AtomicU64::new(0),
+            }),
+            Err(e) => warn!("Proxy upstream {} skipped: {}", address, e),
+        }
+    }
+    if upstreams.is_empty() {
+        return Err("None of the upstreams could be set up".to_string());
+    }
+    Ok(upstreams)
+}
+
+async fn bind(listen: SocketAddr) -> Result<(UdpSocket, TcpListener, SocketAddr), String> {
+    let udp = UdpSocket::bind(listen)
+        .await
+        .map_err(|e| format!("Could not listen on udp {}: {}", listen, e))?;
+    // With port 0, TCP takes the port UDP got
+    let bound = udp.local_addr().map_err(|e| e.to_string())?;
+    let tcp = TcpListener::bind(bound)
+        .await
+        .map_err(|e| format!("Could not listen on tcp {}: {}", bound, e))?;
+    Ok((udp, tcp, bound))
+}
+
+// Dropping the runtime closes the sockets and cancels in-flight queries.
+pub async fn stop() {
+    if let Some(running) = RUNNING.lock().await.take() {
+        running.runtime.shutdown_background();
+        info!("DNS proxy on {} stopped", running.listen);
+    }
+}
+
+pub async fn get_status() -> ProxyStatus {
+    let running = RUNNING.lock().await;
+    let Some(running) = running.as_ref() else {
+        return ProxyStatus::default();
+    };
+    let proxy = &running.proxy;
+    ProxyStatus {
+        running: true,
+        listen: Some(running.listen.to_string()),
+        race: proxy.race,
+        started_at: Some(running.started_at),
+        queries: proxy.queries.load(Ordering::Relaxed),
+        cache_hits: proxy.cache_hits.load(Ordering::Relaxed),
+        failures: proxy.failures.load(Ordering::Relaxed),
+        upstreams: proxy
+            .upstreams
+            .iter()
+            .map(|u| {
+                let answers = u.answers.load(Ordering::Relaxed);
+                UpstreamStats {
+                    server_address: u.address.clone(),
+                    queries: u.queries.load(Ordering::Relaxed),
+                    answers,
+                    failures: u.failures.load(Ordering::Relaxed),
+                    avg_ms: (answers > 0).then(|| {
+                        u.total_us.load(Ordering::Relaxed) as f64 / answers as f64 / 1000.0
+                    }),
+                }
+            })
+            .collect(),
+        recent: proxy
+            .recent
+            .lock()
+            .map(|r| r.iter().cloned().collect())
+            .unwrap_or_default(),
+    }
+}
+
+async fn serve_udp(socket: Arc<UdpSocket>, proxy: Arc<Proxy>) {
+    let mut buf = vec![0u8; 4096];
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e) => {
+                // e.g. ICMP port unreachable from an earlier reply on Windows
+                warn!("Proxy udp receive failed: {}", e);
+                continue;
+            }
+        };
+        let request = buf[..len].to_vec();
+        let (socket, proxy) = (socket.clone(), proxy.clone());
+        tokio::spawn(async move {
+            if let Some(response) = proxy.handle(&request, true).await {
+                let _ = socket.send_to(&response, peer).await;
+            }
+        });
+    }
+}
+
+async fn serve_tcp(listener: TcpListener, proxy: Arc<Proxy>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let proxy = proxy.clone();
+                tokio::spawn(async move {
+                    let _ = serve_tcp_connection(stream, proxy).await;
+                });
+            }
+            Err(e) => warn!("Proxy tcp accept failed: {}", e),
+        }
+    }
+}
+
+// Length-prefixed messages until the client closes or goes idle.
+async fn serve_tcp_connection(mut stream: TcpStream, proxy: Arc<Proxy>) -> std::io::Result<()> {
+    loop {
+        let mut len = [0u8; 2];
+        match tokio::time::timeout(TCP_IDLE_TIMEOUT, stream.read_exact(&mut len)).await {
+            Ok(Ok(_)) => {}
+            _ => return Ok(()),
+        }
+        let mut request = vec![0u8; u16::from_be_bytes(len) as usize];
+        stream.read_exact(&mut request).await?;
+        if let Some(response) = proxy.handle(&request, false).await {
+            stream
+                .write_all(&(response.len() as u16).to_be_bytes())
+                .await?;
+            stream.write_all(&response).await?;
+        }
+    }
+}
+
+// Either an answer or an authoritative "no such name / no such type".
+enum Outcome {
+    Records(Lookup),
+    Negative {
+        code: ResponseCode,
+        soa: Option<Record>,
+    },
+}
+
+impl Proxy {
+    async fn ask(&self, upstream: &Upstream, query: &Query) -> Result<Outcome, String> {
+        upstream.queries.fetch_add(1, Ordering::Relaxed);
+        let started = Instant::now();
+        let result = tokio::time::timeout(
+            self.timeout,
+            upstream
+                .resolver
+                .lookup(query.name().clone(), query.query_type()),
+        )
+        .await;
+        let outcome = match result {
+            Ok(Ok(lookup)) => Ok(Outcome::Records(lookup)),
+            Ok(Err(e)) => match e.kind() {
+                ResolveErrorKind::Proto(p) => match p.kind() {
+                    ProtoErrorKind::NoRecordsFound {
+                        response_code, soa, ..
+                    } => Ok(Outcome::Negative {
+                        code: *response_code,
+                        soa: soa.as_ref().map(|s| s.as_ref().clone().into_record_of_rdata()),
+                    }),
+                    _ => Err(e.to_string()),
+                },
+                _ => Err(e.to_string()),
+            },
+            Err(_) => Err(format!("{} timed out", upstream.address)),
+        };
+        match &outcome {
+            Ok(_) => {
+                upstream.answers.fetch_add(1, Ordering::Relaxed);
+                upstream
+                    .total_us
+                    .fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+            }
+            Err(_) => {
+                upstream.failures.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        outcome
+    }
+
+    // Failover walks the ranked list; racing takes whichever upstream answers first.
+    async fn resolve(&self, query: &Query) -> Result<(Outcome, String), String> {
+        if self.race && self.upstreams.len() > 1 {
+            let attempts = self.upstreams.iter().map(|u| {
+                Box::pin(async move { self.ask(u, query).await.map(|o| (o, u.address.clone())) })
+            });
+            return select_ok(attempts).await.map(|(won, _)| won);
+        }
+        let mut last_error = "No upstreams".to_string();
+        for upstream in &self.upstreams {
+            match self.ask(upstream, query).await {
+                Ok(outcome) => return Ok((outcome, upstream.address.clone())),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn handle(&self, request: &[u8], udp: bool) -> Option<Vec<u8>> {
+        // Not even a header: nothing sensible to reply to
+        let request = Message::from_vec(request).ok()?;
+        let started = Instant::now();
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(request.op_code())
+            .set_recursion_desired(request.recursion_desired())
+            .set_recursion_available(true)
+            .add_queries(request.queries().to_vec());
+        if let Some(edns) = request.extensions() {
+            let mut reply_edns = Edns::new();
+            reply_edns.set_max_payload(edns.max_payload().max(512));
+            response.set_edns(reply_edns);
+        }
+        let query = match (request.op_code(), request.queries()) {
+            (OpCode::Query, [query]) => query.clone(),
+            (OpCode::Query, _) => {
+                response.set_response_code(ResponseCode::FormErr);
+                return response.to_vec().ok();
+            }
+            _ => {
+                response.set_response_code(ResponseCode::NotImp);
+                return response.to_vec().ok();
+            }
+        };
+        self.queries.fetch_add(1, Ordering::Relaxed);
+
+        let mut upstream = None;
+        let cached = match self.cache.get(&query, Instant::now()) {
+            Some(Ok(lookup)) => Some(lookup),
+            _ => None,
+        };
+        if let Some(lookup) = &cached {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+            response.add_answers(lookup.records().iter().cloned());
+        } else {
+            match self.resolve(&query).await {
+                Ok((Outcome::Records(lookup), from)) => {
+                    self.cache.insert_records(
+                        query.clone(),
+                        lookup.records().iter().cloned(),
+                        Instant::now(),
+                    );
+                    response.add_answers(lookup.records().iter().cloned());
+                    upstream = Some(from);
+                }
+                Ok((Outcome::Negative { code, soa }, from)) => {
+                    response.set_response_code(code);
+                    response.add_name_servers(soa);
+                    upstream = Some(from);
+                }
+                Err(e) => {
+                    warn!("Proxy could not resolve {}: {}", query, e);
+                    self.failures.fetch_add(1, Ordering::Relaxed);
+                    response.set_response_code(ResponseCode::ServFail);
+                }
+            }
+        }
+
+        self.log(QueryLogEntry {
+            at: history::now_ms(),
+            name: query.name().to_string(),
+            record_type: query.query_type().to_string(),
+            response_code: response.response_code().to_string(),
+            upstream,
+            cached: cached.is_some(),
+            elapsed_ms: started.elapsed().as_secs_f64() * 1000.0,
+        });
+        let bytes = response.to_vec().ok()?;
+        // Too big for the client's UDP buffer: send the header only so it retries over TCP
+        let limit = request.extensions().as_ref().map_or(512, |e| e.max_payload().max(512));
+        if udp && bytes.len() > limit as usize {
+            return response.truncate().to_vec().ok();
+        }
+        Some(bytes)
+    }
+
+    fn log(&self, entry: QueryLogEntry) {
+        if let Ok(mut recent) = self.recent.lock() {
+            recent.push_front(entry);
+            recent.truncate(RECENT_QUERIES);
+        }
+    }
+}
@@ -0,0 +1,904 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::assertions::{Measured, Metric};
+use crate::dns_tester::{DnsErrorKind, DnsTestResult};
+use crate::history::{self, RunDetail, RunKind};
+use crate::speed_tester::DownloadTestResult;
+use crate::storage;
+
+// Servers drawn in the CDF chart; more lines than this are unreadable
+const CDF_SERIES: usize = 8;
+const PALETTE: [&str; CDF_SERIES] = [
+    "#2563eb", "#dc2626", "#16a34a", "#d97706", "#7c3aed", "#0891b2", "#db2777", "#4b5563",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Csv,
+    Json,
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    // Guesses the format from a file name ("report.md", "run.html", ...)
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        Some(match ext.as_str() {
+            "csv" => ReportFormat::Csv,
+            "json" => ReportFormat::Json,
+            "md" | "markdown" => ReportFormat::Markdown,
+            "html" | "htm" => ReportFormat::Html,
+            _ => return None,
+        })
+    }
+}
+
+// Read back through StoredRun (see below)
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", content = "results", rename_all = "lowercase")]
+pub enum RunResults {
+    Dns(Vec<DnsTestResult>),
+    Speed(Vec<DownloadTestResult>),
+}
+
+// A run as it goes into a report. The JSON report and a stored RunDetail share this shape,
+// so both can be read back (see `load_file`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "StoredRun")]
+pub struct ReportRun {
+    pub title: Option<String>,
+    // History id, when the run came from the database
+    pub id: Option<i64>,
+    // Unix milliseconds
+    pub started_at: Option<i64>,
+    pub params: Value,
+    pub environment: Value,
+    #[serde(flatten)]
+    pub results: RunResults,
+}
+
+// Read side of ReportRun: flatten buffers values in a form that cannot hold the u128 fields,
+// so results are kept as JSON until the kind is known.
+#[derive(Deserialize)]
+struct StoredRun {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    id: Option<i64>,
+    #[serde(default)]
+    started_at: Option<i64>,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    environment: Value,
+    kind: RunKind,
+    results: Value,
+}
+
+impl TryFrom<StoredRun> for ReportRun {
+    type Error = String;
+
+    fn try_from(run: StoredRun) -> Result<Self, String> {
+        let results = match run.kind {
+            RunKind::Dns => {
+                RunResults::Dns(serde_json::from_value(run.results).map_err(|e| e.to_string())?)
+            }
+            RunKind::Speed => {
+                RunResults::Speed(serde_json::from_value(run.results).map_err(|e| e.to_string())?)
+            }
+        };
+        Ok(ReportRun {
+            title: run.title,
+            id: run.id,
+            started_at: run.started_at,
+            params: run.params,
+            environment: run.environment,
+            results,
+        })
+    }
+}
+
+impl ReportRun {
+    pub fn from_detail(detail: RunDetail) -> Result<Self, String> {
+        let id = detail.summary.id;
+        serde_json::to_value(detail)
+            .and_then(serde_json::from_value)
+            .map_err(|e| format!("Run {} has unreadable results: {}", id, e))
+    }
+
+    fn heading(&self) -> String {
+        if let Some(title) = &self.title {
+            return title.clone();
+        }
+        let target = match &self.results {
+            RunResults::Dns(_) => param(&self.params, &["domain_or_ip", "domainOrIp"]),
+            RunResults::Speed(_) => param(&self.params, &["url"]),
+        };
+        let kind = match &self.results {
+            RunResults::Dns(_) => "DNS benchmark",
+            RunResults::Speed(_) => "Download speed test",
+        };
+        match target {
+            Some(t) => format!("{}: {}", kind, t),
+            None => kind.to_string(),
+        }
+    }
+}
+
+pub async fn load_run(id: i64) -> Result<ReportRun, String> {
+    ReportRun::from_detail(history::get_run(id).await?)
+}
+
+// Reads a JSON report, a stored run, or a bare array of results as exported by the app.
+pub fn load_file(path: &Path) -> Result<ReportRun, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_run(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn parse_run(text: &str) -> Result<ReportRun, String> {
+    let value: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    if !value.is_array() {
+        return serde_json::from_value(value).map_err(|e| e.to_string());
+    }
+    // Bare arrays carry no kind; DNS results are the ones with a success_percent
+    let is_dns = value
+        .as_array()
+        .and_then(|a| a.first())
+        .is_none_or(|r| r.get("success_percent").is_some());
+    let results = if is_dns {
+        RunResults::Dns(serde_json::from_value(value).map_err(|e| e.to_string())?)
+    } else {
+        RunResults::Speed(serde_json::from_value(value).map_err(|e| e.to_string())?)
+    };
+    Ok(ReportRun {
+        title: None,
+        id: None,
+        started_at: None,
+        params: Value::Null,
+        environment: Value::Null,
+        results,
+    })
+}
+
+pub fn render(run: &ReportRun, format: ReportFormat) -> Result<String, String> {
+    Ok(match format {
+        ReportFormat::Csv => {
+            let (headers, rows) = csv_table(&run.results);
+            let mut out = headers.join(",");
+            for row in rows {
+                out.push('\n');
+                let cells: Vec<String> = row.iter().map(|c| csv_escape(c)).collect();
+                out.push_str(&cells.join(","));
+            }
+            out.push('\n');
+            out
+        }
+        ReportFormat::Json => {
+            let mut out = serde_json::to_string_pretty(run).map_err(|e| e.to_string())?;
+            out.push('\n');
+            out
+        }
+        ReportFormat::Markdown => markdown(run),
+        ReportFormat::Html => html(run),
+    })
+}
+
+pub fn write_report(run: &ReportRun, format: ReportFormat, path: &Path) -> Result<(), String> {
+    storage::write_atomic(path, render(run, format)?.as_bytes())
+}
+
+fn param(params: &Value, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|k| match params.get(k)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Null => None,
+        other => Some(other.to_string()),
+    })
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn ms(value: Option<f64>) -> String {
+    value.map(|v| format!("{:.1}", v)).unwrap_or_default()
+}
+
+// Resolver errors can carry a backtrace; reports only show the message.
+pub fn error_line(error: Option<&String>) -> String {
+    error
+        .and_then(|e| e.split("   0: ").next())
+        .map(|e| e.lines().next().unwrap_or_default().trim().to_string())
+        .unwrap_or_default()
+}
+
+// Failed samples by cause, most frequent first: "timeout 3, SERVFAIL 1".
+pub fn error_breakdown(counts: &BTreeMap<DnsErrorKind, u32>) -> String {
+    let mut counts: Vec<_> = counts.iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(a.1));
+    counts
+        .iter()
+        .map(|(kind, count)| format!("{} {}", kind.label(), count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// The columns of the app's CSV export (src/utils/export.ts), plus error_kind and error_counts.
+pub fn csv_table(results: &RunResults) -> (Vec<&'static str>, Vec<Vec<String>>) {
+    match results {
+        RunResults::Dns(results) => (
+            vec![
+                "server_address",
+                "provider",
+                "query_successful",
+                "success_percent",
+                "latency_avg_ms",
+                "jitter_avg_ms",
+                "resolution_time_ms",
+                "avg_time",
+                "dnssec_validated",
+                "ipv4_ips",
+                "ipv6_ips",
+                "error_msg",
+                "error_kind",
+                "error_counts",
+            ],
+            results
+                .iter()
+                .map(|r| {
+                    vec![
+                        r.server_address.clone(),
+                        opt(r.provider.as_ref()),
+                        r.query_successful.to_string(),
+                        r.success_percent.to_string(),
+                        opt(r.latency_avg_ms),
+                        opt(r.jitter_avg_ms),
+                        opt(r.resolution_time_ms),
+                        opt(r.avg_time),
+                        r.dnssec_validated.to_string(),
+                        r.ipv4_ips.join(";"),
+                        r.ipv6_ips.join(";"),
+                        opt(r.error_msg.as_ref()),
+                        opt(r.error_kind.map(|k| k.label())),
+                        error_breakdown(&r.error_counts),
+                    ]
+                })
+                .collect(),
+        ),
+        RunResults::Speed(results) => (
+            vec![
+                "server_address",
+                "provider",
+                "resolved_ip",
+                "query_successful",
+                "http_status",
+                "duration_ms",
+                "bytes_read",
+                "bandwidth_mbps",
+                "error_msg",
+            ],
+            results
+                .iter()
+                .map(|r| {
+                    vec![
+                        r.server_address.clone(),
+                        opt(r.provider.as_ref()),
+                        opt(r.resolved_ip.as_ref()),
+                        r.query_successful.to_string(),
+                        opt(r.http_status),
+                        r.duration_ms.to_string(),
+                        r.bytes_read.to_string(),
+                        r.bandwidth_mbps.to_string(),
+                        opt(r.error_msg.as_ref()),
+                    ]
+                })
+                .collect(),
+        ),
+    }
+}
+
+// The human-readable table shared by Markdown and HTML, best servers first.
+fn summary_table(results: &RunResults) -> (Vec<&'static str>, Vec<Vec<String>>) {
+    match results {
+        RunResults::Dns(results) => {
+            let mut sorted: Vec<&DnsTestResult> = results.iter().collect();
+            sorted.sort_by(|a, b| {
+                b.query_successful.cmp(&a.query_successful).then(
+                    a.latency_avg_ms
+                        .unwrap_or(f64::MAX)
+                        .total_cmp(&b.latency_avg_ms.unwrap_or(f64::MAX)),
+                )
+            });
+            (
+                vec![
+                    "Server",
+                    "Provider",
+                    "Median ms",
+                    "p95 ms",
+                    "Jitter ms",
+                    "Success",
+                    "DNSSEC",
+                    "Answers",
+                    "Failures",
+                    "Error",
+                ],
+                sorted
+                    .iter()
+                    .map(|r| {
+                        vec![
+                            r.server_address.clone(),
+                            opt(r.provider.as_ref()),
+                            ms(r.latency_avg_ms),
+                            ms(r.metric(Metric::P95)),
+                            ms(r.jitter_avg_ms),
+                            format!("{:.0}%", r.success_percent),
+                            match r.dnssec_secure() {
+                                Some(true) => "secure",
+                                Some(false) if r.dnssec_bogus => "bogus",
+                                Some(false) => "insecure",
+                                None => "",
+                            }
+                            .to_string(),
+                            r.answers().join(", "),
+                            error_breakdown(&r.error_counts),
+                            error_line(r.error_msg.as_ref()),
+                        ]
+                    })
+                    .collect(),
+            )
+        }
+        RunResults::Speed(results) => {
+            let mut sorted: Vec<&DownloadTestResult> = results.iter().collect();
+            sorted.sort_by(|a, b| b.bandwidth_mbps.total_cmp(&a.bandwidth_mbps));
+            (
+                vec![
+                    "Server",
+                    "Provider",
+                    "Resolved IP",
+                    "Mbps",
+                    "HTTP",
+                    "Bytes",
+                    "Error",
+                ],
+                sorted
+                    .iter()
+                    .map(|r| {
+                        vec![
+                            r.server_address.clone(),
+                            opt(r.provider.as_ref()),
+                            opt(r.resolved_ip.as_ref()),
+                            format!("{:.2}", r.bandwidth_mbps),
+                            opt(r.http_status),
+                            r.bytes_read.to_string(),
+                            error_line(r.error_msg.as_ref()),
+                        ]
+                    })
+                    .collect(),
+            )
+        }
+    }
+}
+
+// Run parameters and environment as (key, value) pairs for the report header.
+fn metadata(run: &ReportRun) -> Vec<(String, String)> {
+    let mut items = vec![];
+    if let Some(id) = run.id {
+        items.push(("Run".to_string(), format!("#{}", id)));
+    }
+    if let Some(started_at) = run.started_at {
+        items.push(("Started".to_string(), format_time(started_at)));
+    }
+    for value in [&run.params, &run.environment] {
+        if let Value::Object(map) = value {
+            for (key, v) in map {
+                let text = match v {
+                    Value::Null => continue,
+                    Value::String(s) => s.clone(),
+                    Value::Array(a) if a.is_empty() => continue,
+                    other => other.to_string(),
+                };
+                items.push((key.clone(), text));
+            }
+        }
+    }
+    items
+}
+
+// UTC, "2025-01-31 14:05:09 UTC"; no timezone database needed.
+fn format_time(ms: i64) -> String {
+    let secs = ms.div_euclid(1000);
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+    // Civil-from-days (Howard Hinnant)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+fn md_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+fn markdown(run: &ReportRun) -> String {
+    let mut out = format!("# {}\n\n", md_cell(&run.heading()));
+    for (key, value) in metadata(run) {
+        let _ = writeln!(out, "- **{}**: {}", md_cell(&key), md_cell(&value));
+    }
+    let (headers, rows) = summary_table(&run.results);
+    let _ = writeln!(out, "\n| {} |", headers.join(" | "));
+    let _ = writeln!(out, "|{}", "---|".repeat(headers.len()));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|c| md_cell(c)).collect();
+        let _ = writeln!(out, "| {} |", cells.join(" | "));
+    }
+    out
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Horizontal bars, one per (label, value), scaled to the largest value.
+fn bar_chart(title: &str, unit: &str, bars: &[(String, f64)], max: Option<f64>) -> String {
+    if bars.is_empty() {
+        return String::new();
+    }
+    let (label_w, chart_w, row_h) = (220.0, 420.0, 22.0);
+    let max = max
+        .unwrap_or_else(|| bars.iter().map(|b| b.1).fold(0.0, f64::max))
+        .max(f64::EPSILON);
+    let height = bars.len() as f64 * row_h + 10.0;
+    let mut svg = format!(
+        "<h2>{}</h2>\n<svg viewBox=\"0 0 {} {}\" width=\"{}\" height=\"{}\" role=\"img\">\n",
+        escape_html(title),
+        label_w + chart_w + 90.0,
+        height,
+        label_w + chart_w + 90.0,
+        height
+    );
+    for (i, (label, value)) in bars.iter().enumerate() {
+        let y = i as f64 * row_h + 5.0;
+        let w = (value / max * chart_w).max(1.0);
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.0}\" y=\"{:.0}\" text-anchor=\"end\">{}</text>\
+             <rect x=\"{:.0}\" y=\"{:.0}\" width=\"{:.1}\" height=\"{:.0}\" fill=\"{}\"/>\
+             <text x=\"{:.1}\" y=\"{:.0}\">{:.1}{}</text>",
+            label_w - 8.0,
+            y + 15.0,
+            escape_html(label),
+            label_w,
+            y + 3.0,
+            w,
+            row_h - 6.0,
+            PALETTE[0],
+            label_w + w + 6.0,
+            y + 15.0,
+            value,
+            unit
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Cumulative distribution of successful sample latencies for the fastest servers.
+fn cdf_chart(results: &[DnsTestResult]) -> String {
+    let mut series: Vec<(&str, Vec<f64>)> = results
+        .iter()
+        .filter_map(|r| {
+            let mut ok: Vec<f64> = r
+                .samples
+                .iter()
+                .filter(|s| s.ok)
+                .map(|s| s.latency_ms)
+                .collect();
+            if ok.is_empty() {
+                return None;
+            }
+            ok.sort_by(|a, b| a.total_cmp(b));
+            Some((r.server_address.as_str(), ok))
+        })
+        .collect();
+    if series.is_empty() {
+        return String::new();
+    }
+    series.sort_by(|a, b| a.1[a.1.len() / 2].total_cmp(&b.1[b.1.len() / 2]));
+    series.truncate(CDF_SERIES);
+
+    let (left, top, w, h) = (50.0, 10.0, 560.0, 260.0);
+    let max = series
+        .iter()
+        .filter_map(|s| s.1.last())
+        .fold(0.0, |a: f64, b| a.max(*b))
+        .max(1.0);
+    let mut svg = format!(
+        "<h2>Latency distribution (CDF)</h2>\n<svg viewBox=\"0 0 {w2} {h2}\" width=\"{w2}\" height=\"{h2}\" role=\"img\">\n\
+         <line x1=\"{left}\" y1=\"{bottom}\" x2=\"{right}\" y2=\"{bottom}\" stroke=\"#999\"/>\
+         <line x1=\"{left}\" y1=\"{top}\" x2=\"{left}\" y2=\"{bottom}\" stroke=\"#999\"/>\n\
+         <text x=\"{left}\" y=\"{label_y}\">0 ms</text>\
+         <text x=\"{right}\" y=\"{label_y}\" text-anchor=\"end\">{max:.0} ms</text>\
+         <text x=\"{axis_x}\" y=\"{top_y}\" text-anchor=\"end\">100%</text>\
+         <text x=\"{axis_x}\" y=\"{bottom}\" text-anchor=\"end\">0%</text>\n",
+        w2 = left + w + 220.0,
+        h2 = top + h + 30.0,
+        bottom = top + h,
+        right = left + w,
+        label_y = top + h + 18.0,
+        axis_x = left - 6.0,
+        top_y = top + 10.0,
+    );
+    for (i, (server, latencies)) in series.iter().enumerate() {
+        let color = PALETTE[i % PALETTE.len()];
+        let n = latencies.len() as f64;
+        let mut points = format!("{:.1},{:.1}", left, top + h);
+        for (k, latency) in latencies.iter().enumerate() {
+            let x = left + latency / max * w;
+            // Step: rise at each sample
+            let _ = write!(
+                points,
+                " {:.1},{:.1} {:.1},{:.1}",
+                x,
+                top + h - k as f64 / n * h,
+                x,
+                top + h - (k + 1) as f64 / n * h
+            );
+        }
+        let _ = writeln!(
+            svg,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>\
+             <rect x=\"{:.0}\" y=\"{:.0}\" width=\"12\" height=\"12\" fill=\"{}\"/>\
+             <text x=\"{:.0}\" y=\"{:.0}\">{}</text>",
+            points,
+            color,
+            left + w + 20.0,
+            top + i as f64 * 20.0,
+            color,
+            left + w + 38.0,
+            top + i as f64 * 20.0 + 11.0,
+            escape_html(server)
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn charts(results: &RunResults) -> String {
+    match results {
+        RunResults::Dns(results) => {
+            let mut latency: Vec<(String, f64)> = results
+                .iter()
+                .filter_map(|r| Some((r.server_address.clone(), r.latency_avg_ms?)))
+                .collect();
+            latency.sort_by(|a, b| a.1.total_cmp(&b.1));
+            let mut success: Vec<(String, f64)> = results
+                .iter()
+                .map(|r| (r.server_address.clone(), r.success_percent))
+                .collect();
+            success.sort_by(|a, b| b.1.total_cmp(&a.1));
+            bar_chart("Median latency", " ms", &latency, None)
+                + &cdf_chart(results)
+                + &bar_chart("Success rate", "%", &success, Some(100.0))
+        }
+        RunResults::Speed(results) => {
+            let mut bandwidth: Vec<(String, f64)> = results
+                .iter()
+                .filter(|r| r.query_successful)
+                .map(|r| (r.server_address.clone(), r.bandwidth_mbps))
+                .collect();
+            bandwidth.sort_by(|a, b| b.1.total_cmp(&a.1));
+            bar_chart("Bandwidth", " Mbps", &bandwidth, None)
+        }
+    }
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem;color:#111}\
+table{border-collapse:collapse;font-size:14px}th,td{border:1px solid #ddd;padding:4px 8px;text-align:left}\
+th{background:#f3f4f6}dl{display:grid;grid-template-columns:max-content auto;gap:2px 12px}\
+dt{font-weight:600}dd{margin:0}svg{font-size:12px;display:block;margin-bottom:1rem}";
+
+// One file with inline CSS and SVG, so it opens anywhere without network access.
+fn html(run: &ReportRun) -> String {
+    let heading = escape_html(&run.heading());
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n<dl>\n",
+        heading, STYLE, heading
+    );
+    for (key, value) in metadata(run) {
+        let _ = writeln!(
+            out,
+            "<dt>{}</dt><dd>{}</dd>",
+            escape_html(&key),
+            escape_html(&value)
+        );
+    }
+    out.push_str("</dl>\n");
+    out.push_str(&charts(&run.results));
+    let (headers, rows) = summary_table(&run.results);
+    out.push_str("<h2>Results</h2>\n<table>\n<tr>");
+    for h in headers {
+        let _ = write!(out, "<th>{}</th>", escape_html(h));
+    }
+    out.push_str("</tr>\n");
+    for row in rows {
+        out.push_str("<tr>");
+        for cell in row {
+            let _ = write!(out, "<td>{}</td>", escape_html(&cell));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns_tester::{DnsError, Sample};
+
+    // A DNS export from before samples, bootstrap and TLS details existed
+    const BASELINE_EXPORT: &str = r#"[
+  {
+    "server_address": "1.1.1.1",
+    "resolution_time_ms": 12,
+    "query_successful": true,
+    "latency_avg_ms": 12.5,
+    "jitter_avg_ms": 1.25,
+    "success_percent": 100.0,
+    "dnssec_validated": false,
+    "dnssec_enabled": false,
+    "ipv4_ips": ["93.184.216.34"],
+    "ipv6_ips": [],
+    "error_msg": null,
+    "avg_time": 12.5
+  },
+  {
+    "server_address": "9.9.9.9",
+    "resolution_time_ms": null,
+    "query_successful": false,
+    "latency_avg_ms": null,
+    "jitter_avg_ms": null,
+    "success_percent": 0.0,
+    "dnssec_validated": false,
+    "dnssec_enabled": false,
+    "ipv4_ips": [],
+    "ipv6_ips": [],
+    "error_msg": "request timed out",
+    "avg_time": null
+  }
+]"#;
+
+    fn dns_run() -> ReportRun {
+        let mut fast = DnsTestResult::failure(
+            "tls://1.1.1.1@one.one.one.one".to_string(),
+            true,
+            DnsError::new(DnsErrorKind::Other, ""),
+        );
+        fast.provider = Some("Cloudflare".to_string());
+        fast.query_successful = true;
+        fast.error_kind = None;
+        fast.error_msg = None;
+        fast.success_percent = 100.0;
+        fast.latency_avg_ms = Some(12.5);
+        fast.jitter_avg_ms = Some(1.0);
+        fast.dnssec_validated = true;
+        fast.dnssec_secure = true;
+        fast.ipv4_ips = vec!["192.0.2.1".to_string(), "192.0.2.2".to_string()];
+        fast.samples = [12.0, 12.5, 13.0]
+            .iter()
+            .map(|&latency_ms| Sample {
+                latency_ms,
+                ok: true,
+                error: None,
+            })
+            .collect();
+        let mut broken = DnsTestResult::failure(
+            "<script>|evil".to_string(),
+            false,
+            DnsError::new(DnsErrorKind::ServFail, "server said \"no\", twice & more"),
+        );
+        broken.error_counts = BTreeMap::from([(DnsErrorKind::ServFail, 2)]);
+        ReportRun {
+            title: None,
+            id: Some(7),
+            started_at: Some(1_704_067_200_000),
+            params: serde_json::json!({ "domain_or_ip": "example.com", "servers": [] }),
+            environment: serde_json::json!({ "os": "linux" }),
+            results: RunResults::Dns(vec![broken, fast]),
+        }
+    }
+
+    fn speed_run() -> ReportRun {
+        ReportRun {
+            title: Some("Mirror check".to_string()),
+            id: None,
+            started_at: None,
+            params: Value::Null,
+            environment: Value::Null,
+            results: RunResults::Speed(vec![DownloadTestResult {
+                server_address: "8.8.8.8".to_string(),
+                provider: Some("Google".to_string()),
+                resolved_ip: Some("192.0.2.80".to_string()),
+                duration_ms: 1000,
+                bytes_read: 2_500_000,
+                bandwidth_mbps: 20.0,
+                query_successful: true,
+                http_status: Some(200),
+                error_msg: None,
+            }]),
+        }
+    }
+
+    fn dns_results(run: &ReportRun) -> &[DnsTestResult] {
+        match &run.results {
+            RunResults::Dns(results) => results,
+            RunResults::Speed(_) => panic!("expected DNS results"),
+        }
+    }
+
+    #[test]
+    fn csv_quotes_cells_and_adds_error_columns() {
+        let csv = render(&dns_run(), ReportFormat::Csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "server_address,provider,query_successful,success_percent,latency_avg_ms,\
+             jitter_avg_ms,resolution_time_ms,avg_time,dnssec_validated,ipv4_ips,ipv6_ips,\
+             error_msg,error_kind,error_counts"
+        );
+        assert_eq!(
+            lines[1],
+            "<script>|evil,,false,0,,,,,false,,,\"server said \"\"no\"\", twice & more\",\
+             SERVFAIL,SERVFAIL 2"
+        );
+        assert_eq!(
+            lines[2],
+            "tls://1.1.1.1@one.one.one.one,Cloudflare,true,100,12.5,1,,,true,\
+             192.0.2.1;192.0.2.2,,,,"
+        );
+
+        let csv = render(&speed_run(), ReportFormat::Csv).unwrap();
+        assert_eq!(
+            csv,
+            "server_address,provider,resolved_ip,query_successful,http_status,duration_ms,\
+             bytes_read,bandwidth_mbps,error_msg\n\
+             8.8.8.8,Google,192.0.2.80,true,200,1000,2500000,20,\n"
+        );
+    }
+
+    #[test]
+    fn json_reports_read_back() {
+        let run = dns_run();
+        let json = render(&run, ReportFormat::Json).unwrap();
+        let read = parse_run(&json).unwrap();
+        assert_eq!(
+            (read.id, read.started_at),
+            (Some(7), Some(1_704_067_200_000))
+        );
+        assert_eq!(read.params, run.params);
+        assert_eq!(read.environment, run.environment);
+        let results = dns_results(&read);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].samples.len(), 3);
+        assert_eq!(results[0].error_counts, dns_results(&run)[0].error_counts);
+
+        let read = parse_run(&render(&speed_run(), ReportFormat::Json).unwrap()).unwrap();
+        assert_eq!(read.title.as_deref(), Some("Mirror check"));
+        assert!(matches!(&read.results, RunResults::Speed(r) if r[0].bandwidth_mbps == 20.0));
+    }
+
+    #[test]
+    fn stored_runs_read_back() {
+        let run = dns_run();
+        let detail = serde_json::json!({
+            "id": 7,
+            "kind": "dns",
+            "source": "manual",
+            "started_at": 1_704_067_200_000i64,
+            "finished_at": 1_704_067_205_000i64,
+            "params": run.params,
+            "server_count": 2,
+            "success_count": 1,
+            "environment": run.environment,
+            "results": dns_results(&run),
+        });
+        let detail: RunDetail = serde_json::from_value(detail).unwrap();
+        let read = ReportRun::from_detail(detail).unwrap();
+        assert_eq!(read.id, Some(7));
+        assert_eq!(read.heading(), "DNS benchmark: example.com");
+        assert_eq!(
+            dns_results(&read)[1].server_address,
+            "tls://1.1.1.1@one.one.one.one"
+        );
+
+        let bad = serde_json::json!({ "kind": "dns", "results": [{ "server_address": 1 }] });
+        assert!(parse_run(&bad.to_string()).is_err());
+    }
+
+    #[test]
+    fn markdown_lists_metadata_and_escapes_pipes() {
+        let md = render(&dns_run(), ReportFormat::Markdown).unwrap();
+        assert!(md.starts_with("# DNS benchmark: example.com\n\n- **Run**: #7\n"));
+        assert!(md.contains("- **Started**: 2024-01-01 00:00:00 UTC\n"));
+        assert!(md.contains("- **os**: linux\n"));
+        // empty arrays are not listed
+        assert!(!md.contains("servers"));
+        let rows: Vec<&str> = md.lines().filter(|l| l.starts_with('|')).collect();
+        assert_eq!(rows.len(), 4);
+        // the working server sorts first
+        assert_eq!(
+            rows[2],
+            "| tls://1.1.1.1@one.one.one.one | Cloudflare | 12.5 | 13.0 | 1.0 | 100% | secure \
+             | 192.0.2.1, 192.0.2.2 |  |  |"
+        );
+        assert!(rows[3].starts_with("| <script>\\|evil |"), "{}", rows[3]);
+        assert!(rows[3].ends_with("| SERVFAIL 2 | server said \"no\", twice & more |"));
+    }
+
+    #[test]
+    fn html_escapes_addresses_and_errors() {
+        let html = render(&dns_run(), ReportFormat::Html).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>DNS benchmark: example.com</title>"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("<td>&lt;script&gt;|evil</td>"));
+        assert!(html.contains("<td>server said &quot;no&quot;, twice &amp; more</td>"));
+        // chart labels are escaped too; the failed server only shows in the success chart
+        assert!(html.contains("<h2>Latency distribution (CDF)</h2>"));
+        assert!(html.contains("text-anchor=\"end\">&lt;script&gt;|evil</text>"));
+        assert!(html.ends_with("</table>\n</body>\n</html>\n"));
+
+        let html = render(&speed_run(), ReportFormat::Html).unwrap();
+        assert!(html.contains("<h1>Mirror check</h1>"));
+        assert!(html.contains("<h2>Bandwidth</h2>"));
+        assert!(html.contains("<td>20.00</td>"));
+    }
+
+    #[test]
+    fn parses_exports_from_before_the_new_fields() {
+        let run = parse_run(BASELINE_EXPORT).unwrap();
+        let RunResults::Dns(results) = &run.results else {
+            panic!("expected DNS results");
+        };
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].latency_avg_ms, Some(12.5));
+        assert_eq!(results[0].ipv4_ips, vec!["93.184.216.34"]);
+        assert!(results[0].samples.is_empty());
+        assert!(results[0].bootstrap_ips.is_empty());
+        assert!(!results[0].dnssec_bogus);
+        assert_eq!(results[1].error_msg.as_deref(), Some("request timed out"));
+        assert_eq!(results[1].error_kind, None);
+    }
+}
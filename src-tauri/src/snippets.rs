@@ -0,0 +1,833 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+use crate::dns_tester::bootstrap;
+use crate::dns_tester::spec::{self, ServerSpec};
+use crate::dns_tester::stamps::{encode_stamp, DnsStamp, StampProtocol};
+use crate::dns_tester::tls_hosts::TLS_HOST_MAP;
+use crate::system_dns::{self, Nameserver};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SnippetFormat {
+    Unbound,
+    Dnsmasq,
+    Coredns,
+    DnscryptProxy,
+    AdguardHome,
+    Pihole,
+    SystemdResolved,
+    AndroidPrivateDns,
+}
+
+impl SnippetFormat {
+    pub const ALL: [SnippetFormat; 8] = [
+        SnippetFormat::Unbound,
+        SnippetFormat::Dnsmasq,
+        SnippetFormat::Coredns,
+        SnippetFormat::DnscryptProxy,
+        SnippetFormat::AdguardHome,
+        SnippetFormat::Pihole,
+        SnippetFormat::SystemdResolved,
+        SnippetFormat::AndroidPrivateDns,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SnippetFormat::Unbound => "Unbound",
+            SnippetFormat::Dnsmasq => "dnsmasq",
+            SnippetFormat::Coredns => "CoreDNS",
+            SnippetFormat::DnscryptProxy => "dnscrypt-proxy",
+            SnippetFormat::AdguardHome => "AdGuard Home",
+            SnippetFormat::Pihole => "Pi-hole",
+            SnippetFormat::SystemdResolved => "systemd-resolved",
+            SnippetFormat::AndroidPrivateDns => "Android Private DNS",
+        }
+    }
+
+    // Where the snippet goes
+    fn target(self) -> &'static str {
+        match self {
+            SnippetFormat::Unbound => "/etc/unbound/unbound.conf.d/resoboost.conf",
+            SnippetFormat::Dnsmasq => "/etc/dnsmasq.d/resoboost.conf",
+            SnippetFormat::Coredns => "Corefile",
+            SnippetFormat::DnscryptProxy => "dnscrypt-proxy.toml",
+            SnippetFormat::AdguardHome => "AdGuardHome.yaml",
+            SnippetFormat::Pihole => "/etc/pihole/pihole.toml",
+            SnippetFormat::SystemdResolved => "/etc/systemd/resolved.conf.d/resoboost.conf",
+            SnippetFormat::AndroidPrivateDns => "Settings > Network & internet > Private DNS",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SkippedServer {
+    pub server: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snippet {
+    pub format: SnippetFormat,
+    pub target: String,
+    // Empty when none of the servers fit the format
+    pub content: String,
+    pub skipped: Vec<SkippedServer>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    Plain,
+    Dot,
+    Doh,
+    Doq,
+    DnsCrypt,
+}
+
+// A server entry with everything the formats need spelled out.
+struct Endpoint {
+    server: String,
+    transport: Transport,
+    // IP or hostname as written in the entry
+    host: String,
+    port: u16,
+    // Certificate name: from the entry, the hostname, or TLS_HOST_MAP for bare IPs
+    tls_name: Option<String>,
+    // The host's IP, or its bootstrap answer (empty when that failed)
+    ips: Vec<IpAddr>,
+    // DoH path plus query
+    path: String,
+    h3: bool,
+    hashes: Vec<[u8; 32]>,
+    stamp: Option<String>,
+}
+
+impl Endpoint {
+    fn default_port(&self) -> u16 {
+        match self.transport {
+            Transport::Plain => 53,
+            Transport::Dot | Transport::Doq => 853,
+            Transport::Doh | Transport::DnsCrypt => 443,
+        }
+    }
+
+    // "name[:port]" for URLs; the certificate name when known, else the host
+    fn authority(&self) -> String {
+        let name = self.tls_name.as_deref().unwrap_or(&self.host);
+        host_port(name, self.port, self.default_port())
+    }
+
+    fn doh_url(&self) -> String {
+        let scheme = if self.h3 { "h3" } else { "https" };
+        format!("{}://{}{}", scheme, self.authority(), self.path)
+    }
+
+    fn skip(&self, reason: impl Into<String>) -> SkippedServer {
+        SkippedServer {
+            server: self.server.clone(),
+            reason: reason.into(),
+        }
+    }
+}
+
+// Brackets IPv6 only when a port follows.
+fn host_port(host: &str, port: u16, default_port: u16) -> String {
+    match (port == default_port, host.contains(':')) {
+        (true, _) => host.to_string(),
+        (false, true) => format!("[{}]:{}", host, port),
+        (false, false) => format!("{}:{}", host, port),
+    }
+}
+
+async fn mapped_name(ip: &IpAddr) -> Option<String> {
+    TLS_HOST_MAP
+        .get()?
+        .read()
+        .await
+        .get(&ip.to_string())
+        .cloned()
+}
+
+async fn endpoint(server: &str) -> Result<Endpoint, String> {
+    let parsed = spec::parse(server).map_err(|e| format!("{}: {}", server, e))?;
+    let mut hashes = parsed.pins.tbs.clone();
+    let (transport, host, port, tls_name, path, h3, stamp) = match parsed.spec {
+        ServerSpec::Udp { host, port } => (
+            Transport::Plain,
+            host,
+            port,
+            None,
+            String::new(),
+            false,
+            None,
+        ),
+        ServerSpec::Dot {
+            host,
+            port,
+            tls_name,
+        } => (
+            Transport::Dot,
+            host,
+            port,
+            tls_name,
+            String::new(),
+            false,
+            None,
+        ),
+        ServerSpec::Doq {
+            host,
+            port,
+            tls_name,
+        } => (
+            Transport::Doq,
+            host,
+            port,
+            tls_name,
+            String::new(),
+            false,
+            None,
+        ),
+        ServerSpec::Doh {
+            host,
+            port,
+            endpoint,
+            h3,
+        } => (Transport::Doh, host, port, None, endpoint, h3, None),
+        ServerSpec::Stamp { stamp, decoded } => {
+            let (transport, default_port) = match decoded.protocol {
+                StampProtocol::Plain => (Transport::Plain, 53),
+                StampProtocol::Dot => (Transport::Dot, 853),
+                StampProtocol::Doq => (Transport::Doq, 853),
+                StampProtocol::Doh => (Transport::Doh, 443),
+                StampProtocol::DnsCrypt => (Transport::DnsCrypt, 443),
+            };
+            hashes.extend(decoded.hashes.iter().copied());
+            let (hostname, host, port) = decoded.endpoint(default_port)?;
+            let tls_name = Some(hostname).filter(|h| !h.is_empty());
+            (
+                transport,
+                host,
+                port,
+                tls_name,
+                decoded.path,
+                false,
+                Some(stamp),
+            )
+        }
+        ServerSpec::System => {
+            return Err("'system' has no address to export".to_string());
+        }
+    };
+    Ok(resolve(Endpoint {
+        server: server.to_string(),
+        transport,
+        host,
+        port,
+        tls_name,
+        ips: vec![],
+        path,
+        h3,
+        hashes,
+        stamp,
+    })
+    .await)
+}
+
+// Fills in IPs and, for encrypted transports, the certificate name.
+async fn resolve(mut ep: Endpoint) -> Endpoint {
+    let encrypted = matches!(
+        ep.transport,
+        Transport::Dot | Transport::Doh | Transport::Doq
+    );
+    match ep.host.parse::<IpAddr>() {
+        Ok(ip) => {
+            if encrypted && ep.tls_name.is_none() {
+                ep.tls_name = mapped_name(&ip).await;
+            }
+            ep.ips = vec![ip];
+        }
+        Err(_) => {
+            if encrypted && ep.tls_name.is_none() {
+                ep.tls_name = Some(ep.host.clone());
+            }
+            // Formats that take names only don't need this, so a failure is not fatal
+            ep.ips = bootstrap::resolve_host(&ep.host)
+                .await
+                .map(|a| a.ips)
+                .unwrap_or_default();
+        }
+    }
+    ep
+}
+
+fn unresolved(ep: &Endpoint) -> SkippedServer {
+    ep.skip(format!("no IP address known for {}", ep.host))
+}
+
+// Unbound, CoreDNS and systemd-resolved turn TLS on for every upstream at once,
+// so DoT servers win over plain ones when both are given.
+fn tls_or_plain(endpoints: &[Endpoint]) -> Transport {
+    if endpoints.iter().any(|ep| ep.transport == Transport::Dot) {
+        Transport::Dot
+    } else {
+        Transport::Plain
+    }
+}
+
+fn unbound(endpoints: &[Endpoint]) -> (String, Vec<SkippedServer>) {
+    let wanted = tls_or_plain(endpoints);
+    let tls = wanted == Transport::Dot;
+    let mut lines = vec![];
+    let mut skipped = vec![];
+    for ep in endpoints {
+        if ep.transport != wanted {
+            skipped.push(ep.skip(match ep.transport {
+                Transport::Plain => "forward-tls-upstream applies to every forward-addr",
+                _ => "Unbound forwards over plain DNS or DNS-over-TLS only",
+            }));
+            continue;
+        }
+        if ep.ips.is_empty() {
+            skipped.push(unresolved(ep));
+        }
+        for ip in &ep.ips {
+            let mut addr = ip.to_string();
+            if ep.port != ep.default_port() {
+                addr.push_str(&format!("@{}", ep.port));
+            }
+            if let Some(name) = &ep.tls_name {
+                addr.push_str(&format!("#{}", name));
+            }
+            lines.push(format!("    forward-addr: {}", addr));
+        }
+    }
+    if lines.is_empty() {
+        return (String::new(), skipped);
+    }
+    let mut out = String::new();
+    if tls {
+        out.push_str("# DNS-over-TLS also needs tls-cert-bundle in the server: section\n");
+    }
+    out.push_str("forward-zone:\n    name: \".\"\n");
+    if tls {
+        out.push_str("    forward-tls-upstream: yes\n");
+    }
+    out.push_str(&(lines.join("\n") + "\n"));
+    (out, skipped)
+}
+
+// Plain DNS only; `render` turns (ip, port) pairs into the format's lines.
+fn plain_servers(
+    endpoints: &[Endpoint],
+    software: &str,
+    mut render: impl FnMut(&IpAddr, u16),
+) -> Vec<SkippedServer> {
+    let mut skipped = vec![];
+    for ep in endpoints {
+        if ep.transport != Transport::Plain {
+            skipped.push(ep.skip(format!("{} forwards over plain DNS only", software)));
+        } else if ep.ips.is_empty() {
+            skipped.push(unresolved(ep));
+        } else {
+            ep.ips.iter().for_each(|ip| render(ip, ep.port));
+        }
+    }
+    skipped
+}
+
+fn dnsmasq(endpoints: &[Endpoint]) -> (String, Vec<SkippedServer>) {
+    let mut lines = vec![];
+    let skipped = plain_servers(endpoints, "dnsmasq", |ip, port| {
+        lines.push(match port {
+            53 => format!("server={}", ip),
+            port => format!("server={}#{}", ip, port),
+        })
+    });
+    if lines.is_empty() {
+        return (String::new(), skipped);
+    }
+    // Ignore /etc/resolv.conf so only these servers are used
+    (format!("no-resolv\n{}\n", lines.join("\n")), skipped)
+}
+
+fn pihole(endpoints: &[Endpoint]) -> (String, Vec<SkippedServer>) {
+    let mut upstreams = vec![];
+    let skipped = plain_servers(endpoints, "Pi-hole", |ip, port| {
+        upstreams.push(match port {
+            53 => format!("\"{}\"", ip),
+            port => format!("\"{}#{}\"", ip, port),
+        })
+    });
+    if upstreams.is_empty() {
+        return (String::new(), skipped);
+    }
+    (
+        format!("[dns]\n  upstreams = [{}]\n", upstreams.join(", ")),
+        skipped,
+    )
+}
+
+fn coredns(endpoints: &[Endpoint]) -> (String, Vec<SkippedServer>) {
+    let mut targets = vec![];
+    let mut skipped = vec![];
+    // One tls_servername per forward block: the first DoT server's name
+    let mut servername: Option<Option<&str>> = None;
+    let wanted = tls_or_plain(endpoints);
+    let tls = wanted == Transport::Dot;
+    for ep in endpoints {
+        if ep.transport != wanted {
+            skipped.push(ep.skip(match ep.transport {
+                Transport::Plain => "a forward block uses one protocol",
+                _ => "CoreDNS forwards over plain DNS or DNS-over-TLS only",
+            }));
+            continue;
+        }
+        if tls {
+            let name = ep.tls_name.as_deref();
+            match servername {
+                Some(chosen) if chosen != name => {
+                    skipped.push(ep.skip("CoreDNS takes one tls_servername per forward block"));
+                    continue;
+                }
+                _ => servername = Some(name),
+            }
+        }
+        if ep.ips.is_empty() {
+            skipped.push(unresolved(ep));
+        }
+        for ip in &ep.ips {
+            let addr = host_port(&ip.to_string(), ep.port, ep.default_port());
+            targets.push(if tls { format!("tls://{}", addr) } else { addr });
+        }
+    }
+    if targets.is_empty() {
+        return (String::new(), skipped);
+    }
+    let forward = match servername.flatten() {
+        Some(name) => format!(
+            "    forward . {} {{\n        tls_servername {}\n    }}\n",
+            targets.join(" "),
+            name
+        ),
+        None => format!("    forward . {}\n", targets.join(" ")),
+    };
+    (format!(". {{\n{}    cache\n}}\n", forward), skipped)
+}
+
+// Lowercase letters, digits and dashes, unique within the file.
+fn stamp_name(ep: &Endpoint, taken: &mut Vec<String>) -> String {
+    let base: String = ep
+        .tls_name
+        .as_deref()
+        .unwrap_or(&ep.host)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let mut name = base.clone();
+    let mut n = 2;
+    while taken.contains(&name) {
+        name = format!("{}-{}", base, n);
+        n += 1;
+    }
+    taken.push(name.clone());
+    name
+}
+
+fn dnscrypt_proxy(endpoints: &[Endpoint]) -> (String, Vec<SkippedServer>) {
+    let mut names = vec![];
+    let mut entries = vec![];
+    let mut skipped = vec![];
+    for ep in endpoints {
+        let stamp = match (ep.transport, &ep.stamp) {
+            (Transport::Doh | Transport::DnsCrypt, Some(stamp)) => stamp.clone(),
+            (Transport::Doh, None) => {
+                let hostname = ep.authority();
+                let addr = ep
+                    .ips
+                    .first()
+                    .map(|ip| host_port(&ip.to_string(), ep.port, ep.default_port()))
+                    .unwrap_or_default();
+                let stamp = DnsStamp {
+                    protocol: StampProtocol::Doh,
+                    dnssec: false,
+                    no_logs: false,
+                    no_filter: false,
+                    addr,
+                    hashes: ep.hashes.clone(),
+                    hostname,
+                    path: ep.path.clone(),
+                };
+                match encode_stamp(&stamp) {
+                    Ok(stamp) => stamp,
+                    Err(e) => {
+                        skipped.push(ep.skip(e));
+                        continue;
+                    }
+                }
+            }
+            _ => {
+                skipped
+                    .push(ep.skip("dnscrypt-proxy forwards over DNSCrypt or DNS-over-HTTPS only"));
+                continue;
+            }
+        };
+        let name = stamp_name(ep, &mut names);
+        entries.push(format!("  [static.'{}']\n  stamp = '{}'\n", name, stamp));
+    }
+    if entries.is_empty() {
+        return (String::new(), skipped);
+    }
+    let quoted: Vec<String> = names.iter().map(|n| format!("'{}'", n)).collect();
+    (
+        format!(
+            "server_names = [{}]\n\n[static]\n{}",
+            quoted.join(", "),
+            entries.join("\n")
+        ),
+        skipped,
+    )
+}
+
+fn adguard_home(endpoints: &[Endpoint]) -> (String, Vec<SkippedServer>) {
+    let lines: Vec<String> = endpoints
+        .iter()
+        .map(|ep| match ep.transport {
+            Transport::Plain => host_port(&ep.host, ep.port, 53),
+            Transport::Dot => format!("tls://{}", ep.authority()),
+            Transport::Doq => format!("quic://{}", ep.authority()),
+            Transport::Doh => ep.doh_url(),
+            Transport::DnsCrypt => ep.stamp.clone().unwrap_or_default(),
+        })
+        .map(|line| format!("    - {}", line))
+        .collect();
+    if lines.is_empty() {
+        return (String::new(), vec![]);
+    }
+    (
+        format!(
+            "# Or paste the entries into Settings > DNS settings > Upstream DNS servers\ndns:\n  upstream_dns:\n{}\n",
+            lines.join("\n")
+        ),
+        vec![],
+    )
+}
+
+fn systemd_resolved(endpoints: &[Endpoint]) -> (String, Vec<SkippedServer>) {
+    let wanted = tls_or_plain(endpoints);
+    let tls = wanted == Transport::Dot;
+    let mut nameservers = vec![];
+    let mut skipped = vec![];
+    for ep in endpoints {
+        if ep.transport != wanted {
+            skipped.push(ep.skip(match ep.transport {
+                Transport::Plain => "DNSOverTLS=yes applies to every server",
+                _ => "systemd-resolved speaks plain DNS or DNS-over-TLS only",
+            }));
+            continue;
+        }
+        if ep.ips.is_empty() {
+            skipped.push(unresolved(ep));
+        }
+        nameservers.extend(ep.ips.iter().map(|ip| Nameserver {
+            ip: *ip,
+            port: ep.port,
+            tls: tls.then(|| ep.tls_name.clone()),
+        }));
+    }
+    if nameservers.is_empty() {
+        return (String::new(), skipped);
+    }
+    match system_dns::resolved_drop_in(&nameservers) {
+        Ok(content) => (content, skipped),
+        Err(e) => (
+            String::new(),
+            endpoints.iter().map(|ep| ep.skip(e.clone())).collect(),
+        ),
+    }
+}
+
+fn android_private_dns(endpoints: &[Endpoint]) -> (String, Vec<SkippedServer>) {
+    let mut chosen: Option<String> = None;
+    let mut skipped = vec![];
+    for ep in endpoints {
+        let reason = match (ep.transport, &ep.tls_name) {
+            (Transport::Dot, _) if ep.port != 853 => "Private DNS always uses port 853",
+            (Transport::Dot, Some(name)) if name.parse::<IpAddr>().is_err() => {
+                if chosen.is_none() {
+                    chosen = Some(name.clone());
+                    continue;
+                }
+                "Private DNS takes a single hostname"
+            }
+            (Transport::Dot, _) => "Private DNS needs the server's hostname",
+            _ => "Private DNS is DNS-over-TLS only",
+        };
+        skipped.push(ep.skip(reason));
+    }
+    (chosen.map(|name| name + "\n").unwrap_or_default(), skipped)
+}
+
+// Renders `servers` (in order of preference) for each of `formats`; no formats means all of
+// them, no servers is an error.
+pub async fn generate(
+    servers: &[String],
+    formats: &[SnippetFormat],
+) -> Result<Vec<Snippet>, String> {
+    if servers.is_empty() {
+        return Err("No servers selected".to_string());
+    }
+    let mut endpoints = vec![];
+    for server in servers {
+        endpoints.push(endpoint(server.trim()).await?);
+    }
+    let formats = if formats.is_empty() {
+        &SnippetFormat::ALL[..]
+    } else {
+        formats
+    };
+    Ok(formats
+        .iter()
+        .map(|&format| {
+            let (content, skipped) = match format {
+                SnippetFormat::Unbound => unbound(&endpoints),
+                SnippetFormat::Dnsmasq => dnsmasq(&endpoints),
+                SnippetFormat::Coredns => coredns(&endpoints),
+                SnippetFormat::DnscryptProxy => dnscrypt_proxy(&endpoints),
+                SnippetFormat::AdguardHome => adguard_home(&endpoints),
+                SnippetFormat::Pihole => pihole(&endpoints),
+                SnippetFormat::SystemdResolved => systemd_resolved(&endpoints),
+                SnippetFormat::AndroidPrivateDns => android_private_dns(&endpoints),
+            };
+            Snippet {
+                format,
+                target: format.target().to_string(),
+                content,
+                skipped,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Addresses only, so nothing needs a bootstrap lookup
+    const PLAIN: &str = "192.0.2.1";
+    const PLAIN_V6: &str = "udp://[2001:db8::1]:5353";
+    const DOT: &str = "tls://192.0.2.2@dns.example";
+    const DOT_V6: &str = "tls://[2001:db8::2]:8853@dns.example";
+    const DOQ: &str = "quic://192.0.2.4@doq.example";
+
+    fn doh() -> String {
+        encode_stamp(&DnsStamp {
+            protocol: StampProtocol::Doh,
+            dnssec: false,
+            no_logs: false,
+            no_filter: false,
+            addr: "192.0.2.3".to_string(),
+            hashes: vec![],
+            hostname: "doh.example".to_string(),
+            path: "/dns-query".to_string(),
+        })
+        .unwrap()
+    }
+
+    async fn render(servers: &[&str], format: SnippetFormat) -> (String, Vec<(String, String)>) {
+        let servers: Vec<String> = servers.iter().map(|s| s.to_string()).collect();
+        let mut snippets = generate(&servers, &[format]).await.unwrap();
+        assert_eq!(snippets.len(), 1);
+        let snippet = snippets.remove(0);
+        assert_eq!(snippet.format, format);
+        let skipped = snippet
+            .skipped
+            .into_iter()
+            .map(|s| (s.server, s.reason))
+            .collect();
+        (snippet.content, skipped)
+    }
+
+    fn skipped(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(server, reason)| (server.to_string(), reason.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn unbound_forwards_plain_or_tls_with_the_tls_name() {
+        let (content, dropped) = render(&[PLAIN, PLAIN_V6], SnippetFormat::Unbound).await;
+        assert_eq!(
+            content,
+            "forward-zone:\n    name: \".\"\n    forward-addr: 192.0.2.1\n    forward-addr: 2001:db8::1@5353\n"
+        );
+        assert!(dropped.is_empty());
+
+        let (content, dropped) = render(&[DOT, DOT_V6, PLAIN, DOQ], SnippetFormat::Unbound).await;
+        assert_eq!(
+            content,
+            "# DNS-over-TLS also needs tls-cert-bundle in the server: section\n\
+             forward-zone:\n    name: \".\"\n    forward-tls-upstream: yes\n\
+             \x20   forward-addr: 192.0.2.2#dns.example\n\
+             \x20   forward-addr: 2001:db8::2@8853#dns.example\n"
+        );
+        assert_eq!(
+            dropped,
+            skipped(&[
+                (PLAIN, "forward-tls-upstream applies to every forward-addr"),
+                (DOQ, "Unbound forwards over plain DNS or DNS-over-TLS only"),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn dnsmasq_and_pihole_take_plain_servers_with_hash_ports() {
+        let (content, dropped) = render(&[PLAIN, DOT, PLAIN_V6], SnippetFormat::Dnsmasq).await;
+        assert_eq!(
+            content,
+            "no-resolv\nserver=192.0.2.1\nserver=2001:db8::1#5353\n"
+        );
+        assert_eq!(
+            dropped,
+            skipped(&[(DOT, "dnsmasq forwards over plain DNS only")])
+        );
+
+        let (content, dropped) = render(&[PLAIN, DOT, PLAIN_V6], SnippetFormat::Pihole).await;
+        assert_eq!(
+            content,
+            "[dns]\n  upstreams = [\"192.0.2.1\", \"2001:db8::1#5353\"]\n"
+        );
+        assert_eq!(
+            dropped,
+            skipped(&[(DOT, "Pi-hole forwards over plain DNS only")])
+        );
+    }
+
+    #[tokio::test]
+    async fn coredns_brackets_ipv6_and_names_the_tls_server() {
+        let (content, _) = render(&[PLAIN, PLAIN_V6], SnippetFormat::Coredns).await;
+        assert_eq!(
+            content,
+            ". {\n    forward . 192.0.2.1 [2001:db8::1]:5353\n    cache\n}\n"
+        );
+
+        let (content, dropped) = render(&[DOT, DOT_V6, PLAIN], SnippetFormat::Coredns).await;
+        assert_eq!(
+            content,
+            ". {\n    forward . tls://192.0.2.2 tls://[2001:db8::2]:8853 {\n        \
+             tls_servername dns.example\n    }\n    cache\n}\n"
+        );
+        assert_eq!(
+            dropped,
+            skipped(&[(PLAIN, "a forward block uses one protocol")])
+        );
+    }
+
+    #[tokio::test]
+    async fn dnscrypt_proxy_lists_stamps() {
+        let doh = doh();
+        let (content, dropped) = render(&[&doh, DOT], SnippetFormat::DnscryptProxy).await;
+        assert_eq!(
+            content,
+            format!(
+                "server_names = ['doh-example']\n\n[static]\n  [static.'doh-example']\n  stamp = '{}'\n",
+                doh
+            )
+        );
+        assert_eq!(
+            dropped,
+            skipped(&[(
+                DOT,
+                "dnscrypt-proxy forwards over DNSCrypt or DNS-over-HTTPS only"
+            )])
+        );
+    }
+
+    #[tokio::test]
+    async fn adguard_home_takes_every_transport() {
+        let doh = doh();
+        let servers = [PLAIN, PLAIN_V6, DOT, DOT_V6, &doh, DOQ];
+        let (content, dropped) = render(&servers, SnippetFormat::AdguardHome).await;
+        assert_eq!(
+            content,
+            "# Or paste the entries into Settings > DNS settings > Upstream DNS servers\n\
+             dns:\n  upstream_dns:\n\
+             \x20   - 192.0.2.1\n\
+             \x20   - [2001:db8::1]:5353\n\
+             \x20   - tls://dns.example\n\
+             \x20   - tls://dns.example:8853\n\
+             \x20   - https://doh.example/dns-query\n\
+             \x20   - quic://doq.example\n"
+        );
+        assert!(dropped.is_empty());
+    }
+
+    #[tokio::test]
+    async fn systemd_resolved_uses_one_tls_mode() {
+        let header =
+            "# Written by ResoBoost. Delete this file (or revert from ResoBoost) and restart\n\
+                      # systemd-resolved to go back to the previous DNS servers.\n[Resolve]\n";
+        let (content, _) = render(&[PLAIN, PLAIN_V6], SnippetFormat::SystemdResolved).await;
+        assert_eq!(
+            content,
+            format!(
+                "{}DNS=192.0.2.1 [2001:db8::1]:5353\nDNSOverTLS=no\nDomains=~.\n",
+                header
+            )
+        );
+
+        let (content, dropped) =
+            render(&[DOT, DOT_V6, PLAIN], SnippetFormat::SystemdResolved).await;
+        assert_eq!(
+            content,
+            format!(
+                "{}DNS=192.0.2.2#dns.example [2001:db8::2]:8853#dns.example\nDNSOverTLS=yes\nDomains=~.\n",
+                header
+            )
+        );
+        assert_eq!(
+            dropped,
+            skipped(&[(PLAIN, "DNSOverTLS=yes applies to every server")])
+        );
+    }
+
+    #[tokio::test]
+    async fn android_private_dns_wants_a_hostname_on_port_853() {
+        let (content, dropped) =
+            render(&[PLAIN, DOT_V6, DOT], SnippetFormat::AndroidPrivateDns).await;
+        assert_eq!(content, "dns.example\n");
+        assert_eq!(
+            dropped,
+            skipped(&[
+                (PLAIN, "Private DNS is DNS-over-TLS only"),
+                (DOT_V6, "Private DNS always uses port 853"),
+            ])
+        );
+
+        let (content, _) = render(&[PLAIN], SnippetFormat::AndroidPrivateDns).await;
+        assert!(content.is_empty());
+    }
+
+    #[tokio::test]
+    async fn formats_default_to_all_and_servers_are_required() {
+        let all = generate(&[PLAIN.to_string()], &[]).await.unwrap();
+        let formats: Vec<SnippetFormat> = all.iter().map(|s| s.format).collect();
+        assert_eq!(formats, SnippetFormat::ALL);
+        assert_eq!(all[0].target, "/etc/unbound/unbound.conf.d/resoboost.conf");
+
+        assert_eq!(
+            generate(&[], &[SnippetFormat::Unbound]).await.unwrap_err(),
+            "No servers selected"
+        );
+        assert_eq!(
+            generate(&["system".to_string()], &[]).await.unwrap_err(),
+            "'system' has no address to export"
+        );
+    }
+}
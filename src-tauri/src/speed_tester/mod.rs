@@ -1,7 +1,11 @@
-use crate::dns_tester::{build_resolver_for_server, get_servers};
+use crate::dns_tester::bootstrap::BootstrapCache;
+use crate::dns_tester::{build_resolver_for_server, get_servers, servers};
+use crate::exporter;
+use crate::history;
 use futures::{stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use std::time::Instant;
 use tokio::time::timeout;
 use tracing::info;
@@ -11,6 +15,8 @@ use tokio::runtime::Builder as TokioRtBuilder; // isolated DNS resolve runtime w
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DownloadTestResult {
     pub server_address: String,
+    #[serde(default)]
+    pub provider: Option<String>,
     pub resolved_ip: Option<String>,
     pub duration_ms: u128,
     pub bytes_read: u64,
@@ -20,7 +26,7 @@ pub struct DownloadTestResult {
     pub error_msg: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct DownloadSpeedArgs {
     pub url: String,
     #[serde(alias = "durationSecs")] 
@@ -31,8 +37,18 @@ pub struct DownloadSpeedArgs {
     pub custom_servers: Option<Vec<String>>,
 }
 
-#[tauri::command]
+// Runs a speed test and stores it in the history database.
 pub async fn perform_download_speed_test(args: DownloadSpeedArgs) -> Vec<DownloadTestResult> {
+    let started_at = history::now_ms();
+    let params = serde_json::to_value(&args).unwrap_or_default();
+    let url = args.url.clone();
+    let results = run_download_speed_test(args).await;
+    history::record_speed_run(started_at, params, &results).await;
+    exporter::observe_speed(&url, &results).await;
+    results
+}
+
+pub async fn run_download_speed_test(args: DownloadSpeedArgs) -> Vec<DownloadTestResult> {
     let test_duration = args.duration_secs.unwrap_or(10); // default 10s
     let timeout = args.timeout_secs.unwrap_or(15).max(test_duration + 5);
 
@@ -41,6 +57,7 @@ pub async fn perform_download_speed_test(args: DownloadSpeedArgs) -> Vec<Downloa
         Err(e) => {
             return vec![DownloadTestResult {
                 server_address: "invalid_url".to_string(),
+                provider: None,
                 resolved_ip: None,
                 duration_ms: 0,
                 bytes_read: 0,
@@ -56,6 +73,7 @@ pub async fn perform_download_speed_test(args: DownloadSpeedArgs) -> Vec<Downloa
     if scheme != "http" && scheme != "https" {
         return vec![DownloadTestResult {
             server_address: "unsupported_scheme".to_string(),
+            provider: None,
             resolved_ip: None,
             duration_ms: 0,
             bytes_read: 0,
@@ -71,6 +89,7 @@ pub async fn perform_download_speed_test(args: DownloadSpeedArgs) -> Vec<Downloa
         None => {
             return vec![DownloadTestResult {
                 server_address: "invalid_url".to_string(),
+                provider: None,
                 resolved_ip: None,
                 duration_ms: 0,
                 bytes_read: 0,
@@ -93,17 +112,25 @@ pub async fn perform_download_speed_test(args: DownloadSpeedArgs) -> Vec<Downloa
     if servers_list.len() > MAX_SERVERS {
         servers_list.truncate(MAX_SERVERS);
     }
+    let bootstrap_cache = Arc::new(BootstrapCache::default());
 
     // Process servers with bounded concurrency without extra task spawning.
     const CONCURRENCY: usize = 6;
-    stream::iter(servers_list.into_iter().map(|server| {
+    let providers = servers::provider_map().await;
+    let mut results: Vec<DownloadTestResult> = stream::iter(servers_list.into_iter().map(|server| {
         let url_clone = args.url.clone();
         let host_clone = host.clone();
-        async move { download_via_dns_server(&server, &host_clone, port, &url_clone, test_duration, timeout).await }
+        let cache = bootstrap_cache.clone();
+        async move { download_via_dns_server(&server, &host_clone, port, &url_clone, test_duration, timeout, cache).await }
     }))
     .buffer_unordered(CONCURRENCY)
     .collect()
-    .await
+    .await;
+
+    for r in results.iter_mut() {
+        r.provider = providers.get(&r.server_address).cloned();
+    }
+    results
 }
 
 async fn download_via_dns_server(
@@ -113,6 +140,7 @@ async fn download_via_dns_server(
     url: &str,
     test_duration_secs: u64,
     timeout_secs: u64,
+    bootstrap_cache: Arc<BootstrapCache>,
 ) -> DownloadTestResult {
     info!("Speed test via {} for {}", server_address, host);
 
@@ -120,7 +148,7 @@ async fn download_via_dns_server(
     let resolved_ip: Result<IpAddr, String> = tokio::task::spawn_blocking({
         let server = server_address.to_string();
         let host = host.to_string();
-        move || resolve_ip_in_isolated_rt(server, host, timeout_secs)
+        move || resolve_ip_in_isolated_rt(server, host, timeout_secs, &bootstrap_cache)
     })
     .await
     .unwrap_or_else(|e| Err(format!("Resolve task error: {}", e)));
@@ -130,6 +158,7 @@ async fn download_via_dns_server(
         Err(msg) => {
             return DownloadTestResult {
                 server_address: server_address.to_string(),
+                provider: None,
                 resolved_ip: None,
                 duration_ms: 0,
                 bytes_read: 0,
@@ -157,6 +186,7 @@ async fn download_via_dns_server(
         Err(e) => {
             return DownloadTestResult {
                 server_address: server_address.to_string(),
+                provider: None,
                 resolved_ip: Some(ip.to_string()),
                 duration_ms: 0,
                 bytes_read: 0,
@@ -204,6 +234,7 @@ async fn download_via_dns_server(
 
     DownloadTestResult {
         server_address: server_address.to_string(),
+        provider: None,
         resolved_ip: Some(ip.to_string()),
         duration_ms: elapsed_ms,
         bytes_read: total_bytes,
@@ -216,7 +247,12 @@ async fn download_via_dns_server(
 
 // Resolve a single IP for `host` using a resolver configured for `server_address`,
 // executing in a dedicated runtime with larger thread stack.
-fn resolve_ip_in_isolated_rt(server_address: String, host: String, timeout_secs: u64) -> Result<IpAddr, String> {
+fn resolve_ip_in_isolated_rt(
+    server_address: String,
+    host: String,
+    timeout_secs: u64,
+    bootstrap_cache: &BootstrapCache,
+) -> Result<IpAddr, String> {
     let rt = TokioRtBuilder::new_current_thread()
         .enable_all()
         .thread_stack_size(4 * 1024 * 1024)
@@ -224,9 +260,10 @@ fn resolve_ip_in_isolated_rt(server_address: String, host: String, timeout_secs:
         .map_err(|e| format!("Runtime build error: {}", e))?;
 
     rt.block_on(async move {
-        let resolver = build_resolver_for_server(&server_address, timeout_secs, true)
+        let resolver = build_resolver_for_server(&server_address, timeout_secs, true, bootstrap_cache)
             .await
-            .map_err(|e| format!("Resolver error: {}", e))?;
+            .map_err(|e| format!("Resolver error: {}", e))?
+            .resolver;
 
         match timeout(std::time::Duration::from_secs(timeout_secs), resolver.lookup_ip(&host)).await {
             Ok(Ok(lookup)) => {
@@ -0,0 +1,61 @@
+use once_cell::sync::OnceCell;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::PathBuf;
+use tracing::warn;
+
+// App data directory (set once at startup). Without it, state stays in memory only.
+static DATA_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+pub fn set_data_dir(dir: PathBuf) {
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        warn!("Could not create data dir {}: {}", dir.display(), e);
+    }
+    DATA_DIR.set(dir).ok();
+}
+
+pub fn data_file(name: &str) -> Option<PathBuf> {
+    DATA_DIR.get().map(|dir| dir.join(name))
+}
+
+// Missing files and parse errors both yield None (parse errors are logged).
+pub fn read_json<T: DeserializeOwned>(name: &str) -> Option<T> {
+    load_json(name).unwrap_or_else(|e| {
+        warn!("Ignoring {}", e);
+        None
+    })
+}
+
+// Ok(None) when the file (or the data dir) doesn't exist, Err when it exists but can't be
+// read or parsed, for callers that must not overwrite such a file with defaults.
+pub fn load_json<T: DeserializeOwned>(name: &str) -> Result<Option<T>, String> {
+    let Some(path) = data_file(name) else {
+        return Ok(None);
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("unreadable {}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| format!("unreadable {}: {}", path.display(), e))
+}
+
+// Writes to a temporary file first so a crash never leaves a half-written file behind.
+pub fn write_json<T: Serialize>(name: &str, value: &T) -> Result<(), String> {
+    let Some(path) = data_file(name) else {
+        return Ok(());
+    };
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    write_atomic(&path, json.as_bytes())
+}
+
+pub fn write_atomic(path: &std::path::Path, bytes: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, bytes).map_err(|e| format!("{}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("{}: {}", path.display(), e))
+}
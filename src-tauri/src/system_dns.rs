@@ -0,0 +1,813 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::dns_tester::spec::{self, ServerSpec};
+use crate::dns_tester::stamps::StampProtocol;
+use crate::dns_tester::tls_inspect::TlsTransport;
+use crate::dns_tester::{bootstrap, tls_name_for_ip};
+use crate::history;
+use crate::storage;
+
+const BACKUPS_FILE: &str = "dns_backups.json";
+const DROP_IN_NAME: &str = "resoboost.conf";
+// Settings the NetworkManager backend changes (and restores)
+const NM_FIELDS: [&str; 4] = [
+    "ipv4.dns",
+    "ipv4.ignore-auto-dns",
+    "ipv6.dns",
+    "ipv6.ignore-auto-dns",
+];
+// glibc ignores nameserver lines past the third
+const RESOLV_CONF_MAX: usize = 3;
+const DIFF_CONTEXT: usize = 3;
+
+fn default_drop_in_dir() -> PathBuf {
+    PathBuf::from("/etc/systemd/resolved.conf.d")
+}
+
+fn default_resolv_conf() -> PathBuf {
+    PathBuf::from("/etc/resolv.conf")
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum ApplyTarget {
+    SystemdResolved {
+        #[serde(default = "default_drop_in_dir")]
+        drop_in_dir: PathBuf,
+    },
+    NetworkManager {
+        // None picks the first active connection
+        #[serde(default)]
+        connection: Option<String>,
+    },
+    ResolvConf {
+        #[serde(default = "default_resolv_conf")]
+        path: PathBuf,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApplyRequest {
+    // Tried in order by the system; encrypted entries need systemd-resolved
+    pub servers: Vec<String>,
+    pub target: ApplyTarget,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApplyOutcome {
+    pub dry_run: bool,
+    // Unified diff of the change; empty when nothing would change
+    pub diff: String,
+    pub backup_id: Option<i64>,
+    // Commands run (or that would run) and caveats
+    pub notes: Vec<String>,
+}
+
+// What a target looked like before a change.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Previous {
+    File {
+        path: PathBuf,
+        // None when the file did not exist
+        content: Option<String>,
+        // Set when the path was a symlink (e.g. resolv.conf -> stub-resolv.conf)
+        symlink: Option<PathBuf>,
+    },
+    Connection {
+        name: String,
+        settings: Vec<(String, String)>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DnsBackup {
+    pub id: i64,
+    pub servers: Vec<String>,
+    pub target: ApplyTarget,
+    pub previous: Previous,
+}
+
+enum Change {
+    File { path: PathBuf, content: String },
+    Connection { name: String, settings: Vec<(String, String)> },
+}
+
+pub(crate) struct Nameserver {
+    pub ip: IpAddr,
+    pub port: u16,
+    // Some for DNS-over-TLS (the name may still be unknown for bare IPs)
+    pub tls: Option<Option<String>>,
+}
+
+// Oldest first; reverting walks it backwards.
+static BACKUPS: Lazy<Mutex<Vec<DnsBackup>>> =
+    Lazy::new(|| Mutex::new(storage::read_json(BACKUPS_FILE).unwrap_or_default()));
+
+pub async fn list_backups() -> Vec<DnsBackup> {
+    BACKUPS.lock().await.clone()
+}
+
+fn unsupported(server: &str) -> String {
+    format!(
+        "{}: DNS-over-HTTPS, DNS-over-QUIC and DNSCrypt can't be set system-wide; run the local proxy and apply its address with systemd-resolved instead",
+        server
+    )
+}
+
+async fn nameservers(server: &str) -> Result<Vec<Nameserver>, String> {
+    let parsed = spec::parse(server).map_err(|e| format!("{}: {}", server, e))?;
+    let (host, port, tls) = match parsed.spec {
+        ServerSpec::Udp { host, port } => (host, port, None),
+        ServerSpec::Dot {
+            host,
+            port,
+            tls_name,
+        } => (host, port, Some(tls_name)),
+        ServerSpec::Stamp { decoded, .. } => {
+            let tls = match decoded.protocol {
+                StampProtocol::Plain => false,
+                StampProtocol::Dot => true,
+                _ => return Err(unsupported(server)),
+            };
+            let (hostname, host, port) = decoded.endpoint(if tls { 853 } else { 53 })?;
+            (host, port, tls.then(|| Some(hostname).filter(|h| !h.is_empty())))
+        }
+        ServerSpec::System => {
+            return Err("'system' already is the system resolver".to_string());
+        }
+        ServerSpec::Doh { .. } | ServerSpec::Doq { .. } => return Err(unsupported(server)),
+    };
+    let (ips, tls) = match host.parse::<IpAddr>() {
+        Ok(ip) => (vec![ip], tls),
+        Err(_) => {
+            let answer = bootstrap::resolve_host(&host)
+                .await
+                .map_err(|e| format!("{}: {}", host, e))?;
+            // A hostname is its own certificate name
+            (answer.ips, tls.map(|name| name.or(Some(host))))
+        }
+    };
+    let mut resolved = vec![];
+    for ip in ips {
+        let tls = match &tls {
+            Some(None) => Some(tls_name_for_ip(&ip, TlsTransport::Dot, port, 3).await),
+            other => other.clone(),
+        };
+        resolved.push(Nameserver { ip, port, tls });
+    }
+    Ok(resolved)
+}
+
+// resolved.conf syntax: address[:port][#name], with brackets around IPv6 when a port is given.
+fn resolved_entry(ns: &Nameserver) -> String {
+    let default_port = if ns.tls.is_some() { 853 } else { 53 };
+    let mut entry = match (ns.ip, ns.port == default_port) {
+        (ip, true) => ip.to_string(),
+        (IpAddr::V4(ip), false) => format!("{}:{}", ip, ns.port),
+        (IpAddr::V6(ip), false) => format!("[{}]:{}", ip, ns.port),
+    };
+    if let Some(Some(name)) = &ns.tls {
+        entry.push('#');
+        entry.push_str(name);
+    }
+    entry
+}
+
+pub(crate) fn resolved_drop_in(nameservers: &[Nameserver]) -> Result<String, String> {
+    let tls = nameservers.iter().filter(|ns| ns.tls.is_some()).count();
+    if tls > 0 && tls < nameservers.len() {
+        // DNSOverTLS is a global switch
+        return Err("systemd-resolved can't mix plain and DNS-over-TLS servers".to_string());
+    }
+    let entries: Vec<String> = nameservers.iter().map(resolved_entry).collect();
+    Ok(format!(
+        "# Written by ResoBoost. Delete this file (or revert from ResoBoost) and restart\n\
+         # systemd-resolved to go back to the previous DNS servers.\n\
+         [Resolve]\nDNS={}\nDNSOverTLS={}\nDomains=~.\n",
+        entries.join(" "),
+        if tls > 0 { "yes" } else { "no" }
+    ))
+}
+
+fn plain_only(nameservers: &[Nameserver], backend: &str) -> Result<(), String> {
+    match nameservers.iter().find(|ns| ns.tls.is_some() || ns.port != 53) {
+        Some(ns) => Err(format!(
+            "{} only takes plain DNS on port 53 ({}:{} is not); use systemd-resolved instead",
+            backend, ns.ip, ns.port
+        )),
+        None => Ok(()),
+    }
+}
+
+// Replaces the nameserver lines and keeps search/options/comments where they were.
+fn resolv_conf(existing: &str, nameservers: &[Nameserver]) -> String {
+    const HEADER: &str = "# nameservers set by ResoBoost";
+    let block: Vec<String> = std::iter::once(HEADER.to_string())
+        .chain(nameservers.iter().map(|ns| format!("nameserver {}", ns.ip)))
+        .collect();
+    let mut lines = vec![];
+    let mut inserted = false;
+    for line in existing.lines() {
+        let is_nameserver = line.split_whitespace().next() == Some("nameserver");
+        if is_nameserver && !inserted {
+            lines.extend(block.iter().cloned());
+            inserted = true;
+        }
+        if !is_nameserver && line != HEADER {
+            lines.push(line.to_string());
+        }
+    }
+    if !inserted {
+        lines.extend(block);
+    }
+    lines.join("\n") + "\n"
+}
+
+async fn run(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("{}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// nmcli's terse output escapes ':' and '\'.
+fn nm_unescape(value: &str) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+async fn active_connection() -> Result<String, String> {
+    let output = run("nmcli", &["-t", "-f", "NAME,TYPE", "connection", "show", "--active"]).await?;
+    output
+        .lines()
+        .filter_map(|line| line.rsplit_once(':'))
+        .find(|(_, kind)| *kind != "loopback")
+        .map(|(name, _)| nm_unescape(name))
+        .ok_or_else(|| "No active NetworkManager connection".to_string())
+}
+
+async fn nm_settings(connection: &str) -> Result<Vec<(String, String)>, String> {
+    let mut settings = vec![];
+    for field in NM_FIELDS {
+        let value = run("nmcli", &["-g", field, "connection", "show", connection]).await?;
+        settings.push((field.to_string(), nm_unescape(value.trim())));
+    }
+    Ok(settings)
+}
+
+fn settings_text(settings: &[(String, String)]) -> String {
+    settings
+        .iter()
+        .map(|(field, value)| format!("{}: {}\n", field, value))
+        .collect()
+}
+
+fn read_previous(path: &Path) -> Result<Previous, String> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    Ok(Previous::File {
+        path: path.to_path_buf(),
+        content,
+        symlink: std::fs::read_link(path).ok(),
+    })
+}
+
+async fn plan(
+    target: &ApplyTarget,
+    nameservers: &[Nameserver],
+) -> Result<(Previous, Change, Vec<String>), String> {
+    let mut notes = vec![];
+    let (previous, change) = match target {
+        ApplyTarget::SystemdResolved { drop_in_dir } => {
+            let path = drop_in_dir.join(DROP_IN_NAME);
+            let content = resolved_drop_in(nameservers)?;
+            notes.push("Runs: systemctl restart systemd-resolved".to_string());
+            (read_previous(&path)?, Change::File { path, content })
+        }
+        ApplyTarget::NetworkManager { connection } => {
+            plain_only(nameservers, "NetworkManager")?;
+            let name = match connection {
+                Some(name) => name.clone(),
+                None => active_connection().await?,
+            };
+            let join = |v4: bool| {
+                nameservers
+                    .iter()
+                    .filter(|ns| ns.ip.is_ipv4() == v4)
+                    .map(|ns| ns.ip.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            // Ignore DHCP/RA servers on both families so they don't take over
+            let settings = vec![
+                ("ipv4.dns".to_string(), join(true)),
+                ("ipv4.ignore-auto-dns".to_string(), "yes".to_string()),
+                ("ipv6.dns".to_string(), join(false)),
+                ("ipv6.ignore-auto-dns".to_string(), "yes".to_string()),
+            ];
+            notes.push(format!("Runs: nmcli connection up '{}'", name));
+            let previous = Previous::Connection {
+                settings: nm_settings(&name).await?,
+                name: name.clone(),
+            };
+            (previous, Change::Connection { name, settings })
+        }
+        ApplyTarget::ResolvConf { path } => {
+            plain_only(nameservers, "resolv.conf")?;
+            let previous = read_previous(path)?;
+            let existing = match &previous {
+                Previous::File { content, symlink, .. } => {
+                    if let Some(link) = symlink {
+                        notes.push(format!(
+                            "{} links to {}; it becomes a regular file (revert restores the link), and the service that owns it may overwrite it",
+                            path.display(),
+                            link.display()
+                        ));
+                    }
+                    content.clone().unwrap_or_default()
+                }
+                Previous::Connection { .. } => String::new(),
+            };
+            if nameservers.len() > RESOLV_CONF_MAX {
+                notes.push(format!(
+                    "Only the first {} nameservers are used",
+                    RESOLV_CONF_MAX
+                ));
+            }
+            let content = resolv_conf(&existing, nameservers);
+            (previous, Change::File { path: path.clone(), content })
+        }
+    };
+    Ok((previous, change, notes))
+}
+
+fn diff(previous: &Previous, change: &Change) -> String {
+    match (previous, change) {
+        (Previous::File { content, .. }, Change::File { path, content: new }) => unified_diff(
+            &path.display().to_string(),
+            content.as_deref().unwrap_or(""),
+            new,
+        ),
+        (Previous::Connection { settings, .. }, Change::Connection { name, settings: new }) => {
+            unified_diff(
+                &format!("nmcli connection '{}'", name),
+                &settings_text(settings),
+                &settings_text(new),
+            )
+        }
+        _ => String::new(),
+    }
+}
+
+// Restart or re-activate whatever reads the changed setting.
+async fn activate(target: &ApplyTarget, connection: Option<&str>) -> Result<(), String> {
+    match (target, connection) {
+        (ApplyTarget::SystemdResolved { .. }, _) => {
+            run("systemctl", &["restart", "systemd-resolved"]).await?;
+        }
+        (ApplyTarget::NetworkManager { .. }, Some(name)) => {
+            run("nmcli", &["connection", "up", name]).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+async fn write_change(change: &Change) -> Result<(), String> {
+    match change {
+        Change::File { path, content } => storage::write_atomic(path, content.as_bytes()),
+        Change::Connection { name, settings } => nm_modify(name, settings).await,
+    }
+}
+
+async fn nm_modify(name: &str, settings: &[(String, String)]) -> Result<(), String> {
+    let mut args = vec!["connection", "modify", name];
+    for (field, value) in settings {
+        args.push(field);
+        args.push(value);
+    }
+    run("nmcli", &args).await.map(|_| ())
+}
+
+#[cfg(unix)]
+fn relink(link: &Path, path: &Path) -> Result<(), String> {
+    std::os::unix::fs::symlink(link, path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(not(unix))]
+fn relink(_link: &Path, path: &Path) -> Result<(), String> {
+    Err(format!("{}: symlinks can't be restored here", path.display()))
+}
+
+async fn restore(backup: &DnsBackup) -> Result<(), String> {
+    let connection = match &backup.previous {
+        Previous::File {
+            path,
+            content,
+            symlink,
+        } => {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(format!("{}: {}", path.display(), e));
+                }
+                _ => {}
+            }
+            match (symlink, content) {
+                (Some(link), _) => relink(link, path)?,
+                (None, Some(content)) => storage::write_atomic(path, content.as_bytes())?,
+                (None, None) => {}
+            }
+            None
+        }
+        Previous::Connection { name, settings } => {
+            nm_modify(name, settings).await?;
+            Some(name.as_str())
+        }
+    };
+    activate(&backup.target, connection).await
+}
+
+// Writes the servers into the chosen system configuration after recording a backup.
+// With dry_run, only reports the diff.
+pub async fn apply(request: ApplyRequest) -> Result<ApplyOutcome, String> {
+    if !cfg!(target_os = "linux") {
+        return Err("Applying DNS settings is only supported on Linux".to_string());
+    }
+    if request.servers.is_empty() {
+        return Err("No servers to apply".to_string());
+    }
+    let mut resolved = vec![];
+    for server in &request.servers {
+        resolved.extend(nameservers(server.trim()).await?);
+    }
+    // Backups outlive the working directory
+    let absolute = |path: &PathBuf| {
+        std::path::absolute(path).map_err(|e| format!("{}: {}", path.display(), e))
+    };
+    let target = match request.target {
+        ApplyTarget::SystemdResolved { drop_in_dir } => ApplyTarget::SystemdResolved {
+            drop_in_dir: absolute(&drop_in_dir)?,
+        },
+        ApplyTarget::ResolvConf { path } => ApplyTarget::ResolvConf {
+            path: absolute(&path)?,
+        },
+        other => other,
+    };
+    // Serializes changes so each backup sees the previous change's result
+    let mut backups = BACKUPS.lock().await;
+    let (previous, change, notes) = plan(&target, &resolved).await?;
+    let diff = diff(&previous, &change);
+    if request.dry_run || diff.is_empty() {
+        return Ok(ApplyOutcome {
+            dry_run: request.dry_run,
+            diff,
+            backup_id: None,
+            notes,
+        });
+    }
+
+    let id = history::now_ms().max(backups.last().map_or(0, |b| b.id + 1));
+    backups.push(DnsBackup {
+        id,
+        servers: request.servers.clone(),
+        target: target.clone(),
+        previous,
+    });
+    storage::write_json(BACKUPS_FILE, &*backups)?;
+    if let Err(e) = write_change(&change).await {
+        backups.pop();
+        storage::write_json(BACKUPS_FILE, &*backups)?;
+        return Err(e);
+    }
+    let connection = match &change {
+        Change::Connection { name, .. } => Some(name.as_str()),
+        Change::File { .. } => None,
+    };
+    // The change is on disk and backed up; a failed restart is reported but revertable
+    activate(&target, connection).await?;
+    info!("Applied DNS servers {:?} (backup {})", request.servers, id);
+    Ok(ApplyOutcome {
+        dry_run: false,
+        diff,
+        backup_id: Some(id),
+        notes,
+    })
+}
+
+// Undoes every recorded change, newest first. Returns the servers of each undone change.
+pub async fn revert() -> Result<Vec<String>, String> {
+    let mut backups = BACKUPS.lock().await;
+    let mut reverted = vec![];
+    while let Some(backup) = backups.last() {
+        if let Err(e) = restore(backup).await {
+            storage::write_json(BACKUPS_FILE, &*backups)?;
+            return Err(format!(
+                "{} (after reverting {} change(s))",
+                e,
+                reverted.len()
+            ));
+        }
+        reverted.push(backup.servers.join(", "));
+        backups.pop();
+    }
+    storage::write_json(BACKUPS_FILE, &*backups)?;
+    Ok(reverted)
+}
+
+// Line diff by longest common subsequence; config files are small.
+fn diff_lines<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<(char, &'a str)> {
+    let (n, m) = (a.len(), b.len());
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut ops = vec![];
+    while i < n || j < m {
+        if i < n && j < m && a[i] == b[j] {
+            ops.push((' ', a[i]));
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(('-', a[i]));
+            i += 1;
+        } else {
+            ops.push(('+', b[j]));
+            j += 1;
+        }
+    }
+    ops
+}
+
+// Unified diff with the usual three lines of context; empty when the texts are equal.
+pub fn unified_diff(label: &str, old: &str, new: &str) -> String {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    if a == b {
+        return String::new();
+    }
+    let ops = diff_lines(&a, &b);
+    // Hunks as op ranges: changes plus context, merged when they touch
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for (idx, _) in ops.iter().enumerate().filter(|(_, (op, _))| *op != ' ') {
+        let start = idx.saturating_sub(DIFF_CONTEXT);
+        let end = (idx + DIFF_CONTEXT + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    let mut out = format!("--- {}\n+++ {}\n", label, label);
+    for (start, end) in hunks {
+        let before = &ops[..start];
+        let old_before = before.iter().filter(|(op, _)| *op != '+').count();
+        let new_before = before.iter().filter(|(op, _)| *op != '-').count();
+        let hunk = &ops[start..end];
+        let old_len = hunk.iter().filter(|(op, _)| *op != '+').count();
+        let new_len = hunk.iter().filter(|(op, _)| *op != '-').count();
+        // An empty side is numbered from the line before it
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_before + usize::from(old_len > 0),
+            old_len,
+            new_before + usize::from(new_len > 0),
+            new_len
+        ));
+        for (op, line) in hunk {
+            out.push(*op);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(ip: &str) -> Nameserver {
+        Nameserver {
+            ip: ip.parse().unwrap(),
+            port: 53,
+            tls: None,
+        }
+    }
+
+    fn tls(ip: &str, port: u16, name: Option<&str>) -> Nameserver {
+        Nameserver {
+            ip: ip.parse().unwrap(),
+            port,
+            tls: Some(name.map(str::to_string)),
+        }
+    }
+
+    const EXISTING: &str = "# Generated by NetworkManager\n\
+                            search lan example.org\n\
+                            nameserver 192.168.1.1\n\
+                            nameserver 192.168.1.2\n\
+                            options edns0 trust-ad\n";
+
+    #[test]
+    fn resolv_conf_replaces_only_the_nameservers() {
+        let out = resolv_conf(EXISTING, &[plain("1.1.1.1"), plain("2606:4700:4700::1111")]);
+        assert_eq!(
+            out,
+            "# Generated by NetworkManager\n\
+             search lan example.org\n\
+             # nameservers set by ResoBoost\n\
+             nameserver 1.1.1.1\n\
+             nameserver 2606:4700:4700::1111\n\
+             options edns0 trust-ad\n"
+        );
+    }
+
+    #[test]
+    fn resolv_conf_reapplies_idempotently() {
+        let once = resolv_conf(EXISTING, &[plain("1.1.1.1")]);
+        assert_eq!(resolv_conf(&once, &[plain("1.1.1.1")]), once);
+        // a second apply swaps the servers without stacking headers
+        let twice = resolv_conf(&once, &[plain("9.9.9.9")]);
+        assert_eq!(twice.matches("set by ResoBoost").count(), 1);
+        assert!(twice.contains("nameserver 9.9.9.9\n"));
+        assert!(!twice.contains("1.1.1.1"));
+    }
+
+    #[test]
+    fn resolv_conf_appends_to_a_file_without_nameservers() {
+        let out = resolv_conf("search lan\n", &[plain("1.1.1.1")]);
+        assert_eq!(
+            out,
+            "search lan\n# nameservers set by ResoBoost\nnameserver 1.1.1.1\n"
+        );
+    }
+
+    #[test]
+    fn resolved_entries_carry_ports_and_names() {
+        let drop_in = resolved_drop_in(&[
+            tls("1.1.1.1", 853, Some("cloudflare-dns.com")),
+            tls("2606:4700:4700::1111", 8853, Some("cloudflare-dns.com")),
+            tls("9.9.9.9", 853, None),
+        ])
+        .unwrap();
+        assert!(drop_in.contains(
+            "\nDNS=1.1.1.1#cloudflare-dns.com [2606:4700:4700::1111]:8853#cloudflare-dns.com 9.9.9.9\n"
+        ));
+        assert!(drop_in.contains("\nDNSOverTLS=yes\n"));
+        assert!(drop_in.contains("\nDomains=~.\n"));
+
+        let plain_drop_in = resolved_drop_in(&[
+            plain("192.0.2.1"),
+            Nameserver {
+                port: 5353,
+                ..plain("::1")
+            },
+        ])
+        .unwrap();
+        assert!(plain_drop_in.contains("\nDNS=192.0.2.1 [::1]:5353\n"));
+        assert!(plain_drop_in.contains("\nDNSOverTLS=no\n"));
+    }
+
+    #[test]
+    fn resolved_rejects_mixed_plain_and_tls() {
+        let mixed = resolved_drop_in(&[plain("1.1.1.1"), tls("9.9.9.9", 853, None)]);
+        assert!(mixed.is_err());
+    }
+
+    #[test]
+    fn diff_hunks_have_line_ranges() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        assert_eq!(
+            unified_diff("f", old, new),
+            "--- f\n+++ f\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -8,3 +8,4 @@\n h\n i\n j\n+k\n"
+        );
+        assert_eq!(unified_diff("f", old, old), "");
+    }
+
+    #[test]
+    fn diff_numbers_an_empty_side_from_the_line_before() {
+        assert_eq!(
+            unified_diff("f", "", "x\n"),
+            "--- f\n+++ f\n@@ -0,0 +1,1 @@\n+x\n"
+        );
+        assert_eq!(
+            unified_diff("f", "x\n", ""),
+            "--- f\n+++ f\n@@ -1,1 +0,0 @@\n-x\n"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "resoboost-system-dns-{}-{}",
+            std::process::id(),
+            history::now_ms()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn apply_resolv_conf(path: &Path, server: &str) -> ApplyOutcome {
+        apply(ApplyRequest {
+            servers: vec![server.to_string()],
+            target: ApplyTarget::ResolvConf {
+                path: path.to_path_buf(),
+            },
+            dry_run: false,
+        })
+        .await
+        .unwrap()
+    }
+
+    // One test for both cases: revert() undoes every recorded change in the process.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn resolv_conf_apply_and_revert_round_trip() {
+        let dir = temp_dir();
+
+        // A regular file, changed twice and reverted in one go
+        let file = dir.join("resolv.conf");
+        std::fs::write(&file, EXISTING).unwrap();
+        let dry = apply(ApplyRequest {
+            servers: vec!["1.1.1.1".to_string()],
+            target: ApplyTarget::ResolvConf { path: file.clone() },
+            dry_run: true,
+        })
+        .await
+        .unwrap();
+        assert!(dry.diff.contains("+nameserver 1.1.1.1\n"));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), EXISTING);
+
+        let first = apply_resolv_conf(&file, "1.1.1.1").await;
+        assert!(first.backup_id.is_some());
+        apply_resolv_conf(&file, "9.9.9.9").await;
+        let written = std::fs::read_to_string(&file).unwrap();
+        assert!(written.contains("nameserver 9.9.9.9\n"));
+        assert!(written.contains("search lan example.org\n"));
+        // re-applying the same servers changes nothing and records no backup
+        assert_eq!(apply_resolv_conf(&file, "9.9.9.9").await.backup_id, None);
+
+        // A symlink, as with systemd-resolved's stub
+        let stub = dir.join("stub-resolv.conf");
+        std::fs::write(&stub, "nameserver 127.0.0.53\n").unwrap();
+        let link = dir.join("linked-resolv.conf");
+        std::os::unix::fs::symlink(&stub, &link).unwrap();
+        let linked = apply_resolv_conf(&link, "1.1.1.1").await;
+        assert!(linked.notes.iter().any(|n| n.contains("links to")));
+        assert!(std::fs::read_link(&link).is_err());
+        assert_eq!(
+            std::fs::read_to_string(&stub).unwrap(),
+            "nameserver 127.0.0.53\n"
+        );
+
+        assert_eq!(revert().await.unwrap().len(), 3);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), EXISTING);
+        assert_eq!(std::fs::read_link(&link).unwrap(), stub);
+        assert_eq!(
+            std::fs::read_to_string(&link).unwrap(),
+            "nameserver 127.0.0.53\n"
+        );
+        assert!(list_backups().await.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
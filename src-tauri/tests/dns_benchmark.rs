@@ -0,0 +1,183 @@
+// End-to-end benchmark runs against the loopback servers in `support`.
+mod support;
+
+use std::time::Duration;
+use support::{example, Action, MockServer, Transport, ANSWER, TLS_NAME};
+use tauri_lib::dns_tester::spec::SYSTEM_SERVER;
+use tauri_lib::dns_tester::{perform_dns_benchmark, DnsErrorKind, DnsTestResult};
+
+// A-only, so every sample is exactly one query and scripted actions line up with samples.
+async fn bench(servers: &[&MockServer], samples: u32, validate: bool) -> Vec<DnsTestResult> {
+    perform_dns_benchmark(
+        "example.test".to_string(),
+        Some(servers.iter().map(|s| s.spec()).collect()),
+        Some(2),
+        Some(samples),
+        Some(validate),
+        Some(false),
+        Some(true),
+        Some("A".to_string()),
+        Some(false),
+    )
+    .await
+}
+
+fn result_for<'a>(results: &'a [DnsTestResult], server: &MockServer) -> Option<&'a DnsTestResult> {
+    results.iter().find(|r| r.server_address == server.spec())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn every_transport_answers() {
+    let plain = MockServer::start(Transport::Plain, example()).await;
+    let dot = MockServer::start(Transport::Tls, example()).await;
+    let doh = MockServer::start(Transport::Https, example()).await;
+    let doq = MockServer::start(Transport::Quic, example()).await;
+
+    let results = bench(&[&plain, &dot, &doh, &doq], 3, false).await;
+
+    // the system baseline is opted out
+    assert_eq!(results.len(), 4);
+    for server in [&plain, &dot, &doh, &doq] {
+        let result = result_for(&results, server).expect("server should be benchmarked");
+        assert!(
+            result.query_successful,
+            "{}: {:?}",
+            server.spec(),
+            result.error_msg
+        );
+        assert_eq!(result.success_percent, 100.0);
+        assert_eq!(result.ipv4_ips, vec![ANSWER.to_string()]);
+        assert_eq!(result.samples.len(), 3);
+    }
+    assert_eq!(plain.stream_queries(), 0);
+    for server in [&dot, &doh, &doq] {
+        assert_eq!(server.stream_queries(), server.queries());
+        let tls = result_for(&results, server)
+            .and_then(|r| r.tls_details.as_ref())
+            .expect("TLS details should be inspected");
+        assert!(tls.verified, "{}: {:?}", server.spec(), tls.error);
+        assert_eq!(tls.tls_name, TLS_NAME);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn explicit_lists_are_measured_against_the_system_baseline() {
+    let plain = MockServer::start(Transport::Plain, example()).await;
+
+    let results = perform_dns_benchmark(
+        "example.test".to_string(),
+        Some(vec![plain.spec()]),
+        Some(1),
+        Some(1),
+        Some(false),
+        Some(false),
+        Some(false),
+        Some("A".to_string()),
+        None,
+    )
+    .await;
+
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().any(|r| r.server_address == SYSTEM_SERVER));
+    assert!(result_for(&results, &plain).is_some());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn delayed_server_measures_slower() {
+    let fast = MockServer::start(Transport::Plain, example()).await;
+    let slow = MockServer::start(
+        Transport::Plain,
+        example().always(Action::Delay(Duration::from_millis(200))),
+    )
+    .await;
+
+    let results = bench(&[&fast, &slow], 3, false).await;
+
+    let fast = result_for(&results, &fast).unwrap().latency_avg_ms.unwrap();
+    let slow = result_for(&results, &slow).unwrap().latency_avg_ms.unwrap();
+    assert!(slow >= 200.0, "slow server measured {} ms", slow);
+    assert!(slow > fast, "slow {} ms vs fast {} ms", slow, fast);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dropped_queries_fail_their_samples() {
+    // Samples alternate answer/drop.
+    let flaky = MockServer::start(
+        Transport::Plain,
+        example().cycle([Action::Answer, Action::Drop]),
+    )
+    .await;
+
+    let results = bench(&[&flaky], 4, false).await;
+
+    let result = result_for(&results, &flaky).unwrap();
+    assert_eq!(result.success_percent, 50.0);
+    assert!(result.error_msg.is_some());
+    assert_eq!(result.error_kind, Some(DnsErrorKind::Timeout));
+    assert_eq!(result.error_counts.get(&DnsErrorKind::Timeout), Some(&2));
+    assert_eq!(result.samples.iter().filter(|s| s.ok).count(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unresponsive_server_is_reported_as_failed() {
+    let silent = MockServer::start(Transport::Plain, example().always(Action::Drop)).await;
+
+    let results = bench(&[&silent], 2, false).await;
+
+    let result = result_for(&results, &silent).expect("a down server should be reported");
+    assert!(!result.query_successful);
+    assert_eq!(result.success_percent, 0.0);
+    assert_eq!(result.error_counts.get(&DnsErrorKind::Timeout), Some(&2));
+    // explicit lists skip the reachability precheck
+    assert_eq!(silent.queries(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn truncated_udp_answers_retry_over_tcp() {
+    let server = MockServer::start(Transport::Plain, example().always(Action::Truncate)).await;
+
+    let results = bench(&[&server], 3, false).await;
+
+    let result = result_for(&results, &server).unwrap();
+    assert_eq!(result.success_percent, 100.0);
+    assert_eq!(result.ipv4_ips, vec![ANSWER.to_string()]);
+    // three samples, each truncated over UDP and then answered over TCP
+    assert_eq!(server.stream_queries(), 3);
+    assert_eq!(server.queries(), 6);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn servfail_counts_as_a_failed_sample() {
+    let server = MockServer::start(
+        Transport::Plain,
+        example().cycle([Action::Answer, Action::ServFail]),
+    )
+    .await;
+
+    let results = bench(&[&server], 2, false).await;
+
+    let result = result_for(&results, &server).unwrap();
+    assert_eq!(result.success_percent, 50.0);
+    assert!(result.query_successful);
+    assert!(result.error_msg.is_some());
+    assert_eq!(result.error_kind, Some(DnsErrorKind::ServFail));
+    assert_eq!(result.error_counts.len(), 1);
+    assert_eq!(result.error_counts.get(&DnsErrorKind::ServFail), Some(&1));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bogus_signatures_are_flagged_when_validating() {
+    let server = MockServer::start(Transport::Plain, example().always(Action::BogusDnssec)).await;
+
+    let unvalidated = bench(&[&server], 2, false).await;
+    let validated = bench(&[&server], 2, true).await;
+
+    let unvalidated = result_for(&unvalidated, &server).unwrap();
+    assert!(unvalidated.query_successful);
+    assert!(!unvalidated.dnssec_bogus);
+    assert!(!unvalidated.dnssec_secure);
+    let validated = result_for(&validated, &server).unwrap();
+    assert!(validated.dnssec_enabled);
+    assert!(validated.dnssec_bogus);
+    assert!(!validated.dnssec_secure);
+}
@@ -0,0 +1,41 @@
+// Monitor checks against the loopback servers in `support`.
+mod support;
+
+use support::{example, Action, MockServer, Transport};
+use tauri_lib::monitor::{self, AlertRule, MonitorConfig};
+
+fn config(servers: &[&MockServer]) -> MonitorConfig {
+    MonitorConfig {
+        servers: servers.iter().map(|s| s.spec()).collect(),
+        domains: vec!["example.test".to_string()],
+        record_types: vec!["A".to_string()],
+        samples: 2,
+        timeout_secs: 1,
+        include_system: false,
+        rules: vec![AlertRule::SuccessRateBelow { percent: 80.0 }],
+        desktop_notifications: false,
+        ..MonitorConfig::default()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_server_that_is_down_raises_a_success_rate_alert() {
+    let down = MockServer::start(Transport::Plain, example().always(Action::Drop)).await;
+    let up = MockServer::start(Transport::Plain, example()).await;
+
+    let (results, alerts) = monitor::check(&config(&[&down, &up]), "example.test", "A").await;
+
+    let result = results
+        .iter()
+        .find(|r| r.server_address == down.spec())
+        .expect("the down server should be benchmarked");
+    assert_eq!(result.success_percent, 0.0);
+    assert_eq!(alerts.len(), 1, "{:?}", alerts);
+    assert_eq!(alerts[0].server_address, down.spec());
+    assert_eq!(
+        alerts[0].rule,
+        AlertRule::SuccessRateBelow { percent: 80.0 }
+    );
+    // warm-up plus two samples, no precheck
+    assert_eq!(down.queries(), 3);
+}
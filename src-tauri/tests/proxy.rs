@@ -0,0 +1,162 @@
+// The local DNS proxy in front of the loopback servers in `support`.
+mod support;
+
+use hickory_resolver::proto::op::{Message, Query, ResponseCode};
+use hickory_resolver::proto::rr::{Name, RData, RecordType};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use support::{example, Action, MockServer, Script, Transport, ANSWER};
+use tauri_lib::proxy::{self, ProxyConfig, ProxyStatus};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::Mutex;
+
+// One proxy runs per process, so the tests take turns.
+static PROXY: Mutex<()> = Mutex::const_new(());
+
+async fn launch(upstreams: &[&MockServer]) -> Result<ProxyStatus, String> {
+    proxy::launch(&ProxyConfig {
+        listen: "127.0.0.1:0".to_string(),
+        upstreams: upstreams.iter().map(|s| s.spec()).collect(),
+        timeout_secs: 1,
+        ..ProxyConfig::default()
+    })
+    .await
+}
+
+fn listen(status: &ProxyStatus) -> SocketAddr {
+    status.listen.as_deref().unwrap().parse().unwrap()
+}
+
+fn query(name: &str) -> Vec<u8> {
+    let mut message = Message::new();
+    message
+        .set_id(4242)
+        .set_recursion_desired(true)
+        .add_query(Query::query(Name::from_str(name).unwrap(), RecordType::A));
+    message.to_vec().unwrap()
+}
+
+async fn ask_udp(proxy: SocketAddr, name: &str) -> Message {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.send_to(&query(name), proxy).await.unwrap();
+    let mut buf = vec![0u8; 4096];
+    let len = socket.recv(&mut buf).await.unwrap();
+    Message::from_vec(&buf[..len]).unwrap()
+}
+
+async fn ask_tcp(proxy: SocketAddr, name: &str) -> Message {
+    let mut stream = TcpStream::connect(proxy).await.unwrap();
+    let request = query(name);
+    stream
+        .write_all(&(request.len() as u16).to_be_bytes())
+        .await
+        .unwrap();
+    stream.write_all(&request).await.unwrap();
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len).await.unwrap();
+    let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut response).await.unwrap();
+    Message::from_vec(&response).unwrap()
+}
+
+fn ips(response: &Message) -> Vec<Ipv4Addr> {
+    response
+        .answers()
+        .iter()
+        .filter_map(|r| match r.data() {
+            RData::A(a) => Some(a.0),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn udp_and_tcp_are_forwarded_and_repeats_come_from_the_cache() {
+    let _turn = PROXY.lock().await;
+    let upstream = MockServer::start(Transport::Plain, example()).await;
+    let address = listen(&launch(&[&upstream]).await.unwrap());
+
+    let first = ask_udp(address, "example.test.").await;
+    assert_eq!(first.id(), 4242);
+    assert_eq!(ips(&first), vec![ANSWER]);
+    let second = ask_tcp(address, "example.test.").await;
+    assert_eq!(ips(&second), vec![ANSWER]);
+    let missing = ask_udp(address, "missing.test.").await;
+    assert_eq!(missing.response_code(), ResponseCode::NXDomain);
+
+    let status = proxy::get_status().await;
+    assert_eq!((status.queries, status.cache_hits), (3, 1));
+    assert_eq!(upstream.queries(), 2);
+    assert!(status.recent.iter().any(|q| q.cached));
+    proxy::stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_silent_upstream_fails_over_to_the_next() {
+    let _turn = PROXY.lock().await;
+    let down = MockServer::start(Transport::Plain, example().always(Action::Drop)).await;
+    let up = MockServer::start(Transport::Plain, example()).await;
+    let address = listen(&launch(&[&down, &up]).await.unwrap());
+
+    let response = ask_udp(address, "example.test.").await;
+    assert_eq!(ips(&response), vec![ANSWER]);
+
+    let status = proxy::get_status().await;
+    assert_eq!(status.failures, 0);
+    assert_eq!(status.upstreams[0].failures, 1);
+    assert_eq!(status.upstreams[1].answers, 1);
+    assert_eq!(status.recent[0].upstream, Some(up.spec()));
+    proxy::stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn answers_too_big_for_udp_are_truncated_and_complete_over_tcp() {
+    let _turn = PROXY.lock().await;
+    // 40 A records are well over the 512 bytes a client without EDNS accepts; the upstream
+    // itself only hands them out over TCP
+    let big = (1..=40).fold(Script::new(), |script, i| {
+        script.a("big.test", Ipv4Addr::new(192, 0, 2, i))
+    });
+    let upstream = MockServer::start(Transport::Plain, big.always(Action::Truncate)).await;
+    let address = listen(&launch(&[&upstream]).await.unwrap());
+
+    let over_udp = ask_udp(address, "big.test.").await;
+    assert!(over_udp.truncated());
+    assert!(over_udp.answers().is_empty());
+    let over_tcp = ask_tcp(address, "big.test.").await;
+    assert!(!over_tcp.truncated());
+    assert_eq!(ips(&over_tcp).len(), 40);
+    // the second answer came from the cache
+    assert_eq!(upstream.stream_queries(), 1);
+    proxy::stop().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_failed_restart_keeps_the_running_proxy() {
+    let _turn = PROXY.lock().await;
+    let upstream = MockServer::start(Transport::Plain, example()).await;
+    let address = listen(&launch(&[&upstream]).await.unwrap());
+
+    let broken = ProxyConfig {
+        listen: address.to_string(),
+        upstreams: vec!["not a server".to_string()],
+        ..ProxyConfig::default()
+    };
+    assert!(proxy::launch(&broken).await.is_err());
+    let status = proxy::get_status().await;
+    assert!(status.running);
+    assert_eq!(listen(&status), address);
+    assert_eq!(ips(&ask_udp(address, "example.test.").await), vec![ANSWER]);
+
+    // Restarting on the port the proxy already holds
+    let again = ProxyConfig {
+        listen: address.to_string(),
+        upstreams: vec![upstream.spec()],
+        ..ProxyConfig::default()
+    };
+    assert_eq!(listen(&proxy::launch(&again).await.unwrap()), address);
+    assert_eq!(ips(&ask_tcp(address, "example.test.").await), vec![ANSWER]);
+    assert_eq!(proxy::get_status().await.queries, 1);
+    proxy::stop().await;
+}
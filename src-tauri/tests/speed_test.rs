@@ -0,0 +1,55 @@
+// Download speed tests that resolve through a loopback DNS server to a loopback HTTP server.
+mod support;
+
+use std::net::Ipv4Addr;
+use support::{Action, MockHttp, MockServer, Script, Transport};
+use tauri_lib::speed_tester::{perform_download_speed_test, DownloadSpeedArgs};
+
+const HOST: &str = "download.mock.test";
+const SIZE: usize = 1024 * 1024;
+
+fn args(http: &MockHttp, dns: &MockServer) -> DownloadSpeedArgs {
+    DownloadSpeedArgs {
+        url: format!("http://{}:{}/blob", HOST, http.addr.port()),
+        duration_secs: Some(5),
+        timeout_secs: Some(5),
+        custom_servers: Some(vec![dns.spec()]),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn download_uses_the_address_the_server_returned() {
+    let http = MockHttp::start(SIZE).await;
+    let dns = MockServer::start(Transport::Plain, Script::new().a(HOST, Ipv4Addr::LOCALHOST)).await;
+
+    let results = perform_download_speed_test(args(&http, &dns)).await;
+
+    assert_eq!(results.len(), 1);
+    let result = &results[0];
+    assert!(result.query_successful, "{:?}", result.error_msg);
+    assert_eq!(result.resolved_ip.as_deref(), Some("127.0.0.1"));
+    assert_eq!(result.http_status, Some(200));
+    assert_eq!(result.bytes_read, SIZE as u64);
+    assert!(result.bandwidth_mbps > 0.0);
+    assert_eq!(http.requests(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_resolution_skips_the_download() {
+    let http = MockHttp::start(SIZE).await;
+    let dns = MockServer::start(
+        Transport::Plain,
+        Script::new()
+            .a(HOST, Ipv4Addr::LOCALHOST)
+            .always(Action::ServFail),
+    )
+    .await;
+
+    let results = perform_download_speed_test(args(&http, &dns)).await;
+
+    let result = &results[0];
+    assert!(!result.query_successful);
+    assert!(result.resolved_ip.is_none());
+    assert!(result.error_msg.is_some());
+    assert_eq!(http.requests(), 0);
+}
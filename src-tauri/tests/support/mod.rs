@@ -0,0 +1,551 @@
+// Loopback DNS servers for integration tests: plain UDP/TCP, DoT, DoH and DoQ, all answering
+// from one scripted zone, plus a small HTTP server for download speed tests.
+//
+// The certificate fixtures are a throwaway CA and a "mock.test"/127.0.0.1 leaf it signed,
+// valid for 100 years. The CA key was discarded. To regenerate:
+//   openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -keyout ca.key \
+//     -days 36500 -subj "/CN=ResoBoost Test CA" -addext "basicConstraints=critical,CA:TRUE" \
+//     -addext "keyUsage=critical,keyCertSign,cRLSign" -outform DER -out ca.der
+//   openssl req -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -keyout server.key \
+//     -subj "/CN=mock.test" -out server.csr
+//   openssl x509 -req -in server.csr -CA ca.der -CAform DER -CAkey ca.key -days 36500 \
+//     -extfile ext.cnf -outform DER -out server.der
+//   openssl pkcs8 -topk8 -nocrypt -in server.key -outform DER -out server.key.der
+// where ext.cnf sets CA:FALSE, serverAuth and subjectAltName=DNS:mock.test,IP:127.0.0.1.
+
+// Each test binary uses a different part of this module.
+#![allow(dead_code)]
+
+use bytes::Bytes;
+use hickory_resolver::proto::dnssec::rdata::{DNSSECRData, RRSIG};
+use hickory_resolver::proto::dnssec::Algorithm;
+use hickory_resolver::proto::op::{Edns, Message, MessageType, OpCode, ResponseCode};
+use hickory_resolver::proto::quic::QuicServer;
+use hickory_resolver::proto::rr::rdata::{A, AAAA};
+use hickory_resolver::proto::rr::{Name, RData, Record};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Once};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri_lib::dns_tester::{self, tls_inspect};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio_rustls::TlsAcceptor;
+
+// Name in the server certificate; DoT and DoQ specs ask for it explicitly.
+pub const TLS_NAME: &str = "mock.test";
+
+const CA_CERT: &[u8] = include_bytes!("certs/ca.der");
+const SERVER_CERT: &[u8] = include_bytes!("certs/server.der");
+const SERVER_KEY: &[u8] = include_bytes!("certs/server.key.der");
+
+// Loads the saved/built-in config like the CLI does and trusts the fixture CA.
+pub fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        dns_tester::load_configs();
+        tls_inspect::set_extra_roots(vec![CertificateDer::from(CA_CERT.to_vec())])
+            .expect("fixture CA should load");
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Answer,
+    // Answer after a pause
+    Delay(Duration),
+    // Never reply; the client has to time out
+    Drop,
+    // Header-only reply with TC set over UDP; stream transports answer normally
+    Truncate,
+    ServFail,
+    // Answer plus an RRSIG that can't validate
+    BogusDnssec,
+}
+
+// Zone contents plus what to do with each query. Queries take the actions in turn,
+// starting over at the end; names that have no records at all get NXDOMAIN.
+#[derive(Debug, Clone)]
+pub struct Script {
+    records: Vec<Record>,
+    actions: Vec<Action>,
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Script {
+            records: vec![],
+            actions: vec![Action::Answer],
+        }
+    }
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn a(mut self, name: &str, ip: Ipv4Addr) -> Self {
+        self.records
+            .push(Record::from_rdata(fqdn(name), 300, RData::A(A(ip))));
+        self
+    }
+
+    pub fn aaaa(mut self, name: &str, ip: Ipv6Addr) -> Self {
+        self.records
+            .push(Record::from_rdata(fqdn(name), 300, RData::AAAA(AAAA(ip))));
+        self
+    }
+
+    pub fn always(self, action: Action) -> Self {
+        self.cycle([action])
+    }
+
+    pub fn cycle(mut self, actions: impl IntoIterator<Item = Action>) -> Self {
+        self.actions = actions.into_iter().collect();
+        assert!(
+            !self.actions.is_empty(),
+            "a script needs at least one action"
+        );
+        self
+    }
+
+    fn action(&self, query_index: usize) -> Action {
+        self.actions[query_index % self.actions.len()]
+    }
+}
+
+// The address `example()` answers with.
+pub const ANSWER: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+
+// The zone most tests serve: one A record for example.test.
+pub fn example() -> Script {
+    Script::new().a("example.test", ANSWER)
+}
+
+fn fqdn(name: &str) -> Name {
+    let mut name = Name::from_str(name).expect("valid test name");
+    name.set_fqdn(true);
+    name
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    // UDP and TCP on the same port
+    Plain,
+    Tls,
+    Https,
+    Quic,
+}
+
+struct State {
+    script: Script,
+    queries: AtomicUsize,
+    stream_queries: AtomicUsize,
+}
+
+pub struct MockServer {
+    pub addr: SocketAddr,
+    pub transport: Transport,
+    state: Arc<State>,
+}
+
+impl MockServer {
+    // Serves on 127.0.0.1 from the current Tokio runtime until the test ends.
+    pub async fn start(transport: Transport, script: Script) -> MockServer {
+        init();
+        let state = Arc::new(State {
+            script,
+            queries: AtomicUsize::new(0),
+            stream_queries: AtomicUsize::new(0),
+        });
+        let addr = match transport {
+            Transport::Plain => {
+                let (udp, tcp) = bind_udp_and_tcp().await;
+                let addr = udp.local_addr().unwrap();
+                tokio::spawn(serve_udp(udp, state.clone()));
+                tokio::spawn(serve_tcp(tcp, None, state.clone()));
+                addr
+            }
+            Transport::Tls => {
+                let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let addr = tcp.local_addr().unwrap();
+                let acceptor = TlsAcceptor::from(Arc::new(server_config(b"dot")));
+                tokio::spawn(serve_tcp(tcp, Some(acceptor), state.clone()));
+                addr
+            }
+            Transport::Https => {
+                let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let addr = tcp.local_addr().unwrap();
+                let acceptor = TlsAcceptor::from(Arc::new(server_config(b"h2")));
+                tokio::spawn(serve_https(tcp, acceptor, state.clone()));
+                addr
+            }
+            Transport::Quic => {
+                let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+                let addr = udp.local_addr().unwrap();
+                let certs = server_config(b"doq").cert_resolver.clone();
+                let server = QuicServer::with_socket(udp, certs).expect("DoQ server");
+                tokio::spawn(serve_quic(server, state.clone()));
+                addr
+            }
+        };
+        MockServer {
+            addr,
+            transport,
+            state,
+        }
+    }
+
+    // Address as typed into the app. DoH relies on SNI discovery to find the certificate name.
+    pub fn spec(&self) -> String {
+        let port = self.addr.port();
+        match self.transport {
+            Transport::Plain => format!("127.0.0.1:{}", port),
+            Transport::Tls => format!("tls://127.0.0.1:{}@{}", port, TLS_NAME),
+            Transport::Https => format!("https://127.0.0.1:{}/dns-query", port),
+            Transport::Quic => format!("quic://127.0.0.1:{}@{}", port, TLS_NAME),
+        }
+    }
+
+    pub fn queries(&self) -> usize {
+        self.state.queries.load(Ordering::SeqCst)
+    }
+
+    // Queries that arrived over TCP, TLS, HTTP/2 or QUIC streams rather than UDP
+    pub fn stream_queries(&self) -> usize {
+        self.state.stream_queries.load(Ordering::SeqCst)
+    }
+}
+
+// Ephemeral UDP port that is also free for TCP.
+async fn bind_udp_and_tcp() -> (UdpSocket, TcpListener) {
+    for _ in 0..20 {
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        if let Ok(tcp) = TcpListener::bind(udp.local_addr().unwrap()).await {
+            return (udp, tcp);
+        }
+    }
+    panic!("no free port for both UDP and TCP");
+}
+
+fn server_config(alpn: &[u8]) -> rustls::ServerConfig {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![CertificateDer::from(SERVER_CERT.to_vec())],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(SERVER_KEY.to_vec())),
+        )
+        .expect("fixture certificate should load");
+    config.alpn_protocols = vec![alpn.to_vec()];
+    config
+}
+
+impl State {
+    // None when the script drops the query.
+    async fn respond(&self, request: &[u8], udp: bool) -> Option<Vec<u8>> {
+        let request = Message::from_vec(request).ok()?;
+        let action = self
+            .script
+            .action(self.queries.fetch_add(1, Ordering::SeqCst));
+        if !udp {
+            self.stream_queries.fetch_add(1, Ordering::SeqCst);
+        }
+        let mut response = Message::new();
+        response
+            .set_id(request.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(request.op_code())
+            .set_recursion_desired(request.recursion_desired())
+            .set_recursion_available(true)
+            .add_queries(request.queries().to_vec());
+        if let Some(edns) = request.extensions() {
+            let mut reply_edns = Edns::new();
+            reply_edns.set_max_payload(edns.max_payload().max(512));
+            reply_edns.set_dnssec_ok(edns.flags().dnssec_ok);
+            response.set_edns(reply_edns);
+        }
+        if request.op_code() != OpCode::Query {
+            response.set_response_code(ResponseCode::NotImp);
+            return response.to_vec().ok();
+        }
+
+        match action {
+            Action::Drop => return None,
+            Action::ServFail => {
+                response.set_response_code(ResponseCode::ServFail);
+            }
+            Action::Truncate if udp => {
+                response.set_truncated(true);
+            }
+            Action::Delay(pause) => {
+                tokio::time::sleep(pause).await;
+                self.answer(&request, &mut response, false);
+            }
+            Action::BogusDnssec => self.answer(&request, &mut response, true),
+            Action::Answer | Action::Truncate => self.answer(&request, &mut response, false),
+        }
+        response.to_vec().ok()
+    }
+
+    fn answer(&self, request: &Message, response: &mut Message, bogus: bool) {
+        for query in request.queries() {
+            let named: Vec<&Record> = self
+                .script
+                .records
+                .iter()
+                .filter(|r| r.name() == query.name())
+                .collect();
+            if named.is_empty() {
+                response.set_response_code(ResponseCode::NXDomain);
+                continue;
+            }
+            let answers: Vec<Record> = named
+                .into_iter()
+                .filter(|r| r.record_type() == query.query_type())
+                .cloned()
+                .collect();
+            if bogus && !answers.is_empty() {
+                response.add_answer(bogus_rrsig(&answers[0]));
+            }
+            response.add_answers(answers);
+        }
+    }
+}
+
+// Well-formed signature over the record's RRset that no key will ever verify.
+fn bogus_rrsig(record: &Record) -> Record {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32;
+    let rrsig = RRSIG::new(
+        record.record_type(),
+        Algorithm::ECDSAP256SHA256,
+        record.name().num_labels(),
+        record.ttl(),
+        now + 86_400,
+        now - 86_400,
+        4242,
+        record.name().clone(),
+        vec![0x5a; 64],
+    );
+    Record::from_rdata(
+        record.name().clone(),
+        record.ttl(),
+        RData::DNSSEC(DNSSECRData::RRSIG(rrsig)),
+    )
+}
+
+async fn serve_udp(socket: UdpSocket, state: Arc<State>) {
+    let socket = Arc::new(socket);
+    let mut buf = vec![0u8; 4096];
+    loop {
+        let Ok((len, peer)) = socket.recv_from(&mut buf).await else {
+            continue;
+        };
+        let request = buf[..len].to_vec();
+        let (socket, state) = (socket.clone(), state.clone());
+        tokio::spawn(async move {
+            if let Some(response) = state.respond(&request, true).await {
+                let _ = socket.send_to(&response, peer).await;
+            }
+        });
+    }
+}
+
+// Plain TCP, or DoT when given an acceptor.
+async fn serve_tcp(listener: TcpListener, tls: Option<TlsAcceptor>, state: Arc<State>) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let (tls, state) = (tls.clone(), state.clone());
+        tokio::spawn(async move {
+            match tls {
+                Some(acceptor) => {
+                    if let Ok(stream) = acceptor.accept(stream).await {
+                        serve_stream(stream, state).await;
+                    }
+                }
+                None => serve_stream(stream, state).await,
+            }
+        });
+    }
+}
+
+// Length-prefixed messages, answered in order, until the client hangs up.
+async fn serve_stream<S>(stream: S, state: Arc<State>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut reader, writer) = tokio::io::split(stream);
+    let writer = Arc::new(tokio::sync::Mutex::new(writer));
+    loop {
+        let mut len = [0u8; 2];
+        if reader.read_exact(&mut len).await.is_err() {
+            return;
+        }
+        let mut request = vec![0u8; u16::from_be_bytes(len) as usize];
+        if reader.read_exact(&mut request).await.is_err() {
+            return;
+        }
+        // Each query is answered on its own so a delayed one doesn't hold up the rest.
+        let (writer, state) = (writer.clone(), state.clone());
+        tokio::spawn(async move {
+            if let Some(response) = state.respond(&request, false).await {
+                let mut writer = writer.lock().await;
+                let _ = writer
+                    .write_all(&(response.len() as u16).to_be_bytes())
+                    .await;
+                let _ = writer.write_all(&response).await;
+            }
+        });
+    }
+}
+
+// DoH (RFC 8484) POSTs to /dns-query over HTTP/2.
+async fn serve_https(listener: TcpListener, acceptor: TlsAcceptor, state: Arc<State>) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let (acceptor, state) = (acceptor.clone(), state.clone());
+        tokio::spawn(async move {
+            let Ok(stream) = acceptor.accept(stream).await else {
+                return;
+            };
+            let Ok(mut connection) = h2::server::handshake(stream).await else {
+                return;
+            };
+            while let Some(Ok((request, respond))) = connection.accept().await {
+                tokio::spawn(answer_https(request, respond, state.clone()));
+            }
+        });
+    }
+}
+
+async fn answer_https(
+    request: http::Request<h2::RecvStream>,
+    mut respond: h2::server::SendResponse<Bytes>,
+    state: Arc<State>,
+) {
+    if request.method() != http::Method::POST || request.uri().path() != "/dns-query" {
+        let response = http::Response::builder().status(404).body(()).unwrap();
+        let _ = respond.send_response(response, true);
+        return;
+    }
+    let mut body = request.into_body();
+    let mut query = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let Ok(chunk) = chunk else {
+            return;
+        };
+        let _ = body.flow_control().release_capacity(chunk.len());
+        query.extend_from_slice(&chunk);
+    }
+    let Some(answer) = state.respond(&query, false).await else {
+        // Keep the stream open so the client times out instead of seeing a reset
+        return std::future::pending().await;
+    };
+    let response = http::Response::builder()
+        .status(200)
+        .header(http::header::CONTENT_TYPE, "application/dns-message")
+        .header(http::header::CONTENT_LENGTH, answer.len())
+        .body(())
+        .unwrap();
+    if let Ok(mut send) = respond.send_response(response, false) {
+        let _ = send.send_data(Bytes::from(answer), true);
+    }
+}
+
+// DoQ (RFC 9250): one query per bidirectional stream.
+async fn serve_quic(mut server: QuicServer, state: Arc<State>) {
+    loop {
+        let mut streams = match server.next().await {
+            Ok(Some((streams, _))) => streams,
+            Ok(None) => return,
+            Err(_) => continue,
+        };
+        let state = state.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(mut stream)) = streams.next().await {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let Ok(query) = stream.receive_bytes().await else {
+                        return;
+                    };
+                    match state.respond(&query, false).await {
+                        Some(answer) => {
+                            let _ = stream.send_bytes(Bytes::from(answer)).await;
+                            let _ = stream.finish().await;
+                        }
+                        None => std::future::pending().await,
+                    }
+                });
+            }
+        });
+    }
+}
+
+// Answers every GET with `size` bytes of body, for download speed tests.
+pub struct MockHttp {
+    pub addr: SocketAddr,
+    requests: Arc<AtomicUsize>,
+}
+
+impl MockHttp {
+    pub async fn start(size: usize) -> MockHttp {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(serve_download(stream, size));
+            }
+        });
+        MockHttp { addr, requests }
+    }
+
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+async fn serve_download(mut stream: tokio::net::TcpStream, size: usize) {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => head.extend_from_slice(&buf[..n]),
+        }
+    }
+    let header = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        size
+    );
+    if stream.write_all(header.as_bytes()).await.is_err() {
+        return;
+    }
+    let chunk = vec![0xa5u8; 16 * 1024];
+    let mut left = size;
+    while left > 0 {
+        let n = left.min(chunk.len());
+        if stream.write_all(&chunk[..n]).await.is_err() {
+            return;
+        }
+        left -= n;
+    }
+    let _ = stream.shutdown().await;
+}
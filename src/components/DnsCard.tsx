@@ -1,4 +1,21 @@
-import { DnsTestResult } from "../types";
+import { DnsErrorKind, DnsTestResult } from "../types";
+
+const ERROR_LABELS: Record<DnsErrorKind, string> = {
+  timeout: "timeout",
+  connection_refused: "connection refused",
+  tls_handshake: "TLS handshake",
+  certificate_invalid: "certificate invalid",
+  pin_mismatch: "pin mismatch",
+  bootstrap: "bootstrap",
+  servfail: "SERVFAIL",
+  refused: "REFUSED",
+  nxdomain: "NXDOMAIN",
+  nodata: "NODATA",
+  dnssec_bogus: "DNSSEC bogus",
+  invalid_server: "invalid server",
+  runtime: "runtime",
+  other: "other",
+};
 
 function copy(text: string) {
   if (navigator?.clipboard?.writeText) {
@@ -24,6 +41,15 @@ export default function DnsCard({ r }: { r: DnsTestResult }) {
   const jitter = num(r.jitter_avg_ms);
   const fmtMs = (v?: number) => (v == null ? "" : (v < 1 ? `${v.toFixed(1)} ms` : `${Math.round(v)} ms`));
   const ok = r.query_successful && (r.success_percent ?? 0) > 0;
+  const isSystem = r.server_address.trim().toLowerCase() === "system";
+  const vsSystem = num(r.vs_system_ms);
+  const vsSystemPct = num(r.vs_system_percent);
+  // Why samples failed, most frequent first
+  const failures = Object.entries(r.error_counts ?? {})
+    .filter(([, count]) => (count ?? 0) > 0)
+    .sort((a, b) => (b[1] ?? 0) - (a[1] ?? 0))
+    .map(([kind, count]) => `${ERROR_LABELS[kind as DnsErrorKind] ?? kind} ${count}`)
+    .join(" · ");
 
   return (
     <div className={["card flex items-center justify-between gap-4", ok ? "border-emerald-500/30" : "border-red-500/30"].join(" ") }>
@@ -32,9 +58,12 @@ export default function DnsCard({ r }: { r: DnsTestResult }) {
           Copy
         </button>
         <div className="truncate">
-          <div className="font-semibold truncate">{r.server_address}</div>
+          <div className="font-semibold truncate">
+            {r.server_address}
+            {isSystem && <span className="badge ml-2 bg-sky-500/20 text-sky-400">Baseline</span>}
+          </div>
           <div className="text-xs text-[var(--muted)] truncate max-w-[52ch]">
-            {r.error_msg ? r.error_msg : (r.ipv4_ips?.length || r.ipv6_ips?.length ? [...r.ipv4_ips, ...r.ipv6_ips].slice(0,3).join(", ") : "")}
+            {r.error_msg ? `${r.error_kind ? `[${ERROR_LABELS[r.error_kind] ?? r.error_kind}] ` : ""}${r.error_msg}` : (r.ipv4_ips?.length || r.ipv6_ips?.length ? [...r.ipv4_ips, ...r.ipv6_ips].slice(0,3).join(", ") : "")}
           </div>
         </div>
       </div>
@@ -42,6 +71,12 @@ export default function DnsCard({ r }: { r: DnsTestResult }) {
         <div>
           <div className="text-xs text-[var(--muted)]">Latency</div>
           <div className="font-medium">{fmtMs(latency)}</div>
+          {vsSystem != null && (
+            <div className={["text-xs", vsSystem < 0 ? "text-emerald-400" : "text-red-400"].join(" ")} title="Compared with the system resolver">
+              {`${vsSystem < 0 ? "" : "+"}${Math.round(vsSystem)} ms`}
+              {vsSystemPct != null ? ` (${vsSystemPct < 0 ? "" : "+"}${Math.round(vsSystemPct)}%)` : ""}
+            </div>
+          )}
         </div>
         <div>
           <div className="text-xs text-[var(--muted)]">Jitter</div>
@@ -50,6 +85,11 @@ export default function DnsCard({ r }: { r: DnsTestResult }) {
         <div>
           <div className="text-xs text-[var(--muted)]">Success rate</div>
           <div className="font-medium">{`${Math.round(r.success_percent)}%`}</div>
+          {failures && (
+            <div className="text-xs text-red-400 truncate max-w-[24ch]" title={`Failed samples: ${failures}`}>
+              {failures}
+            </div>
+          )}
         </div>
         <div className="text-right">
           <div className="text-xs text-[var(--muted)]">DNSSEC</div>
@@ -19,7 +19,8 @@ export default function ServersModal({ open, onClose }: { open: boolean; onClose
 
   async function save() {
     setSaving(true); setError(null);
-    const servers = text.split(/\r?\n/).map((s) => s.trim()).filter(Boolean);
+    // Blank lines are kept so backend errors point at the right line numbers.
+    const servers = text.split(/\r?\n/).map((s) => s.trim());
     try {
       await invoke("set_dns_servers", { servers });
       onClose();
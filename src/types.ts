@@ -1,5 +1,6 @@
 export type DnsTestResult = {
   server_address: string;
+  provider?: string | null;
   resolution_time_ms?: number | null;
   query_successful: boolean;
   latency_avg_ms?: number | null;
@@ -10,11 +11,81 @@ export type DnsTestResult = {
   ipv4_ips: string[];
   ipv6_ips: string[];
   error_msg?: string | null;
+  error_kind?: DnsErrorKind | null;
+  // Failed samples by cause
+  error_counts?: Partial<Record<DnsErrorKind, number>>;
   avg_time?: number | null;
+  bootstrap_ms?: number | null;
+  bootstrap_ips?: string[];
+  tls_details?: TlsDetails | null;
+  vs_system_ms?: number | null;
+  vs_system_percent?: number | null;
+  samples?: Sample[];
+  answers?: string[];
+  dnssec_bogus?: boolean;
+  dnssec_secure?: boolean;
 };
 
+export type Sample = {
+  latency_ms: number;
+  ok: boolean;
+  error?: DnsErrorKind | null;
+};
+
+export type DnsErrorKind =
+  | "timeout"
+  | "connection_refused"
+  | "tls_handshake"
+  | "certificate_invalid"
+  | "pin_mismatch"
+  | "bootstrap"
+  | "servfail"
+  | "refused"
+  | "nxdomain"
+  | "nodata"
+  | "dnssec_bogus"
+  | "invalid_server"
+  | "runtime"
+  | "other";
+
+export type CertInfo = {
+  subject: string;
+  issuer: string;
+  sans: string[];
+  serial: string;
+  not_before: string;
+  not_after: string;
+  days_until_expiry: number;
+  spki_sha256: string;
+  sha256_fingerprint: string;
+};
+
+export type TlsDetails = {
+  transport: "dot" | "doh" | "doq";
+  connected_ip: string;
+  port: number;
+  tls_name: string;
+  name_from_host_map: boolean;
+  handshake_ms?: number | null;
+  verified: boolean;
+  tls_version?: string | null;
+  cipher_suite?: string | null;
+  alpn?: string | null;
+  ocsp_stapled: boolean;
+  pin_matched?: boolean | null;
+  chain: CertInfo[];
+  warnings: string[];
+  error?: string | null;
+};
+
+export type BootstrapStrategy =
+  | { mode: "system" }
+  | { mode: "plain"; servers: string[] }
+  | { mode: "static"; hosts: Record<string, string[]> };
+
 export type DownloadTestResult = {
   server_address: string;
+  provider?: string | null;
   resolved_ip?: string | null;
   duration_ms: number;
   bytes_read: number;
@@ -31,6 +102,9 @@ export type DnsBenchmarkParams = {
   customServers?: string[];
   validateDnssec?: boolean;
   warmUp?: boolean;
+  inspectTls?: boolean;
+  // "A/AAAA" (default), "A", "MX", "TXT", ...
+  recordType?: string;
 };
 
 export type DownloadSpeedParams = {
@@ -39,3 +113,374 @@ export type DownloadSpeedParams = {
   timeoutSecs?: number;
   customServers?: string[];
 };
+
+export type ServerProtocol = "udp" | "dot" | "doh" | "doq" | "dnscrypt";
+
+export type ServerTag = "filtering" | "ecs" | "dnssec" | "no_log";
+
+export type ServerEntry = {
+  address: string;
+  provider?: string | null;
+  protocol: ServerProtocol;
+  region?: string | null;
+  tags: ServerTag[];
+  enabled: boolean;
+  notes?: string | null;
+};
+
+export type ServerProfileSummary = {
+  name: string;
+  active: boolean;
+  server_count: number;
+  enabled_count: number;
+  follow_remote: boolean;
+  user_edited: boolean;
+};
+
+export type MergeRule = "append" | "replace" | "intersect";
+
+export type Subscription = {
+  url: string;
+  enabled: boolean;
+  merge: MergeRule;
+  public_key?: string | null;
+  signature_url?: string | null;
+};
+
+export type SubscriptionStatus = Subscription & {
+  etag?: string | null;
+  last_modified?: string | null;
+  last_checked?: number | null;
+  last_updated?: number | null;
+  accepted: number;
+  rejected: string[];
+  from_cache: boolean;
+  last_error?: string | null;
+};
+
+export type TlsHostEntry = {
+  ip: string;
+  host: string;
+  source: "default" | "discovered" | "remote" | "user";
+};
+
+export type TlsHostImport = {
+  imported: number;
+  rejected: string[];
+};
+
+export type ImportFormat =
+  | "dnscrypt_md"
+  | "adguard_json"
+  | "resolv_conf"
+  | "unbound"
+  | "dnsmasq"
+  | "resolved_conf"
+  | "csv";
+
+export type ImportMode = "append" | "replace";
+
+export type ImportPreview = {
+  format: ImportFormat;
+  mode: ImportMode;
+  entries: ServerEntry[];
+  added: string[];
+  already_present: string[];
+  removed: string[];
+  skipped: string[];
+};
+
+export type SpecError = {
+  column: number;
+  message: string;
+};
+
+export type LineValidation = {
+  line: number;
+  input: string;
+  canonical?: string | null;
+  error?: SpecError | null;
+  duplicate_of?: number | null;
+};
+
+export type ValidationReport = {
+  lines: LineValidation[];
+  valid: number;
+  invalid: number;
+  duplicates: number;
+};
+
+export type RunKind = "dns" | "speed";
+
+export type RunSummary = {
+  id: number;
+  kind: RunKind;
+  source: "manual" | "monitor";
+  started_at: number;
+  finished_at: number;
+  params: Record<string, unknown>;
+  server_count: number;
+  success_count: number;
+};
+
+export type RunDetail = RunSummary & {
+  environment: Record<string, unknown>;
+  results: (DnsTestResult | DownloadTestResult)[];
+};
+
+export type TrendPoint = {
+  server_address: string;
+  bucket_start: number;
+  runs: number;
+  samples: number;
+  p50_ms?: number | null;
+  p95_ms?: number | null;
+  success_rate: number;
+};
+
+export type AlertRule =
+  | { kind: "success_rate_below"; percent: number }
+  | { kind: "p95_above"; ms: number }
+  | { kind: "answer_changed" }
+  | { kind: "dnssec_bogus" };
+
+export type MonitorConfig = {
+  enabled: boolean;
+  interval_minutes: number;
+  servers: string[];
+  domains: string[];
+  record_types: string[];
+  samples: number;
+  timeout_secs: number;
+  validate_dnssec: boolean;
+  include_system: boolean;
+  rules: AlertRule[];
+  desktop_notifications: boolean;
+};
+
+export type MonitorAlert = {
+  rule: AlertRule;
+  server_address: string;
+  domain: string;
+  record_type: string;
+  message: string;
+  at: number;
+};
+
+export type MonitorStatus = {
+  running: boolean;
+  last_run_at?: number | null;
+  next_run_at?: number | null;
+  last_error?: string | null;
+  recent_alerts: MonitorAlert[];
+};
+
+export type AssertionOutcome = {
+  assertion: string;
+  server_address?: string | null;
+  passed: boolean;
+  actual?: string | null;
+  message: string;
+};
+
+export type Verdict = {
+  passed: boolean;
+  total: number;
+  failed: number;
+  outcomes: AssertionOutcome[];
+};
+
+export type ReportFormat = "csv" | "json" | "markdown" | "html";
+
+// Results passed to export_report when the run is not in the history
+export type ReportRun = {
+  title?: string | null;
+  id?: number | null;
+  started_at?: number | null;
+  params?: unknown;
+  environment?: unknown;
+} & (
+  | { kind: "dns"; results: DnsTestResult[] }
+  | { kind: "speed"; results: DownloadTestResult[] }
+);
+
+// One side of compare_runs: a history run, a JSON file, or results in memory
+export type RunRef = { run: number } | { file: string } | { results: ReportRun };
+
+export type ServerChange =
+  | "unchanged"
+  | "newly_failing"
+  | "recovered"
+  | "still_failing"
+  | "added"
+  | "removed";
+
+export type MetricDelta = {
+  metric: "p50" | "p95" | "p99" | "median" | "jitter" | "success" | "bandwidth_mbps";
+  a?: number | null;
+  b?: number | null;
+  delta?: number | null;
+  percent?: number | null;
+  p_value?: number | null;
+  significant: boolean;
+  improved?: boolean | null;
+};
+
+export type ServerDiff = {
+  server_address: string;
+  provider?: string | null;
+  change: ServerChange;
+  metrics: MetricDelta[];
+  answers_added: string[];
+  answers_removed: string[];
+};
+
+export type RunComparison = {
+  kind: RunKind;
+  a: { id?: number | null; started_at?: number | null; title?: string | null };
+  b: { id?: number | null; started_at?: number | null; title?: string | null };
+  servers: ServerDiff[];
+  newly_failing: string[];
+  recovered: string[];
+  answer_changes: string[];
+  significant_changes: string[];
+};
+
+export type ExporterConfig = {
+  enabled: boolean;
+  bind: string;
+};
+
+export type ExporterStatus = {
+  listening_on?: string | null;
+  last_error?: string | null;
+  targets: number;
+};
+
+export type ProxyConfig = {
+  listen: string;
+  upstreams: string[];
+  upstream_count: number;
+  race: boolean;
+  cache_size: number;
+  timeout_secs: number;
+};
+
+export type UpstreamStats = {
+  server_address: string;
+  queries: number;
+  answers: number;
+  failures: number;
+  avg_ms?: number | null;
+};
+
+export type QueryLogEntry = {
+  at: number;
+  name: string;
+  record_type: string;
+  response_code: string;
+  upstream?: string | null;
+  cached: boolean;
+  elapsed_ms: number;
+};
+
+export type ProxyStatus = {
+  running: boolean;
+  listen?: string | null;
+  race: boolean;
+  started_at?: number | null;
+  queries: number;
+  cache_hits: number;
+  failures: number;
+  upstreams: UpstreamStats[];
+  recent: QueryLogEntry[];
+};
+
+export type ApplyTarget =
+  | { backend: "systemd_resolved"; drop_in_dir?: string }
+  | { backend: "network_manager"; connection?: string | null }
+  | { backend: "resolv_conf"; path?: string };
+
+export type ApplyRequest = {
+  servers: string[];
+  target: ApplyTarget;
+  dry_run?: boolean;
+};
+
+export type ApplyOutcome = {
+  dry_run: boolean;
+  diff: string;
+  backup_id?: number | null;
+  notes: string[];
+};
+
+export type DnsBackup = {
+  id: number;
+  servers: string[];
+  target: ApplyTarget;
+  previous:
+    | { kind: "file"; path: string; content?: string | null; symlink?: string | null }
+    | { kind: "connection"; name: string; settings: [string, string][] };
+};
+
+export type SnippetFormat =
+  | "unbound"
+  | "dnsmasq"
+  | "coredns"
+  | "dnscrypt_proxy"
+  | "adguard_home"
+  | "pihole"
+  | "systemd_resolved"
+  | "android_private_dns";
+
+export type SkippedServer = {
+  server: string;
+  reason: string;
+};
+
+export type Snippet = {
+  format: SnippetFormat;
+  target: string;
+  content: string;
+  skipped: SkippedServer[];
+};
+
+export type LoadTestArgs = {
+  servers: string[];
+  domain: string;
+  record_type?: string | null;
+  start_qps?: number | null;
+  step_qps?: number | null;
+  max_qps?: number | null;
+  step_secs?: number | null;
+  concurrency?: number | null;
+  timeout_secs?: number | null;
+  error_threshold_percent?: number | null;
+  confirm: boolean;
+};
+
+export type LoadStep = {
+  target_qps: number;
+  sent: number;
+  not_sent: number;
+  achieved_qps: number;
+  answered: number;
+  servfail: number;
+  refused: number;
+  timeouts: number;
+  other_errors: number;
+  error_percent: number;
+  latency_avg_ms?: number | null;
+  latency_p50_ms?: number | null;
+  latency_p95_ms?: number | null;
+  latency_p99_ms?: number | null;
+};
+
+export type LoadTestResult = {
+  server_address: string;
+  steps: LoadStep[];
+  max_clean_qps?: number | null;
+  saturation_qps?: number | null;
+  stopped: boolean;
+  error_msg?: string | null;
+};
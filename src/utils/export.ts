@@ -98,6 +98,7 @@ function csvEscape(value: string): string {
 export function toDnsCsv(results: DnsTestResult[]): string {
   const headers = [
     "server_address",
+    "provider",
     "query_successful",
     "success_percent",
     "latency_avg_ms",
@@ -114,6 +115,7 @@ export function toDnsCsv(results: DnsTestResult[]): string {
   for (const r of results) {
     const row = [
       r.server_address ?? "",
+      r.provider ?? "",
       String(!!r.query_successful),
       r.success_percent != null ? String(r.success_percent) : "",
       r.latency_avg_ms != null ? String(r.latency_avg_ms) : "",
@@ -133,6 +135,7 @@ export function toDnsCsv(results: DnsTestResult[]): string {
 export function toDownloadCsv(results: DownloadTestResult[]): string {
   const headers = [
     "server_address",
+    "provider",
     "resolved_ip",
     "query_successful",
     "http_status",
@@ -146,6 +149,7 @@ export function toDownloadCsv(results: DownloadTestResult[]): string {
   for (const r of results) {
     const row = [
       r.server_address ?? "",
+      r.provider ?? "",
       r.resolved_ip ?? "",
       String(!!r.query_successful),
       r.http_status != null ? String(r.http_status) : "",